@@ -0,0 +1,6 @@
+DROP INDEX expenses_group_id_expense_date;
+
+ALTER TABLE expenses DROP COLUMN expense_date;
+ALTER TABLE expenses DROP COLUMN updated_at;
+
+ALTER TABLE expenses RENAME COLUMN created_at TO creation_date;
@@ -0,0 +1,11 @@
+-- `expense_date` is the day the expense refers to, chosen by the user, while `created_at` and
+-- `updated_at` are kept by the server. existing expenses are dated on the day they were created
+ALTER TABLE expenses RENAME COLUMN creation_date TO created_at;
+
+-- sqlite can only add a NOT NULL column with a default, it is overwritten right away
+ALTER TABLE expenses ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+ALTER TABLE expenses ADD COLUMN expense_date DATE NOT NULL DEFAULT '1970-01-01';
+
+UPDATE expenses SET updated_at = created_at, expense_date = date(created_at);
+
+CREATE INDEX expenses_group_id_expense_date ON expenses (group_id, expense_date, id);
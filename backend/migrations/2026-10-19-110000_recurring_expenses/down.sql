@@ -0,0 +1,2 @@
+DROP TABLE recurring_expense_participations;
+DROP TABLE recurring_expenses;
@@ -0,0 +1,33 @@
+-- a recurring expense is the template of the expenses added to its group on every occurrence of
+-- its rule, `next_date` is the first occurrence that has not been added yet
+CREATE TABLE recurring_expenses (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    group_id INTEGER NOT NULL,
+    created_by INTEGER NOT NULL,
+    desc TEXT NOT NULL,
+    total_amount DECIMAL NOT NULL,
+    paid_by INTEGER NOT NULL,
+    category_id INTEGER,
+    frequency TEXT NOT NULL CHECK (frequency IN ('MONTHLY', 'WEEKLY', 'EVERY_N_DAYS')),
+    day_of_month INTEGER,
+    interval_days INTEGER,
+    start_date DATE NOT NULL,
+    end_date DATE,
+    next_date DATE NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (paid_by) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (category_id) REFERENCES categories(id) ON DELETE SET NULL
+);
+
+CREATE INDEX recurring_expenses_next_date ON recurring_expenses (next_date);
+
+CREATE TABLE recurring_expense_participations (
+    recurring_expense_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    amount_due DECIMAL,
+    PRIMARY KEY (recurring_expense_id, user_id),
+    FOREIGN KEY (recurring_expense_id) REFERENCES recurring_expenses(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
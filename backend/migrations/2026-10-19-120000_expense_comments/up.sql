@@ -0,0 +1,45 @@
+CREATE TABLE expense_comments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    expense_id INTEGER NOT NULL,
+    author_id INTEGER NOT NULL,
+    body TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (expense_id) REFERENCES expenses(id) ON DELETE CASCADE,
+    FOREIGN KEY (author_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX expense_comments_expense_id ON expense_comments (expense_id, created_at, id);
+
+-- sqlite cannot change a CHECK constraint, the notifications table is rebuilt with the new type
+ALTER TABLE notifications RENAME TO notifications_old;
+
+CREATE TABLE notifications (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    notified_user_id INTEGER NOT NULL,
+    notification_type TEXT CHECK (notification_type IN (
+        'NEW_EXPENSE', 'EXPENSE_DELETED', 'EXPENSE_MODIFIED',
+        'REMOVED_FROM_GROUP', 'GROUP_DELETED', 'ADMIN_PROMOTION',
+        'ADMIN_DEMOTION', 'FRIENDSHIP_REQUEST_ACCEPTED', 'FRIENDSHIP_REQUEST_DENIED',
+        'EXPENSE_COMMENT')),
+    group_id INTEGER,
+    user_id INTEGER,
+    expense_id INTEGER,
+    creation_date TIMESTAMP NOT NULL,
+    read BOOLEAN NOT NULL DEFAULT FALSE,
+    FOREIGN KEY (notified_user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE SET NULL,
+    FOREIGN KEY (expense_id) REFERENCES expenses(id) ON DELETE SET NULL
+);
+
+INSERT INTO notifications (
+    id, notified_user_id, notification_type, group_id, user_id,
+    expense_id, creation_date, read
+)
+SELECT
+    id, notified_user_id, notification_type, group_id, user_id,
+    expense_id, creation_date, read
+FROM notifications_old;
+
+DROP TABLE notifications_old;
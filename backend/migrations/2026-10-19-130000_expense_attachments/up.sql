@@ -0,0 +1,16 @@
+-- the files themselves are in the attachment storage, under their content hash
+CREATE TABLE attachments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    expense_id INTEGER NOT NULL,
+    uploaded_by INTEGER,
+    file_name TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    size BIGINT NOT NULL,
+    content_hash TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    UNIQUE (expense_id, content_hash),
+    FOREIGN KEY (expense_id) REFERENCES expenses(id) ON DELETE CASCADE,
+    FOREIGN KEY (uploaded_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX attachments_content_hash ON attachments (content_hash);
@@ -0,0 +1,4 @@
+ALTER TABLE notifications DROP COLUMN revision_id;
+
+DROP TABLE expense_revision_participations;
+DROP TABLE expense_revisions;
@@ -0,0 +1,35 @@
+-- every revision is a snapshot of a group expense after it was added or modified
+CREATE TABLE expense_revisions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    expense_id INTEGER NOT NULL,
+    revision INTEGER NOT NULL,
+    changed_by INTEGER,
+    changed_at TIMESTAMP NOT NULL,
+    desc TEXT NOT NULL,
+    total_amount DOUBLE NOT NULL,
+    paid_by INTEGER NOT NULL,
+    expense_date DATE NOT NULL,
+    category_id INTEGER,
+    UNIQUE (expense_id, revision),
+    FOREIGN KEY (expense_id) REFERENCES expenses(id) ON DELETE CASCADE,
+    FOREIGN KEY (changed_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE TABLE expense_revision_participations (
+    revision_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    amount_due DOUBLE,
+    PRIMARY KEY (revision_id, user_id),
+    FOREIGN KEY (revision_id) REFERENCES expense_revisions(id) ON DELETE CASCADE
+);
+
+-- the existing group expenses start from their current state, by an unknown author
+INSERT INTO expense_revisions (expense_id, revision, changed_by, changed_at, desc, total_amount, paid_by, expense_date, category_id)
+    SELECT id, 1, NULL, updated_at, desc, total_amount, paid_by, expense_date, category_id
+    FROM expenses WHERE group_id IS NOT NULL;
+
+INSERT INTO expense_revision_participations (revision_id, user_id, amount_due)
+    SELECT r.id, p.user_id, p.amount_due
+    FROM expense_revisions r JOIN expense_participations p ON p.expense_id = r.expense_id;
+
+ALTER TABLE notifications ADD COLUMN revision_id INTEGER REFERENCES expense_revisions(id) ON DELETE SET NULL;
@@ -0,0 +1,6 @@
+-- deleted expenses and groups stay in the trash until they are restored or purged
+ALTER TABLE expenses ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE groups ADD COLUMN deleted_at TIMESTAMP;
+
+CREATE INDEX expenses_deleted_at ON expenses (deleted_at);
+CREATE INDEX groups_deleted_at ON groups (deleted_at);
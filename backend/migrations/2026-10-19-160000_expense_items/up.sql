@@ -0,0 +1,19 @@
+-- the line items of a receipt, the division of the expense is computed from them
+CREATE TABLE expense_items (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    expense_id INTEGER NOT NULL,
+    desc TEXT NOT NULL,
+    amount DOUBLE NOT NULL,
+    FOREIGN KEY (expense_id) REFERENCES expenses(id) ON DELETE CASCADE
+);
+
+CREATE INDEX expense_items_expense_id ON expense_items (expense_id);
+
+-- who shared an item, it is split evenly between them
+CREATE TABLE expense_item_consumers (
+    item_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    PRIMARY KEY (item_id, user_id),
+    FOREIGN KEY (item_id) REFERENCES expense_items(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,7 @@
+ALTER TABLE notifications DROP COLUMN emailed;
+
+DROP INDEX users_email_unsubscribe_token;
+
+ALTER TABLE users DROP COLUMN last_digest_at;
+ALTER TABLE users DROP COLUMN email_unsubscribe_token;
+ALTER TABLE users DROP COLUMN email_notifications;
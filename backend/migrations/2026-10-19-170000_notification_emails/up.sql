@@ -0,0 +1,11 @@
+-- how the notifications of a user are sent by email: `OFF`, `IMMEDIATE` or once a day in a `DIGEST`
+ALTER TABLE users ADD COLUMN email_notifications TEXT NOT NULL DEFAULT 'OFF';
+-- lets the user turn the emails off from the link at the bottom of every email
+ALTER TABLE users ADD COLUMN email_unsubscribe_token TEXT;
+ALTER TABLE users ADD COLUMN last_digest_at TIMESTAMP;
+
+CREATE UNIQUE INDEX users_email_unsubscribe_token ON users (email_unsubscribe_token);
+
+-- the notifications that were sent by email, or that will never be
+ALTER TABLE notifications ADD COLUMN emailed BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE notifications SET emailed = TRUE;
@@ -0,0 +1,36 @@
+-- sqlite cannot change a CHECK constraint, the notifications table is rebuilt with the new types
+ALTER TABLE notifications RENAME TO notifications_old;
+
+CREATE TABLE notifications (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    notified_user_id INTEGER NOT NULL,
+    notification_type TEXT CHECK (notification_type IN (
+        'NEW_EXPENSE', 'EXPENSE_DELETED', 'EXPENSE_MODIFIED',
+        'REMOVED_FROM_GROUP', 'GROUP_DELETED', 'ADMIN_PROMOTION',
+        'ADMIN_DEMOTION', 'FRIENDSHIP_REQUEST_ACCEPTED', 'FRIENDSHIP_REQUEST_DENIED',
+        'EXPENSE_COMMENT',
+        'GROUP_INVITE', 'MEMBER_ADDED')),
+    group_id INTEGER,
+    user_id INTEGER,
+    expense_id INTEGER,
+    creation_date TIMESTAMP NOT NULL,
+    read BOOLEAN NOT NULL DEFAULT FALSE,
+    revision_id INTEGER,
+    emailed BOOLEAN NOT NULL DEFAULT FALSE,
+    FOREIGN KEY (notified_user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE SET NULL,
+    FOREIGN KEY (expense_id) REFERENCES expenses(id) ON DELETE SET NULL,
+    FOREIGN KEY (revision_id) REFERENCES expense_revisions(id) ON DELETE SET NULL
+);
+
+INSERT INTO notifications (
+    id, notified_user_id, notification_type, group_id, user_id,
+    expense_id, creation_date, read, revision_id, emailed
+)
+SELECT
+    id, notified_user_id, notification_type, group_id, user_id,
+    expense_id, creation_date, read, revision_id, emailed
+FROM notifications_old;
+
+DROP TABLE notifications_old;
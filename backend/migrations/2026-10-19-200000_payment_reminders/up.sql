@@ -0,0 +1,65 @@
+-- a member reminds another one of what they owe, `sender_id` is null for the automatic reminders
+CREATE TABLE payment_reminders (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    group_id INTEGER NOT NULL,
+    sender_id INTEGER,
+    recipient_id INTEGER NOT NULL,
+    amount DOUBLE NOT NULL,
+    message TEXT,
+    created_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
+    FOREIGN KEY (sender_id) REFERENCES users(id) ON DELETE SET NULL,
+    FOREIGN KEY (recipient_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX payment_reminders_recipient ON payment_reminders (group_id, recipient_id, created_at);
+
+-- the members of a group that owe since more than `after_days` days are reminded automatically
+CREATE TABLE reminder_schedules (
+    group_id INTEGER PRIMARY KEY NOT NULL,
+    after_days INTEGER NOT NULL CHECK (after_days > 0),
+    updated_by INTEGER,
+    updated_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
+    FOREIGN KEY (updated_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+-- sqlite cannot change a CHECK constraint, the notifications table is rebuilt with the new type
+-- and the reminder a `BALANCE_REQUESTED` notification is about
+ALTER TABLE notifications RENAME TO notifications_old;
+
+CREATE TABLE notifications (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    notified_user_id INTEGER NOT NULL,
+    notification_type TEXT CHECK (notification_type IN (
+        'NEW_EXPENSE', 'EXPENSE_DELETED', 'EXPENSE_MODIFIED',
+        'REMOVED_FROM_GROUP', 'GROUP_DELETED', 'ADMIN_PROMOTION',
+        'ADMIN_DEMOTION', 'FRIENDSHIP_REQUEST_ACCEPTED', 'FRIENDSHIP_REQUEST_DENIED',
+        'EXPENSE_COMMENT', 'GROUP_INVITE', 'MEMBER_ADDED',
+        'BALANCE_REQUESTED')),
+    group_id INTEGER,
+    user_id INTEGER,
+    expense_id INTEGER,
+    creation_date TIMESTAMP NOT NULL,
+    read BOOLEAN NOT NULL DEFAULT FALSE,
+    revision_id INTEGER,
+    emailed BOOLEAN NOT NULL DEFAULT FALSE,
+    reminder_id INTEGER,
+    FOREIGN KEY (notified_user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE SET NULL,
+    FOREIGN KEY (expense_id) REFERENCES expenses(id) ON DELETE SET NULL,
+    FOREIGN KEY (revision_id) REFERENCES expense_revisions(id) ON DELETE SET NULL,
+    FOREIGN KEY (reminder_id) REFERENCES payment_reminders(id) ON DELETE SET NULL
+);
+
+INSERT INTO notifications (
+    id, notified_user_id, notification_type, group_id, user_id,
+    expense_id, creation_date, read, revision_id, emailed
+)
+SELECT
+    id, notified_user_id, notification_type, group_id, user_id,
+    expense_id, creation_date, read, revision_id, emailed
+FROM notifications_old;
+
+DROP TABLE notifications_old;
@@ -0,0 +1,10 @@
+DROP TABLE IF EXISTS notification_preferences;
+DROP TABLE IF EXISTS group_invites;
+DROP TABLE IF EXISTS notifications;
+DROP TABLE IF EXISTS expense_participations;
+DROP TABLE IF EXISTS expenses;
+DROP TABLE IF EXISTS group_members;
+DROP TABLE IF EXISTS group_administrators;
+DROP TABLE IF EXISTS groups;
+DROP TABLE IF EXISTS users;
+
@@ -0,0 +1,16 @@
+DROP TABLE IF EXISTS notifications;
+
+CREATE TABLE notifications (
+    id SERIAL PRIMARY KEY,
+    notified_user_id INTEGER NOT NULL,
+    notification_type TEXT CHECK (notification_type IN ( 'NEW_EXPENSE',  'EXPENSE_DELETED','EXPENSE_MODIFIED', 'REMOVED_FROM_GROUP', 'GROUP_DELETED', 'ADMIN_PROMOTION', 'ADMIN_DEMOTION','FRIENDSHIP_REQUEST_ACCEPTED','FRIENDSHIP_REQUEST_DENIED')),
+    group_id INTEGER,
+    user_id INTEGER,
+    expense_id INTEGER,
+    creation_date TIMESTAMP NOT NULL,
+    read BOOLEAN NOT NULL DEFAULT FALSE,
+    CONSTRAINT notifications_notified_user_id_fkey FOREIGN KEY (notified_user_id) REFERENCES users(id) ON DELETE CASCADE,
+    CONSTRAINT notifications_user_id_fkey FOREIGN KEY (user_id) REFERENCES users(id),
+    CONSTRAINT notifications_group_id_fkey FOREIGN KEY (group_id) REFERENCES groups(id),
+    CONSTRAINT notifications_expense_id_fkey FOREIGN KEY (expense_id) REFERENCES expenses(id)
+);
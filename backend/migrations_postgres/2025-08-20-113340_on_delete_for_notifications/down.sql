@@ -0,0 +1,9 @@
+ALTER TABLE notifications
+    DROP CONSTRAINT notifications_user_id_fkey,
+    DROP CONSTRAINT notifications_group_id_fkey,
+    DROP CONSTRAINT notifications_expense_id_fkey;
+
+ALTER TABLE notifications
+    ADD CONSTRAINT notifications_user_id_fkey FOREIGN KEY (user_id) REFERENCES users(id),
+    ADD CONSTRAINT notifications_group_id_fkey FOREIGN KEY (group_id) REFERENCES groups(id),
+    ADD CONSTRAINT notifications_expense_id_fkey FOREIGN KEY (expense_id) REFERENCES expenses(id);
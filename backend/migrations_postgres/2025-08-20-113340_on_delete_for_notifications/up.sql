@@ -0,0 +1,10 @@
+-- postgres can swap the foreign keys in place, no need to rebuild the table like on sqlite
+ALTER TABLE notifications
+    DROP CONSTRAINT notifications_user_id_fkey,
+    DROP CONSTRAINT notifications_group_id_fkey,
+    DROP CONSTRAINT notifications_expense_id_fkey;
+
+ALTER TABLE notifications
+    ADD CONSTRAINT notifications_user_id_fkey FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL,
+    ADD CONSTRAINT notifications_group_id_fkey FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE SET NULL,
+    ADD CONSTRAINT notifications_expense_id_fkey FOREIGN KEY (expense_id) REFERENCES expenses(id) ON DELETE SET NULL;
@@ -0,0 +1,15 @@
+-- `expense_date` is the day the expense refers to, chosen by the user, while `created_at` and
+-- `updated_at` are kept by the server. existing expenses are dated on the day they were created
+ALTER TABLE expenses RENAME COLUMN creation_date TO created_at;
+
+ALTER TABLE expenses
+    ADD COLUMN updated_at TIMESTAMP,
+    ADD COLUMN expense_date DATE;
+
+UPDATE expenses SET updated_at = created_at, expense_date = created_at::date;
+
+ALTER TABLE expenses
+    ALTER COLUMN updated_at SET NOT NULL,
+    ALTER COLUMN expense_date SET NOT NULL;
+
+CREATE INDEX expenses_group_id_expense_date ON expenses (group_id, expense_date, id);
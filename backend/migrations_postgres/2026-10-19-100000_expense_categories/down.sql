@@ -0,0 +1,3 @@
+ALTER TABLE expenses DROP COLUMN category_id;
+
+DROP TABLE categories;
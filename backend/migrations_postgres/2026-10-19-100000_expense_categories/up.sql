@@ -0,0 +1,15 @@
+-- categories without a group are the built-in ones, available to every expense, the others are
+-- defined by a group and only available to its expenses
+CREATE TABLE categories (
+    id SERIAL PRIMARY KEY,
+    group_id INTEGER,
+    name TEXT NOT NULL,
+    UNIQUE (group_id, name),
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+INSERT INTO categories (name) VALUES
+    ('food'), ('groceries'), ('transport'), ('lodging'), ('utilities'), ('rent'),
+    ('entertainment'), ('health'), ('shopping'), ('other');
+
+ALTER TABLE expenses ADD COLUMN category_id INTEGER REFERENCES categories(id) ON DELETE SET NULL;
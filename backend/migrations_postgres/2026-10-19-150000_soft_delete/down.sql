@@ -0,0 +1,5 @@
+DROP INDEX groups_deleted_at;
+DROP INDEX expenses_deleted_at;
+
+ALTER TABLE groups DROP COLUMN deleted_at;
+ALTER TABLE expenses DROP COLUMN deleted_at;
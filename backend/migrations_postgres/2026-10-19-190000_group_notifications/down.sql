@@ -0,0 +1,10 @@
+DELETE FROM notifications WHERE notification_type IN ('GROUP_INVITE', 'MEMBER_ADDED');
+
+ALTER TABLE notifications DROP CONSTRAINT notifications_notification_type_check;
+
+ALTER TABLE notifications ADD CONSTRAINT notifications_notification_type_check
+    CHECK (notification_type IN (
+        'NEW_EXPENSE', 'EXPENSE_DELETED', 'EXPENSE_MODIFIED',
+        'REMOVED_FROM_GROUP', 'GROUP_DELETED', 'ADMIN_PROMOTION',
+        'ADMIN_DEMOTION', 'FRIENDSHIP_REQUEST_ACCEPTED', 'FRIENDSHIP_REQUEST_DENIED',
+        'EXPENSE_COMMENT'));
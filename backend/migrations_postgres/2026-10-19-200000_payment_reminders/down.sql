@@ -0,0 +1,15 @@
+DELETE FROM notifications WHERE notification_type = 'BALANCE_REQUESTED';
+
+ALTER TABLE notifications DROP COLUMN reminder_id;
+
+ALTER TABLE notifications DROP CONSTRAINT notifications_notification_type_check;
+
+ALTER TABLE notifications ADD CONSTRAINT notifications_notification_type_check
+    CHECK (notification_type IN (
+        'NEW_EXPENSE', 'EXPENSE_DELETED', 'EXPENSE_MODIFIED',
+        'REMOVED_FROM_GROUP', 'GROUP_DELETED', 'ADMIN_PROMOTION',
+        'ADMIN_DEMOTION', 'FRIENDSHIP_REQUEST_ACCEPTED', 'FRIENDSHIP_REQUEST_DENIED',
+        'EXPENSE_COMMENT', 'GROUP_INVITE', 'MEMBER_ADDED'));
+
+DROP TABLE reminder_schedules;
+DROP TABLE payment_reminders;
@@ -0,0 +1,38 @@
+-- a member reminds another one of what they owe, `sender_id` is null for the automatic reminders
+CREATE TABLE payment_reminders (
+    id SERIAL PRIMARY KEY,
+    group_id INTEGER NOT NULL,
+    sender_id INTEGER,
+    recipient_id INTEGER NOT NULL,
+    amount DOUBLE PRECISION NOT NULL,
+    message TEXT,
+    created_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
+    FOREIGN KEY (sender_id) REFERENCES users(id) ON DELETE SET NULL,
+    FOREIGN KEY (recipient_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX payment_reminders_recipient ON payment_reminders (group_id, recipient_id, created_at);
+
+-- the members of a group that owe since more than `after_days` days are reminded automatically
+CREATE TABLE reminder_schedules (
+    group_id INTEGER PRIMARY KEY NOT NULL,
+    after_days INTEGER NOT NULL CHECK (after_days > 0),
+    updated_by INTEGER,
+    updated_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
+    FOREIGN KEY (updated_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+ALTER TABLE notifications DROP CONSTRAINT notifications_notification_type_check;
+
+ALTER TABLE notifications ADD CONSTRAINT notifications_notification_type_check
+    CHECK (notification_type IN (
+        'NEW_EXPENSE', 'EXPENSE_DELETED', 'EXPENSE_MODIFIED',
+        'REMOVED_FROM_GROUP', 'GROUP_DELETED', 'ADMIN_PROMOTION',
+        'ADMIN_DEMOTION', 'FRIENDSHIP_REQUEST_ACCEPTED', 'FRIENDSHIP_REQUEST_DENIED',
+        'EXPENSE_COMMENT', 'GROUP_INVITE', 'MEMBER_ADDED',
+        'BALANCE_REQUESTED'));
+
+-- the reminder a `BALANCE_REQUESTED` notification is about
+ALTER TABLE notifications ADD COLUMN reminder_id INTEGER REFERENCES payment_reminders(id) ON DELETE SET NULL;
@@ -0,0 +1,17 @@
+-- links that let anyone who has them join a group, until they expire, run out of uses or are
+-- revoked by an admin
+CREATE TABLE group_invite_links (
+    id SERIAL PRIMARY KEY,
+    group_id INTEGER NOT NULL,
+    token TEXT NOT NULL UNIQUE,
+    created_by INTEGER,
+    created_at TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    max_uses INTEGER CHECK (max_uses IS NULL OR max_uses > 0),
+    uses INTEGER NOT NULL DEFAULT 0,
+    revoked_at TIMESTAMP,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX group_invite_links_group_id ON group_invite_links (group_id, created_at);
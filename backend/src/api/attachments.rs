@@ -0,0 +1,326 @@
+//! files attached to group expenses, such as the pictures of their receipts. the members of the
+//! group can upload and download them, the files are kept in the [`Storage`] under the hash of
+//! their content, so a file attached to many expenses is stored once
+
+use crate::{
+    api::groups::group_expense,
+    config::Config,
+    db::DbConnection,
+    establish_connection,
+    guards::GroupMembership,
+    models::Attachment,
+    schema::attachments,
+    storage::{Storage, content_hash, sniff},
+};
+
+use diesel::{
+    ExpressionMethods, Insertable, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+    dsl::exists, result::Error::NotFound, select,
+};
+use rocket::{
+    State,
+    form::Form,
+    fs::TempFile,
+    http::{ContentType, Header, Status},
+    serde::json::Json,
+    tokio::io::AsyncReadExt,
+};
+use rocket_okapi::{
+    r#gen::OpenApiGenerator, okapi::openapi3::OpenApi, okapi::openapi3::Responses, openapi,
+    openapi_get_routes_spec, response::OpenApiResponderInner, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use std::sync::{Arc, Mutex};
+
+pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
+    openapi_get_routes_spec![settings:add_attachment,get_attachments,download_attachment,delete_attachment]
+}
+
+/// storing a file and recording it are serialized with checking whether a file is still needed
+/// and removing it, so that a file is never removed while it is being attached again
+static FILES: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, FromForm, JsonSchema)]
+pub struct Upload<'r> {
+    /// the file, a jpeg, png, gif or webp image or a pdf document
+    #[schemars(with = "Vec<u8>")]
+    pub file: TempFile<'r>,
+}
+
+/// the content of an attachment, shown by the browser rather than downloaded
+#[derive(Responder)]
+pub struct AttachmentFile {
+    data: (ContentType, Vec<u8>),
+    disposition: Header<'static>,
+    nosniff: Header<'static>,
+}
+
+impl OpenApiResponderInner for AttachmentFile {
+    fn responses(generator: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        <(ContentType, Vec<u8>)>::responses(generator)
+    }
+}
+
+/// the extension of the files of a content type among the supported ones
+fn extension(content_type: &str) -> &'static str {
+    match content_type {
+        "image/jpeg" => "jpg",
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        _ => "pdf",
+    }
+}
+
+/// helper function that loads attachment `aid` of expense `exid`
+fn expense_attachment(exid: i32, aid: i32) -> Result<Attachment, Status> {
+    let mut conn = establish_connection();
+
+    match attachments::table
+        .filter(attachments::id.eq(aid))
+        .filter(attachments::expense_id.eq(exid))
+        .first::<Attachment>(&mut conn)
+    {
+        Ok(a) => Ok(a),
+        Err(NotFound) => Err(Status::NotFound),
+        Err(e) => {
+            error!("error loading attachment: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// the keys of the files attached to the expenses `exids`, to be handed to
+/// [`remove_unreferenced`] once the expenses are deleted
+pub fn attached_files(conn: &mut DbConnection, exids: &[i32]) -> QueryResult<Vec<String>> {
+    attachments::table
+        .filter(attachments::expense_id.eq_any(exids))
+        .select(attachments::content_hash)
+        .distinct()
+        .get_results::<String>(conn)
+}
+
+/// removes from `storage` the files among `keys` that no attachment refers to anymore, failures
+/// are only logged since the attachments are already gone
+pub fn remove_unreferenced(storage: &dyn Storage, keys: Vec<String>) {
+    let _files = FILES.lock().unwrap_or_else(|e| e.into_inner());
+    let mut conn = establish_connection();
+
+    for key in keys {
+        match select(exists(
+            attachments::table.filter(attachments::content_hash.eq(&key)),
+        ))
+        .get_result::<bool>(&mut conn)
+        {
+            Ok(true) => (),
+            Ok(false) => {
+                if let Err(e) = storage.delete(&key) {
+                    error!("error removing attachment file {}: {}", key, e);
+                }
+            }
+            Err(e) => error!(
+                "error checking references to attachment file {}: {:?}",
+                key, e
+            ),
+        }
+    }
+}
+
+/// attaches a file to a group expense, only images and pdf documents up to
+/// `attachments.max_size` are accepted: `413 Payload Too Large` for larger files and
+/// `415 Unsupported Media Type` for the other ones. attaching the same file twice returns the
+/// attachment that is already there. needs to be executed by a member of the group
+#[openapi(tag = "GroupExpenses")]
+#[post("/<gid>/expenses/<exid>/attachments", data = "<upload>")]
+async fn add_attachment(
+    gid: i32,
+    exid: i32,
+    upload: Form<Upload<'_>>,
+    member: GroupMembership,
+    config: &State<Config>,
+    storage: &State<Arc<dyn Storage>>,
+) -> Result<Json<Attachment>, Status> {
+    group_expense(gid, exid)?;
+
+    let file = &upload.file;
+    if file.len() > config.attachments.max_size {
+        error!("attachment is larger than the maximum size");
+        return Err(Status::PayloadTooLarge);
+    }
+
+    let mut data = Vec::with_capacity(file.len() as usize);
+    if let Err(e) = async { file.open().await?.read_to_end(&mut data).await }.await {
+        error!("error reading uploaded attachment: {}", e);
+        return Err(Status::InternalServerError);
+    }
+
+    let content_type = match sniff(&data) {
+        Some(t) if config.attachments.allowed_types.iter().any(|a| a == t) => t,
+        _ => {
+            error!("attachment is not of an allowed type");
+            return Err(Status::UnsupportedMediaType);
+        }
+    };
+    let file_name = format!(
+        "{}.{}",
+        file.name()
+            .filter(|n| !n.is_empty())
+            .unwrap_or("attachment"),
+        extension(content_type)
+    );
+    let key = content_hash(&data);
+
+    let _files = FILES.lock().unwrap_or_else(|e| e.into_inner());
+    let mut conn = establish_connection();
+
+    let stored = attachments::table
+        .filter(attachments::content_hash.eq(&key))
+        .select(attachments::expense_id)
+        .get_results::<i32>(&mut conn)
+        .map_err(|e| {
+            error!(
+                "error looking for an attachment with the same content: {:?}",
+                e
+            );
+            Status::InternalServerError
+        })?;
+
+    if stored.contains(&exid) {
+        return match attachments::table
+            .filter(attachments::expense_id.eq(exid))
+            .filter(attachments::content_hash.eq(&key))
+            .first::<Attachment>(&mut conn)
+        {
+            Ok(a) => Ok(Json(a)),
+            Err(e) => {
+                error!("error loading attachment: {:?}", e);
+                Err(Status::InternalServerError)
+            }
+        };
+    }
+
+    if stored.is_empty()
+        && let Err(e) = storage.put(&key, &data)
+    {
+        error!("error storing attachment file: {}", e);
+        return Err(Status::InternalServerError);
+    }
+
+    match (
+        attachments::expense_id.eq(exid),
+        attachments::uploaded_by.eq(member.user.id),
+        attachments::file_name.eq(&file_name),
+        attachments::content_type.eq(content_type),
+        attachments::size.eq(data.len() as i64),
+        attachments::content_hash.eq(&key),
+        attachments::created_at.eq(diesel::dsl::now),
+    )
+        .insert_into(attachments::table)
+        .get_result::<Attachment>(&mut conn)
+    {
+        Ok(a) => Ok(Json(a)),
+        Err(e) => {
+            error!("error inserting attachment: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// returns the attachments of a group expense, oldest first, needs to be executed by a member of
+/// the group
+#[openapi(tag = "GroupExpenses")]
+#[get("/<gid>/expenses/<exid>/attachments")]
+fn get_attachments(
+    gid: i32,
+    exid: i32,
+    _member: GroupMembership,
+) -> Result<Json<Vec<Attachment>>, Status> {
+    group_expense(gid, exid)?;
+
+    let mut conn = establish_connection();
+
+    match attachments::table
+        .filter(attachments::expense_id.eq(exid))
+        .order(attachments::id)
+        .get_results::<Attachment>(&mut conn)
+    {
+        Ok(v) => Ok(Json(v)),
+        Err(e) => {
+            error!("error loading attachments: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// returns the content of an attachment, needs to be executed by a member of the group
+#[openapi(tag = "GroupExpenses")]
+#[get("/<gid>/expenses/<exid>/attachments/<aid>")]
+fn download_attachment(
+    gid: i32,
+    exid: i32,
+    aid: i32,
+    _member: GroupMembership,
+    storage: &State<Arc<dyn Storage>>,
+) -> Result<AttachmentFile, Status> {
+    group_expense(gid, exid)?;
+    let attachment = expense_attachment(exid, aid)?;
+
+    let data = storage.get(&attachment.content_hash).map_err(|e| {
+        error!("error reading attachment file: {}", e);
+        Status::InternalServerError
+    })?;
+    let content_type =
+        ContentType::parse_flexible(&attachment.content_type).unwrap_or(ContentType::Binary);
+    let file_name: String = attachment
+        .file_name
+        .chars()
+        .filter(|c| *c != '"' && *c != '\\')
+        .collect();
+
+    Ok(AttachmentFile {
+        data: (content_type, data),
+        disposition: Header::new(
+            "Content-Disposition",
+            format!("inline; filename=\"{}\"", file_name),
+        ),
+        nosniff: Header::new("X-Content-Type-Options", "nosniff"),
+    })
+}
+
+/// removes an attachment, can only be performed by who uploaded it, who paid for the expense or
+/// an admin of the group. the file is removed too unless it is attached elsewhere
+#[openapi(tag = "GroupExpenses")]
+#[delete("/<gid>/expenses/<exid>/attachments/<aid>")]
+fn delete_attachment(
+    gid: i32,
+    exid: i32,
+    aid: i32,
+    member: GroupMembership,
+    storage: &State<Arc<dyn Storage>>,
+) -> Result<Json<Attachment>, Status> {
+    let expense = group_expense(gid, exid)?;
+    let attachment = expense_attachment(exid, aid)?;
+
+    let usrid = member.user.id;
+    if attachment.uploaded_by != Some(usrid) && expense.paid_by != usrid && !member.is_admin {
+        error!("trying to delete attachment but user is not its uploader, the payer or admin");
+        return Err(Status::Forbidden);
+    }
+
+    let mut conn = establish_connection();
+
+    match diesel::delete(attachments::table.filter(attachments::id.eq(aid)))
+        .get_result::<Attachment>(&mut conn)
+        .optional()
+    {
+        Ok(Some(a)) => {
+            remove_unreferenced(storage.inner().as_ref(), vec![a.content_hash.clone()]);
+            Ok(Json(a))
+        }
+        Ok(None) => Err(Status::NotFound),
+        Err(e) => {
+            error!("error deleting attachment: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! comments on group expenses, so that the members can discuss an expense where it is recorded.
+//! every member of the group can read and write them, only their authors can edit or delete them
+
+use crate::{
+    api::groups::group_expense,
+    api::pagination::{Cursor, Page, Param, SortOrder, keyset, page_limit},
+    establish_connection,
+    guards::GroupMembership,
+    models::ExpenseComment,
+    notify::{Notice, NotificationKind, dispatch, notify},
+    schema::{expense_comments, expense_participations},
+};
+
+use chrono::NaiveDateTime;
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, Insertable, QueryDsl, RunQueryDsl,
+    connection::Connection, result::Error::NotFound,
+};
+use rocket::{http::Status, serde::json::Json};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
+    openapi_get_routes_spec![settings:get_comments,add_comment,update_comment,delete_comment]
+}
+
+/// longest comment accepted, in characters
+pub const MAX_COMMENT_LENGTH: usize = 2000;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PutComment {
+    pub body: String,
+}
+
+impl PutComment {
+    /// the text of the comment without the surrounding whitespace, `400 Bad Request` if it is
+    /// empty or longer than [`MAX_COMMENT_LENGTH`]
+    fn text(&self) -> Result<&str, Status> {
+        let text = self.body.trim();
+        if text.is_empty() || text.chars().count() > MAX_COMMENT_LENGTH {
+            error!("comment is empty or too long");
+            return Err(Status::BadRequest);
+        }
+        Ok(text)
+    }
+}
+
+/// pagination of the comments of an expense, the oldest come first
+#[derive(Debug, FromForm, JsonSchema)]
+pub struct CommentQuery {
+    /// `next_cursor` of the previous page
+    pub cursor: Param<String>,
+    /// comments in the page, 50 by default and 100 at most
+    pub limit: Param<i64>,
+}
+
+/// helper function that loads comment `cid` of expense `exid` and checks that it was written by
+/// `usrid`, the only one that can modify it
+fn own_comment(exid: i32, cid: i32, usrid: i32) -> Result<ExpenseComment, Status> {
+    let mut conn = establish_connection();
+
+    let comment = match expense_comments::table
+        .filter(expense_comments::id.eq(cid))
+        .filter(expense_comments::expense_id.eq(exid))
+        .first::<ExpenseComment>(&mut conn)
+    {
+        Ok(c) => c,
+        Err(NotFound) => return Err(Status::NotFound),
+        Err(e) => {
+            error!("error loading expense comment: {:?}", e);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    if comment.author_id == usrid {
+        Ok(comment)
+    } else {
+        error!("trying to modify comment but user is not its author");
+        Err(Status::Forbidden)
+    }
+}
+
+/// returns the comments of a group expense, oldest first and paginated, needs to be executed by a
+/// member of the group
+#[openapi(tag = "GroupExpenses")]
+#[get("/<gid>/expenses/<exid>/comments?<query..>")]
+fn get_comments(
+    gid: i32,
+    exid: i32,
+    _member: GroupMembership,
+    query: CommentQuery,
+) -> Result<Json<Page<ExpenseComment>>, Status> {
+    group_expense(gid, exid)?;
+    let limit = page_limit(query.limit.value())?;
+    let cursor = Cursor::<NaiveDateTime>::from_query(&query.cursor)?;
+
+    let q = expense_comments::table
+        .filter(expense_comments::expense_id.eq(exid))
+        .into_boxed();
+    let q = keyset!(
+        q,
+        expense_comments::created_at,
+        expense_comments::id,
+        SortOrder::Asc,
+        cursor
+    );
+
+    let mut conn = establish_connection();
+
+    match q.limit(limit + 1).get_results::<ExpenseComment>(&mut conn) {
+        Ok(v) => Ok(Json(Page::from_rows(v, limit, |c| {
+            Cursor::new(c.created_at, c.id).encode()
+        }))),
+        Err(e) => {
+            error!("error loading expense comments: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// comments a group expense, the ones that paid for or take part in the expense are notified,
+/// except the author of the comment. needs to be executed by a member of the group
+#[openapi(tag = "GroupExpenses")]
+#[post("/<gid>/expenses/<exid>/comments", data = "<comment>")]
+fn add_comment(
+    gid: i32,
+    exid: i32,
+    comment: Json<PutComment>,
+    member: GroupMembership,
+) -> Result<Json<ExpenseComment>, Status> {
+    let expense = group_expense(gid, exid)?;
+    let text = comment.text()?;
+    let author = member.user.id;
+
+    let mut conn = establish_connection();
+
+    match conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let comment = (
+            expense_comments::expense_id.eq(exid),
+            expense_comments::author_id.eq(author),
+            expense_comments::body.eq(text),
+            expense_comments::created_at.eq(diesel::dsl::now),
+            expense_comments::updated_at.eq(diesel::dsl::now),
+        )
+            .insert_into(expense_comments::table)
+            .get_result::<ExpenseComment>(conn)?;
+
+        let mut notified = expense_participations::table
+            .filter(expense_participations::expense_id.eq(exid))
+            .select(expense_participations::user_id)
+            .get_results::<i32>(conn)?;
+        notified.push(expense.paid_by);
+
+        let sent = notify(
+            conn,
+            &Notice::new(NotificationKind::ExpenseComment, author)
+                .in_group(gid)
+                .about_expense(exid),
+            &notified,
+        )?;
+
+        Ok((comment, sent))
+    }) {
+        Ok((c, sent)) => {
+            dispatch(&sent);
+            Ok(Json(c))
+        }
+        Err(e) => {
+            error!("error running add_comment transaction: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// edits a comment, can only be performed by its author while still a member of the group
+#[openapi(tag = "GroupExpenses")]
+#[put("/<gid>/expenses/<exid>/comments/<cid>", data = "<comment>")]
+fn update_comment(
+    gid: i32,
+    exid: i32,
+    cid: i32,
+    comment: Json<PutComment>,
+    member: GroupMembership,
+) -> Result<Json<ExpenseComment>, Status> {
+    group_expense(gid, exid)?;
+    own_comment(exid, cid, member.user.id)?;
+    let text = comment.text()?;
+
+    let mut conn = establish_connection();
+
+    match diesel::update(expense_comments::table.filter(expense_comments::id.eq(cid)))
+        .set((
+            expense_comments::body.eq(text),
+            expense_comments::updated_at.eq(diesel::dsl::now),
+        ))
+        .get_result::<ExpenseComment>(&mut conn)
+    {
+        Ok(c) => Ok(Json(c)),
+        Err(e) => {
+            error!("error updating expense comment: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// deletes a comment, can only be performed by its author while still a member of the group
+#[openapi(tag = "GroupExpenses")]
+#[delete("/<gid>/expenses/<exid>/comments/<cid>")]
+fn delete_comment(
+    gid: i32,
+    exid: i32,
+    cid: i32,
+    member: GroupMembership,
+) -> Result<Json<ExpenseComment>, Status> {
+    group_expense(gid, exid)?;
+    own_comment(exid, cid, member.user.id)?;
+
+    let mut conn = establish_connection();
+
+    match diesel::delete(expense_comments::table.filter(expense_comments::id.eq(cid)))
+        .get_result::<ExpenseComment>(&mut conn)
+    {
+        Ok(c) => Ok(Json(c)),
+        Err(NotFound) => Err(Status::NotFound),
+        Err(e) => {
+            error!("error deleting expense comment: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
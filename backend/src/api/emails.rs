@@ -0,0 +1,618 @@
+//! notifications by email. every user chooses whether to receive them as soon as they are created,
+//! in a daily digest or not at all, and every email carries a link to turn them off without
+//! logging in. the emails are written in the preferred language of the user.
+//!
+//! the notifications keep track of whether they were emailed, so a background task sends what is
+//! left whenever a notification is created and at the configured interval, and the digests once a
+//! day at `notification_emails.digest_hour`
+
+use crate::{
+    config::{Config, NotificationEmailsConfig},
+    db::DbConnection,
+    establish_connection,
+    mail::{Email, Mailer},
+    models::{Notification, User},
+    notify::{Channel, NotificationKind, register_channel},
+    schema::{expenses, groups, notifications, payment_reminders, users},
+};
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+    ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+    connection::Connection,
+};
+use rocket::{fairing::AdHoc, http::Status, serde::json::Json, tokio::sync::Notify};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
+    openapi_get_routes_spec![settings:get_email_delivery,set_email_delivery,unsubscribe]
+}
+
+/// how a user receives the notifications by email
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum EmailDelivery {
+    /// no emails, the default
+    Off,
+    /// an email for every notification, as soon as it is created
+    Immediate,
+    /// an email a day with the notifications that were not read yet
+    Digest,
+}
+
+impl EmailDelivery {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EmailDelivery::Off => "OFF",
+            EmailDelivery::Immediate => "IMMEDIATE",
+            EmailDelivery::Digest => "DIGEST",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "OFF" => Some(EmailDelivery::Off),
+            "IMMEDIATE" => Some(EmailDelivery::Immediate),
+            "DIGEST" => Some(EmailDelivery::Digest),
+            _ => None,
+        }
+    }
+
+    pub fn of(user: &User) -> Self {
+        EmailDelivery::parse(&user.email_notifications).unwrap_or(EmailDelivery::Off)
+    }
+}
+
+/// returns how the requesting user receives the notifications by email: `OFF`, `IMMEDIATE` or
+/// `DIGEST`
+#[openapi(tag = "Notifications")]
+#[get("/email")]
+fn get_email_delivery(user: User) -> Json<EmailDelivery> {
+    Json(EmailDelivery::of(&user))
+}
+
+/// sets how the requesting user receives the notifications by email: `OFF`, `IMMEDIATE` or
+/// `DIGEST`. the notifications created while the emails were off are not sent, the first digest
+/// is sent the day after it is chosen
+#[openapi(tag = "Notifications")]
+#[put("/email/<delivery>")]
+fn set_email_delivery(user: User, delivery: &str) -> Result<Json<EmailDelivery>, Status> {
+    let Some(delivery) = EmailDelivery::parse(delivery) else {
+        error!("unknown email delivery `{}`", delivery);
+        return Err(Status::BadRequest);
+    };
+
+    let mut conn = establish_connection();
+
+    match conn.transaction::<(), diesel::result::Error, _>(|conn| {
+        if EmailDelivery::of(&user) == EmailDelivery::Off && delivery != EmailDelivery::Off {
+            diesel::update(notifications::table)
+                .filter(notifications::notified_user_id.eq(user.id))
+                .set(notifications::emailed.eq(true))
+                .execute(conn)?;
+        }
+        if user.email_unsubscribe_token.is_none() {
+            diesel::update(users::table.find(user.id))
+                .set(users::email_unsubscribe_token.eq(Uuid::new_v4().simple().to_string()))
+                .execute(conn)?;
+        }
+        if delivery == EmailDelivery::Digest && EmailDelivery::of(&user) != delivery {
+            diesel::update(users::table.find(user.id))
+                .set(users::last_digest_at.eq(Utc::now().naive_utc()))
+                .execute(conn)?;
+        }
+
+        diesel::update(users::table.find(user.id))
+            .set(users::email_notifications.eq(delivery.as_str()))
+            .execute(conn)?;
+        Ok(())
+    }) {
+        Ok(()) => Ok(Json(delivery)),
+        Err(e) => {
+            error!("error setting email delivery: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// turns off the notification emails of the user the `token` of an unsubscribe link belongs to,
+/// does not need to be logged in
+#[openapi(tag = "Notifications")]
+#[post("/email/unsubscribe/<token>")]
+fn unsubscribe(token: &str) -> Result<(), Status> {
+    let mut conn = establish_connection();
+
+    match diesel::update(users::table.filter(users::email_unsubscribe_token.eq(token)))
+        .set(users::email_notifications.eq(EmailDelivery::Off.as_str()))
+        .execute(&mut conn)
+    {
+        Ok(0) => Err(Status::NotFound),
+        Ok(_) => Ok(()),
+        Err(e) => {
+            error!("error unsubscribing from emails: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+// ####################################################################
+// ########################|TEMPLATES|#################################
+// ####################################################################
+
+/// the languages the emails are written in, chosen by `preferred_language`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Language {
+    Italian,
+    English,
+}
+
+impl Language {
+    /// italian unless the user asked for english
+    fn of(user: &User) -> Self {
+        match user.preferred_language.starts_with("en") {
+            true => Language::English,
+            false => Language::Italian,
+        }
+    }
+}
+
+/// the fixed parts of the emails
+struct Texts {
+    someone: &'static str,
+    greeting: &'static str,
+    digest_subject: &'static str,
+    digest_intro: &'static str,
+    see_all: &'static str,
+    unsubscribe: &'static str,
+}
+
+const ITALIAN: Texts = Texts {
+    someone: "Qualcuno",
+    greeting: "Ciao",
+    digest_subject: "il riepilogo delle tue notifiche",
+    digest_intro: "ecco cosa è successo:",
+    see_all: "Vedi le tue notifiche:",
+    unsubscribe: "Non vuoi più ricevere queste email?",
+};
+
+const ENGLISH: Texts = Texts {
+    someone: "Someone",
+    greeting: "Hi",
+    digest_subject: "your notification digest",
+    digest_intro: "here is what happened:",
+    see_all: "See your notifications:",
+    unsubscribe: "Don't want these emails anymore?",
+};
+
+fn texts(language: Language) -> &'static Texts {
+    match language {
+        Language::Italian => &ITALIAN,
+        Language::English => &ENGLISH,
+    }
+}
+
+/// one sentence that says what notification `n` is about
+fn describe(conn: &mut DbConnection, n: &Notification, language: Language) -> QueryResult<String> {
+    let actor = match n.user_id {
+        Some(uid) => users::table
+            .find(uid)
+            .select(users::username)
+            .first::<String>(conn)
+            .optional()?,
+        None => None,
+    }
+    .unwrap_or_else(|| texts(language).someone.to_owned());
+    let group = match n.group_id {
+        Some(gid) => groups::table
+            .find(gid)
+            .select(groups::group_name)
+            .first::<String>(conn)
+            .optional()?,
+        None => None,
+    };
+    let expense = match n.expense_id {
+        Some(exid) => expenses::table
+            .find(exid)
+            .select(expenses::desc)
+            .first::<String>(conn)
+            .optional()?,
+        None => None,
+    };
+
+    let reminder = match n.reminder_id {
+        Some(rid) => payment_reminders::table
+            .find(rid)
+            .select((payment_reminders::amount, payment_reminders::message))
+            .first::<(f64, Option<String>)>(conn)
+            .optional()?,
+        None => None,
+    };
+    let note = reminder
+        .as_ref()
+        .and_then(|(_, m)| m.as_ref())
+        .map(|m| format!(": \"{}\"", m))
+        .unwrap_or_default();
+    let owed = |unknown: &str| match &reminder {
+        Some((amount, _)) => format!("{:.2} €", amount),
+        None => unknown.to_owned(),
+    };
+
+    let Some(kind) = n
+        .notification_type
+        .as_deref()
+        .and_then(NotificationKind::parse)
+    else {
+        return Ok(match language {
+            Language::Italian => "Hai una nuova notifica".to_owned(),
+            Language::English => "You have a new notification".to_owned(),
+        });
+    };
+
+    let named = |article: &str, name: &Option<String>, unknown: &str| match name {
+        Some(name) => format!("{} \"{}\"", article, name),
+        None => unknown.to_owned(),
+    };
+
+    Ok(match language {
+        Language::Italian => {
+            let e = named("la spesa", &expense, "una spesa");
+            let place = group
+                .as_ref()
+                .map(|g| format!(" nel gruppo \"{}\"", g))
+                .unwrap_or_default();
+            match kind {
+                NotificationKind::NewExpense => format!("{} ha aggiunto {}{}", actor, e, place),
+                NotificationKind::ExpenseDeleted => {
+                    format!("{} ha eliminato {}{}", actor, e, place)
+                }
+                NotificationKind::ExpenseRestored => {
+                    format!("{} ha ripristinato {}{}", actor, e, place)
+                }
+                NotificationKind::ExpenseModified => {
+                    format!("{} ha modificato {}{}", actor, e, place)
+                }
+                NotificationKind::ExpenseComment => {
+                    format!("{} ha commentato {}{}", actor, e, place)
+                }
+                NotificationKind::RemovedFromGroup => format!(
+                    "{} ti ha rimosso {}",
+                    actor,
+                    named("dal gruppo", &group, "da un gruppo")
+                ),
+                NotificationKind::GroupDeleted => format!(
+                    "{} ha eliminato {}",
+                    actor,
+                    named("il gruppo", &group, "un gruppo")
+                ),
+                NotificationKind::GroupRestored => format!(
+                    "{} ha ripristinato {}",
+                    actor,
+                    named("il gruppo", &group, "un gruppo")
+                ),
+                NotificationKind::AdminPromotion => format!(
+                    "{} ti ha nominato amministratore {}",
+                    actor,
+                    named("del gruppo", &group, "di un gruppo")
+                ),
+                NotificationKind::AdminDemotion => format!(
+                    "{} ti ha tolto il ruolo di amministratore {}",
+                    actor,
+                    named("del gruppo", &group, "di un gruppo")
+                ),
+                NotificationKind::GroupInvite => format!(
+                    "{} ti ha invitato {}",
+                    actor,
+                    named("nel gruppo", &group, "in un gruppo")
+                ),
+                NotificationKind::MemberAdded => format!(
+                    "{} ti ha aggiunto {}",
+                    actor,
+                    named("al gruppo", &group, "a un gruppo")
+                ),
+                NotificationKind::MemberJoined => format!(
+                    "{} è entrato {} con un link di invito",
+                    actor,
+                    named("nel gruppo", &group, "in un gruppo")
+                ),
+                NotificationKind::BalanceRequested if n.user_id.is_none() => {
+                    format!("Ti ricordiamo che devi {}{}", owed("dei soldi"), place)
+                }
+                NotificationKind::BalanceRequested => format!(
+                    "{} ti ricorda che devi {}{}{}",
+                    actor,
+                    owed("dei soldi"),
+                    place,
+                    note
+                ),
+                NotificationKind::FriendshipRequestAccepted => {
+                    format!("{} ha accettato la tua richiesta di amicizia", actor)
+                }
+                NotificationKind::FriendshipRequestDenied => {
+                    format!("{} ha rifiutato la tua richiesta di amicizia", actor)
+                }
+            }
+        }
+        Language::English => {
+            let e = named("the expense", &expense, "an expense");
+            let place = group
+                .as_ref()
+                .map(|g| format!(" in the group \"{}\"", g))
+                .unwrap_or_default();
+            match kind {
+                NotificationKind::NewExpense => format!("{} added {}{}", actor, e, place),
+                NotificationKind::ExpenseDeleted => format!("{} deleted {}{}", actor, e, place),
+                NotificationKind::ExpenseRestored => format!("{} restored {}{}", actor, e, place),
+                NotificationKind::ExpenseModified => format!("{} changed {}{}", actor, e, place),
+                NotificationKind::ExpenseComment => {
+                    format!("{} commented on {}{}", actor, e, place)
+                }
+                NotificationKind::RemovedFromGroup => format!(
+                    "{} removed you from {}",
+                    actor,
+                    named("the group", &group, "a group")
+                ),
+                NotificationKind::GroupDeleted => format!(
+                    "{} deleted {}",
+                    actor,
+                    named("the group", &group, "a group")
+                ),
+                NotificationKind::GroupRestored => format!(
+                    "{} restored {}",
+                    actor,
+                    named("the group", &group, "a group")
+                ),
+                NotificationKind::AdminPromotion => format!(
+                    "{} made you an admin of {}",
+                    actor,
+                    named("the group", &group, "a group")
+                ),
+                NotificationKind::AdminDemotion => format!(
+                    "{} removed you as an admin of {}",
+                    actor,
+                    named("the group", &group, "a group")
+                ),
+                NotificationKind::GroupInvite => format!(
+                    "{} invited you to {}",
+                    actor,
+                    named("the group", &group, "a group")
+                ),
+                NotificationKind::MemberAdded => format!(
+                    "{} added you to {}",
+                    actor,
+                    named("the group", &group, "a group")
+                ),
+                NotificationKind::MemberJoined => format!(
+                    "{} joined {} with an invite link",
+                    actor,
+                    named("the group", &group, "a group")
+                ),
+                NotificationKind::BalanceRequested if n.user_id.is_none() => {
+                    format!("A reminder that you owe {}{}", owed("money"), place)
+                }
+                NotificationKind::BalanceRequested => format!(
+                    "{} reminds you that you owe {}{}{}",
+                    actor,
+                    owed("money"),
+                    place,
+                    note
+                ),
+                NotificationKind::FriendshipRequestAccepted => {
+                    format!("{} accepted your friend request", actor)
+                }
+                NotificationKind::FriendshipRequestDenied => {
+                    format!("{} declined your friend request", actor)
+                }
+            }
+        }
+    })
+}
+
+/// an email to `user` with `subject`, made of `lines` between the greeting and the links
+fn compose(
+    user: &User,
+    config: &NotificationEmailsConfig,
+    subject: String,
+    lines: &[String],
+) -> Email {
+    let t = texts(Language::of(user));
+    let app_url = config.app_url.trim_end_matches('/');
+    let unsubscribe = user
+        .email_unsubscribe_token
+        .as_ref()
+        .map(|token| format!("{}/unsubscribe?token={}", app_url, token));
+
+    let mut body = format!(
+        "{} {},\n\n{}\n\n",
+        t.greeting,
+        user.username,
+        lines.join("\n")
+    );
+    body.push_str(&format!("{} {}/notifications\n", t.see_all, app_url));
+    if let Some(url) = &unsubscribe {
+        body.push_str(&format!("\n{} {}\n", t.unsubscribe, url));
+    }
+
+    Email {
+        to: user.email.clone(),
+        subject: format!("SplitSmart: {}", subject),
+        body,
+        unsubscribe,
+    }
+}
+
+// ####################################################################
+// ##########################|SENDING|#################################
+// ####################################################################
+
+/// the notifications of `user` that were not emailed yet, oldest first
+fn pending(conn: &mut DbConnection, user: &User) -> QueryResult<Vec<Notification>> {
+    notifications::table
+        .filter(notifications::notified_user_id.eq(user.id))
+        .filter(notifications::emailed.eq(false))
+        .order((notifications::creation_date.asc(), notifications::id.asc()))
+        .get_results::<Notification>(conn)
+}
+
+/// records that `n` was emailed, unless it was brought up to date in the meantime
+fn mark_emailed(conn: &mut DbConnection, n: &Notification) -> QueryResult<usize> {
+    diesel::update(
+        notifications::table
+            .filter(notifications::id.eq(n.id))
+            .filter(notifications::creation_date.eq(n.creation_date)),
+    )
+    .set(notifications::emailed.eq(true))
+    .execute(conn)
+}
+
+/// the users that receive the emails with `delivery`, only user `uid` if given
+fn recipients(
+    conn: &mut DbConnection,
+    delivery: EmailDelivery,
+    uid: Option<i32>,
+) -> QueryResult<Vec<User>> {
+    let mut q = users::table
+        .filter(users::email_notifications.eq(delivery.as_str()))
+        .into_boxed();
+    if let Some(uid) = uid {
+        q = q.filter(users::id.eq(uid));
+    }
+    q.order(users::id).get_results::<User>(conn)
+}
+
+/// sends the notifications that were not emailed yet to the users that want them immediately and,
+/// if `now` is past today's digest hour, the digests that were not sent today. only to user `uid`
+/// if given. the notifications that were read in the meantime are not sent. returns the number of
+/// emails sent, the ones that could not be sent are tried again on the next run
+pub fn send_emails(
+    mailer: &dyn Mailer,
+    config: &NotificationEmailsConfig,
+    now: NaiveDateTime,
+    uid: Option<i32>,
+) -> QueryResult<usize> {
+    let mut conn = establish_connection();
+    let mut sent = 0;
+
+    for user in recipients(&mut conn, EmailDelivery::Immediate, uid)? {
+        for n in pending(&mut conn, &user)? {
+            if !n.read {
+                let sentence = describe(&mut conn, &n, Language::of(&user))?;
+                let email = compose(&user, config, sentence.clone(), &[format!("{}.", sentence)]);
+                if let Err(e) = mailer.send(&email) {
+                    error!("error sending notification email to {}: {}", user.email, e);
+                    break;
+                }
+                sent += 1;
+            }
+            mark_emailed(&mut conn, &n)?;
+        }
+    }
+
+    let due = now
+        .date()
+        .and_hms_opt(config.digest_hour, 0, 0)
+        .unwrap_or(now);
+    if now < due {
+        return Ok(sent);
+    }
+
+    for user in recipients(&mut conn, EmailDelivery::Digest, uid)? {
+        if user.last_digest_at.is_some_and(|last| last >= due) {
+            continue;
+        }
+
+        let notifications = pending(&mut conn, &user)?;
+        let t = texts(Language::of(&user));
+        let mut lines = vec![t.digest_intro.to_owned()];
+        for n in notifications.iter().filter(|n| !n.read) {
+            lines.push(format!(
+                "- {}",
+                describe(&mut conn, n, Language::of(&user))?
+            ));
+        }
+        if lines.len() > 1 {
+            let email = compose(&user, config, t.digest_subject.to_owned(), &lines);
+            if let Err(e) = mailer.send(&email) {
+                error!("error sending notification digest to {}: {}", user.email, e);
+                continue;
+            }
+            sent += 1;
+        }
+
+        conn.transaction::<(), diesel::result::Error, _>(|conn| {
+            for n in notifications.iter() {
+                mark_emailed(conn, n)?;
+            }
+            diesel::update(users::table.find(user.id))
+                .set(users::last_digest_at.eq(now))
+                .execute(conn)?;
+            Ok(())
+        })?;
+    }
+
+    Ok(sent)
+}
+
+/// wakes the task that sends the emails whenever a notification is created, which is committed by
+/// then
+struct Wake(Arc<Notify>);
+
+impl Channel for Wake {
+    fn send(&self, _notification: &Notification) {
+        self.0.notify_one();
+    }
+}
+
+/// the background task that sends the notification emails, at launch, whenever a notification
+/// is created and at the configured interval, until the rocket shuts down. it does not run when
+/// emails are disabled
+pub fn sender() -> AdHoc {
+    AdHoc::on_liftoff("Notification emails", |rocket| {
+        Box::pin(async move {
+            let (Some(config), Some(mailer)) =
+                (rocket.state::<Config>(), rocket.state::<Arc<dyn Mailer>>())
+            else {
+                return;
+            };
+
+            let config = config.notification_emails.clone();
+            let mailer = mailer.clone();
+            let woken = Arc::new(Notify::new());
+            let wake: Arc<dyn Channel> = Arc::new(Wake(woken.clone()));
+            register_channel(&wake);
+            let period = Duration::from_secs(config.interval_secs);
+            let mut shutdown = rocket.shutdown();
+            rocket::tokio::spawn(async move {
+                // registered for as long as the task runs
+                let _wake = wake;
+                let mut interval = rocket::tokio::time::interval(period);
+                loop {
+                    rocket::tokio::select! {
+                        _ = interval.tick() => (),
+                        _ = woken.notified() => (),
+                        _ = &mut shutdown => break,
+                    }
+
+                    let config = config.clone();
+                    let mailer = mailer.clone();
+                    match rocket::tokio::task::spawn_blocking(move || {
+                        send_emails(mailer.as_ref(), &config, Utc::now().naive_utc(), None)
+                    })
+                    .await
+                    {
+                        Ok(Ok(0)) => (),
+                        Ok(Ok(sent)) => info!("sent {} notification emails", sent),
+                        Ok(Err(e)) => error!("error sending notification emails: {:?}", e),
+                        Err(e) => error!("notification email task failed: {:?}", e),
+                    }
+                }
+            });
+        })
+    })
+}
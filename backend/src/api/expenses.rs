@@ -1,15 +1,24 @@
 use crate::{
+    api::pagination::{Cursor, Page, Param, QueryDate, SortOrder, keyset, page_limit},
+    api::receipts::{PutExpenseItem, split_items},
+    api::trash::{check_restorable, restorable_since},
+    config::Config,
+    db::DbBackend,
     establish_connection,
-    models::{Expense, ExpenseParticipation, Group, GroupInvite, GroupMember, User},
-    schema::{
-        expense_participations, expenses, group_administrators, group_invites, group_members,
-        notifications,
-    },
+    models::{Expense, ExpenseParticipation, User},
+    notify::{Notice, NotificationKind, dispatch, notify},
+    schema::{categories, expense_participations, expenses},
 };
 
-use diesel::{connection::Connection, result::Error::NotFound, SelectableHelper};
-use diesel::{dsl::exists, select, ExpressionMethods, Insertable, QueryDsl, RunQueryDsl};
-use rocket::{http::Status, serde::json::Json};
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use diesel::sql_types::Text;
+use diesel::{
+    BelongingToDsl, BoolExpressionMethods, EscapeExpressionMethods, ExpressionMethods, GroupedBy,
+    Insertable, QueryDsl, RunQueryDsl, TextExpressionMethods, define_sql_function, dsl::exists,
+    select,
+};
+use diesel::{connection::Connection, result::Error::NotFound};
+use rocket::{State, http::Status, serde::json::Json};
 use rocket_okapi::{
     okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
 };
@@ -17,7 +26,7 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
-    openapi_get_routes_spec![settings:add_private_expense,get_private_expenses,delete_private_expense,update_private_expense]
+    openapi_get_routes_spec![settings:add_private_expense,get_private_expenses,delete_private_expense,update_private_expense,get_private_trash,restore_private_expense]
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
@@ -25,22 +34,108 @@ pub struct PutExpense {
     pub desc: String,
     pub total_amount: f64,
     pub paid_by: i32,
+    /// how the expense is divided, can be left out when it has `items`
+    #[serde(default)]
     pub division: Vec<(i32, f64)>,
+    /// the day the expense refers to, today when adding an expense without it, left unchanged
+    /// when updating an expense without it
+    pub expense_date: Option<NaiveDate>,
+    /// the category of the expense, a built-in one or, for group expenses, one of the group, the
+    /// expense has no category without it
+    pub category_id: Option<i32>,
+    /// the line items of the receipt, when present the division is computed from them and the
+    /// one sent is ignored: every item is split evenly between its consumers and what
+    /// `total_amount` exceeds the items by, the tax and the tip, in proportion to what everyone
+    /// consumed. the items are kept, and can be listed, for group expenses only
+    #[serde(default)]
+    pub items: Option<Vec<PutExpenseItem>>,
+}
+
+impl PutExpense {
+    /// the date of a new expense
+    pub fn date_or_today(&self) -> NaiveDate {
+        self.expense_date.unwrap_or_else(|| Utc::now().date_naive())
+    }
+
+    /// replaces the division with the one computed from the items, if any, returns `400 Bad
+    /// Request` if the items cannot describe the expense
+    pub fn itemize(&mut self) -> Result<(), Status> {
+        let Some(items) = &self.items else {
+            return Ok(());
+        };
+
+        match split_items(items, self.total_amount) {
+            Some(division) => {
+                self.division = division;
+                Ok(())
+            }
+            None => {
+                error!(
+                    "the items do not add up to an expense of {}",
+                    self.total_amount
+                );
+                Err(Status::BadRequest)
+            }
+        }
+    }
+
+    /// checks that the category, if any, can be used by an expense of group `gid`, or by a private
+    /// expense when `gid` is `None`, returns `400 Bad Request` if it cannot
+    pub fn check_category(&self, gid: Option<i32>) -> Result<(), Status> {
+        let Some(cid) = self.category_id else {
+            return Ok(());
+        };
+
+        let mut conn = establish_connection();
+
+        let mut query = categories::table
+            .filter(categories::id.eq(cid))
+            .into_boxed();
+        query = match gid {
+            Some(gid) => query.filter(
+                categories::group_id
+                    .is_null()
+                    .or(categories::group_id.eq(gid)),
+            ),
+            None => query.filter(categories::group_id.is_null()),
+        };
+
+        match select(exists(query)).get_result::<bool>(&mut conn) {
+            Ok(true) => Ok(()),
+            Ok(false) => {
+                error!("category {} cannot be used by the expense", cid);
+                Err(Status::BadRequest)
+            }
+            Err(e) => {
+                error!("error checking expense category: {:?}", e);
+                Err(Status::InternalServerError)
+            }
+        }
+    }
 }
 
 /// adds a private expense, the division array specifies how the expense is divided: 'division: Vec<(i32, f64)>'
 #[openapi(tag = "PrivateExpenses")]
 #[post("/", data = "<new_expense>")]
-fn add_private_expense(new_expense: Json<PutExpense>, user: User) -> Result<Json<Expense>, Status> {
+fn add_private_expense(
+    mut new_expense: Json<PutExpense>,
+    user: User,
+) -> Result<Json<Expense>, Status> {
     let mut conn = establish_connection();
 
+    new_expense.itemize()?;
+    new_expense.check_category(None)?;
+
     // TODO: check that the division array sum equals the total
-    match conn.transaction::<Expense, diesel::result::Error, _>(|conn| {
+    match conn.transaction::<_, diesel::result::Error, _>(|conn| {
         let expense = (
             expenses::desc.eq(new_expense.desc.clone()),
             expenses::total_amount.eq(new_expense.total_amount),
             expenses::paid_by.eq(new_expense.paid_by),
-            expenses::creation_date.eq(diesel::dsl::now),
+            expenses::expense_date.eq(new_expense.date_or_today()),
+            expenses::category_id.eq(new_expense.category_id),
+            expenses::created_at.eq(diesel::dsl::now),
+            expenses::updated_at.eq(diesel::dsl::now),
             //expenses::group_id.eq(None),
         )
             .insert_into(expenses::table)
@@ -54,21 +149,21 @@ fn add_private_expense(new_expense: Json<PutExpense>, user: User) -> Result<Json
             )
                 .insert_into(expense_participations::table)
                 .execute(conn)?;
-
-            (
-                notifications::notified_user_id.eq(d),
-                notifications::notification_type.eq("NEW_EXPENSE"),
-                notifications::expense_id.eq(expense.id),
-                notifications::user_id.eq(user.id),
-                notifications::creation_date.eq(diesel::dsl::now),
-            )
-                .insert_into(notifications::table)
-                .execute(conn)?;
         }
 
-        Ok(expense)
+        let participants: Vec<i32> = new_expense.division.iter().map(|(u, _)| *u).collect();
+        let sent = notify(
+            conn,
+            &Notice::new(NotificationKind::NewExpense, user.id).about_expense(expense.id),
+            &participants,
+        )?;
+
+        Ok((expense, sent))
     }) {
-        Ok(e) => Ok(Json(e)),
+        Ok((e, sent)) => {
+            dispatch(&sent);
+            Ok(Json(e))
+        }
         Err(e) => {
             error!("error running add_expense transaction: {:?}", e);
             Err(Status::InternalServerError)
@@ -76,77 +171,215 @@ fn add_private_expense(new_expense: Json<PutExpense>, user: User) -> Result<Json
     }
 }
 
-type ExpenseList = Vec<(Expense, Vec<ExpenseParticipation>)>;
+/// an expense along with how it is divided
+pub type ExpenseWithParticipations = (Expense, Vec<ExpenseParticipation>);
+pub type ExpenseList = Vec<ExpenseWithParticipations>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, FromFormField, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpenseSort {
+    #[default]
+    #[field(value = "date")]
+    Date,
+    #[field(value = "amount")]
+    Amount,
+}
+
+/// filters, sorting and pagination of the expense lists, every filter is optional
+#[derive(Debug, FromForm, JsonSchema)]
+pub struct ExpenseQuery {
+    /// only the expenses dated on this day, formatted as `YYYY-MM-DD`, or later
+    pub from: Param<QueryDate>,
+    /// only the expenses dated on this day, formatted as `YYYY-MM-DD`, or earlier
+    pub to: Param<QueryDate>,
+    /// only the expenses paid by this user
+    pub paid_by: Param<i32>,
+    /// only the expenses this user takes part in
+    pub participant: Param<i32>,
+    pub min_amount: Param<f64>,
+    pub max_amount: Param<f64>,
+    /// only the expenses of this category
+    pub category: Param<i32>,
+    /// case insensitive text to look for in the description
+    pub search: Param<String>,
+    /// `date` (default), the date of the expense, or `amount`
+    pub sort: Param<ExpenseSort>,
+    /// `asc` or `desc` (default)
+    pub order: Param<SortOrder>,
+    /// `next_cursor` of the previous page
+    pub cursor: Param<String>,
+    /// expenses in the page, 50 by default and 100 at most
+    pub limit: Param<i64>,
+}
+
+define_sql_function!(fn lower(x: Text) -> Text);
+
+/// loads the page of expenses, with their participations, selected by `query` among the ones
+/// selected by `base`, used both for group and private expenses
+pub fn expense_page(
+    base: expenses::BoxedQuery<'static, DbBackend>,
+    query: &ExpenseQuery,
+) -> Result<Json<Page<ExpenseWithParticipations>>, Status> {
+    let limit = page_limit(query.limit.value())?;
+    let mut q = base;
+
+    if let Some(from) = query.from.value() {
+        q = q.filter(expenses::expense_date.ge(from.0));
+    }
+    if let Some(to) = query.to.value() {
+        q = q.filter(expenses::expense_date.le(to.0));
+    }
+    if let Some(payer) = query.paid_by.value() {
+        q = q.filter(expenses::paid_by.eq(payer));
+    }
+    if let Some(participant) = query.participant.value() {
+        q = q.filter(
+            expenses::id.eq_any(
+                expense_participations::table
+                    .filter(expense_participations::user_id.eq(participant))
+                    .select(expense_participations::expense_id),
+            ),
+        );
+    }
+    if let Some(min) = query.min_amount.value() {
+        q = q.filter(expenses::total_amount.ge(min));
+    }
+    if let Some(max) = query.max_amount.value() {
+        q = q.filter(expenses::total_amount.le(max));
+    }
+    if let Some(category) = query.category.value() {
+        q = q.filter(expenses::category_id.eq(category));
+    }
+    if let Some(text) = query.search.get() {
+        // the wildcards typed by the user are matched literally
+        let escaped = text
+            .to_lowercase()
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        q = q.filter(
+            lower(expenses::desc)
+                .like(format!("%{}%", escaped))
+                .escape('\\'),
+        );
+    }
+
+    let order = query.order.or_default();
+    let sort = query.sort.or_default();
+    let q = match sort {
+        ExpenseSort::Date => {
+            let cursor = Cursor::<NaiveDate>::from_query(&query.cursor)?;
+            keyset!(q, expenses::expense_date, expenses::id, order, cursor)
+        }
+        ExpenseSort::Amount => {
+            let cursor = Cursor::<f64>::from_query(&query.cursor)?;
+            keyset!(q, expenses::total_amount, expenses::id, order, cursor)
+        }
+    };
 
-/// returns all the information about the user private expenses, the ones paid by him and the ones
-/// where he was included, including the participations
-#[openapi(tag = "PrivateExpenses")]
-#[get("/")]
-fn get_private_expenses(
-    user: User,
-) -> Result<Json<Vec<(Expense, Vec<ExpenseParticipation>)>>, Status> {
     let mut conn = establish_connection();
 
     match conn.transaction::<ExpenseList, diesel::result::Error, _>(|conn| {
-        let exp_ids = expense_participations::table
-            .filter(expense_participations::user_id.eq(user.id))
-            .select(expense_participations::expense_id)
-            .get_results::<i32>(conn)?;
-        let expenses = expenses::table
-            .filter(expenses::group_id.is_null())
-            .filter(expenses::id.eq_any(exp_ids))
-            .get_results::<Expense>(conn)?;
-        let mut v: ExpenseList = Vec::new();
-        for e in expenses {
-            let participations = expense_participations::table
-                .filter(expense_participations::expense_id.eq(e.id))
-                .get_results::<ExpenseParticipation>(conn)?;
-            v.push((e, participations));
-        }
-        Ok(v)
+        // two queries whatever the size of the page: the expenses, then all their participations
+        let expenses = q.limit(limit + 1).get_results::<Expense>(conn)?;
+        let participations = ExpenseParticipation::belonging_to(&expenses)
+            .get_results::<ExpenseParticipation>(conn)?
+            .grouped_by(&expenses);
+        Ok(expenses.into_iter().zip(participations).collect())
     }) {
-        Ok(e) => Ok(Json(e)),
+        Ok(v) => Ok(Json(Page::from_rows(v, limit, |(e, _)| match sort {
+            ExpenseSort::Date => Cursor::new(e.expense_date, e.id).encode(),
+            ExpenseSort::Amount => Cursor::new(e.total_amount, e.id).encode(),
+        }))),
         Err(e) => {
-            error!("error running get_expenses transaction: {:?}", e);
+            error!("error loading expense page: {:?}", e);
             Err(Status::InternalServerError)
         }
     }
 }
 
-/// deletes private expense, needs to be performed by expense creator
+/// returns the user private expenses, the ones paid by him and the ones where he was included,
+/// including the participations. the expenses are paginated and can be filtered and sorted
+#[openapi(tag = "PrivateExpenses")]
+#[get("/?<query..>")]
+fn get_private_expenses(
+    user: User,
+    query: ExpenseQuery,
+) -> Result<Json<Page<ExpenseWithParticipations>>, Status> {
+    let base = expenses::table
+        .filter(expenses::group_id.is_null())
+        .filter(expenses::deleted_at.is_null())
+        .filter(
+            expenses::id.eq_any(
+                expense_participations::table
+                    .filter(expense_participations::user_id.eq(user.id))
+                    .select(expense_participations::expense_id),
+            ),
+        )
+        .into_boxed();
+
+    expense_page(base, &query)
+}
+
+/// helper function that loads private expense `exid`, in the trash if `deleted`, and checks that it
+/// was paid by `user`, the only one that can modify it
+fn own_private_expense(exid: i32, user: &User, deleted: bool) -> Result<Expense, Status> {
+    let mut conn = establish_connection();
+
+    let expense = match expenses::table
+        .filter(expenses::id.eq(exid))
+        .filter(expenses::group_id.is_null())
+        .filter(expenses::deleted_at.is_not_null().eq(deleted))
+        .first::<Expense>(&mut conn)
+    {
+        Ok(e) => e,
+        Err(NotFound) => return Err(Status::NotFound),
+        Err(e) => {
+            error!("error loading private expense: {:?}", e);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    if expense.paid_by == user.id {
+        Ok(expense)
+    } else {
+        error!("trying to modify expense but user is not creator of expense");
+        Err(Status::Forbidden)
+    }
+}
+
+/// moves a private expense to the trash, from where it can be restored for
+/// `trash.retention_days`, needs to be performed by expense creator
 #[openapi(tag = "PrivateExpenses")]
 #[delete("/<exid>")]
 fn delete_private_expense(exid: i32, user: User) -> Result<Json<Expense>, Status> {
     let mut conn = establish_connection();
 
-    match conn.transaction::<Expense, diesel::result::Error, _>(|conn| {
-        let res = expense_participations::table
+    own_private_expense(exid, &user, false)?;
+
+    match conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let participants = expense_participations::table
             .filter(expense_participations::expense_id.eq(exid))
-            .get_results::<ExpenseParticipation>(conn)?;
+            .select(expense_participations::user_id)
+            .get_results::<i32>(conn)?;
 
         // notify users of expense deletion
-        for ep in res {
-            (
-                notifications::notified_user_id.eq(ep.user_id),
-                notifications::notification_type.eq("EXPENSE_DELETED"),
-                notifications::user_id.eq(user.id),
-                notifications::creation_date.eq(diesel::dsl::now),
-            )
-                .insert_into(notifications::table)
-                .execute(conn)?;
-        }
+        let sent = notify(
+            conn,
+            &Notice::new(NotificationKind::ExpenseDeleted, user.id).about_expense(exid),
+            &participants,
+        )?;
 
-        let expense = diesel::delete(expenses::table.filter(expenses::id.eq(exid)))
+        let expense = diesel::update(expenses::table.filter(expenses::id.eq(exid)))
+            .set(expenses::deleted_at.eq(diesel::dsl::now))
             .get_result::<Expense>(conn)?;
 
-        if expense.paid_by != user.id {
-            error!("trying to delete expense but user is not creator of expense");
-            return Err(diesel::result::Error::RollbackTransaction);
-        };
-
-        Ok(expense)
+        Ok((expense, sent))
     }) {
-        Ok(e) => Ok(Json(e)),
+        Ok((e, sent)) => {
+            dispatch(&sent);
+            Ok(Json(e))
+        }
         Err(e) => {
             error!("error running delete_expense transaction: {:?}", e);
             Err(Status::InternalServerError)
@@ -154,23 +387,96 @@ fn delete_private_expense(exid: i32, user: User) -> Result<Json<Expense>, Status
     }
 }
 
-/// updates a private expense, the division array specifies how the expense is divided: 'division: Vec<(i32, f64)>'
+/// returns the private expenses paid by the user that are in the trash and can still be restored,
+/// paginated like the private expenses
+#[openapi(tag = "PrivateExpenses")]
+#[get("/trash?<query..>")]
+fn get_private_trash(
+    user: User,
+    query: ExpenseQuery,
+    config: &State<Config>,
+) -> Result<Json<Page<ExpenseWithParticipations>>, Status> {
+    let base = expenses::table
+        .filter(expenses::group_id.is_null())
+        .filter(expenses::paid_by.eq(user.id))
+        .filter(expenses::deleted_at.ge(restorable_since(&config.trash)))
+        .into_boxed();
+
+    expense_page(base, &query)
+}
+
+/// takes a private expense out of the trash and tells its participants, needs to be performed by
+/// expense creator within `trash.retention_days` of its deletion
+#[openapi(tag = "PrivateExpenses")]
+#[post("/<exid>/restore")]
+fn restore_private_expense(
+    exid: i32,
+    user: User,
+    config: &State<Config>,
+) -> Result<Json<Expense>, Status> {
+    let expense = own_private_expense(exid, &user, true)?;
+    if let Some(deleted) = expense.deleted_at {
+        check_restorable(deleted, &config.trash)?;
+    }
+
+    let mut conn = establish_connection();
+
+    match conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let participants = expense_participations::table
+            .filter(expense_participations::expense_id.eq(exid))
+            .select(expense_participations::user_id)
+            .get_results::<i32>(conn)?;
+
+        // told like they were of the deletion
+        let sent = notify(
+            conn,
+            &Notice::new(NotificationKind::ExpenseRestored, user.id).about_expense(exid),
+            &participants,
+        )?;
+
+        let expense = diesel::update(expenses::table.filter(expenses::id.eq(exid)))
+            .set(expenses::deleted_at.eq(None::<NaiveDateTime>))
+            .get_result::<Expense>(conn)?;
+
+        Ok((expense, sent))
+    }) {
+        Ok((e, sent)) => {
+            dispatch(&sent);
+            Ok(Json(e))
+        }
+        Err(e) => {
+            error!("error restoring private expense: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// updates a private expense, the division array specifies how the expense is divided: 'division: Vec<(i32, f64)>',
+/// needs to be performed by expense creator
 #[openapi(tag = "PrivateExpenses")]
 #[put("/<exid>", data = "<new_expense>")]
 fn update_private_expense(
-    new_expense: Json<PutExpense>,
+    mut new_expense: Json<PutExpense>,
     user: User,
     exid: i32,
 ) -> Result<Json<Expense>, Status> {
     let mut conn = establish_connection();
 
-    match conn.transaction::<Expense, diesel::result::Error, _>(|conn| {
+    own_private_expense(exid, &user, false)?;
+    new_expense.itemize()?;
+    new_expense.check_category(None)?;
+
+    match conn.transaction::<_, diesel::result::Error, _>(|conn| {
         let expense = diesel::update(expenses::table.filter(expenses::id.eq(exid)))
             .set((
                 expenses::desc.eq(new_expense.desc.clone()),
                 expenses::total_amount.eq(new_expense.total_amount),
                 expenses::paid_by.eq(new_expense.paid_by),
-                expenses::creation_date.eq(diesel::dsl::now),
+                new_expense
+                    .expense_date
+                    .map(|d| expenses::expense_date.eq(d)),
+                expenses::category_id.eq(new_expense.category_id),
+                expenses::updated_at.eq(diesel::dsl::now),
             ))
             .get_result::<Expense>(conn)?;
 
@@ -187,21 +493,21 @@ fn update_private_expense(
             )
                 .insert_into(expense_participations::table)
                 .execute(conn)?;
-
-            (
-                notifications::notified_user_id.eq(d),
-                notifications::notification_type.eq("EXPENSE_MODIFIED"),
-                notifications::expense_id.eq(expense.id),
-                notifications::user_id.eq(user.id),
-                notifications::creation_date.eq(diesel::dsl::now),
-            )
-                .insert_into(notifications::table)
-                .execute(conn)?;
         }
 
-        Ok(expense)
+        let participants: Vec<i32> = new_expense.division.iter().map(|(u, _)| *u).collect();
+        let sent = notify(
+            conn,
+            &Notice::new(NotificationKind::ExpenseModified, user.id).about_expense(expense.id),
+            &participants,
+        )?;
+
+        Ok((expense, sent))
     }) {
-        Ok(e) => Ok(Json(e)),
+        Ok((e, sent)) => {
+            dispatch(&sent);
+            Ok(Json(e))
+        }
         Err(e) => {
             error!("error running add_expense transaction: {:?}", e);
             Err(Status::InternalServerError)
@@ -1,6 +1,6 @@
 use diesel::{
-    result::Error, BoolExpressionMethods, Connection, ExpressionMethods, Insertable, QueryDsl,
-    RunQueryDsl,
+    BoolExpressionMethods, Connection, ExpressionMethods, Insertable, QueryDsl, RunQueryDsl,
+    result::Error,
 };
 use rocket::{http::Status, serde::json::Json};
 use rocket_okapi::{
@@ -11,8 +11,9 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     establish_connection,
-    models::{FriendInvite, Friendship, Group, User},
-    schema::{friend_invites, friendships, notifications},
+    models::{FriendInvite, Friendship, User},
+    notify::{Notice, NotificationKind, dispatch, notify},
+    schema::{friend_invites, friendships},
 };
 
 pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
@@ -97,6 +98,7 @@ fn invite_friend(invite: Json<InviteUser>, user: User) -> Result<Json<FriendInvi
         .first::<User>(&mut conn)
     {
         Ok(usr) => usr.id,
+        Err(Error::NotFound) => return Err(Status::NotFound),
         Err(_) => return Err(Status::InternalServerError),
     };
 
@@ -124,7 +126,7 @@ fn invite_friend(invite: Json<InviteUser>, user: User) -> Result<Json<FriendInvi
 fn accept_invite(user: User, invite_id: i32) -> Result<Json<FriendInvite>, Status> {
     let mut conn = establish_connection();
 
-    let res = conn.transaction::<FriendInvite, diesel::result::Error, _>(|conn| {
+    let res = conn.transaction::<_, diesel::result::Error, _>(|conn| {
         // try to update the single affected friend invite and mark it as accepted
         let invite = diesel::update(
             friend_invites::table
@@ -137,7 +139,7 @@ fn accept_invite(user: User, invite_id: i32) -> Result<Json<FriendInvite>, Statu
         .get_result::<FriendInvite>(conn)?;
 
         // add friendship to database
-        let friendship = (
+        (
             friendships::user1.eq(invite.inviting_user_id.min(user.id)),
             friendships::user2.eq(user.id.max(invite.inviting_user_id)),
         )
@@ -145,23 +147,26 @@ fn accept_invite(user: User, invite_id: i32) -> Result<Json<FriendInvite>, Statu
             .execute(conn)?;
 
         // add notification of FRIENDSHIP_REQUEST_ACCEPTED
-        (
-            notifications::notified_user_id.eq(invite.inviting_user_id),
-            notifications::notification_type.eq("FRIENDSHIP_REQUEST_ACCEPTED"),
-            notifications::user_id.eq(invite.invited_user_id),
-            notifications::creation_date.eq(diesel::dsl::now),
-        )
-            .insert_into(notifications::table)
-            .execute(conn)?;
-
-        Ok(invite)
+        let sent = notify(
+            conn,
+            &Notice::new(
+                NotificationKind::FriendshipRequestAccepted,
+                invite.invited_user_id,
+            ),
+            &[invite.inviting_user_id],
+        )?;
+
+        Ok((invite, sent))
     });
     match res {
-        Ok(v) => Ok(Json(v)),
-        Err(Error::NotFound) => return Err(Status::NotFound),
+        Ok((v, sent)) => {
+            dispatch(&sent);
+            Ok(Json(v))
+        }
+        Err(Error::NotFound) => Err(Status::NotFound),
         Err(e) => {
             error!("error trying to accept friendship invite: {:?}", e);
-            return Err(Status::InternalServerError);
+            Err(Status::InternalServerError)
         }
     }
 }
@@ -173,7 +178,7 @@ fn accept_invite(user: User, invite_id: i32) -> Result<Json<FriendInvite>, Statu
 fn reject_invite(user: User, invite_id: i32) -> Result<Json<FriendInvite>, Status> {
     let mut conn = establish_connection();
 
-    let res = conn.transaction::<FriendInvite, diesel::result::Error, _>(|conn| {
+    let res = conn.transaction::<_, diesel::result::Error, _>(|conn| {
         let invite = diesel::update(
             friend_invites::table
                 // find the unique friend invite
@@ -184,19 +189,22 @@ fn reject_invite(user: User, invite_id: i32) -> Result<Json<FriendInvite>, Statu
         .set(friend_invites::invite_status.eq("REJECTED"))
         .get_result::<FriendInvite>(conn)?;
 
-        (
-            notifications::notified_user_id.eq(invite.inviting_user_id),
-            notifications::notification_type.eq("FRIENDSHIP_REQUEST_DENIED"),
-            notifications::user_id.eq(invite.invited_user_id),
-            notifications::creation_date.eq(diesel::dsl::now),
-        )
-            .insert_into(notifications::table)
-            .execute(conn)?;
+        let sent = notify(
+            conn,
+            &Notice::new(
+                NotificationKind::FriendshipRequestDenied,
+                invite.invited_user_id,
+            ),
+            &[invite.inviting_user_id],
+        )?;
 
-        Ok(invite)
+        Ok((invite, sent))
     });
     match res {
-        Ok(v) => Ok(Json(v)),
+        Ok((v, sent)) => {
+            dispatch(&sent);
+            Ok(Json(v))
+        }
         Err(Error::NotFound) => Err(Status::NotFound),
         Err(e) => {
             error!("error trying to reject friendship invite: {:?}", e);
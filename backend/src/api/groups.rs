@@ -1,26 +1,40 @@
 use crate::{
+    api::expenses::{ExpenseQuery, ExpenseWithParticipations, PutExpense, expense_page},
+    api::history::record_revision,
+    api::live::{GroupEvent, GroupEventKind, publish, record},
+    api::pagination::{Cursor, Page, Param, QueryDate, SortOrder, keyset, page_limit},
+    api::receipts::store_items,
+    db::DbConnection,
     establish_connection,
-    models::{Expense, ExpenseParticipation, Group, GroupInvite, GroupMember, User},
+    guards::{GroupAdmin, GroupMembership},
+    models::{Category, Expense, Group, GroupInvite, GroupMember, Notification, User},
+    notify::{Notice, NotificationKind, dispatch, notify},
     schema::{
-        expense_participations, expenses, group_administrators, group_invites, group_members,
-        notifications,
+        categories, expense_participations, expenses, group_administrators, group_invites,
+        group_members,
     },
 };
 
-use diesel::{connection::Connection, result::Error::NotFound, SelectableHelper};
-use diesel::{dsl::exists, select, ExpressionMethods, Insertable, QueryDsl, RunQueryDsl};
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, Insertable, QueryDsl, RunQueryDsl,
+    dsl::{self, count_star, exists},
+    select,
+};
+use diesel::{connection::Connection, result::Error::NotFound};
 use rocket::{http::Status, serde::json::Json};
 use rocket_okapi::{
     okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 use crate::schema;
 use crate::schema::groups::dsl::*;
 
 pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
-    openapi_get_routes_spec![settings:create_group, get_groups,get_group,update_group,delete_group,add_member,invite_user,remove_member,promote_to_admin,demote_admin,add_expense,get_expenses,update_expense,delete_expense,view_members,view_admins]
+    openapi_get_routes_spec![settings:create_group, get_groups,get_group,update_group,delete_group,add_member,invite_user,remove_member,promote_to_admin,demote_admin,add_expense,get_expenses,update_expense,delete_expense,view_members,view_admins,view_categories,create_category,delete_category,category_report]
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
@@ -28,7 +42,8 @@ pub struct PutUser {
     user_id: i32,
 }
 
-/// helper function to check if user with id `usrid` is member to group with id `gid`
+/// helper function to check if user with id `usrid` is member to group with id `gid`, used on the
+/// target of an operation, the user making the request is checked by the group guards
 fn is_member(gid: i32, usrid: i32) -> Result<(), Status> {
     use crate::schema::group_members::dsl::*;
 
@@ -42,7 +57,7 @@ fn is_member(gid: i32, usrid: i32) -> Result<(), Status> {
     .get_result::<bool>(&mut conn)
     {
         Ok(true) => Ok(()),
-        Ok(false) => Err(Status::Unauthorized),
+        Ok(false) => Err(Status::NotFound),
         Err(_) => {
             error!("internal error while checking if user is group member");
             Err(Status::InternalServerError)
@@ -50,26 +65,31 @@ fn is_member(gid: i32, usrid: i32) -> Result<(), Status> {
     }
 }
 
-/// helper function to check if user with id `usrid` is admin to group with id `gid`
-fn is_admin(gid: i32, usrid: i32) -> Result<(), Status> {
+/// helper function to check that removing admin rights from user with id `usrid` does not leave
+/// the group with id `gid` without admins, to be run in the transaction that removes them.
+/// returns `409 Conflict` if it would
+fn not_last_admin(
+    conn: &mut DbConnection,
+    gid: i32,
+    usrid: i32,
+) -> Result<Result<(), Status>, diesel::result::Error> {
     use crate::schema::group_administrators::dsl::*;
 
-    let mut conn = establish_connection();
-
-    match select(exists(
-        group_administrators
-            .filter(group_id.eq(gid))
-            .filter(user_id.eq(usrid)),
-    ))
-    .get_result::<bool>(&mut conn)
-    {
-        Ok(true) => Ok(()),
-        Ok(false) => Err(Status::Unauthorized),
-        Err(_) => {
-            error!("internal error while checking if user is group member");
-            Err(Status::InternalServerError)
-        }
+    let admins = group_administrators
+        .filter(group_id.eq(gid))
+        .select(user_id);
+    // on postgres the admins are locked until the transaction ends, so that two admins removing
+    // each other at the same time cannot both pass the check. sqlite has no row locks, but it
+    // lets only one of the two transactions write and fails the other one
+    #[cfg(feature = "postgres")]
+    let admins = admins.for_update().get_results::<i32>(conn)?;
+    #[cfg(feature = "sqlite")]
+    let admins = admins.get_results::<i32>(conn)?;
+    if admins == [usrid] {
+        error!("trying to remove the last admin of group {}", gid);
+        return Ok(Err(Status::Conflict));
     }
+    Ok(Ok(()))
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
@@ -122,34 +142,78 @@ fn create_group(new_group: Json<PutGroup>, user: User) -> Result<Json<Group>, St
     }
 }
 
-/// returns all the groups the user is a member of
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, FromFormField, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupSort {
+    #[field(value = "name")]
+    Name,
+    #[default]
+    #[field(value = "created")]
+    Created,
+}
+
+/// sorting and pagination of the group list
+#[derive(Debug, FromForm, JsonSchema)]
+pub struct GroupQuery {
+    /// `name` or `created` (default)
+    pub sort: Param<GroupSort>,
+    /// `asc` or `desc` (default)
+    pub order: Param<SortOrder>,
+    /// `next_cursor` of the previous page
+    pub cursor: Param<String>,
+    /// groups in the page, 50 by default and 100 at most
+    pub limit: Param<i64>,
+}
+
+/// returns the groups the user is a member of, paginated
 #[openapi(tag = "Groups")]
-#[get("/")]
-fn get_groups(user: User) -> Result<Json<Vec<Group>>, Status> {
-    let mut conn = establish_connection();
+#[get("/?<query..>")]
+fn get_groups(user: User, query: GroupQuery) -> Result<Json<Page<Group>>, Status> {
+    let limit = page_limit(query.limit.value())?;
+    let order = query.order.or_default();
+    let sort = query.sort.or_default();
+
+    let q = schema::groups::table
+        .filter(deleted_at.is_null())
+        .filter(
+            id.eq_any(
+                group_members::table
+                    .filter(group_members::user_id.eq(user.id))
+                    .select(group_members::group_id),
+            ),
+        )
+        .into_boxed();
+    let q = match sort {
+        GroupSort::Name => {
+            let cursor = Cursor::<String>::from_query(&query.cursor)?;
+            keyset!(q, group_name, id, order, cursor)
+        }
+        GroupSort::Created => {
+            let cursor = Cursor::<NaiveDateTime>::from_query(&query.cursor)?;
+            keyset!(q, creation_date, id, order, cursor)
+        }
+    };
 
-    let res = diesel::QueryDsl::select(
-        schema::groups::table
-            .inner_join(group_members::table)
-            .filter(group_members::user_id.eq(user.id)),
-        Group::as_select(),
-    )
-    .load::<Group>(&mut conn);
+    let mut conn = establish_connection();
 
-    match res {
-        Ok(v) => Ok(Json(v)),
-        Err(_) => Err(Status::InternalServerError),
+    match q.limit(limit + 1).load::<Group>(&mut conn) {
+        Ok(v) => Ok(Json(Page::from_rows(v, limit, |g| match sort {
+            GroupSort::Name => Cursor::new(g.group_name.clone(), g.id).encode(),
+            GroupSort::Created => Cursor::new(g.creation_date, g.id).encode(),
+        }))),
+        Err(e) => {
+            error!("error loading groups: {:?}", e);
+            Err(Status::InternalServerError)
+        }
     }
 }
 
-/// returns requested group by id
+/// returns requested group by id, needs to be executed by a member of the group
 #[openapi(tag = "Groups")]
 #[get("/<gid>")]
-fn get_group(gid: i32, user: User) -> Result<Json<Group>, Status> {
+fn get_group(gid: i32, _member: GroupMembership) -> Result<Json<Group>, Status> {
     let mut conn = establish_connection();
 
-    //is_member(gid, user.id)?;
-
     match groups.filter(id.eq(gid)).first::<Group>(&mut conn) {
         Ok(group) => Ok(Json(group)),
         Err(_) => Err(Status::NotFound),
@@ -159,11 +223,9 @@ fn get_group(gid: i32, user: User) -> Result<Json<Group>, Status> {
 /// updates existing_group, needs to be executed by an admin of the group
 #[openapi(tag = "Groups")]
 #[put("/<gid>", data = "<new_group>")]
-fn update_group(gid: i32, new_group: Json<PutGroup>, user: User) -> Result<Status, Status> {
+fn update_group(gid: i32, new_group: Json<PutGroup>, _admin: GroupAdmin) -> Result<Status, Status> {
     let mut conn = establish_connection();
 
-    is_admin(gid, user.id)?;
-
     let group = groups.filter(id.eq(gid)).first::<Group>(&mut conn);
 
     match group {
@@ -186,23 +248,50 @@ fn update_group(gid: i32, new_group: Json<PutGroup>, user: User) -> Result<Statu
     }
 }
 
-/// deletes a group, can only be performed by an admin
+/// moves a group to the trash, from where its admins can restore it for `trash.retention_days`,
+/// can only be performed by an admin
 #[openapi(tag = "Groups")]
 #[delete("/<gid>")]
-fn delete_group(gid: i32, user: User) -> Result<Status, Status> {
+fn delete_group(gid: i32, admin: GroupAdmin) -> Result<Status, Status> {
     let mut conn = establish_connection();
 
-    is_admin(gid, user.id)?;
-
-    // Attempt to delete the group by `id`
-    let deleted_rows = diesel::delete(groups.filter(id.eq(gid)))
-        .execute(&mut conn)
-        .map_err(|_| Status::InternalServerError)?;
-
-    if deleted_rows > 0 {
-        Ok(Status::Ok) // Return 200 OK if deletion was successful
-    } else {
-        Err(Status::NotFound) // Return 404 if no group was found
+    // Attempt to move the group by `id` to the trash, telling its members
+    let deleted = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            let deleted_rows =
+                diesel::update(groups.filter(id.eq(gid)).filter(deleted_at.is_null()))
+                    .set(deleted_at.eq(diesel::dsl::now))
+                    .execute(conn)?;
+            if deleted_rows == 0 {
+                return Ok(None);
+            }
+            let members = group_members::table
+                .filter(group_members::group_id.eq(gid))
+                .select(group_members::user_id)
+                .get_results::<i32>(conn)?;
+            let sent = notify(
+                conn,
+                &Notice::new(NotificationKind::GroupDeleted, admin.user.id).in_group(gid),
+                &members,
+            )?;
+            let event = record(
+                conn,
+                GroupEvent::new(GroupEventKind::GroupDeleted, gid, Some(admin.user.id)),
+            )?;
+            Ok(Some((sent, event)))
+        })
+        .map_err(|e| {
+            error!("error running delete_group transaction: {:?}", e);
+            Status::InternalServerError
+        })?;
+
+    match deleted {
+        Some((sent, event)) => {
+            dispatch(&sent);
+            publish(event);
+            Ok(Status::Ok) // Return 200 OK if deletion was successful
+        }
+        None => Err(Status::NotFound), // Return 404 if no group was found
     }
 }
 
@@ -210,61 +299,84 @@ fn delete_group(gid: i32, user: User) -> Result<Status, Status> {
 // ###############################|EXPENSES|###################################
 // ############################################################################
 
-#[derive(Debug, Serialize, Deserialize, JsonSchema)]
-pub struct PutExpense {
-    pub desc: String,
-    pub total_amount: f64,
-    pub paid_by: i32,
-    pub division: Vec<(i32, f64)>,
+/// adds expense `new_expense` to group `gid` along with its participations and notifies the
+/// participants that `author` added it, shared by [`add_expense`] and the recurring expenses.
+/// returns the notifications recorded too, to be dispatched once committed
+pub fn insert_group_expense(
+    conn: &mut DbConnection,
+    gid: i32,
+    new_expense: &PutExpense,
+    author: i32,
+) -> Result<(Expense, Vec<Notification>), diesel::result::Error> {
+    let expense = (
+        expenses::desc.eq(new_expense.desc.clone()),
+        expenses::total_amount.eq(new_expense.total_amount),
+        expenses::paid_by.eq(new_expense.paid_by),
+        expenses::expense_date.eq(new_expense.date_or_today()),
+        expenses::category_id.eq(new_expense.category_id),
+        expenses::created_at.eq(diesel::dsl::now),
+        expenses::updated_at.eq(diesel::dsl::now),
+        expenses::group_id.eq(gid),
+    )
+        .insert_into(expenses::table)
+        .get_result::<Expense>(conn)?;
+
+    for (d, a) in new_expense.division.iter() {
+        // TODO: check that user is in group
+        (
+            expense_participations::expense_id.eq(expense.id),
+            expense_participations::user_id.eq(d),
+            expense_participations::amount_due.eq(a),
+        )
+            .insert_into(expense_participations::table)
+            .execute(conn)?;
+    }
+
+    let participants: Vec<i32> = new_expense.division.iter().map(|(u, _)| *u).collect();
+    let sent = notify(
+        conn,
+        &Notice::new(NotificationKind::NewExpense, author)
+            .in_group(gid)
+            .about_expense(expense.id),
+        &participants,
+    )?;
+
+    store_items(conn, expense.id, new_expense.items.as_deref())?;
+    record_revision(conn, &expense, author)?;
+
+    Ok((expense, sent))
 }
 
-/// adds a group expense, the division array specifies how the expense is divided: division: Vec<(i32, f64)>
+/// adds a group expense, the division array specifies how the expense is divided: division: Vec<(i32, f64)>,
+/// needs to be executed by a member of the group
 #[openapi(tag = "GroupExpenses")]
 #[post("/<gid>/expenses", data = "<new_expense>")]
 fn add_expense(
     gid: i32,
-    new_expense: Json<PutExpense>,
-    user: User,
+    mut new_expense: Json<PutExpense>,
+    member: GroupMembership,
 ) -> Result<Json<Expense>, Status> {
+    let user = member.user;
     let mut conn = establish_connection();
 
-    // TODO: check that the division array sum equals the total
-    match conn.transaction::<Expense, diesel::result::Error, _>(|conn| {
-        let expense = (
-            expenses::desc.eq(new_expense.desc.clone()),
-            expenses::total_amount.eq(new_expense.total_amount),
-            expenses::paid_by.eq(new_expense.paid_by),
-            expenses::creation_date.eq(diesel::dsl::now),
-            expenses::group_id.eq(gid),
-        )
-            .insert_into(expenses::table)
-            .get_result::<Expense>(conn)?;
-
-        for (d, a) in new_expense.division.iter() {
-            // TODO: check that user is in group
-            (
-                expense_participations::expense_id.eq(expense.id),
-                expense_participations::user_id.eq(d),
-                expense_participations::amount_due.eq(a),
-            )
-                .insert_into(expense_participations::table)
-                .execute(conn)?;
+    new_expense.itemize()?;
+    new_expense.check_category(Some(gid))?;
 
-            (
-                notifications::notified_user_id.eq(d),
-                notifications::notification_type.eq("NEW_EXPENSE"),
-                notifications::expense_id.eq(expense.id),
-                notifications::group_id.eq(gid),
-                notifications::user_id.eq(user.id),
-                notifications::creation_date.eq(diesel::dsl::now),
-            )
-                .insert_into(notifications::table)
-                .execute(conn)?;
-        }
-
-        Ok(expense)
+    // TODO: check that the division array sum equals the total
+    match conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let (expense, sent) = insert_group_expense(conn, gid, &new_expense, user.id)?;
+        let event = record(
+            conn,
+            GroupEvent::new(GroupEventKind::ExpenseCreated, gid, Some(user.id))
+                .about_expense(expense.id),
+        )?;
+        Ok((expense, sent, event))
     }) {
-        Ok(e) => Ok(Json(e)),
+        Ok((e, sent, event)) => {
+            dispatch(&sent);
+            publish(event);
+            Ok(Json(e))
+        }
         Err(e) => {
             error!("error running add_expense transaction: {:?}", e);
             Err(Status::InternalServerError)
@@ -272,75 +384,110 @@ fn add_expense(
     }
 }
 
-type ExpenseList = Vec<(Expense, Vec<ExpenseParticipation>)>;
-
-/// returns all the information about the group expenses, including the participations
+/// returns all the information about the group expenses, including the participations. the
+/// expenses are paginated and can be filtered and sorted
 #[openapi(tag = "GroupExpenses")]
-#[get("/<gid>/expenses")]
+#[get("/<gid>/expenses?<query..>")]
 fn get_expenses(
     gid: i32,
-    user: User,
-) -> Result<Json<Vec<(Expense, Vec<ExpenseParticipation>)>>, Status> {
-    let mut conn = establish_connection();
+    _member: GroupMembership,
+    query: ExpenseQuery,
+) -> Result<Json<Page<ExpenseWithParticipations>>, Status> {
+    let base = expenses::table
+        .filter(expenses::group_id.eq(gid))
+        .filter(expenses::deleted_at.is_null())
+        .into_boxed();
+
+    expense_page(base, &query)
+}
 
-    is_member(gid, user.id)?;
+/// helper function that loads expense `exid` of group `gid`, `404 Not Found` if the group has no
+/// such expense or it is in the trash
+pub fn group_expense(gid: i32, exid: i32) -> Result<Expense, Status> {
+    let mut conn = establish_connection();
 
-    match conn.transaction::<ExpenseList, diesel::result::Error, _>(|conn| {
-        let expenses = expenses::table
-            .filter(expenses::group_id.eq(gid))
-            .get_results::<Expense>(conn)?;
-        let mut v: ExpenseList = Vec::new();
-        for e in expenses {
-            let participations = expense_participations::table
-                .filter(expense_participations::expense_id.eq(e.id))
-                .get_results::<ExpenseParticipation>(conn)?;
-            v.push((e, participations));
-        }
-        Ok(v)
-    }) {
-        Ok(e) => Ok(Json(e)),
+    match expenses::table
+        .filter(expenses::id.eq(exid))
+        .filter(expenses::group_id.eq(gid))
+        .filter(expenses::deleted_at.is_null())
+        .first::<Expense>(&mut conn)
+    {
+        Ok(e) => Ok(e),
+        Err(NotFound) => Err(Status::NotFound),
         Err(e) => {
-            error!("error running get_expenses transaction: {:?}", e);
+            error!("error loading group expense: {:?}", e);
             Err(Status::InternalServerError)
         }
     }
 }
 
-/// deletese group expense, needs to be performed either by expense creator or admin user
+/// helper function that loads expense `exid` of the group of `member` and checks that it can be
+/// modified by the member, that is if the member paid for it or is an admin of the group
+fn modifiable_expense(exid: i32, member: &GroupMembership) -> Result<Expense, Status> {
+    let expense = group_expense(member.group_id, exid)?;
+
+    if expense.paid_by == member.user.id || member.is_admin {
+        Ok(expense)
+    } else {
+        error!("trying to modify expense but user is not admin or creator of expense");
+        Err(Status::Forbidden)
+    }
+}
+
+/// moves a group expense to the trash, from where it can be restored for
+/// `trash.retention_days`, needs to be performed either by expense creator or admin user
 #[openapi(tag = "GroupExpenses")]
 #[delete("/<gid>/expenses/<exid>")]
-fn delete_expense(gid: i32, exid: i32, user: User) -> Result<Json<Expense>, Status> {
+fn delete_expense(gid: i32, exid: i32, member: GroupMembership) -> Result<Json<Expense>, Status> {
     let mut conn = establish_connection();
 
-    match conn.transaction::<Expense, diesel::result::Error, _>(|conn| {
-        let res = expense_participations::table
+    modifiable_expense(exid, &member)?;
+    let user = member.user;
+
+    match conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        // only if it is not in the trash yet, the same expense deleted twice at the same time is
+        // told and timed once
+        let expense = match diesel::update(
+            expenses::table
+                .filter(expenses::id.eq(exid))
+                .filter(expenses::group_id.eq(gid))
+                .filter(expenses::deleted_at.is_null()),
+        )
+        .set(expenses::deleted_at.eq(diesel::dsl::now))
+        .get_result::<Expense>(conn)
+        {
+            Ok(e) => e,
+            Err(NotFound) => return Ok(Err(Status::NotFound)),
+            Err(e) => return Err(e),
+        };
+
+        let participants = expense_participations::table
             .filter(expense_participations::expense_id.eq(exid))
-            .get_results::<ExpenseParticipation>(conn)?;
+            .select(expense_participations::user_id)
+            .get_results::<i32>(conn)?;
 
         // notify users of expense deletion
-        for ep in res {
-            (
-                notifications::notified_user_id.eq(ep.user_id),
-                notifications::notification_type.eq("EXPENSE_DELETED"),
-                notifications::group_id.eq(gid),
-                notifications::user_id.eq(user.id),
-                notifications::creation_date.eq(diesel::dsl::now),
-            )
-                .insert_into(notifications::table)
-                .execute(conn)?;
-        }
-
-        let expense = diesel::delete(expenses::table.filter(expenses::id.eq(exid)))
-            .get_result::<Expense>(conn)?;
-
-        if !((expense.paid_by == user.id) || is_admin(gid, user.id).is_ok()) {
-            error!("trying to delete expense but user is not admin or creator of expense");
-            return Err(diesel::result::Error::RollbackTransaction);
-        };
-
-        Ok(expense)
+        let sent = notify(
+            conn,
+            &Notice::new(NotificationKind::ExpenseDeleted, user.id)
+                .in_group(gid)
+                .about_expense(exid),
+            &participants,
+        )?;
+
+        let event = record(
+            conn,
+            GroupEvent::new(GroupEventKind::ExpenseDeleted, gid, Some(user.id)).about_expense(exid),
+        )?;
+
+        Ok(Ok((expense, sent, event)))
     }) {
-        Ok(e) => Ok(Json(e)),
+        Ok(Ok((e, sent, event))) => {
+            dispatch(&sent);
+            publish(event);
+            Ok(Json(e))
+        }
+        Ok(Err(status)) => Err(status),
         Err(e) => {
             error!("error running delete_expense transaction: {:?}", e);
             Err(Status::InternalServerError)
@@ -348,32 +495,46 @@ fn delete_expense(gid: i32, exid: i32, user: User) -> Result<Json<Expense>, Stat
     }
 }
 
-/// updates a group expense, the division array specifies how the expense is divided: division: Vec<(i32, f64)>, can only be executed by who inserted the expense or an admin
+/// updates a group expense, the division array specifies how the expense is divided: division: Vec<(i32, f64)>, can only be executed by who inserted the expense or an admin.
+/// the new state is recorded in the history of the expense
 #[openapi(tag = "GroupExpenses")]
 #[put("/<gid>/expenses/<exid>", data = "<new_expense>")]
 fn update_expense(
     gid: i32,
     exid: i32,
-    new_expense: Json<PutExpense>,
-    user: User,
+    mut new_expense: Json<PutExpense>,
+    member: GroupMembership,
 ) -> Result<Json<Expense>, Status> {
     let mut conn = establish_connection();
 
+    let old = modifiable_expense(exid, &member)?;
+    new_expense.itemize()?;
+    new_expense.check_category(Some(gid))?;
+    let user = member.user;
+
     // TODO: check that the division array sum equals the total
-    match conn.transaction::<Expense, diesel::result::Error, _>(|conn| {
+    match conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        // who took part in the expense before the change is told as well, along with both payers
+        let mut participants = expense_participations::table
+            .filter(expense_participations::expense_id.eq(exid))
+            .select(expense_participations::user_id)
+            .get_results::<i32>(conn)?;
+        participants.extend(new_expense.division.iter().map(|(u, _)| *u));
+        participants.extend([old.paid_by, new_expense.paid_by]);
+
         let expense = diesel::update(expenses::table.filter(expenses::id.eq(exid)))
             .set((
                 expenses::desc.eq(new_expense.desc.clone()),
                 expenses::total_amount.eq(new_expense.total_amount),
                 expenses::paid_by.eq(new_expense.paid_by),
+                new_expense
+                    .expense_date
+                    .map(|d| expenses::expense_date.eq(d)),
+                expenses::category_id.eq(new_expense.category_id),
+                expenses::updated_at.eq(diesel::dsl::now),
             ))
             .get_result::<Expense>(conn)?;
 
-        if !((expense.paid_by == user.id) || is_admin(gid, user.id).is_ok()) {
-            error!("trying to update expense but user is not admin or creator of expense");
-            return Err(diesel::result::Error::RollbackTransaction);
-        };
-
         diesel::delete(
             expense_participations::table.filter(expense_participations::expense_id.eq(exid)),
         )
@@ -388,22 +549,31 @@ fn update_expense(
             )
                 .insert_into(expense_participations::table)
                 .execute(conn)?;
-
-            (
-                notifications::notified_user_id.eq(d),
-                notifications::notification_type.eq("EXPENSE_MODIFIED"),
-                notifications::expense_id.eq(expense.id),
-                notifications::group_id.eq(gid),
-                notifications::user_id.eq(user.id),
-                notifications::creation_date.eq(diesel::dsl::now),
-            )
-                .insert_into(notifications::table)
-                .execute(conn)?;
         }
 
-        Ok(expense)
+        store_items(conn, expense.id, new_expense.items.as_deref())?;
+        let revision = record_revision(conn, &expense, user.id)?;
+
+        let sent = notify(
+            conn,
+            &Notice::new(NotificationKind::ExpenseModified, user.id)
+                .in_group(gid)
+                .about_expense(expense.id)
+                .at_revision(revision.id),
+            &participants,
+        )?;
+        let event = record(
+            conn,
+            GroupEvent::new(GroupEventKind::ExpenseUpdated, gid, Some(user.id)).about_expense(exid),
+        )?;
+
+        Ok((expense, sent, event))
     }) {
-        Ok(e) => Ok(Json(e)),
+        Ok((e, sent, event)) => {
+            dispatch(&sent);
+            publish(event);
+            Ok(Json(e))
+        }
         Err(e) => {
             error!("error running add_expense transaction: {:?}", e);
             Err(Status::InternalServerError)
@@ -418,24 +588,39 @@ fn update_expense(
 /// adds a user to the group, can only be performed by an admin
 #[openapi(tag = "Groups")]
 #[post("/<gid>/members", data = "<p_user>")]
-fn add_member(gid: i32, p_user: Json<PutUser>, user: User) -> Result<(), Status> {
+fn add_member(gid: i32, p_user: Json<PutUser>, admin: GroupAdmin) -> Result<(), Status> {
     let mut conn = establish_connection();
     use crate::schema::group_members::dsl::*;
     use crate::schema::users::dsl::*;
 
-    is_admin(gid, user.id)?;
-
     match users.filter(id.eq(p_user.user_id)).first::<User>(&mut conn) {
         Ok(_) => (),
         Err(_) => return Err(Status::NotFound),
     };
 
-    let result = (group_id.eq(gid), user_id.eq(p_user.user_id))
-        .insert_into(group_members)
-        .execute(&mut conn);
+    let result = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        (group_id.eq(gid), user_id.eq(p_user.user_id))
+            .insert_into(group_members)
+            .execute(conn)?;
+        let sent = notify(
+            conn,
+            &Notice::new(NotificationKind::MemberAdded, admin.user.id).in_group(gid),
+            &[p_user.user_id],
+        )?;
+        let event = record(
+            conn,
+            GroupEvent::new(GroupEventKind::MemberAdded, gid, Some(admin.user.id))
+                .about_member(p_user.user_id),
+        )?;
+        Ok((sent, event))
+    });
 
     match result {
-        Ok(_) => Ok(()),
+        Ok((sent, event)) => {
+            dispatch(&sent);
+            publish(event);
+            Ok(())
+        }
         Err(_) => Err(Status::InternalServerError),
     }
 }
@@ -443,11 +628,9 @@ fn add_member(gid: i32, p_user: Json<PutUser>, user: User) -> Result<(), Status>
 /// list of the members of the group
 #[openapi(tag = "Groups")]
 #[get("/<gid>/members")]
-fn view_members(gid: i32, user: User) -> Result<Json<Vec<GroupMember>>, Status> {
+fn view_members(gid: i32, _member: GroupMembership) -> Result<Json<Vec<GroupMember>>, Status> {
     let mut conn = establish_connection();
 
-    is_member(gid, user.id)?;
-
     match group_members::table
         .filter(group_members::group_id.eq(gid))
         .get_results::<GroupMember>(&mut conn)
@@ -472,67 +655,101 @@ pub struct InviteUser {
 fn invite_user(
     gid: i32,
     invite: Json<InviteUser>,
-    user: User,
+    admin: GroupAdmin,
 ) -> Result<Json<GroupInvite>, Status> {
     use crate::schema::users;
     let mut conn = establish_connection();
-
-    is_admin(gid, user.id)?;
+    let user = admin.user;
 
     let invited_id = match users::table
         .filter(users::email.eq(&invite.email))
         .first::<User>(&mut conn)
     {
         Ok(usr) => usr.id,
+        Err(NotFound) => return Err(Status::NotFound),
         Err(_) => return Err(Status::InternalServerError),
     };
 
-    match (
-        group_invites::group_id.eq(gid),
-        group_invites::inviting_user_id.eq(user.id),
-        group_invites::invited_user_id.eq(invited_id),
-        group_invites::invite_status.eq("PENDING"),
-        group_invites::optional_message.eq(invite.message.clone()),
-        group_invites::invite_date.eq(diesel::dsl::now),
-    )
-        .insert_into(group_invites::table)
-        .get_result::<GroupInvite>(&mut conn)
-    {
-        Ok(gi) => Ok(Json(gi)),
+    match conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let gi = (
+            group_invites::group_id.eq(gid),
+            group_invites::inviting_user_id.eq(user.id),
+            group_invites::invited_user_id.eq(invited_id),
+            group_invites::invite_status.eq("PENDING"),
+            group_invites::optional_message.eq(invite.message.clone()),
+            group_invites::invite_date.eq(diesel::dsl::now),
+        )
+            .insert_into(group_invites::table)
+            .get_result::<GroupInvite>(conn)?;
+        let sent = notify(
+            conn,
+            &Notice::new(NotificationKind::GroupInvite, user.id).in_group(gid),
+            &[invited_id],
+        )?;
+        Ok((gi, sent))
+    }) {
+        Ok((gi, sent)) => {
+            dispatch(&sent);
+            Ok(Json(gi))
+        }
         Err(e) => {
-            error!("error running create_group transaction: {:?}", e);
+            error!("error running invite_user transaction: {:?}", e);
             Err(Status::InternalServerError)
         }
     }
 }
 
-/// removes a member from the group(and from admin table if he is admin), can only be performed by another admin
+/// removes a member from the group(and from admin table if he is admin), can only be performed by
+/// an admin, the last admin of the group cannot be removed
 #[openapi(tag = "Groups")]
 #[delete("/<gid>/members/<uid>")]
-fn remove_member(gid: i32, uid: i32, user: User) -> Result<(), Status> {
+fn remove_member(gid: i32, uid: i32, admin: GroupAdmin) -> Result<(), Status> {
     let mut conn = establish_connection();
     use crate::schema::group_members::dsl::*;
 
-    is_admin(gid, user.id)?;
+    let res = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        if let Err(status) = not_last_admin(conn, gid, uid)? {
+            return Ok(Err(status));
+        }
 
-    let r1 = diesel::delete(
-        group_members
-            .filter(group_id.eq(gid))
-            .filter(user_id.eq(uid)),
-    )
-    .execute(&mut conn);
+        let rows_deleted = diesel::delete(
+            group_members
+                .filter(group_id.eq(gid))
+                .filter(user_id.eq(uid)),
+        )
+        .execute(conn)?;
 
-    let r2 = diesel::delete(
-        group_administrators::table
-            .filter(group_administrators::group_id.eq(gid))
-            .filter(group_administrators::user_id.eq(uid)),
-    )
-    .execute(&mut conn);
+        diesel::delete(
+            group_administrators::table
+                .filter(group_administrators::group_id.eq(gid))
+                .filter(group_administrators::user_id.eq(uid)),
+        )
+        .execute(conn)?;
+
+        if rows_deleted == 0 {
+            return Ok(Err(Status::NotFound)); // User was not a member of the group
+        }
+        let sent = notify(
+            conn,
+            &Notice::new(NotificationKind::RemovedFromGroup, admin.user.id).in_group(gid),
+            &[uid],
+        )?;
+        let event = record(
+            conn,
+            GroupEvent::new(GroupEventKind::MemberRemoved, gid, Some(admin.user.id))
+                .about_member(uid),
+        )?;
+        Ok(Ok((sent, event)))
+    });
 
-    match (r1, r2) {
-        (Ok(rows_deleted), Ok(_)) if rows_deleted > 0 => Ok(()), // Successfully deleted
-        (Ok(_), Ok(_)) => Err(Status::NotFound), // User was not a member of the group
-        (Err(_), _) | (_, Err(_)) => Err(Status::InternalServerError), // An error occurred
+    match res {
+        Ok(Ok((sent, event))) => {
+            dispatch(&sent);
+            publish(event);
+            Ok(())
+        }
+        Ok(Err(status)) => Err(status),
+        Err(_) => Err(Status::InternalServerError), // An error occurred
     }
 }
 
@@ -543,59 +760,94 @@ fn remove_member(gid: i32, uid: i32, user: User) -> Result<(), Status> {
 /// promotes member to admin, can only be performed by another admin
 #[openapi(tag = "Groups")]
 #[post("/<gid>/admins/<uid>")]
-fn promote_to_admin(gid: i32, uid: i32, user: User) -> Result<(), Status> {
+fn promote_to_admin(gid: i32, uid: i32, admin: GroupAdmin) -> Result<(), Status> {
     let mut conn = establish_connection();
 
-    // this ensures that the user that made the request is an admin
-    is_admin(gid, user.id)?;
     // this ensures that the uid is of a real user, the gid of a real group, and together that the
     // user to be promoted is part of the group
     is_member(gid, uid)?;
 
     use crate::schema::group_administrators::dsl::*;
 
-    let result = (group_id.eq(gid), user_id.eq(uid))
-        .insert_into(group_administrators)
-        .execute(&mut conn);
+    let result = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        (group_id.eq(gid), user_id.eq(uid))
+            .insert_into(group_administrators)
+            .execute(conn)?;
+        let sent = notify(
+            conn,
+            &Notice::new(NotificationKind::AdminPromotion, admin.user.id).in_group(gid),
+            &[uid],
+        )?;
+        let event = record(
+            conn,
+            GroupEvent::new(GroupEventKind::AdminPromoted, gid, Some(admin.user.id))
+                .about_member(uid),
+        )?;
+        Ok((sent, event))
+    });
 
     match result {
-        Ok(_) => Ok(()),
+        Ok((sent, event)) => {
+            dispatch(&sent);
+            publish(event);
+            Ok(())
+        }
         Err(_) => Err(Status::InternalServerError),
     }
 }
 
-/// demotes group admin to member, can only be performed by another admin
+/// demotes group admin to member, can only be performed by an admin, the last admin of the group
+/// cannot be demoted
 #[openapi(tag = "Groups")]
 #[delete("/<gid>/admins/<uid>")]
-fn demote_admin(gid: i32, uid: i32, user: User) -> Result<(), Status> {
+fn demote_admin(gid: i32, uid: i32, admin: GroupAdmin) -> Result<(), Status> {
     let mut conn = establish_connection();
     use crate::schema::group_administrators::dsl::*;
 
-    // this ensures that the user that made the request is an admin
-    is_admin(gid, user.id)?;
+    let result = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        if let Err(status) = not_last_admin(conn, gid, uid)? {
+            return Ok(Err(status));
+        }
 
-    let result = diesel::delete(
-        group_administrators
-            .filter(group_id.eq(gid))
-            .filter(user_id.eq(uid)),
-    )
-    .execute(&mut conn);
+        let deleted_rows = diesel::delete(
+            group_administrators
+                .filter(group_id.eq(gid))
+                .filter(user_id.eq(uid)),
+        )
+        .execute(conn)?;
+        if deleted_rows == 0 {
+            return Ok(Err(Status::NotFound)); // User was not an admin
+        }
+        let sent = notify(
+            conn,
+            &Notice::new(NotificationKind::AdminDemotion, admin.user.id).in_group(gid),
+            &[uid],
+        )?;
+        let event = record(
+            conn,
+            GroupEvent::new(GroupEventKind::AdminDemoted, gid, Some(admin.user.id))
+                .about_member(uid),
+        )?;
+        Ok(Ok((sent, event)))
+    });
 
     match result {
-        Ok(deleted_rows) if deleted_rows > 0 => Ok(()), // Successfully deleted
-        Ok(_) => Err(Status::NotFound),                 // User was not an admin
-        Err(_) => Err(Status::InternalServerError),     // An error occurred
+        Ok(Ok((sent, event))) => {
+            dispatch(&sent);
+            publish(event);
+            Ok(())
+        }
+        Ok(Err(status)) => Err(status),
+        Err(_) => Err(Status::InternalServerError), // An error occurred
     }
 }
 
 /// lists all the admins to the group with `gid`
 #[openapi(tag = "Groups")]
 #[get("/<gid>/admins")]
-fn view_admins(gid: i32, user: User) -> Result<Json<Vec<GroupMember>>, Status> {
+fn view_admins(gid: i32, _member: GroupMembership) -> Result<Json<Vec<GroupMember>>, Status> {
     let mut conn = establish_connection();
 
-    is_member(gid, user.id)?;
-
     match group_administrators::table
         .filter(group_administrators::group_id.eq(gid))
         .get_results::<GroupMember>(&mut conn)
@@ -608,3 +860,282 @@ fn view_admins(gid: i32, user: User) -> Result<Json<Vec<GroupMember>>, Status> {
         }
     }
 }
+
+// ############################################################################
+// ##############################|CATEGORIES|##################################
+// ############################################################################
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PutCategory {
+    pub name: String,
+}
+
+/// lists the categories the expenses of the group can be filed under, the built-in ones first and
+/// then the ones defined by the group
+#[openapi(tag = "Groups")]
+#[get("/<gid>/categories")]
+fn view_categories(gid: i32, _member: GroupMembership) -> Result<Json<Vec<Category>>, Status> {
+    let mut conn = establish_connection();
+
+    match categories::table
+        .filter(
+            categories::group_id
+                .is_null()
+                .or(categories::group_id.eq(gid)),
+        )
+        .order(categories::id.asc())
+        .get_results::<Category>(&mut conn)
+    {
+        Ok(c) => Ok(Json(c)),
+        Err(e) => {
+            error!("error running view_categories query: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// adds a custom category to the group, can only be performed by an admin. the name cannot be
+/// empty and cannot be already used, ignoring case, by a built-in category or one of the group,
+/// otherwise returns `409 Conflict`
+#[openapi(tag = "Groups")]
+#[post("/<gid>/categories", data = "<category>")]
+fn create_category(
+    gid: i32,
+    category: Json<PutCategory>,
+    _admin: GroupAdmin,
+) -> Result<Json<Category>, Status> {
+    let name = category.name.trim();
+    if name.is_empty() {
+        error!("trying to create a category without a name");
+        return Err(Status::BadRequest);
+    }
+
+    let mut conn = establish_connection();
+
+    match conn.transaction::<Option<Category>, diesel::result::Error, _>(|conn| {
+        let taken = categories::table
+            .filter(
+                categories::group_id
+                    .is_null()
+                    .or(categories::group_id.eq(gid)),
+            )
+            .select(categories::name)
+            .get_results::<String>(conn)?
+            .iter()
+            .any(|n| n.to_lowercase() == name.to_lowercase());
+        if taken {
+            return Ok(None);
+        }
+
+        let category = (categories::group_id.eq(gid), categories::name.eq(name))
+            .insert_into(categories::table)
+            .get_result::<Category>(conn)?;
+        Ok(Some(category))
+    }) {
+        Ok(Some(c)) => Ok(Json(c)),
+        Ok(None) => {
+            error!("category `{}` already exists in group {}", name, gid);
+            Err(Status::Conflict)
+        }
+        Err(e) => {
+            error!("error running create_category transaction: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// deletes a custom category of the group, the expenses filed under it are left without a
+/// category. can only be performed by an admin, built-in categories cannot be deleted
+#[openapi(tag = "Groups")]
+#[delete("/<gid>/categories/<cid>")]
+fn delete_category(gid: i32, cid: i32, _admin: GroupAdmin) -> Result<(), Status> {
+    let mut conn = establish_connection();
+
+    let result = diesel::delete(
+        categories::table
+            .filter(categories::id.eq(cid))
+            .filter(categories::group_id.eq(gid)),
+    )
+    .execute(&mut conn);
+
+    match result {
+        Ok(deleted_rows) if deleted_rows > 0 => Ok(()),
+        Ok(_) => Err(Status::NotFound),
+        Err(e) => {
+            error!("error running delete_category query: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+// ############################################################################
+// ###############################|REPORTS|####################################
+// ############################################################################
+
+/// the period covered by a report, both ends are optional and included
+#[derive(Debug, FromForm, JsonSchema)]
+pub struct ReportQuery {
+    /// only the expenses dated on this day, formatted as `YYYY-MM-DD`, or later
+    pub from: Param<QueryDate>,
+    /// only the expenses dated on this day, formatted as `YYYY-MM-DD`, or earlier
+    pub to: Param<QueryDate>,
+}
+
+/// how much a member spent in a category
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct MemberSpending {
+    pub user_id: i32,
+    /// the amount the member paid for the expenses of the category
+    pub paid: f64,
+    /// the part of the expenses of the category that is due by the member
+    pub share: f64,
+}
+
+/// how much the group spent in a category
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CategorySpending {
+    /// `null` for the expenses without a category
+    pub category_id: Option<i32>,
+    pub name: Option<String>,
+    pub total: f64,
+    pub expenses: i64,
+    /// the members that paid for or take part in the expenses of the category
+    pub members: Vec<MemberSpending>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CategoryReport {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub total: f64,
+    /// the categories with at least an expense in the period, the most expensive first
+    pub categories: Vec<CategorySpending>,
+}
+
+/// returns how much the group spent in each category in the period, and how much each member
+/// paid and is due in every category, needs to be executed by a member of the group
+#[openapi(tag = "Groups")]
+#[get("/<gid>/reports/categories?<query..>")]
+fn category_report(
+    gid: i32,
+    _member: GroupMembership,
+    query: ReportQuery,
+) -> Result<Json<CategoryReport>, Status> {
+    let from = query.from.value().map(|d| d.0);
+    let to = query.to.value().map(|d| d.0);
+    if let (Some(f), Some(t)) = (from, to)
+        && f > t
+    {
+        error!("report period starts on {} after it ends on {}", f, t);
+        return Err(Status::BadRequest);
+    }
+
+    // the grouped queries are boxed only after grouping, diesel cannot group a boxed query
+    let mut paid = expenses::table
+        .filter(expenses::group_id.eq(gid))
+        .filter(expenses::deleted_at.is_null())
+        .group_by((expenses::category_id, expenses::paid_by))
+        .select((
+            expenses::category_id,
+            expenses::paid_by,
+            dsl::sum(expenses::total_amount),
+            count_star(),
+        ))
+        .into_boxed();
+    let mut shares = expense_participations::table
+        .inner_join(expenses::table)
+        .filter(expenses::group_id.eq(gid))
+        .filter(expenses::deleted_at.is_null())
+        .group_by((expenses::category_id, expense_participations::user_id))
+        .select((
+            expenses::category_id,
+            expense_participations::user_id,
+            dsl::sum(expense_participations::amount_due),
+        ))
+        .into_boxed();
+    if let Some(f) = from {
+        paid = paid.filter(expenses::expense_date.ge(f));
+        shares = shares.filter(expenses::expense_date.ge(f));
+    }
+    if let Some(t) = to {
+        paid = paid.filter(expenses::expense_date.le(t));
+        shares = shares.filter(expenses::expense_date.le(t));
+    }
+
+    let mut conn = establish_connection();
+
+    type Totals = (
+        Vec<(Option<i32>, i32, Option<f64>, i64)>,
+        Vec<(Option<i32>, i32, Option<f64>)>,
+        Vec<Category>,
+    );
+    let (paid, shares, names) = match conn.transaction::<Totals, diesel::result::Error, _>(|conn| {
+        let paid = paid.get_results(conn)?;
+        let shares = shares.get_results(conn)?;
+        let names = categories::table
+            .filter(
+                categories::group_id
+                    .is_null()
+                    .or(categories::group_id.eq(gid)),
+            )
+            .get_results::<Category>(conn)?;
+        Ok((paid, shares, names))
+    }) {
+        Ok(t) => t,
+        Err(e) => {
+            error!("error loading category report: {:?}", e);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    let mut spending = BTreeMap::<Option<i32>, CategorySpending>::new();
+    let mut members = BTreeMap::<(Option<i32>, i32), MemberSpending>::new();
+    for (cid, uid, amount, count) in paid {
+        let amount = amount.unwrap_or_default();
+        let category = spending.entry(cid).or_insert_with(|| CategorySpending {
+            category_id: cid,
+            name: names
+                .iter()
+                .find(|c| Some(c.id) == cid)
+                .map(|c| c.name.clone()),
+            total: 0.0,
+            expenses: 0,
+            members: vec![],
+        });
+        category.total += amount;
+        category.expenses += count;
+        members
+            .entry((cid, uid))
+            .or_insert(MemberSpending {
+                user_id: uid,
+                paid: 0.0,
+                share: 0.0,
+            })
+            .paid += amount;
+    }
+    for (cid, uid, amount) in shares {
+        members
+            .entry((cid, uid))
+            .or_insert(MemberSpending {
+                user_id: uid,
+                paid: 0.0,
+                share: 0.0,
+            })
+            .share += amount.unwrap_or_default();
+    }
+    for ((cid, _), m) in members {
+        if let Some(category) = spending.get_mut(&cid) {
+            category.members.push(m);
+        }
+    }
+
+    let mut categories: Vec<CategorySpending> = spending.into_values().collect();
+    categories.sort_by(|a, b| b.total.total_cmp(&a.total));
+
+    Ok(Json(CategoryReport {
+        from,
+        to,
+        total: categories.iter().map(|c| c.total).sum(),
+        categories,
+    }))
+}
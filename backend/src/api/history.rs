@@ -0,0 +1,182 @@
+//! the history of the group expenses: every time an expense is added or modified its new state is
+//! recorded as a revision, so that the members of the group can see who changed what and when
+
+use crate::{
+    api::groups::group_expense,
+    db::DbConnection,
+    establish_connection,
+    guards::GroupMembership,
+    models::{Expense, ExpenseParticipation, ExpenseRevision, ExpenseRevisionParticipation},
+    schema::{expense_participations, expense_revision_participations, expense_revisions},
+};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::{
+    BelongingToDsl, ExpressionMethods, GroupedBy, Insertable, QueryDsl, QueryResult, RunQueryDsl,
+    dsl,
+};
+use rocket::{http::Status, serde::json::Json};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
+    openapi_get_routes_spec![settings:get_history]
+}
+
+/// what a group expense looked like at a revision
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ExpenseState {
+    pub desc: String,
+    pub total_amount: f64,
+    pub paid_by: i32,
+    pub expense_date: NaiveDate,
+    pub category_id: Option<i32>,
+    /// the amount due by every participant, ordered by user id
+    pub division: Vec<(i32, Option<f64>)>,
+}
+
+impl ExpenseState {
+    fn new(r: ExpenseRevision, participations: Vec<ExpenseRevisionParticipation>) -> Self {
+        let mut division: Vec<(i32, Option<f64>)> = participations
+            .into_iter()
+            .map(|p| (p.user_id, p.amount_due))
+            .collect();
+        division.sort_by_key(|(u, _)| *u);
+
+        ExpenseState {
+            desc: r.desc,
+            total_amount: r.total_amount,
+            paid_by: r.paid_by,
+            expense_date: r.expense_date,
+            category_id: r.category_id,
+            division,
+        }
+    }
+
+    /// the names of the fields that differ between `self` and `other`
+    fn changes(&self, other: &ExpenseState) -> Vec<String> {
+        [
+            ("desc", self.desc != other.desc),
+            ("total_amount", self.total_amount != other.total_amount),
+            ("paid_by", self.paid_by != other.paid_by),
+            ("expense_date", self.expense_date != other.expense_date),
+            ("category_id", self.category_id != other.category_id),
+            ("division", self.division != other.division),
+        ]
+        .into_iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(field, _)| field.to_owned())
+        .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ExpenseHistoryEntry {
+    /// id of the revision, the `EXPENSE_MODIFIED` notifications refer to it
+    pub id: i32,
+    pub revision: i32,
+    /// `None` for the expenses added before their history was kept, or once the user is deleted
+    pub changed_by: Option<i32>,
+    pub changed_at: NaiveDateTime,
+    /// the fields that differ from the previous revision, empty for the first one
+    pub changed: Vec<String>,
+    /// the state before the change, `None` for the first revision
+    pub old: Option<ExpenseState>,
+    pub new: ExpenseState,
+}
+
+/// records the current state of `expense` and of its participations as its next revision, to be
+/// called in the same transaction that adds or modifies it
+pub fn record_revision(
+    conn: &mut DbConnection,
+    expense: &Expense,
+    changed_by: i32,
+) -> QueryResult<ExpenseRevision> {
+    let last = expense_revisions::table
+        .filter(expense_revisions::expense_id.eq(expense.id))
+        .select(dsl::max(expense_revisions::revision))
+        .get_result::<Option<i32>>(conn)?;
+
+    let revision = (
+        expense_revisions::expense_id.eq(expense.id),
+        expense_revisions::revision.eq(last.unwrap_or(0) + 1),
+        expense_revisions::changed_by.eq(changed_by),
+        expense_revisions::changed_at.eq(expense.updated_at),
+        expense_revisions::desc.eq(&expense.desc),
+        expense_revisions::total_amount.eq(expense.total_amount),
+        expense_revisions::paid_by.eq(expense.paid_by),
+        expense_revisions::expense_date.eq(expense.expense_date),
+        expense_revisions::category_id.eq(expense.category_id),
+    )
+        .insert_into(expense_revisions::table)
+        .get_result::<ExpenseRevision>(conn)?;
+
+    let participations = expense_participations::table
+        .filter(expense_participations::expense_id.eq(expense.id))
+        .get_results::<ExpenseParticipation>(conn)?;
+
+    for p in participations {
+        (
+            expense_revision_participations::revision_id.eq(revision.id),
+            expense_revision_participations::user_id.eq(p.user_id),
+            expense_revision_participations::amount_due.eq(p.amount_due),
+        )
+            .insert_into(expense_revision_participations::table)
+            .execute(conn)?;
+    }
+
+    Ok(revision)
+}
+
+/// returns the revisions of a group expense, oldest first, each one with the fields that changed
+/// and the state before and after the change. needs to be executed by a member of the group
+#[openapi(tag = "GroupExpenses")]
+#[get("/<gid>/expenses/<exid>/history")]
+fn get_history(
+    gid: i32,
+    exid: i32,
+    _member: GroupMembership,
+) -> Result<Json<Vec<ExpenseHistoryEntry>>, Status> {
+    group_expense(gid, exid)?;
+
+    let mut conn = establish_connection();
+
+    let revisions = expense_revisions::table
+        .filter(expense_revisions::expense_id.eq(exid))
+        .order(expense_revisions::revision)
+        .get_results::<ExpenseRevision>(&mut conn)
+        .map_err(|e| {
+            error!("error loading expense revisions: {:?}", e);
+            Status::InternalServerError
+        })?;
+
+    let participations = ExpenseRevisionParticipation::belonging_to(&revisions)
+        .get_results::<ExpenseRevisionParticipation>(&mut conn)
+        .map_err(|e| {
+            error!("error loading expense revision participations: {:?}", e);
+            Status::InternalServerError
+        })?
+        .grouped_by(&revisions);
+
+    let mut old: Option<ExpenseState> = None;
+    let mut history = Vec::with_capacity(revisions.len());
+    for (r, p) in revisions.into_iter().zip(participations) {
+        let (id, revision, changed_by, changed_at) = (r.id, r.revision, r.changed_by, r.changed_at);
+        let new = ExpenseState::new(r, p);
+
+        history.push(ExpenseHistoryEntry {
+            id,
+            revision,
+            changed_by,
+            changed_at,
+            changed: old.as_ref().map(|o| o.changes(&new)).unwrap_or_default(),
+            old: old.replace(new.clone()),
+            new,
+        });
+    }
+
+    Ok(Json(history))
+}
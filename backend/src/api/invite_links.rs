@@ -0,0 +1,306 @@
+//! invite links: the admins of a group create links that let anyone who has them join the group,
+//! without knowing their email. a link works until it expires, runs out of uses or is revoked.
+//!
+//! the links are managed under `/groups/<gid>/invite-links`, see [`get_routes_and_docs`], and used
+//! under `/invites/<token>`, see [`get_invite_routes_and_docs`]
+
+use crate::{
+    api::live::{GroupEvent, GroupEventKind, publish, record},
+    establish_connection,
+    guards::GroupAdmin,
+    models::{Group, GroupInviteLink, User},
+    notify::{Notice, NotificationKind, dispatch, notify},
+    schema::{group_administrators, group_invite_links, group_members, groups},
+};
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, Insertable, NullableExpressionMethods, QueryDsl,
+    RunQueryDsl,
+    connection::Connection,
+    dsl::exists,
+    result::{
+        DatabaseErrorKind,
+        Error::{DatabaseError, NotFound},
+    },
+    select,
+};
+use rocket::{http::Status, serde::json::Json};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// the routes the admins manage the links of a group with, mounted under `/groups`
+pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
+    openapi_get_routes_spec![settings:get_invite_links,create_invite_link,revoke_invite_link]
+}
+
+/// the routes the links are used with, mounted under `/invites`
+pub fn get_invite_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
+    openapi_get_routes_spec![settings:preview_invite_link,join_with_invite_link]
+}
+
+/// how long a link lasts when the admin does not say, in hours
+pub const DEFAULT_VALID_HOURS: i64 = 7 * 24;
+
+/// the longest a link can last, in hours
+pub const MAX_VALID_HOURS: i64 = 30 * 24;
+
+/// the most uses a link can be limited to
+pub const MAX_USES: i32 = 1000;
+
+/// whether `link` can still be used to join at `now`
+fn is_usable(link: &GroupInviteLink, now: NaiveDateTime) -> bool {
+    link.revoked_at.is_none()
+        && link.expires_at > now
+        && link.max_uses.is_none_or(|max| link.uses < max)
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PutInviteLink {
+    /// hours the link lasts, a week by default and 30 days at most
+    pub valid_for_hours: Option<i64>,
+    /// how many users can join with the link, unlimited by default
+    pub max_uses: Option<i32>,
+}
+
+/// returns all the invite links of the group, newest first, including the ones that can no
+/// longer be used. can only be performed by an admin
+#[openapi(tag = "Invite")]
+#[get("/<gid>/invite-links")]
+fn get_invite_links(gid: i32, _admin: GroupAdmin) -> Result<Json<Vec<GroupInviteLink>>, Status> {
+    let mut conn = establish_connection();
+
+    match group_invite_links::table
+        .filter(group_invite_links::group_id.eq(gid))
+        .order((
+            group_invite_links::created_at.desc(),
+            group_invite_links::id.desc(),
+        ))
+        .get_results::<GroupInviteLink>(&mut conn)
+    {
+        Ok(v) => Ok(Json(v)),
+        Err(e) => {
+            error!("error loading invite links: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// creates an invite link to the group with a random token, can only be performed by an admin
+#[openapi(tag = "Invite")]
+#[post("/<gid>/invite-links", data = "<link>")]
+fn create_invite_link(
+    gid: i32,
+    link: Json<PutInviteLink>,
+    admin: GroupAdmin,
+) -> Result<Json<GroupInviteLink>, Status> {
+    let hours = link.valid_for_hours.unwrap_or(DEFAULT_VALID_HOURS);
+    if !(1..=MAX_VALID_HOURS).contains(&hours) {
+        error!("invite link valid for {} hours is out of range", hours);
+        return Err(Status::BadRequest);
+    }
+    if link.max_uses.is_some_and(|m| !(1..=MAX_USES).contains(&m)) {
+        error!("invite link with {:?} uses is out of range", link.max_uses);
+        return Err(Status::BadRequest);
+    }
+    let now = Utc::now().naive_utc();
+
+    let mut conn = establish_connection();
+
+    match (
+        group_invite_links::group_id.eq(gid),
+        group_invite_links::token.eq(Uuid::new_v4().simple().to_string()),
+        group_invite_links::created_by.eq(admin.user.id),
+        group_invite_links::created_at.eq(now),
+        group_invite_links::expires_at.eq(now + Duration::hours(hours)),
+        group_invite_links::max_uses.eq(link.max_uses),
+    )
+        .insert_into(group_invite_links::table)
+        .get_result::<GroupInviteLink>(&mut conn)
+    {
+        Ok(l) => Ok(Json(l)),
+        Err(e) => {
+            error!("error creating invite link: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// revokes an invite link of the group, nobody can join with it anymore. can only be performed by
+/// an admin
+#[openapi(tag = "Invite")]
+#[delete("/<gid>/invite-links/<lid>")]
+fn revoke_invite_link(
+    gid: i32,
+    lid: i32,
+    _admin: GroupAdmin,
+) -> Result<Json<GroupInviteLink>, Status> {
+    let mut conn = establish_connection();
+
+    match diesel::update(
+        group_invite_links::table
+            .filter(group_invite_links::id.eq(lid))
+            .filter(group_invite_links::group_id.eq(gid))
+            .filter(group_invite_links::revoked_at.is_null()),
+    )
+    .set(group_invite_links::revoked_at.eq(diesel::dsl::now))
+    .get_result::<GroupInviteLink>(&mut conn)
+    {
+        Ok(l) => Ok(Json(l)),
+        Err(NotFound) => Err(Status::NotFound),
+        Err(e) => {
+            error!("error revoking invite link: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// what the users that open an invite link see of the group
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct InvitePreview {
+    pub group_id: i32,
+    pub group_name: String,
+    pub desc: Option<String>,
+    pub members: i64,
+    pub expires_at: NaiveDateTime,
+}
+
+/// loads the link with `token` along with its group, `404 Not Found` if there is none and `410
+/// Gone` if it can no longer be used
+fn usable_link(token: &str) -> Result<(GroupInviteLink, Group), Status> {
+    let mut conn = establish_connection();
+
+    let (link, group) = match group_invite_links::table
+        .inner_join(groups::table)
+        .filter(group_invite_links::token.eq(token))
+        .filter(groups::deleted_at.is_null())
+        .first::<(GroupInviteLink, Group)>(&mut conn)
+    {
+        Ok(l) => l,
+        Err(NotFound) => return Err(Status::NotFound),
+        Err(e) => {
+            error!("error loading invite link: {:?}", e);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    if is_usable(&link, Utc::now().naive_utc()) {
+        Ok((link, group))
+    } else {
+        error!("invite link is expired, used up or revoked");
+        Err(Status::Gone)
+    }
+}
+
+/// shows the group an invite link is for, to anyone that has the link
+#[openapi(tag = "Invite")]
+#[get("/<token>")]
+fn preview_invite_link(token: &str) -> Result<Json<InvitePreview>, Status> {
+    let (link, group) = usable_link(token)?;
+
+    let mut conn = establish_connection();
+
+    match group_members::table
+        .filter(group_members::group_id.eq(group.id))
+        .count()
+        .get_result::<i64>(&mut conn)
+    {
+        Ok(members) => Ok(Json(InvitePreview {
+            group_id: group.id,
+            group_name: group.group_name,
+            desc: group.desc,
+            members,
+            expires_at: link.expires_at,
+        })),
+        Err(e) => {
+            error!("error counting group members: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// joins the group of an invite link, fails with `409 Conflict` if the user is already a member
+#[openapi(tag = "Invite")]
+#[post("/<token>/join")]
+fn join_with_invite_link(token: &str, user: User) -> Result<Json<Group>, Status> {
+    let (link, group) = usable_link(token)?;
+
+    let mut conn = establish_connection();
+    let now = Utc::now().naive_utc();
+
+    let joined = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let member = select(exists(
+            group_members::table
+                .filter(group_members::group_id.eq(group.id))
+                .filter(group_members::user_id.eq(user.id)),
+        ))
+        .get_result::<bool>(conn)?;
+        if member {
+            error!("user joining with an invite link is already a member");
+            return Ok(Err(Status::Conflict));
+        }
+
+        // counted only while the link is still usable, another user may have taken the last use
+        // or an admin revoked it since it was loaded
+        let counted = diesel::update(
+            group_invite_links::table
+                .filter(group_invite_links::id.eq(link.id))
+                .filter(group_invite_links::revoked_at.is_null())
+                .filter(group_invite_links::expires_at.gt(now))
+                .filter(group_invite_links::max_uses.is_null().or(
+                    group_invite_links::uses.lt(group_invite_links::max_uses.assume_not_null()),
+                )),
+        )
+        .set(group_invite_links::uses.eq(group_invite_links::uses + 1))
+        .execute(conn)?;
+        if counted == 0 {
+            error!("invite link was used up, expired or revoked while joining");
+            return Ok(Err(Status::Gone));
+        }
+
+        (
+            group_members::group_id.eq(group.id),
+            group_members::user_id.eq(user.id),
+        )
+            .insert_into(group_members::table)
+            .execute(conn)?;
+        // the admins are told who joined, the link may have been passed on to anyone
+        let admins = group_administrators::table
+            .filter(group_administrators::group_id.eq(group.id))
+            .select(group_administrators::user_id)
+            .get_results::<i32>(conn)?;
+        let sent = notify(
+            conn,
+            &Notice::new(NotificationKind::MemberJoined, user.id).in_group(group.id),
+            &admins,
+        )?;
+        let event = record(
+            conn,
+            GroupEvent::new(GroupEventKind::MemberAdded, group.id, Some(user.id))
+                .about_member(user.id),
+        )?;
+        Ok(Ok((sent, event)))
+    });
+
+    match joined {
+        Ok(Ok((sent, event))) => {
+            dispatch(&sent);
+            publish(event);
+            Ok(Json(group))
+        }
+        Ok(Err(status)) => Err(status),
+        // joined with the same link at the same time, the use is not counted twice
+        Err(DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            error!("user joining with an invite link is already a member");
+            Err(Status::Conflict)
+        }
+        Err(e) => {
+            error!("error running join_with_invite_link transaction: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! live updates of the groups: what changes in a group is published as a [`GroupEvent`] once it
+//! is committed, and pushed as server-sent events to the members that have the group open, so
+//! that their pages are brought up to date without reloading them
+
+use crate::{api::webhooks, db::DbConnection, guards::GroupMembership};
+
+use diesel::QueryResult;
+use rocket::{
+    Shutdown, State,
+    fairing::AdHoc,
+    futures::stream::BoxStream,
+    response::stream::{Event, EventStream, stream},
+    tokio::{
+        select,
+        sync::broadcast::{self, error::RecvError},
+    },
+};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock, Weak};
+
+pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
+    openapi_get_routes_spec![settings:group_events]
+}
+
+/// what changed in a group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GroupEventKind {
+    ExpenseCreated,
+    ExpenseUpdated,
+    ExpenseDeleted,
+    ExpenseRestored,
+    MemberAdded,
+    MemberRemoved,
+    AdminPromoted,
+    AdminDemoted,
+    GroupDeleted,
+    /// the group was taken out of the trash
+    GroupRestored,
+    /// the stream fell behind and lost some events, the group has to be loaded again
+    Resync,
+}
+
+impl GroupEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GroupEventKind::ExpenseCreated => "EXPENSE_CREATED",
+            GroupEventKind::ExpenseUpdated => "EXPENSE_UPDATED",
+            GroupEventKind::ExpenseDeleted => "EXPENSE_DELETED",
+            GroupEventKind::ExpenseRestored => "EXPENSE_RESTORED",
+            GroupEventKind::MemberAdded => "MEMBER_ADDED",
+            GroupEventKind::MemberRemoved => "MEMBER_REMOVED",
+            GroupEventKind::AdminPromoted => "ADMIN_PROMOTED",
+            GroupEventKind::AdminDemoted => "ADMIN_DEMOTED",
+            GroupEventKind::GroupDeleted => "GROUP_DELETED",
+            GroupEventKind::GroupRestored => "GROUP_RESTORED",
+            GroupEventKind::Resync => "RESYNC",
+        }
+    }
+}
+
+/// a change to group `group_id`, sent as json on its stream
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+pub struct GroupEvent {
+    #[serde(rename = "type")]
+    pub kind: GroupEventKind,
+    pub group_id: i32,
+    /// who made the change, none for the changes made by the server, e.g. the recurring expenses
+    pub actor: Option<i32>,
+    pub expense_id: Option<i32>,
+    /// the user that was added, removed, promoted or demoted
+    pub member_id: Option<i32>,
+}
+
+impl GroupEvent {
+    pub fn new(kind: GroupEventKind, gid: i32, actor: Option<i32>) -> Self {
+        GroupEvent {
+            kind,
+            group_id: gid,
+            actor,
+            expense_id: None,
+            member_id: None,
+        }
+    }
+
+    pub fn about_expense(self, exid: i32) -> Self {
+        GroupEvent {
+            expense_id: Some(exid),
+            ..self
+        }
+    }
+
+    pub fn about_member(self, uid: i32) -> Self {
+        GroupEvent {
+            member_id: Some(uid),
+            ..self
+        }
+    }
+}
+
+/// events that the feed keeps for the slowest client, the ones that lag further behind are told
+/// to load the group again
+const FEED_CAPACITY: usize = 256;
+
+/// the events of all the groups, each stream picks the ones of its group
+pub struct GroupFeed {
+    sender: broadcast::Sender<GroupEvent>,
+}
+
+impl GroupFeed {
+    /// the events published from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<GroupEvent> {
+        self.sender.subscribe()
+    }
+}
+
+static FEEDS: RwLock<Vec<Weak<GroupFeed>>> = RwLock::new(Vec::new());
+
+/// queues `event` for the webhooks of its group, to be called in the transaction that makes the
+/// change so that the deliveries are committed or rolled back along with it. returns the event, to
+/// be [`publish`]ed once committed
+pub fn record(conn: &mut DbConnection, event: GroupEvent) -> QueryResult<GroupEvent> {
+    webhooks::enqueue(conn, &event)?;
+    Ok(event)
+}
+
+/// sends `event` to the clients that have its group open, to be called once the change is
+/// committed
+pub fn publish(event: GroupEvent) {
+    let feeds: Vec<Arc<GroupFeed>> = FEEDS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect();
+    for feed in feeds {
+        // nobody is listening when no client has a group open
+        let _ = feed.sender.send(event);
+    }
+}
+
+/// manages the [`GroupFeed`] that [`publish`] sends the events to
+pub fn feed() -> AdHoc {
+    AdHoc::on_ignite("Group feed", |rocket| async {
+        let (sender, _) = broadcast::channel(FEED_CAPACITY);
+        let feed = Arc::new(GroupFeed { sender });
+
+        let mut feeds = FEEDS.write().unwrap_or_else(|e| e.into_inner());
+        feeds.retain(|f| f.strong_count() > 0);
+        feeds.push(Arc::downgrade(&feed));
+        drop(feeds);
+
+        rocket.manage(feed)
+    })
+}
+
+/// streams the changes to group `gid` as server-sent events, every event carries a
+/// [`GroupEvent`] as json. needs to be executed by a member of the group, the stream ends when
+/// the member is removed from the group or the group is deleted
+#[openapi(tag = "Groups")]
+#[get("/<gid>/live")]
+fn group_events(
+    gid: i32,
+    member: GroupMembership,
+    feed: &State<Arc<GroupFeed>>,
+    mut shutdown: Shutdown,
+) -> EventStream<BoxStream<'static, Event>> {
+    let mut receiver = feed.subscribe();
+    let uid = member.user.id;
+
+    let events = stream! {
+        loop {
+            let event = select! {
+                biased;
+                e = receiver.recv() => match e {
+                    Ok(e) if e.group_id == gid => e,
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(_)) => GroupEvent::new(GroupEventKind::Resync, gid, None),
+                    Err(RecvError::Closed) => break,
+                },
+                _ = &mut shutdown => break,
+            };
+
+            yield Event::json(&event);
+
+            let left = match event.kind {
+                GroupEventKind::MemberRemoved => event.member_id == Some(uid),
+                GroupEventKind::GroupDeleted => true,
+                _ => false,
+            };
+            if left {
+                break;
+            }
+        }
+    };
+
+    EventStream::from(Box::pin(events) as BoxStream<'static, Event>)
+}
@@ -1,5 +1,17 @@
+pub mod attachments;
+pub mod comments;
+pub mod emails;
 pub mod expenses;
 pub mod friends;
 pub mod groups;
+pub mod history;
+pub mod invite_links;
+pub mod live;
 pub mod notifications;
+pub mod pagination;
+pub mod receipts;
+pub mod recurring;
+pub mod reminders;
+pub mod trash;
 pub mod users;
+pub mod webhooks;
@@ -1,46 +1,122 @@
-use diesel::{result::Error, ExpressionMethods, QueryDsl, RunQueryDsl};
-use rocket::{http::Status, serde::json::Json};
+use chrono::NaiveDateTime;
+use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl, result::Error};
+use rocket::{
+    Request, Shutdown, State,
+    fairing::AdHoc,
+    futures::stream::BoxStream,
+    http::Status,
+    request::{FromRequest, Outcome},
+    response::stream::{Event, EventStream, stream},
+    serde::json::Json,
+    tokio::{
+        select,
+        sync::broadcast::{self, error::RecvError},
+    },
+};
 use rocket_okapi::{
-    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, request::OpenApiFromRequest,
+    settings::OpenApiSettings,
 };
+use schemars::JsonSchema;
+use std::sync::Arc;
 
 use crate::{
+    api::pagination::{Cursor, Page, Param, SortOrder, keyset, page_limit},
+    db::DbConnection,
     establish_connection,
     models::{Notification, User},
-    schema::notifications,
+    notify::{Channel, NotificationPreference, register_channel},
+    schema::{notifications, users},
 };
 
 pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
-    openapi_get_routes_spec![settings:get_notifications,read_notification, get_notification_preferences,set_notification_preferences]
+    openapi_get_routes_spec![settings:get_notifications,stream_notifications,read_notification,read_all_notifications,get_unread_count,delete_notification,delete_notifications, get_notification_preferences,set_notification_preferences]
 }
 
-/// returns notification preferences of user, options are: `NONE`, `PERSONAL`, `ALL`
+/// returns notification preferences of user, options are: `SILENCED`, `PERSONAL`, `ALL`
 #[openapi(tag = "Notifications")]
 #[get("/preferences")]
-fn get_notification_preferences(user: User) -> Result<Json<String>, Status> {
-    Err(Status::NotImplemented)
+fn get_notification_preferences(user: User) -> Json<NotificationPreference> {
+    Json(NotificationPreference::of(&user))
 }
 
-/// sets notification preferences of user, options are: `NONE`, `PERSONAL`, `ALL`
+/// sets notification preferences of user, options are: `SILENCED`, `PERSONAL`, `ALL`
 #[openapi(tag = "Notifications")]
 #[put("/preferences/<preference>")]
-fn set_notification_preferences(user: User, preference: String) -> Result<Json<String>, Status> {
-    Err(Status::NotImplemented)
+fn set_notification_preferences(
+    user: User,
+    preference: &str,
+) -> Result<Json<NotificationPreference>, Status> {
+    let Some(preference) = NotificationPreference::parse(preference) else {
+        error!("unknown notification preference `{}`", preference);
+        return Err(Status::BadRequest);
+    };
+
+    let mut conn = establish_connection();
+
+    match diesel::update(users::table.filter(users::id.eq(user.id)))
+        .set(users::notification_preferences.eq(preference.as_str()))
+        .execute(&mut conn)
+    {
+        Ok(_) => Ok(Json(preference)),
+        Err(e) => {
+            error!("error setting notification preferences: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// filters and pagination of the notification list, the newest notifications come first unless
+/// `order` says otherwise
+#[derive(Debug, FromForm, JsonSchema)]
+pub struct NotificationQuery {
+    /// only the notifications of this type, e.g. `NEW_EXPENSE`
+    #[field(name = "type")]
+    #[serde(rename = "type")]
+    pub notification_type: Param<String>,
+    /// only the read (`true`) or unread (`false`) notifications
+    pub read: Param<bool>,
+    /// `asc` or `desc` (default)
+    pub order: Param<SortOrder>,
+    /// `next_cursor` of the previous page
+    pub cursor: Param<String>,
+    /// notifications in the page, 50 by default and 100 at most
+    pub limit: Param<i64>,
 }
 
-/// returns all notifications that the requesting user has received
+/// returns the notifications that the requesting user has received, paginated
 #[openapi(tag = "Notifications")]
-#[get("/")]
-fn get_notifications(user: User) -> Result<Json<Vec<Notification>>, Status> {
-    let mut conn = establish_connection();
+#[get("/?<query..>")]
+fn get_notifications(
+    user: User,
+    query: NotificationQuery,
+) -> Result<Json<Page<Notification>>, Status> {
+    let limit = page_limit(query.limit.value())?;
+    let cursor = Cursor::<NaiveDateTime>::from_query(&query.cursor)?;
 
-    let res = notifications::table
+    let mut q = notifications::table
         .filter(notifications::notified_user_id.eq(user.id))
-        .get_results::<Notification>(&mut conn);
+        .into_boxed();
+    if let Some(t) = query.notification_type.get() {
+        q = q.filter(notifications::notification_type.eq(t));
+    }
+    if let Some(r) = query.read.value() {
+        q = q.filter(notifications::read.eq(r));
+    }
+    let q = keyset!(
+        q,
+        notifications::creation_date,
+        notifications::id,
+        query.order.or_default(),
+        cursor
+    );
 
-    match res {
-        Ok(v) => Ok(Json(v)),
-        Err(Error::NotFound) => Ok(Json(Vec::new())),
+    let mut conn = establish_connection();
+
+    match q.limit(limit + 1).get_results::<Notification>(&mut conn) {
+        Ok(v) => Ok(Json(Page::from_rows(v, limit, |n| {
+            Cursor::new(n.creation_date, n.id).encode()
+        }))),
         Err(e) => {
             error!("error running get notification: {}", e);
             Err(Status::InternalServerError)
@@ -50,7 +126,7 @@ fn get_notifications(user: User) -> Result<Json<Vec<Notification>>, Status> {
 
 /// mark notification with id `nid` as read
 #[openapi(tag = "Notifications")]
-#[get("/<nid>/read")]
+#[put("/<nid>/read", rank = 2)] // after `/preferences/<preference>`
 fn read_notification(nid: i32, user: User) -> Result<Json<Notification>, Status> {
     let mut conn = establish_connection();
 
@@ -64,9 +140,296 @@ fn read_notification(nid: i32, user: User) -> Result<Json<Notification>, Status>
 
     match res {
         Ok(v) => Ok(Json(v)),
+        Err(Error::NotFound) => Err(Status::NotFound),
         Err(e) => {
             error!("error running get notification: {}", e);
             Err(Status::InternalServerError)
         }
     }
 }
+
+/// which notifications are marked as read by `read-all`
+#[derive(Debug, FromForm, JsonSchema)]
+pub struct ReadAllQuery {
+    /// only the notifications about this group
+    pub group: Param<i32>,
+}
+
+/// marks all the unread notifications of the requesting user as read, returns the ones marked
+#[openapi(tag = "Notifications")]
+#[put("/read-all?<query..>")]
+fn read_all_notifications(
+    user: User,
+    query: ReadAllQuery,
+) -> Result<Json<Vec<Notification>>, Status> {
+    let mut q = notifications::table
+        .filter(notifications::notified_user_id.eq(user.id))
+        .filter(notifications::read.eq(false))
+        .select(notifications::id)
+        .into_boxed();
+    if let Some(gid) = query.group.value() {
+        q = q.filter(notifications::group_id.eq(gid));
+    }
+
+    let mut conn = establish_connection();
+
+    match diesel::update(notifications::table.filter(notifications::id.eq_any(q)))
+        .set(notifications::read.eq(true))
+        .get_results::<Notification>(&mut conn)
+    {
+        Ok(v) => Ok(Json(v)),
+        Err(e) => {
+            error!("error marking notifications as read: {}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// returns how many notifications the requesting user has not read yet
+#[openapi(tag = "Notifications")]
+#[get("/unread-count")]
+fn get_unread_count(user: User) -> Result<Json<i64>, Status> {
+    let mut conn = establish_connection();
+
+    match notifications::table
+        .filter(notifications::notified_user_id.eq(user.id))
+        .filter(notifications::read.eq(false))
+        .count()
+        .get_result::<i64>(&mut conn)
+    {
+        Ok(v) => Ok(Json(v)),
+        Err(e) => {
+            error!("error counting unread notifications: {}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// deletes notification with id `nid`
+#[openapi(tag = "Notifications")]
+#[delete("/<nid>")]
+fn delete_notification(nid: i32, user: User) -> Result<Json<Notification>, Status> {
+    let mut conn = establish_connection();
+
+    let res = diesel::delete(
+        notifications::table
+            .filter(notifications::notified_user_id.eq(user.id))
+            .filter(notifications::id.eq(nid)),
+    )
+    .get_result::<Notification>(&mut conn);
+
+    match res {
+        Ok(v) => Ok(Json(v)),
+        Err(Error::NotFound) => Err(Status::NotFound),
+        Err(e) => {
+            error!("error deleting notification: {}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// which notifications are deleted
+#[derive(Debug, FromForm, JsonSchema)]
+pub struct DeleteNotificationsQuery {
+    /// the read (`true`) or unread (`false`) notifications, required so that a request that
+    /// forgets it does not delete everything
+    pub read: Param<bool>,
+}
+
+/// deletes the read or the unread notifications of the requesting user, e.g. `?read=true` clears
+/// the ones already read, fails with `400 Bad Request` without `read`. returns the deleted
+/// notifications
+#[openapi(tag = "Notifications")]
+#[delete("/?<query..>")]
+fn delete_notifications(
+    user: User,
+    query: DeleteNotificationsQuery,
+) -> Result<Json<Vec<Notification>>, Status> {
+    let Some(read) = query.read.value() else {
+        error!("deleting notifications without the read filter");
+        return Err(Status::BadRequest);
+    };
+    let mut conn = establish_connection();
+
+    match diesel::delete(
+        notifications::table
+            .filter(notifications::notified_user_id.eq(user.id))
+            .filter(notifications::read.eq(read)),
+    )
+    .get_results::<Notification>(&mut conn)
+    {
+        Ok(v) => Ok(Json(v)),
+        Err(e) => {
+            error!("error deleting notifications: {}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+// ####################################################################
+// ##########################|STREAM|##################################
+// ####################################################################
+
+/// notifications that the stream keeps for the slowest client, the ones that lag further behind
+/// get the missed notifications from the database
+const STREAM_CAPACITY: usize = 256;
+
+/// the [`Channel`] that pushes the new notifications to the clients connected to
+/// `/notifications/stream`
+pub struct NotificationStream {
+    sender: broadcast::Sender<Notification>,
+}
+
+impl Channel for NotificationStream {
+    fn send(&self, notification: &Notification) {
+        // nobody is listening when no client is connected
+        let _ = self.sender.send(notification.clone());
+    }
+}
+
+/// registers and manages the [`NotificationStream`]
+pub fn broadcaster() -> AdHoc {
+    AdHoc::on_ignite("Notification stream", |rocket| async {
+        let (sender, _) = broadcast::channel(STREAM_CAPACITY);
+        let stream = Arc::new(NotificationStream { sender });
+        let channel: Arc<dyn Channel> = stream.clone();
+        register_channel(&channel);
+
+        rocket.manage(stream)
+    })
+}
+
+/// the `Last-Event-ID` header sent by a client that reconnects to the stream, the id of the last
+/// event it received
+#[derive(Debug, OpenApiFromRequest)]
+pub struct LastEventId(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(LastEventId(
+            req.headers().get_one("Last-Event-ID").map(str::to_owned),
+        ))
+    }
+}
+
+/// the id of the event that carries `notification`. a notification that is brought up to date
+/// gets a new one, since its creation date changes
+fn event_id(notification: &Notification) -> String {
+    Cursor::new(notification.creation_date, notification.id).encode()
+}
+
+/// whether `notification` comes after `cursor` in the order the notifications are streamed in
+fn is_after(notification: &Notification, cursor: &Option<Cursor<NaiveDateTime>>) -> bool {
+    cursor
+        .as_ref()
+        .is_none_or(|c| (notification.creation_date, notification.id) > (c.key, c.id))
+}
+
+/// the notifications of user `uid` that come after `cursor`, all of them without one, oldest first
+fn missed(
+    conn: &mut DbConnection,
+    uid: i32,
+    cursor: Option<Cursor<NaiveDateTime>>,
+) -> diesel::QueryResult<Vec<Notification>> {
+    let q = notifications::table
+        .filter(notifications::notified_user_id.eq(uid))
+        .into_boxed();
+    keyset!(
+        q,
+        notifications::creation_date,
+        notifications::id,
+        SortOrder::Asc,
+        cursor
+    )
+    .get_results::<Notification>(conn)
+}
+
+/// streams the notifications of the requesting user as server-sent events as they are created,
+/// every event carries a notification as json. a client that reconnects with the `Last-Event-ID`
+/// header first receives the notifications it missed
+#[openapi(tag = "Notifications")]
+#[get("/stream")]
+fn stream_notifications(
+    user: User,
+    last_event: LastEventId,
+    stream: &State<Arc<NotificationStream>>,
+    mut shutdown: Shutdown,
+) -> Result<EventStream<BoxStream<'static, Event>>, Status> {
+    // subscribing before reading the database, nothing created in between is lost
+    let mut receiver = stream.sender.subscribe();
+
+    let mut conn = establish_connection();
+
+    let replayed = match last_event.0 {
+        Some(id) => missed(&mut conn, user.id, Some(Cursor::decode(&id)?)),
+        None => Ok(Vec::new()),
+    }
+    .map_err(|e| {
+        error!("error loading missed notifications: {}", e);
+        Status::InternalServerError
+    })?;
+
+    // the newest notification sent, the ones received that do not come after it were already
+    // replayed from the database
+    let mut newest = replayed.last().map(|n| Cursor::new(n.creation_date, n.id));
+    // the newest notification known, where to start again if the stream lags behind
+    let mut last = match newest {
+        Some(_) => newest.clone(),
+        None => notifications::table
+            .filter(notifications::notified_user_id.eq(user.id))
+            .order((
+                notifications::creation_date.desc(),
+                notifications::id.desc(),
+            ))
+            .first::<Notification>(&mut conn)
+            .map(|n| Some(Cursor::new(n.creation_date, n.id)))
+            .or_else(|e| match e {
+                Error::NotFound => Ok(None),
+                e => Err(e),
+            })
+            .map_err(|e| {
+                error!("error loading the last notification: {}", e);
+                Status::InternalServerError
+            })?,
+    };
+    let events = stream! {
+        for n in replayed {
+            yield Event::json(&n).id(event_id(&n));
+        }
+
+        loop {
+            let news = select! {
+                biased;
+                n = receiver.recv() => match n {
+                    Ok(n) if n.notified_user_id == user.id && is_after(&n, &newest) => vec![n],
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(_)) => {
+                        let mut conn = establish_connection();
+                        match missed(&mut conn, user.id, last.clone()) {
+                            Ok(v) => v,
+                            Err(e) => {
+                                error!("error loading missed notifications: {}", e);
+                                break;
+                            }
+                        }
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                _ = &mut shutdown => break,
+            };
+
+            for n in news {
+                last = Some(Cursor::new(n.creation_date, n.id));
+                newest = last.clone();
+                yield Event::json(&n).id(event_id(&n));
+            }
+        }
+    };
+
+    Ok(EventStream::from(
+        Box::pin(events) as BoxStream<'static, Event>
+    ))
+}
@@ -0,0 +1,278 @@
+//! building blocks shared by the list endpoints: the [`Page`] response, the opaque cursors used to
+//! ask for the following page and the query parameters common to every list.
+//!
+//! pagination is keyset based, every list is sorted by some column and then by `id`, the cursor
+//! remembers both values for the last row of a page and the next page starts right after it, so
+//! rows added in the meantime do not shift the pages like an offset would
+
+use chrono::{NaiveDate, NaiveDateTime};
+use rocket::form::{self, DataField, FromForm, FromFormField, ValueField};
+use rocket::http::Status;
+use schemars::{JsonSchema, r#gen::SchemaGenerator, schema::Schema};
+use serde::{Deserialize, Serialize};
+
+/// rows returned when the request does not say otherwise
+pub const DEFAULT_LIMIT: i64 = 50;
+/// most rows that can be asked for in a single page
+pub const MAX_LIMIT: i64 = 100;
+
+/// a page of a list, pass `next_cursor` as the `cursor` parameter of the same request to get the
+/// following page, it is `null` on the last page
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// builds the page out of the rows loaded with a limit of `limit + 1`, the extra row is only
+    /// used to know if there is a following page, whose cursor is computed by `cursor` from the
+    /// last row kept
+    pub fn from_rows(mut rows: Vec<T>, limit: i64, cursor: impl Fn(&T) -> String) -> Self {
+        let next_cursor = if rows.len() as i64 > limit {
+            rows.truncate(limit as usize);
+            rows.last().map(cursor)
+        } else {
+            None
+        };
+
+        Page {
+            items: rows,
+            next_cursor,
+        }
+    }
+}
+
+/// checks the page size asked for, defaulting to [`DEFAULT_LIMIT`]
+pub fn page_limit(limit: Option<i64>) -> Result<i64, Status> {
+    match limit {
+        None => Ok(DEFAULT_LIMIT),
+        Some(l) if (1..=MAX_LIMIT).contains(&l) => Ok(l),
+        Some(l) => {
+            error!("page limit {} out of range", l);
+            Err(Status::BadRequest)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, FromFormField, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[field(value = "asc")]
+    Asc,
+    #[default]
+    #[field(value = "desc")]
+    Desc,
+}
+
+/// values a list can be sorted by, they must be written in a cursor and read back
+pub trait CursorKey: Sized {
+    fn encode(&self) -> String;
+    fn decode(s: &str) -> Option<Self>;
+}
+
+const CURSOR_DATETIME: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+impl CursorKey for NaiveDateTime {
+    fn encode(&self) -> String {
+        self.format(CURSOR_DATETIME).to_string()
+    }
+
+    fn decode(s: &str) -> Option<Self> {
+        NaiveDateTime::parse_from_str(s, CURSOR_DATETIME).ok()
+    }
+}
+
+const CURSOR_DATE: &str = "%Y-%m-%d";
+
+impl CursorKey for NaiveDate {
+    fn encode(&self) -> String {
+        self.format(CURSOR_DATE).to_string()
+    }
+
+    fn decode(s: &str) -> Option<Self> {
+        NaiveDate::parse_from_str(s, CURSOR_DATE).ok()
+    }
+}
+
+impl CursorKey for f64 {
+    fn encode(&self) -> String {
+        self.to_string()
+    }
+
+    fn decode(s: &str) -> Option<Self> {
+        s.parse().ok()
+    }
+}
+
+impl CursorKey for String {
+    fn encode(&self) -> String {
+        self.clone()
+    }
+
+    fn decode(s: &str) -> Option<Self> {
+        Some(s.to_owned())
+    }
+}
+
+/// position of the last row of a page: the value of the sort column and the id, which breaks the
+/// ties between rows with the same value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor<K> {
+    pub key: K,
+    pub id: i32,
+}
+
+impl<K: CursorKey> Cursor<K> {
+    pub fn new(key: K, id: i32) -> Self {
+        Cursor { key, id }
+    }
+
+    /// the cursor as sent to the client, hex encoded so that it is opaque and safe to put in a
+    /// query string whatever the key contains
+    pub fn encode(&self) -> String {
+        format!("{}.{}", self.id, self.key.encode())
+            .bytes()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// reads back a cursor sent by the client, malformed cursors are a bad request
+    pub fn decode(s: &str) -> Result<Self, Status> {
+        let bytes = (0..s.len())
+            .step_by(2)
+            .map(|i| s.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+            .collect::<Option<Vec<u8>>>();
+
+        bytes
+            .and_then(|b| String::from_utf8(b).ok())
+            .and_then(|raw| {
+                let (id, key) = raw.split_once('.')?;
+                Some(Cursor::new(K::decode(key)?, id.parse().ok()?))
+            })
+            .ok_or_else(|| {
+                error!("invalid page cursor `{}`", s);
+                Status::BadRequest
+            })
+    }
+
+    /// decodes the optional `cursor` query parameter
+    pub fn from_query(cursor: &Param<String>) -> Result<Option<Self>, Status> {
+        cursor.get().map(|c| Cursor::decode(c)).transpose()
+    }
+}
+
+/// sorts a boxed query by `$column` and then by `$id`, in the given [`SortOrder`], and skips the
+/// rows up to the optional [`Cursor`], so that the query returns the page after it
+macro_rules! keyset {
+    ($query:expr, $column:expr, $id:expr, $order:expr, $cursor:expr) => {{
+        let mut query = $query;
+        match $order {
+            SortOrder::Asc => {
+                if let Some(c) = $cursor {
+                    query = query.filter(
+                        $column
+                            .gt(c.key.clone())
+                            .or($column.eq(c.key).and($id.gt(c.id))),
+                    );
+                }
+                query.order(($column.asc(), $id.asc()))
+            }
+            SortOrder::Desc => {
+                if let Some(c) = $cursor {
+                    query = query.filter(
+                        $column
+                            .lt(c.key.clone())
+                            .or($column.eq(c.key).and($id.lt(c.id))),
+                    );
+                }
+                query.order(($column.desc(), $id.desc()))
+            }
+        }
+    }};
+}
+pub(crate) use keyset;
+
+/// a day in a query string, formatted as `YYYY-MM-DD`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[schemars(transparent)]
+pub struct QueryDate(pub NaiveDate);
+
+#[rocket::async_trait]
+impl<'v> FromFormField<'v> for QueryDate {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        NaiveDate::parse_from_str(field.value, CURSOR_DATE)
+            .map(QueryDate)
+            .map_err(|_| form::Error::validation("expected a date formatted as YYYY-MM-DD").into())
+    }
+}
+
+/// an optional query parameter that, unlike `Option`, rejects the request when the parameter is
+/// there but cannot be parsed, so that a typo in a filter is not silently ignored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Param<T>(pub Option<T>);
+
+impl<T> Param<T> {
+    pub fn get(&self) -> Option<&T> {
+        self.0.as_ref()
+    }
+}
+
+impl<T: Copy> Param<T> {
+    pub fn value(&self) -> Option<T> {
+        self.0
+    }
+}
+
+impl<T: Copy + Default> Param<T> {
+    pub fn or_default(&self) -> T {
+        self.0.unwrap_or_default()
+    }
+}
+
+#[rocket::async_trait]
+impl<'v, T: FromForm<'v>> FromForm<'v> for Param<T> {
+    /// the context of `T` and whether a value was given at all
+    type Context = (T::Context, bool);
+
+    fn init(opts: form::Options) -> Self::Context {
+        (T::init(opts), false)
+    }
+
+    fn push_value(ctxt: &mut Self::Context, field: ValueField<'v>) {
+        ctxt.1 = true;
+        T::push_value(&mut ctxt.0, field)
+    }
+
+    async fn push_data(ctxt: &mut Self::Context, field: DataField<'v, '_>) {
+        ctxt.1 = true;
+        T::push_data(&mut ctxt.0, field).await
+    }
+
+    fn finalize((ctxt, given): Self::Context) -> form::Result<'v, Self> {
+        if given {
+            T::finalize(ctxt).map(|v| Param(Some(v)))
+        } else {
+            Ok(Param(None))
+        }
+    }
+
+    fn default(_opts: form::Options) -> Option<Self> {
+        Some(Param(None))
+    }
+}
+
+/// documented like an `Option`, that is as a parameter that is not required
+impl<T: JsonSchema> JsonSchema for Param<T> {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        <Option<T>>::schema_name()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        <Option<T>>::json_schema(generator)
+    }
+}
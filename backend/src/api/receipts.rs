@@ -0,0 +1,195 @@
+//! itemized receipts: an expense can be described by its line items and who consumed each of them,
+//! the division of the expense is then computed by the server. every item is split evenly between
+//! its consumers and what the total exceeds the items by, the tax and the tip, is split in
+//! proportion to what everyone consumed
+
+use crate::{
+    api::groups::group_expense,
+    db::DbConnection,
+    establish_connection,
+    guards::GroupMembership,
+    models::{ExpenseItem, ExpenseItemConsumer},
+    schema::{expense_item_consumers, expense_items},
+};
+
+use diesel::{
+    BelongingToDsl, ExpressionMethods, GroupedBy, Insertable, QueryDsl, QueryResult, RunQueryDsl,
+};
+use rocket::{http::Status, serde::json::Json};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
+    openapi_get_routes_spec![settings:get_items]
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PutExpenseItem {
+    pub desc: String,
+    pub amount: f64,
+    /// the users who shared the item, it is split evenly between them
+    pub consumers: Vec<i32>,
+}
+
+/// a line item of a receipt along with who consumed it
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ReceiptItem {
+    pub id: i32,
+    pub desc: String,
+    pub amount: f64,
+    /// ordered by user id
+    pub consumers: Vec<i32>,
+}
+
+fn cents(amount: f64) -> Option<i64> {
+    (amount.is_finite() && amount >= 0.0).then(|| (amount * 100.0).round() as i64)
+}
+
+/// splits `cents` in parts proportional to `weights` that add up to it exactly, the cents left
+/// over by rounding down go to the largest remainders
+fn apportion(cents: i64, weights: &[i64]) -> Vec<i64> {
+    let total = weights.iter().sum::<i64>() as i128;
+    let mut parts: Vec<(i64, i128)> = weights
+        .iter()
+        .map(|&w| {
+            let exact = cents as i128 * w as i128;
+            ((exact / total) as i64, exact % total)
+        })
+        .collect();
+
+    let left = cents - parts.iter().map(|(p, _)| p).sum::<i64>();
+    let mut order: Vec<usize> = (0..parts.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(parts[i].1));
+    for &i in order.iter().take(left as usize) {
+        parts[i].0 += 1;
+    }
+
+    parts.into_iter().map(|(p, _)| p).collect()
+}
+
+/// the amount due by every consumer of `items` on an expense of `total_amount`, ordered by user
+/// id, `None` if the items cannot describe the expense: there are none, an amount is negative, an
+/// item has no consumers or the same one twice, or the items are worth more than the total
+pub fn split_items(items: &[PutExpenseItem], total_amount: f64) -> Option<Vec<(i32, f64)>> {
+    let total = cents(total_amount)?;
+    if items.is_empty() {
+        return None;
+    }
+
+    let mut consumed: BTreeMap<i32, i64> = BTreeMap::new();
+    for item in items {
+        let mut consumers = item.consumers.clone();
+        consumers.sort();
+        consumers.dedup();
+        if consumers.is_empty() || consumers.len() != item.consumers.len() {
+            return None;
+        }
+
+        // the first consumers pay the cent that cannot be split evenly
+        let shares = apportion(cents(item.amount)?, &vec![1; item.consumers.len()]);
+        for (user, share) in item.consumers.iter().zip(shares) {
+            *consumed.entry(*user).or_default() += share;
+        }
+    }
+
+    let subtotal: i64 = consumed.values().sum();
+    let extra = total - subtotal;
+    if extra < 0 || (extra > 0 && subtotal == 0) {
+        return None;
+    }
+
+    let weights: Vec<i64> = consumed.values().copied().collect();
+    let extras = match extra {
+        0 => vec![0; weights.len()],
+        _ => apportion(extra, &weights),
+    };
+
+    Some(
+        consumed
+            .into_iter()
+            .zip(extras)
+            .map(|((user, c), e)| (user, (c + e) as f64 / 100.0))
+            .collect(),
+    )
+}
+
+/// replaces the items of expense `exid` with `items`, removing them if there are none, to be
+/// called in the same transaction that adds or modifies the expense
+pub fn store_items(
+    conn: &mut DbConnection,
+    exid: i32,
+    items: Option<&[PutExpenseItem]>,
+) -> QueryResult<()> {
+    diesel::delete(expense_items::table.filter(expense_items::expense_id.eq(exid)))
+        .execute(conn)?;
+
+    for item in items.unwrap_or_default() {
+        let stored = (
+            expense_items::expense_id.eq(exid),
+            expense_items::desc.eq(&item.desc),
+            expense_items::amount.eq(item.amount),
+        )
+            .insert_into(expense_items::table)
+            .get_result::<ExpenseItem>(conn)?;
+
+        for user in item.consumers.iter() {
+            (
+                expense_item_consumers::item_id.eq(stored.id),
+                expense_item_consumers::user_id.eq(user),
+            )
+                .insert_into(expense_item_consumers::table)
+                .execute(conn)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// returns the line items of a group expense in the order they were sent, empty if the expense
+/// was not itemized. needs to be executed by a member of the group
+#[openapi(tag = "GroupExpenses")]
+#[get("/<gid>/expenses/<exid>/items")]
+fn get_items(
+    gid: i32,
+    exid: i32,
+    _member: GroupMembership,
+) -> Result<Json<Vec<ReceiptItem>>, Status> {
+    group_expense(gid, exid)?;
+
+    let mut conn = establish_connection();
+
+    let items = expense_items::table
+        .filter(expense_items::expense_id.eq(exid))
+        .order(expense_items::id)
+        .get_results::<ExpenseItem>(&mut conn)
+        .map_err(|e| {
+            error!("error loading expense items: {:?}", e);
+            Status::InternalServerError
+        })?;
+
+    let consumers = ExpenseItemConsumer::belonging_to(&items)
+        .order(expense_item_consumers::user_id)
+        .get_results::<ExpenseItemConsumer>(&mut conn)
+        .map_err(|e| {
+            error!("error loading expense item consumers: {:?}", e);
+            Status::InternalServerError
+        })?
+        .grouped_by(&items);
+
+    Ok(Json(
+        items
+            .into_iter()
+            .zip(consumers)
+            .map(|(i, c)| ReceiptItem {
+                id: i.id,
+                desc: i.desc,
+                amount: i.amount,
+                consumers: c.into_iter().map(|c| c.user_id).collect(),
+            })
+            .collect(),
+    ))
+}
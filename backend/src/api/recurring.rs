@@ -0,0 +1,521 @@
+//! recurring expenses: templates of group expenses, like the rent or the bills, that are added to
+//! their group on every occurrence of a recurrence rule.
+//!
+//! the expenses are added by a background task that runs at launch and then every
+//! `recurring.interval_secs`, or on demand by an admin of the group. every run adds the
+//! occurrences up to today that have not been added yet, so a server that has been down adds the
+//! ones it missed once it is back up. a run adds at most [`MAX_CATCH_UP`] expenses of every
+//! recurring expense, the following runs add the rest
+
+use crate::{
+    api::expenses::PutExpense,
+    api::groups::insert_group_expense,
+    api::live::{GroupEvent, GroupEventKind, publish, record},
+    config::Config,
+    db::DbConnection,
+    establish_connection,
+    guards::{GroupAdmin, GroupMembership},
+    models::{Expense, RecurringExpense, RecurringExpenseParticipation},
+    notify::dispatch,
+    schema::{groups, recurring_expense_participations, recurring_expenses},
+};
+
+use chrono::{Datelike, Days, Months, NaiveDate, Utc};
+use diesel::{
+    BelongingToDsl, BoolExpressionMethods, ExpressionMethods, GroupedBy, Insertable,
+    NullableExpressionMethods, QueryDsl, RunQueryDsl,
+};
+use diesel::{connection::Connection, result::Error::NotFound};
+use rocket::fairing::AdHoc;
+use rocket::{http::Status, serde::json::Json};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
+    openapi_get_routes_spec![settings:create_recurring_expense,get_recurring_expenses,delete_recurring_expense,run_recurring_expenses]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Frequency {
+    /// every month on `day_of_month`, or on the last day of the shorter months
+    Monthly,
+    /// every week on the weekday of `start_date`
+    Weekly,
+    /// every `interval_days` days from `start_date`
+    EveryNDays,
+}
+
+impl Frequency {
+    fn as_str(self) -> &'static str {
+        match self {
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::EveryNDays => "EVERY_N_DAYS",
+        }
+    }
+}
+
+/// expenses of a recurring expense added in a single run, and occurrences that can already be due
+/// when a recurring expense is created
+pub const MAX_CATCH_UP: usize = 100;
+/// longest `interval_days` accepted, ten years
+pub const MAX_INTERVAL_DAYS: i32 = 3650;
+
+/// when the expenses of a recurring expense are added
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rule {
+    Monthly { day: u32 },
+    EveryNDays { days: u64 },
+}
+
+impl Rule {
+    /// the rule of a recurring expense, `None` for values that were not validated on insert
+    fn of(recurring: &RecurringExpense) -> Option<Rule> {
+        match recurring.frequency.as_str() {
+            "MONTHLY" => recurring
+                .day_of_month
+                .filter(|d| (1..=31).contains(d))
+                .map(|d| Rule::Monthly { day: d as u32 }),
+            "WEEKLY" => Some(Rule::EveryNDays { days: 7 }),
+            "EVERY_N_DAYS" => recurring
+                .interval_days
+                .filter(|d| (1..=MAX_INTERVAL_DAYS).contains(d))
+                .map(|d| Rule::EveryNDays { days: d as u64 }),
+            _ => None,
+        }
+    }
+
+    /// day `day` of the month of `date`, or its last day if the month is shorter
+    fn day_in_month(date: NaiveDate, day: u32) -> NaiveDate {
+        (0..4)
+            .find_map(|i| date.with_day(day - i))
+            .expect("every month has at least 28 days")
+    }
+
+    /// the first occurrence on or after `start`, `None` past the last date chrono can represent
+    fn first(self, start: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Rule::Monthly { day } => {
+                let candidate = Rule::day_in_month(start, day);
+                if candidate >= start {
+                    Some(candidate)
+                } else {
+                    self.following(candidate)
+                }
+            }
+            Rule::EveryNDays { .. } => Some(start),
+        }
+    }
+
+    /// the occurrence after `date`, which is itself an occurrence, `None` past the last date
+    /// chrono can represent
+    fn following(self, date: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Rule::Monthly { day } => {
+                let next_month = date.with_day(1)?.checked_add_months(Months::new(1))?;
+                Some(Rule::day_in_month(next_month, day))
+            }
+            Rule::EveryNDays { days } => date.checked_add_days(Days::new(days)),
+        }
+    }
+
+    /// the occurrences from `start` on that are due on or before `today` and not after `end`
+    fn due(
+        self,
+        start: NaiveDate,
+        end: Option<NaiveDate>,
+        today: NaiveDate,
+    ) -> impl Iterator<Item = NaiveDate> {
+        std::iter::successors(self.first(start), move |d| self.following(*d))
+            .take_while(move |d| *d <= today && end.is_none_or(|end| *d <= end))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PutRecurringExpense {
+    /// the expense added on every occurrence, its `expense_date` is ignored, every expense is
+    /// dated on its occurrence
+    pub expense: PutExpense,
+    pub frequency: Frequency,
+    /// required for `MONTHLY`, from 1 to 31
+    pub day_of_month: Option<i32>,
+    /// required for `EVERY_N_DAYS`, from 1 to 3650
+    pub interval_days: Option<i32>,
+    /// the first expense is added on the first occurrence on or after this day, at most 100
+    /// occurrences can already be due
+    pub start_date: NaiveDate,
+    /// no expense is added after this day, they go on forever without it
+    pub end_date: Option<NaiveDate>,
+}
+
+impl PutRecurringExpense {
+    /// the rule described by the request, `400 Bad Request` if it is not valid or if more than
+    /// [`MAX_CATCH_UP`] of its occurrences are already due on `today`
+    fn rule(&self, today: NaiveDate) -> Result<Rule, Status> {
+        let rule = match (self.frequency, self.day_of_month, self.interval_days) {
+            (Frequency::Monthly, Some(day), None) if (1..=31).contains(&day) => {
+                Rule::Monthly { day: day as u32 }
+            }
+            (Frequency::Weekly, None, None) => Rule::EveryNDays { days: 7 },
+            (Frequency::EveryNDays, None, Some(days))
+                if (1..=MAX_INTERVAL_DAYS).contains(&days) =>
+            {
+                Rule::EveryNDays { days: days as u64 }
+            }
+            _ => {
+                error!("invalid recurrence rule {:?}", self.frequency);
+                return Err(Status::BadRequest);
+            }
+        };
+
+        match self.end_date {
+            Some(end) if end < self.start_date => {
+                error!("recurring expense ends on {} before it starts", end);
+                Err(Status::BadRequest)
+            }
+            _ if rule
+                .due(self.start_date, self.end_date, today)
+                .nth(MAX_CATCH_UP)
+                .is_some() =>
+            {
+                error!(
+                    "recurring expense starts too far back on {}",
+                    self.start_date
+                );
+                Err(Status::BadRequest)
+            }
+            _ => Ok(rule),
+        }
+    }
+}
+
+/// a recurring expense along with how its expenses are divided
+pub type RecurringExpenseWithParticipations =
+    (RecurringExpense, Vec<RecurringExpenseParticipation>);
+
+/// adds a recurring expense to the group, whose expenses are added on every occurrence of its rule
+/// from `start_date` on, the template expense is checked like the one of a new group expense.
+/// needs to be executed by a member of the group
+#[openapi(tag = "GroupExpenses")]
+#[post("/<gid>/recurring", data = "<recurring>")]
+fn create_recurring_expense(
+    gid: i32,
+    mut recurring: Json<PutRecurringExpense>,
+    member: GroupMembership,
+) -> Result<Json<RecurringExpenseWithParticipations>, Status> {
+    let rule = recurring.rule(Utc::now().date_naive())?;
+    let Some(first) = rule.first(recurring.start_date) else {
+        error!(
+            "recurring expense starts too far ahead on {}",
+            recurring.start_date
+        );
+        return Err(Status::BadRequest);
+    };
+    recurring.expense.itemize()?;
+    recurring.expense.check_category(Some(gid))?;
+
+    let mut conn = establish_connection();
+    let template = &recurring.expense;
+
+    match conn.transaction::<RecurringExpenseWithParticipations, diesel::result::Error, _>(|conn| {
+        let created = (
+            recurring_expenses::group_id.eq(gid),
+            recurring_expenses::created_by.eq(member.user.id),
+            recurring_expenses::desc.eq(template.desc.clone()),
+            recurring_expenses::total_amount.eq(template.total_amount),
+            recurring_expenses::paid_by.eq(template.paid_by),
+            recurring_expenses::category_id.eq(template.category_id),
+            recurring_expenses::frequency.eq(recurring.frequency.as_str()),
+            recurring_expenses::day_of_month.eq(recurring.day_of_month),
+            recurring_expenses::interval_days.eq(recurring.interval_days),
+            recurring_expenses::start_date.eq(recurring.start_date),
+            recurring_expenses::end_date.eq(recurring.end_date),
+            recurring_expenses::next_date.eq(first),
+            recurring_expenses::created_at.eq(diesel::dsl::now),
+        )
+            .insert_into(recurring_expenses::table)
+            .get_result::<RecurringExpense>(conn)?;
+
+        let mut participations = Vec::new();
+        for (d, a) in template.division.iter() {
+            participations.push(
+                (
+                    recurring_expense_participations::recurring_expense_id.eq(created.id),
+                    recurring_expense_participations::user_id.eq(d),
+                    recurring_expense_participations::amount_due.eq(a),
+                )
+                    .insert_into(recurring_expense_participations::table)
+                    .get_result::<RecurringExpenseParticipation>(conn)?,
+            );
+        }
+
+        Ok((created, participations))
+    }) {
+        Ok(r) => Ok(Json(r)),
+        Err(e) => {
+            error!(
+                "error running create_recurring_expense transaction: {:?}",
+                e
+            );
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// lists the recurring expenses of the group, including the ones that have ended, needs to be
+/// executed by a member of the group
+#[openapi(tag = "GroupExpenses")]
+#[get("/<gid>/recurring")]
+fn get_recurring_expenses(
+    gid: i32,
+    _member: GroupMembership,
+) -> Result<Json<Vec<RecurringExpenseWithParticipations>>, Status> {
+    let mut conn = establish_connection();
+
+    match conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let recurring = recurring_expenses::table
+            .filter(recurring_expenses::group_id.eq(gid))
+            .order(recurring_expenses::id.asc())
+            .get_results::<RecurringExpense>(conn)?;
+        let participations = RecurringExpenseParticipation::belonging_to(&recurring)
+            .get_results::<RecurringExpenseParticipation>(conn)?
+            .grouped_by(&recurring);
+        Ok(recurring.into_iter().zip(participations).collect())
+    }) {
+        Ok(r) => Ok(Json(r)),
+        Err(e) => {
+            error!("error loading recurring expenses: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// deletes a recurring expense, the expenses already added are kept. needs to be performed either
+/// by who created the recurring expense or an admin
+#[openapi(tag = "GroupExpenses")]
+#[delete("/<gid>/recurring/<rid>")]
+fn delete_recurring_expense(
+    gid: i32,
+    rid: i32,
+    member: GroupMembership,
+) -> Result<Json<RecurringExpense>, Status> {
+    let mut conn = establish_connection();
+
+    let recurring = match recurring_expenses::table
+        .filter(recurring_expenses::id.eq(rid))
+        .filter(recurring_expenses::group_id.eq(gid))
+        .first::<RecurringExpense>(&mut conn)
+    {
+        Ok(r) => r,
+        Err(NotFound) => return Err(Status::NotFound),
+        Err(e) => {
+            error!("error loading recurring expense: {:?}", e);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    if recurring.created_by != member.user.id && !member.is_admin {
+        error!("trying to delete recurring expense but user is not admin or creator");
+        return Err(Status::Forbidden);
+    }
+
+    match diesel::delete(recurring_expenses::table.filter(recurring_expenses::id.eq(rid)))
+        .get_result::<RecurringExpense>(&mut conn)
+    {
+        Ok(r) => Ok(Json(r)),
+        Err(e) => {
+            error!("error deleting recurring expense: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// adds right away the expenses of the recurring expenses of the group that are due, instead of
+/// waiting for the background task, and returns them. can only be performed by an admin
+#[openapi(tag = "GroupExpenses")]
+#[post("/<gid>/recurring/run")]
+fn run_recurring_expenses(gid: i32, _admin: GroupAdmin) -> Result<Json<Vec<Expense>>, Status> {
+    match add_due_expenses(Utc::now().date_naive(), Some(gid)) {
+        Ok(added) => Ok(Json(added)),
+        Err(e) => {
+            error!("error adding recurring expenses of group {}: {:?}", gid, e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// adds to `added` the expenses of the occurrences of `recurring` due on or before `today`, at
+/// most [`MAX_CATCH_UP`] of them
+fn add_occurrences(
+    conn: &mut DbConnection,
+    recurring: &RecurringExpense,
+    rule: Rule,
+    today: NaiveDate,
+    added: &mut Vec<Expense>,
+) -> Result<(), diesel::result::Error> {
+    let division = recurring_expense_participations::table
+        .filter(recurring_expense_participations::recurring_expense_id.eq(recurring.id))
+        .get_results::<RecurringExpenseParticipation>(conn)?
+        .into_iter()
+        .map(|p| (p.user_id, p.amount_due.unwrap_or_default()))
+        .collect();
+    let mut expense = PutExpense {
+        desc: recurring.desc.clone(),
+        total_amount: recurring.total_amount,
+        paid_by: recurring.paid_by,
+        division,
+        expense_date: None,
+        category_id: recurring.category_id,
+        items: None,
+    };
+
+    // the occurrences from `next_date` on, which is itself an occurrence
+    for date in rule
+        .due(recurring.next_date, recurring.end_date, today)
+        .take(MAX_CATCH_UP)
+    {
+        let Some(next) = rule.following(date) else {
+            error!(
+                "recurring expense {} has no occurrence after {}",
+                recurring.id, date
+            );
+            break;
+        };
+        expense.expense_date = Some(date);
+
+        let result = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            let claimed = diesel::update(
+                recurring_expenses::table
+                    .filter(recurring_expenses::id.eq(recurring.id))
+                    .filter(recurring_expenses::next_date.eq(date)),
+            )
+            .set(recurring_expenses::next_date.eq(next))
+            .execute(conn)?;
+            if claimed == 0 {
+                return Ok(None);
+            }
+
+            let (e, sent) =
+                insert_group_expense(conn, recurring.group_id, &expense, recurring.created_by)?;
+            let event = record(
+                conn,
+                GroupEvent::new(GroupEventKind::ExpenseCreated, recurring.group_id, None)
+                    .about_expense(e.id),
+            )?;
+            Ok(Some((e, sent, event)))
+        })?;
+
+        match result {
+            Some((e, sent, event)) => {
+                dispatch(&sent);
+                publish(event);
+                added.push(e)
+            }
+            // added by another process, which also takes care of the following ones
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+/// runs of this process are serialized, so that the background task and the admins asking for a
+/// run do not race to add the same expenses
+static RUN: Mutex<()> = Mutex::new(());
+
+/// adds the expenses of the recurring expenses due on or before `today`, of group `gid` only if
+/// given, and returns them.
+///
+/// every expense is added in its own transaction that moves `next_date` forward only if nobody
+/// else did it in the meantime, so an occurrence is added exactly once even when more processes
+/// run at the same time. an error does not lose the expenses already added, and one recurring
+/// expense that fails does not stop the others
+pub fn add_due_expenses(
+    today: NaiveDate,
+    gid: Option<i32>,
+) -> Result<Vec<Expense>, diesel::result::Error> {
+    let _run = RUN.lock().unwrap_or_else(|e| e.into_inner());
+    let mut conn = establish_connection();
+
+    let mut query = recurring_expenses::table
+        .filter(recurring_expenses::next_date.le(today))
+        .filter(
+            recurring_expenses::end_date
+                .is_null()
+                .or(recurring_expenses::end_date.ge(recurring_expenses::next_date.nullable())),
+        )
+        .into_boxed();
+    if let Some(gid) = gid {
+        query = query.filter(recurring_expenses::group_id.eq(gid));
+    }
+    // nothing is added to the groups in the trash, what they missed is added if they are restored
+    query = query.filter(
+        recurring_expenses::group_id.eq_any(
+            groups::table
+                .filter(groups::deleted_at.is_null())
+                .select(groups::id),
+        ),
+    );
+    let due = query.get_results::<RecurringExpense>(&mut conn)?;
+
+    let mut added = Vec::new();
+    for recurring in due {
+        let Some(rule) = Rule::of(&recurring) else {
+            error!("recurring expense {} has an invalid rule", recurring.id);
+            continue;
+        };
+        // a recurring expense that cannot be added does not hold back the others
+        if let Err(e) = add_occurrences(&mut conn, &recurring, rule, today, &mut added) {
+            error!(
+                "error adding the expenses of recurring expense {}: {:?}",
+                recurring.id, e
+            );
+        }
+    }
+
+    Ok(added)
+}
+
+/// the background task that adds the recurring expenses when they are due, at launch and then at
+/// the configured interval, until the rocket shuts down
+pub fn scheduler() -> AdHoc {
+    AdHoc::on_liftoff("Recurring expenses", |rocket| {
+        Box::pin(async move {
+            let Some(config) = rocket.state::<Config>() else {
+                return;
+            };
+            if !config.recurring.enabled {
+                return;
+            }
+
+            let period = Duration::from_secs(config.recurring.interval_secs);
+            let mut shutdown = rocket.shutdown();
+            rocket::tokio::spawn(async move {
+                let mut interval = rocket::tokio::time::interval(period);
+                loop {
+                    rocket::tokio::select! {
+                        _ = interval.tick() => (),
+                        _ = &mut shutdown => break,
+                    }
+
+                    let today = Utc::now().date_naive();
+                    match rocket::tokio::task::spawn_blocking(move || add_due_expenses(today, None))
+                        .await
+                    {
+                        Ok(Ok(added)) if !added.is_empty() => {
+                            info!("added {} recurring expenses", added.len())
+                        }
+                        Ok(Ok(_)) => (),
+                        Ok(Err(e)) => error!("error adding recurring expenses: {:?}", e),
+                        Err(e) => error!("recurring expenses task failed: {:?}", e),
+                    }
+                }
+            });
+        })
+    })
+}
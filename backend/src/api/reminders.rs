@@ -0,0 +1,440 @@
+//! payment reminders: a member of a group reminds a member that owes the member money for the
+//! expenses of the group of what it owes, with an optional message. a member can remind the same
+//! member of a group once every `reminders.min_interval_hours`.
+//!
+//! the admins of a group can also schedule automatic reminders for the members that owe since more
+//! than a number of days, which a background task sends at launch and then every
+//! `reminders.interval_secs`.
+//!
+//! the balance of a member is what the member paid for the expenses of the group minus the part
+//! of them that is due by the member, so the members with a positive balance are owed what the
+//! ones with a negative balance owe. the automatic reminders ask to settle that balance with the
+//! group, the ones sent by a member ask for what is owed to that member alone
+
+use crate::{
+    config::Config,
+    db::DbConnection,
+    establish_connection,
+    guards::{GroupAdmin, GroupMembership},
+    models::{Notification, PaymentReminder, ReminderSchedule},
+    notify::{Notice, NotificationKind, dispatch, notify},
+    schema::{
+        expense_participations, expenses, group_members, groups, payment_reminders,
+        reminder_schedules,
+    },
+};
+
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, Insertable, OptionalExtension, QueryDsl, QueryResult,
+    RunQueryDsl, connection::Connection, dsl, dsl::exists, select,
+};
+use rocket::fairing::AdHoc;
+use rocket::{State, http::Status, serde::json::Json};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
+    openapi_get_routes_spec![settings:get_reminders,send_reminder,get_reminder_schedule,set_reminder_schedule]
+}
+
+/// longest message of a reminder, in characters
+pub const MAX_MESSAGE_LENGTH: usize = 500;
+
+/// longest delay of the automatic reminders, in days
+pub const MAX_AFTER_DAYS: i32 = 365;
+
+/// balances closer to zero than this are rounding errors, not debts
+const NEGLIGIBLE: f64 = 0.005;
+
+/// the balance of the members of group `gid` that paid for or take part in its expenses, counting
+/// only the expenses up to `until` if given
+pub fn balances(
+    conn: &mut DbConnection,
+    gid: i32,
+    until: Option<NaiveDate>,
+) -> QueryResult<BTreeMap<i32, f64>> {
+    // the grouped queries are boxed only after grouping, diesel cannot group a boxed query
+    let mut paid = expenses::table
+        .filter(expenses::group_id.eq(gid))
+        .filter(expenses::deleted_at.is_null())
+        .group_by(expenses::paid_by)
+        .select((expenses::paid_by, dsl::sum(expenses::total_amount)))
+        .into_boxed();
+    let mut shares = expense_participations::table
+        .inner_join(expenses::table)
+        .filter(expenses::group_id.eq(gid))
+        .filter(expenses::deleted_at.is_null())
+        .group_by(expense_participations::user_id)
+        .select((
+            expense_participations::user_id,
+            dsl::sum(expense_participations::amount_due),
+        ))
+        .into_boxed();
+    if let Some(u) = until {
+        paid = paid.filter(expenses::expense_date.le(u));
+        shares = shares.filter(expenses::expense_date.le(u));
+    }
+
+    let mut balances = BTreeMap::new();
+    for (uid, amount) in paid.get_results::<(i32, Option<f64>)>(conn)? {
+        *balances.entry(uid).or_default() += amount.unwrap_or_default();
+    }
+    for (uid, amount) in shares.get_results::<(i32, Option<f64>)>(conn)? {
+        *balances.entry(uid).or_default() -= amount.unwrap_or_default();
+    }
+    Ok(balances)
+}
+
+/// what member `debtor` owes member `creditor` for the expenses of group `gid`: the part due by
+/// `debtor` of the expenses paid by `creditor`, less the part due by `creditor` of the expenses
+/// paid by `debtor`. negative if it is `creditor` that owes
+fn debt(conn: &mut DbConnection, gid: i32, creditor: i32, debtor: i32) -> QueryResult<f64> {
+    Ok(due_to(conn, gid, creditor, debtor)? - due_to(conn, gid, debtor, creditor)?)
+}
+
+/// the part due by `participant` of the expenses of group `gid` paid by `payer`
+fn due_to(conn: &mut DbConnection, gid: i32, payer: i32, participant: i32) -> QueryResult<f64> {
+    expense_participations::table
+        .inner_join(expenses::table)
+        .filter(expenses::group_id.eq(gid))
+        .filter(expenses::deleted_at.is_null())
+        .filter(expenses::paid_by.eq(payer))
+        .filter(expense_participations::user_id.eq(participant))
+        .select(dsl::sum(expense_participations::amount_due))
+        .get_result::<Option<f64>>(conn)
+        .map(Option::unwrap_or_default)
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PutReminder {
+    pub message: Option<String>,
+}
+
+impl PutReminder {
+    /// the message without the surrounding whitespace, `None` if there is none and `400 Bad
+    /// Request` if it is longer than [`MAX_MESSAGE_LENGTH`]
+    fn text(&self) -> Result<Option<&str>, Status> {
+        match self.message.as_deref().map(str::trim) {
+            Some(m) if m.chars().count() > MAX_MESSAGE_LENGTH => {
+                error!("reminder message is too long");
+                Err(Status::BadRequest)
+            }
+            Some(m) if !m.is_empty() => Ok(Some(m)),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// records a reminder of `amount` to `recipient` in group `gid` and notifies the recipient of it,
+/// the automatic ones have no `sender`. returns the notifications recorded too
+fn remind(
+    conn: &mut DbConnection,
+    gid: i32,
+    sender: Option<i32>,
+    recipient: i32,
+    amount: f64,
+    message: Option<&str>,
+    now: NaiveDateTime,
+) -> QueryResult<(PaymentReminder, Vec<Notification>)> {
+    let reminder = (
+        payment_reminders::group_id.eq(gid),
+        payment_reminders::sender_id.eq(sender),
+        payment_reminders::recipient_id.eq(recipient),
+        payment_reminders::amount.eq(amount),
+        payment_reminders::message.eq(message),
+        payment_reminders::created_at.eq(now),
+    )
+        .insert_into(payment_reminders::table)
+        .get_result::<PaymentReminder>(conn)?;
+
+    let notice = match sender {
+        Some(s) => Notice::new(NotificationKind::BalanceRequested, s),
+        None => Notice::automatic(NotificationKind::BalanceRequested),
+    };
+    let sent = notify(
+        conn,
+        &notice.in_group(gid).about_reminder(reminder.id),
+        &[recipient],
+    )?;
+    Ok((reminder, sent))
+}
+
+/// returns the reminders the user sent or received in the group, newest first, needs to be
+/// executed by a member of the group
+#[openapi(tag = "Groups")]
+#[get("/<gid>/reminders")]
+fn get_reminders(gid: i32, member: GroupMembership) -> Result<Json<Vec<PaymentReminder>>, Status> {
+    let mut conn = establish_connection();
+
+    match payment_reminders::table
+        .filter(payment_reminders::group_id.eq(gid))
+        .filter(
+            payment_reminders::sender_id
+                .eq(member.user.id)
+                .or(payment_reminders::recipient_id.eq(member.user.id)),
+        )
+        .order((
+            payment_reminders::created_at.desc(),
+            payment_reminders::id.desc(),
+        ))
+        .get_results::<PaymentReminder>(&mut conn)
+    {
+        Ok(v) => Ok(Json(v)),
+        Err(e) => {
+            error!("error loading payment reminders: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// reminds member `uid` of what the member owes the requesting member for the expenses of the
+/// group. fails with `409 Conflict` if `uid` owes the requesting member nothing and with `429 Too
+/// Many Requests` if the requesting member already reminded `uid` in the last
+/// `reminders.min_interval_hours`
+#[openapi(tag = "Groups")]
+#[post("/<gid>/reminders/<uid>", data = "<reminder>")]
+fn send_reminder(
+    gid: i32,
+    uid: i32,
+    reminder: Json<PutReminder>,
+    member: GroupMembership,
+    config: &State<Config>,
+) -> Result<Json<PaymentReminder>, Status> {
+    let sender = member.user.id;
+    if uid == sender {
+        error!("trying to remind oneself");
+        return Err(Status::BadRequest);
+    }
+    let message = reminder.text()?;
+    let now = Utc::now().naive_utc();
+
+    let mut conn = establish_connection();
+
+    let reminded = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let recipient = group_members::table
+            .filter(group_members::group_id.eq(gid))
+            .filter(group_members::user_id.eq(uid))
+            .select(group_members::user_id);
+        // on postgres the recipient is locked until the transaction ends, so that two reminders
+        // sent at the same time cannot both pass the check on the last one. sqlite has no row
+        // locks, but it lets only one of the two transactions write and fails the other one
+        #[cfg(feature = "postgres")]
+        let recipient = recipient.for_update().first::<i32>(conn).optional()?;
+        #[cfg(feature = "sqlite")]
+        let recipient = recipient.first::<i32>(conn).optional()?;
+        if recipient.is_none() {
+            return Ok(Err(Status::NotFound));
+        }
+
+        let recent = select(exists(
+            payment_reminders::table
+                .filter(payment_reminders::group_id.eq(gid))
+                .filter(payment_reminders::sender_id.eq(sender))
+                .filter(payment_reminders::recipient_id.eq(uid))
+                .filter(payment_reminders::created_at.gt(now - config.reminders.min_interval())),
+        ))
+        .get_result::<bool>(conn)?;
+        if recent {
+            error!("member was already reminded by the same member");
+            return Ok(Err(Status::TooManyRequests));
+        }
+
+        let owed = debt(conn, gid, sender, uid)?;
+        if owed < NEGLIGIBLE {
+            error!("trying to remind a member that owes nothing to the requesting member");
+            return Ok(Err(Status::Conflict));
+        }
+
+        remind(conn, gid, Some(sender), uid, owed, message, now).map(Ok)
+    });
+
+    match reminded {
+        Ok(Ok((r, sent))) => {
+            dispatch(&sent);
+            Ok(Json(r))
+        }
+        Ok(Err(status)) => Err(status),
+        Err(e) => {
+            error!("error running send_reminder transaction: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// returns when the members of the group are reminded automatically, `null` if they are not,
+/// needs to be executed by a member of the group
+#[openapi(tag = "Groups")]
+#[get("/<gid>/reminders/schedule")]
+fn get_reminder_schedule(
+    gid: i32,
+    _member: GroupMembership,
+) -> Result<Json<Option<ReminderSchedule>>, Status> {
+    let mut conn = establish_connection();
+
+    match reminder_schedules::table
+        .find(gid)
+        .first::<ReminderSchedule>(&mut conn)
+        .optional()
+    {
+        Ok(s) => Ok(Json(s)),
+        Err(e) => {
+            error!("error loading reminder schedule: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PutReminderSchedule {
+    /// the members that owe since more than these days are reminded, again every these days.
+    /// `null` turns the automatic reminders off
+    pub after_days: Option<i32>,
+}
+
+/// schedules the automatic reminders of the group, or turns them off, can only be performed by an
+/// admin
+#[openapi(tag = "Groups")]
+#[put("/<gid>/reminders/schedule", data = "<schedule>")]
+fn set_reminder_schedule(
+    gid: i32,
+    schedule: Json<PutReminderSchedule>,
+    admin: GroupAdmin,
+) -> Result<Json<Option<ReminderSchedule>>, Status> {
+    let mut conn = establish_connection();
+
+    let result = match schedule.after_days {
+        Some(days) if !(1..=MAX_AFTER_DAYS).contains(&days) => {
+            error!("reminder schedule of {} days is out of range", days);
+            return Err(Status::BadRequest);
+        }
+        Some(days) => conn
+            .transaction::<ReminderSchedule, diesel::result::Error, _>(|conn| {
+                diesel::delete(reminder_schedules::table.find(gid)).execute(conn)?;
+                (
+                    reminder_schedules::group_id.eq(gid),
+                    reminder_schedules::after_days.eq(days),
+                    reminder_schedules::updated_by.eq(admin.user.id),
+                    reminder_schedules::updated_at.eq(diesel::dsl::now),
+                )
+                    .insert_into(reminder_schedules::table)
+                    .get_result::<ReminderSchedule>(conn)
+            })
+            .map(Some),
+        None => diesel::delete(reminder_schedules::table.find(gid))
+            .execute(&mut conn)
+            .map(|_| None),
+    };
+
+    match result {
+        Ok(s) => Ok(Json(s)),
+        Err(e) => {
+            error!("error setting reminder schedule: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// sends the automatic reminders due at `now`, only in group `gid` if given: to every member of a
+/// group with a schedule that owed already `after_days` days ago and still owes, unless the member
+/// was reminded automatically in the last `after_days`. returns the reminders sent
+pub fn send_due_reminders(
+    now: NaiveDateTime,
+    gid: Option<i32>,
+) -> QueryResult<Vec<PaymentReminder>> {
+    let mut conn = establish_connection();
+
+    let mut q = reminder_schedules::table
+        .inner_join(groups::table)
+        .filter(groups::deleted_at.is_null())
+        .select((reminder_schedules::group_id, reminder_schedules::after_days))
+        .into_boxed();
+    if let Some(g) = gid {
+        q = q.filter(reminder_schedules::group_id.eq(g));
+    }
+    let schedules = q.get_results::<(i32, i32)>(&mut conn)?;
+
+    let mut sent = Vec::new();
+    for (gid, after_days) in schedules {
+        let since = now - chrono::Duration::days(after_days.into());
+        let (reminders, notified) = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            let current = balances(conn, gid, None)?;
+            let earlier = balances(conn, gid, Some(since.date()))?;
+            let members = group_members::table
+                .filter(group_members::group_id.eq(gid))
+                .select(group_members::user_id)
+                .order(group_members::user_id)
+                .get_results::<i32>(conn)?;
+
+            let mut reminders = Vec::new();
+            let mut notified = Vec::new();
+            for uid in members {
+                let owed = -current.get(&uid).copied().unwrap_or_default();
+                let owed_earlier = -earlier.get(&uid).copied().unwrap_or_default();
+                if owed < NEGLIGIBLE || owed_earlier < NEGLIGIBLE {
+                    continue;
+                }
+                let reminded = select(exists(
+                    payment_reminders::table
+                        .filter(payment_reminders::group_id.eq(gid))
+                        .filter(payment_reminders::sender_id.is_null())
+                        .filter(payment_reminders::recipient_id.eq(uid))
+                        .filter(payment_reminders::created_at.gt(since)),
+                ))
+                .get_result::<bool>(conn)?;
+                if !reminded {
+                    let (reminder, sent) = remind(conn, gid, None, uid, owed, None, now)?;
+                    reminders.push(reminder);
+                    notified.extend(sent);
+                }
+            }
+            Ok((reminders, notified))
+        })?;
+        dispatch(&notified);
+        sent.extend(reminders);
+    }
+    Ok(sent)
+}
+
+/// the background task that sends the automatic reminders, unless `reminders.enabled` is off
+pub fn scheduler() -> AdHoc {
+    AdHoc::on_liftoff("Payment reminders", |rocket| {
+        Box::pin(async move {
+            let Some(config) = rocket.state::<Config>() else {
+                return;
+            };
+            if !config.reminders.enabled {
+                return;
+            }
+
+            let period = Duration::from_secs(config.reminders.interval_secs);
+            let mut shutdown = rocket.shutdown();
+            rocket::tokio::spawn(async move {
+                let mut interval = rocket::tokio::time::interval(period);
+                loop {
+                    rocket::tokio::select! {
+                        _ = interval.tick() => (),
+                        _ = &mut shutdown => break,
+                    }
+
+                    let now = Utc::now().naive_utc();
+                    match rocket::tokio::task::spawn_blocking(move || send_due_reminders(now, None))
+                        .await
+                    {
+                        Ok(Ok(sent)) if !sent.is_empty() => {
+                            info!("sent {} payment reminders", sent.len())
+                        }
+                        Ok(Ok(_)) => (),
+                        Ok(Err(e)) => error!("error sending payment reminders: {:?}", e),
+                        Err(e) => error!("payment reminders task failed: {:?}", e),
+                    }
+                }
+            });
+        })
+    })
+}
@@ -0,0 +1,319 @@
+//! the trash: deleted expenses and groups are only marked with `deleted_at` and hidden, they can
+//! be restored for `trash.retention_days` and are then purged for good, along with the files
+//! attached to the expenses, by a background task that runs every `trash.purge_interval_secs`
+
+use crate::{
+    api::attachments::{attached_files, remove_unreferenced},
+    api::expenses::{ExpenseQuery, ExpenseWithParticipations, expense_page},
+    api::live::{GroupEvent, GroupEventKind, publish, record},
+    api::pagination::Page,
+    config::{Config, TrashConfig},
+    establish_connection,
+    guards::GroupMembership,
+    models::{Expense, Group, User},
+    notify::{Notice, NotificationKind, dispatch, notify},
+    schema::{expense_participations, expenses, group_administrators, group_members, groups},
+    storage::Storage,
+};
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl,
+    connection::Connection, dsl::exists, result::Error::NotFound, select,
+};
+use rocket::fairing::AdHoc;
+use rocket::{State, http::Status, serde::json::Json};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
+    openapi_get_routes_spec![settings:get_trash,restore_expense,get_deleted_groups,restore_group]
+}
+
+/// the oldest deletion that can still be restored
+pub fn restorable_since(config: &TrashConfig) -> NaiveDateTime {
+    Utc::now().naive_utc() - config.retention()
+}
+
+/// checks that something deleted at `deleted_at` can still be restored, `410 Gone` if it has been
+/// in the trash for longer than the retention window
+pub fn check_restorable(deleted_at: NaiveDateTime, config: &TrashConfig) -> Result<(), Status> {
+    if deleted_at >= restorable_since(config) {
+        Ok(())
+    } else {
+        error!("trying to restore something deleted past the retention window");
+        Err(Status::Gone)
+    }
+}
+
+/// returns the expenses of the group that are in the trash and can still be restored, paginated
+/// like the expenses of the group. needs to be executed by a member of the group
+#[openapi(tag = "GroupExpenses")]
+#[get("/<gid>/trash?<query..>")]
+fn get_trash(
+    gid: i32,
+    _member: GroupMembership,
+    query: ExpenseQuery,
+    config: &State<Config>,
+) -> Result<Json<Page<ExpenseWithParticipations>>, Status> {
+    let base = expenses::table
+        .filter(expenses::group_id.eq(gid))
+        .filter(expenses::deleted_at.ge(restorable_since(&config.trash)))
+        .into_boxed();
+
+    expense_page(base, &query)
+}
+
+/// takes a group expense out of the trash and tells its participants. can only be performed by who
+/// paid for it or an admin of the group, within `trash.retention_days` of its deletion
+#[openapi(tag = "GroupExpenses")]
+#[post("/<gid>/expenses/<exid>/restore")]
+fn restore_expense(
+    gid: i32,
+    exid: i32,
+    member: GroupMembership,
+    config: &State<Config>,
+) -> Result<Json<Expense>, Status> {
+    let mut conn = establish_connection();
+
+    let expense = match expenses::table
+        .filter(expenses::id.eq(exid))
+        .filter(expenses::group_id.eq(gid))
+        .filter(expenses::deleted_at.is_not_null())
+        .first::<Expense>(&mut conn)
+    {
+        Ok(e) => e,
+        Err(NotFound) => return Err(Status::NotFound),
+        Err(e) => {
+            error!("error loading deleted group expense: {:?}", e);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    if expense.paid_by != member.user.id && !member.is_admin {
+        error!("trying to restore expense but user is not admin or creator of expense");
+        return Err(Status::Forbidden);
+    }
+    if let Some(deleted) = expense.deleted_at {
+        check_restorable(deleted, &config.trash)?;
+    }
+
+    match conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let participants = expense_participations::table
+            .filter(expense_participations::expense_id.eq(exid))
+            .select(expense_participations::user_id)
+            .get_results::<i32>(conn)?;
+
+        // told like they were of the deletion
+        let sent = notify(
+            conn,
+            &Notice::new(NotificationKind::ExpenseRestored, member.user.id)
+                .in_group(gid)
+                .about_expense(exid),
+            &participants,
+        )?;
+
+        let expense = diesel::update(expenses::table.filter(expenses::id.eq(exid)))
+            .set(expenses::deleted_at.eq(None::<NaiveDateTime>))
+            .get_result::<Expense>(conn)?;
+        let event = record(
+            conn,
+            GroupEvent::new(GroupEventKind::ExpenseRestored, gid, Some(member.user.id))
+                .about_expense(exid),
+        )?;
+        Ok((expense, sent, event))
+    }) {
+        Ok((e, sent, event)) => {
+            dispatch(&sent);
+            publish(event);
+            Ok(Json(e))
+        }
+        Err(e) => {
+            error!("error restoring group expense: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// returns the groups in the trash that the user can restore, the ones the user is an admin of,
+/// most recently deleted first
+#[openapi(tag = "Groups")]
+#[get("/trash")]
+fn get_deleted_groups(user: User, config: &State<Config>) -> Result<Json<Vec<Group>>, Status> {
+    let mut conn = establish_connection();
+
+    match groups::table
+        .filter(groups::deleted_at.ge(restorable_since(&config.trash)))
+        .filter(
+            groups::id.eq_any(
+                group_administrators::table
+                    .filter(group_administrators::user_id.eq(user.id))
+                    .select(group_administrators::group_id),
+            ),
+        )
+        .order((groups::deleted_at.desc(), groups::id.desc()))
+        .get_results::<Group>(&mut conn)
+    {
+        Ok(v) => Ok(Json(v)),
+        Err(e) => {
+            error!("error loading deleted groups: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// takes a group out of the trash, along with its expenses, and tells its members. can only be
+/// performed by an admin of the group within `trash.retention_days` of its deletion
+#[openapi(tag = "Groups")]
+#[post("/<gid>/restore")]
+fn restore_group(gid: i32, user: User, config: &State<Config>) -> Result<Json<Group>, Status> {
+    let mut conn = establish_connection();
+
+    let group = match groups::table
+        .filter(groups::id.eq(gid))
+        .filter(groups::deleted_at.is_not_null())
+        .first::<Group>(&mut conn)
+    {
+        Ok(g) => g,
+        Err(NotFound) => return Err(Status::NotFound),
+        Err(e) => {
+            error!("error loading deleted group: {:?}", e);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    match select(exists(
+        group_administrators::table
+            .filter(group_administrators::group_id.eq(gid))
+            .filter(group_administrators::user_id.eq(user.id)),
+    ))
+    .get_result::<bool>(&mut conn)
+    {
+        Ok(true) => (),
+        Ok(false) => {
+            error!("trying to restore group but user is not admin");
+            return Err(Status::Forbidden);
+        }
+        Err(e) => {
+            error!("error checking group admin: {:?}", e);
+            return Err(Status::InternalServerError);
+        }
+    }
+    if let Some(deleted) = group.deleted_at {
+        check_restorable(deleted, &config.trash)?;
+    }
+
+    // the members are told, as they were when the group was deleted
+    match conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let group = diesel::update(groups::table.filter(groups::id.eq(gid)))
+            .set(groups::deleted_at.eq(None::<NaiveDateTime>))
+            .get_result::<Group>(conn)?;
+        let members = group_members::table
+            .filter(group_members::group_id.eq(gid))
+            .select(group_members::user_id)
+            .get_results::<i32>(conn)?;
+        let sent = notify(
+            conn,
+            &Notice::new(NotificationKind::GroupRestored, user.id).in_group(gid),
+            &members,
+        )?;
+        let event = record(
+            conn,
+            GroupEvent::new(GroupEventKind::GroupRestored, gid, Some(user.id)),
+        )?;
+        Ok((group, sent, event))
+    }) {
+        Ok((g, sent, event)) => {
+            dispatch(&sent);
+            publish(event);
+            Ok(Json(g))
+        }
+        Err(e) => {
+            error!("error restoring group: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// deletes for good the expenses and the groups moved to the trash before `cutoff`, along with
+/// the expenses of the groups and the files attached to the expenses that are not attached
+/// elsewhere. returns how many expenses and groups were purged
+pub fn purge(cutoff: NaiveDateTime, storage: &dyn Storage) -> QueryResult<(usize, usize)> {
+    let mut conn = establish_connection();
+
+    let (purged, files) = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let expired_groups = groups::table
+            .filter(groups::deleted_at.lt(cutoff))
+            .select(groups::id)
+            .get_results::<i32>(conn)?;
+        let expired_expenses = expenses::table
+            .filter(
+                expenses::deleted_at
+                    .lt(cutoff)
+                    .or(expenses::group_id.eq_any(&expired_groups)),
+            )
+            .select(expenses::id)
+            .get_results::<i32>(conn)?;
+
+        let files = attached_files(conn, &expired_expenses)?;
+        let expenses =
+            diesel::delete(expenses::table.filter(expenses::id.eq_any(&expired_expenses)))
+                .execute(conn)?;
+        let groups = diesel::delete(groups::table.filter(groups::id.eq_any(&expired_groups)))
+            .execute(conn)?;
+
+        Ok(((expenses, groups), files))
+    })?;
+
+    remove_unreferenced(storage, files);
+    Ok(purged)
+}
+
+/// the background task that purges the trash, at launch and then at the configured interval,
+/// until the rocket shuts down
+pub fn purger() -> AdHoc {
+    AdHoc::on_liftoff("Trash purge", |rocket| {
+        Box::pin(async move {
+            let (Some(config), Some(storage)) =
+                (rocket.state::<Config>(), rocket.state::<Arc<dyn Storage>>())
+            else {
+                return;
+            };
+            if !config.trash.purge_enabled {
+                return;
+            }
+
+            let trash = config.trash.clone();
+            let storage = storage.clone();
+            let period = Duration::from_secs(trash.purge_interval_secs);
+            let mut shutdown = rocket.shutdown();
+            rocket::tokio::spawn(async move {
+                let mut interval = rocket::tokio::time::interval(period);
+                loop {
+                    rocket::tokio::select! {
+                        _ = interval.tick() => (),
+                        _ = &mut shutdown => break,
+                    }
+
+                    let cutoff = restorable_since(&trash);
+                    let storage = storage.clone();
+                    match rocket::tokio::task::spawn_blocking(move || {
+                        purge(cutoff, storage.as_ref())
+                    })
+                    .await
+                    {
+                        Ok(Ok((0, 0))) => (),
+                        Ok(Ok((expenses, groups))) => {
+                            info!("purged {} expenses and {} groups", expenses, groups)
+                        }
+                        Ok(Err(e)) => error!("error purging the trash: {:?}", e),
+                        Err(e) => error!("trash purge task failed: {:?}", e),
+                    }
+                }
+            });
+        })
+    })
+}
@@ -1,19 +1,25 @@
 use crate::{
+    Session, SessionStore,
+    api::live::{GroupEvent, GroupEventKind, publish, record},
+    api::pagination::{Cursor, Page, Param, SortOrder, keyset, page_limit},
+    config::Config,
     establish_connection,
     models::{GroupInvite, User},
-    schema::{group_invites, group_members},
-    SessionStore,
+    schema::{group_invites, group_members, groups},
 };
 use chrono::{NaiveDateTime, Utc};
-use diesel::{result::Error::NotFound, ExpressionMethods, Insertable, QueryDsl, RunQueryDsl};
-use rocket::{http::Status, serde::json::Json, time::Duration};
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, Insertable, QueryDsl, RunQueryDsl,
+    result::Error::NotFound,
+};
+use rocket::{State, http::Status, serde::json::Json};
 use rocket_okapi::{
     okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
 };
 
 use argon2::{
-    password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
     Argon2, PasswordHash, PasswordVerifier,
+    password_hash::{PasswordHasher, SaltString, rand_core::OsRng},
 };
 use uuid::Uuid;
 
@@ -75,6 +81,8 @@ fn user_info(uid: i32, _user: User) -> Result<Json<UserInfo>, Status> {
 
 // TODO
 /// set language preference, the `lang` string can be `it` or `eng`
+// not implemented yet
+#[allow(unused_variables)]
 #[openapi(tag = "User")]
 #[put("/language/<lang>")]
 fn set_language(user: User, lang: String) -> Result<Json<String>, Status> {
@@ -83,6 +91,8 @@ fn set_language(user: User, lang: String) -> Result<Json<String>, Status> {
 
 /// activate account (this is the link received via mail), returns `uid` of user that made the
 /// request
+// not implemented yet
+#[allow(unused_variables)]
 #[openapi(tag = "User")]
 #[put("/verify/<token>")]
 fn activate_account(user: User, token: i32) -> Result<Json<i32>, Status> {
@@ -105,7 +115,8 @@ pub struct LoginRequest {
 fn login(
     jar: &CookieJar<'_>,
     login: Json<LoginRequest>,
-    store: &rocket::State<SessionStore>,
+    store: &State<SessionStore>,
+    config: &State<Config>,
 ) -> Result<Json<i32>, Status> {
     let mut conn = establish_connection();
 
@@ -135,12 +146,19 @@ fn login(
         let uuid = Uuid::new_v4();
 
         let mut store = store.sessions.lock().unwrap();
-        store.insert(uuid.to_string(), user.id);
+        store.insert(
+            uuid.to_string(),
+            Session {
+                user_id: user.id,
+                expires: Utc::now().naive_utc() + chrono::Duration::days(config.session.ttl_days),
+            },
+        );
         jar.add(
             Cookie::build(("session_id", uuid.to_string()))
-                .same_site(rocket::http::SameSite::Strict)
+                .same_site(config.session.same_site.into())
+                .secure(config.session.secure_cookie)
                 .http_only(true)
-                .max_age(Duration::days(30)), //TODO .secure(true)
+                .max_age(config.session.ttl()),
         );
         Ok(Json(user.id))
     } else {
@@ -156,7 +174,7 @@ fn logout(jar: &CookieJar<'_>, store: &rocket::State<SessionStore>) -> Status {
     match jar.get("session_id") {
         Some(cookie) => {
             let mut store = store.sessions.lock().unwrap();
-            store.remove(cookie.name());
+            store.remove(cookie.value());
             jar.remove("session_id");
             Status::Ok
         }
@@ -229,6 +247,8 @@ pub fn register(register_data: Json<RegisterRequest>) -> Status {
     }
 }
 
+// not implemented yet, the password routes are not mounted
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ChangePasswordRequest {
     pub oldpassword: String,
@@ -236,6 +256,7 @@ pub struct ChangePasswordRequest {
 }
 
 /// changes password of user that makes the request, returns `uid`
+#[allow(dead_code, unused_variables)]
 #[openapi(tag = "User")]
 #[put("/changepassword", data = "<changerequest>")]
 fn change_password(
@@ -246,12 +267,14 @@ fn change_password(
 }
 
 /// request reset of a password, will trigger an email being sent with a reset password token
+#[allow(dead_code, unused_variables)]
 #[openapi(tag = "User")]
 #[put("/requestpasswordreset")]
 fn reset_password_request(user: User) -> Result<Json<i32>, Status> {
     Err(Status::NotImplemented)
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct PasswordResetRequest {
     pub token: String,
@@ -259,6 +282,7 @@ pub struct PasswordResetRequest {
 }
 
 /// executes the password reset procedure, user provides a token that was sent by mail
+#[allow(dead_code, unused_variables)]
 #[openapi(tag = "User")]
 #[put("/resetpassword", data = "<changerequest>")]
 fn reset_password(
@@ -272,18 +296,45 @@ fn reset_password(
 //                                        INVITES
 // ######################################################################################
 
-/// view all invites, regardless of status, about the user making the request
+/// pagination of the invite list, the newest invites come first unless `order` says otherwise
+#[derive(Debug, FromForm, JsonSchema)]
+pub struct InviteQuery {
+    /// `asc` or `desc` (default)
+    pub order: Param<SortOrder>,
+    /// `next_cursor` of the previous page
+    pub cursor: Param<String>,
+    /// invites in the page, 50 by default and 100 at most
+    pub limit: Param<i64>,
+}
+
+/// view all invites, regardless of status, about the user making the request, paginated
 #[openapi(tag = "Invite")]
-#[get("/invites")]
-fn view_invites(user: User) -> Result<Json<Vec<GroupInvite>>, Status> {
-    let mut conn = establish_connection();
+#[get("/invites?<query..>")]
+fn view_invites(user: User, query: InviteQuery) -> Result<Json<Page<GroupInvite>>, Status> {
+    let limit = page_limit(query.limit.value())?;
+    let cursor = Cursor::<NaiveDateTime>::from_query(&query.cursor)?;
 
-    match group_invites::table
+    let q = group_invites::table
         .filter(group_invites::invited_user_id.eq(user.id))
-        .get_results::<GroupInvite>(&mut conn)
-    {
-        Ok(v) => Ok(Json(v)),
-        Err(_) => Err(Status::InternalServerError),
+        .into_boxed();
+    let q = keyset!(
+        q,
+        group_invites::invite_date,
+        group_invites::id,
+        query.order.or_default(),
+        cursor
+    );
+
+    let mut conn = establish_connection();
+
+    match q.limit(limit + 1).get_results::<GroupInvite>(&mut conn) {
+        Ok(v) => Ok(Json(Page::from_rows(v, limit, |i| {
+            Cursor::new(i.invite_date, i.id).encode()
+        }))),
+        Err(e) => {
+            error!("error loading invites: {:?}", e);
+            Err(Status::InternalServerError)
+        }
     }
 }
 
@@ -293,38 +344,47 @@ fn view_invites(user: User) -> Result<Json<Vec<GroupInvite>>, Status> {
 fn accept_invite(user: User, invite_id: i32) -> Result<Json<GroupInvite>, Status> {
     let mut conn = establish_connection();
 
-    // try to update the single affected group invite and mark it as accepted
-    let invite = match diesel::update(
-        group_invites::table
-            // find the unique group_invite
+    match conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        // the invite must be to the current user and to a group that is not in the trash, a group
+        // in the trash cannot be joined like it cannot be seen
+        let invite = match group_invites::table
+            .inner_join(groups::table)
             .filter(group_invites::id.eq(invite_id))
-            // check that it's invite to the current user
-            .filter(group_invites::invited_user_id.eq(user.id)),
-    )
-    .set(group_invites::invite_status.eq("ACCEPTED"))
-    .get_result::<GroupInvite>(&mut conn)
-    {
-        Ok(v) => v,
-        Err(NotFound) => return Err(Status::NotFound),
-        Err(e) => {
-            error!("error trying to update group invite: {:?}", e);
-            return Err(Status::InternalServerError);
-        }
-    };
+            .filter(group_invites::invited_user_id.eq(user.id))
+            .filter(groups::deleted_at.is_null())
+            .select(group_invites::all_columns)
+            .first::<GroupInvite>(conn)
+        {
+            Ok(v) => v,
+            Err(NotFound) => return Ok(Err(Status::NotFound)),
+            Err(e) => return Err(e),
+        };
 
-    match (
-        group_members::group_id.eq(invite.group_id),
-        group_members::user_id.eq(user.id),
-    )
-        .insert_into(group_members::table)
-        .execute(&mut conn)
-    {
-        Ok(_) => Ok(Json(invite)),
+        // mark it as accepted
+        let invite = diesel::update(group_invites::table.find(invite.id))
+            .set(group_invites::invite_status.eq("ACCEPTED"))
+            .get_result::<GroupInvite>(conn)?;
+
+        (
+            group_members::group_id.eq(invite.group_id),
+            group_members::user_id.eq(user.id),
+        )
+            .insert_into(group_members::table)
+            .execute(conn)?;
+        let event = record(
+            conn,
+            GroupEvent::new(GroupEventKind::MemberAdded, invite.group_id, Some(user.id))
+                .about_member(user.id),
+        )?;
+        Ok(Ok((invite, event)))
+    }) {
+        Ok(Ok((invite, event))) => {
+            publish(event);
+            Ok(Json(invite))
+        }
+        Ok(Err(status)) => Err(status),
         Err(e) => {
-            error!(
-                "error trying to update group members after accepting invite: {:?}",
-                e
-            );
+            error!("error trying to accept group invite: {:?}", e);
             Err(Status::InternalServerError)
         }
     }
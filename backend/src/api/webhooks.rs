@@ -0,0 +1,592 @@
+//! outgoing webhooks: the admins of a group register the urls that the events of the group are
+//! posted to as json, so that other services, e.g. a chat bot, can react to them.
+//!
+//! every [`GroupEvent`] that is published is queued in the database once for every webhook of its
+//! group, and a background task posts the queued deliveries, retrying the failed ones with an
+//! exponential backoff until they run out of attempts. every request is signed with the secret of
+//! the webhook: `X-SplitSmart-Signature` is `sha256=` followed by the hex encoded hmac-sha256 of
+//! the body. the urls can be `https` or `http`, and their host must resolve to public addresses
+//! only, checked both when the webhook is registered and before every delivery, so that the
+//! server cannot be made to post to itself or to its private network
+
+use crate::{
+    api::live::{GroupEvent, GroupEventKind, GroupFeed},
+    api::pagination::{Cursor, Page, Param, SortOrder, keyset, page_limit},
+    config::{Config, WebhooksConfig},
+    db::DbConnection,
+    establish_connection,
+    guards::GroupAdmin,
+    models::{Expense, Webhook, WebhookDelivery},
+    schema::{expenses, webhook_deliveries, webhooks},
+};
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, Insertable, OptionalExtension, QueryDsl, QueryResult,
+    RunQueryDsl, result::Error::NotFound,
+};
+use hmac::{Hmac, Mac};
+use reqwest::{Url, blocking::Client, header::CONTENT_TYPE, redirect::Policy};
+use rocket::{
+    State, fairing::AdHoc, http::Status, serde::json::Json,
+    tokio::sync::broadcast::error::RecvError,
+};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
+    openapi_get_routes_spec![settings:get_webhooks,add_webhook,delete_webhook,get_deliveries]
+}
+
+/// longest url accepted, in characters
+pub const MAX_URL_LENGTH: usize = 2000;
+/// shortest secret accepted, in characters
+pub const MIN_SECRET_LENGTH: usize = 16;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct NewWebhook {
+    /// an `https` or `http` url, the events are posted to it
+    pub url: String,
+    /// the key the requests are signed with, at least 16 characters
+    pub secret: String,
+}
+
+/// the state of a delivery
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DeliveryStatus {
+    /// waiting for its first attempt or for a retry
+    Pending,
+    Delivered,
+    /// given up on after the last attempt
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "PENDING",
+            DeliveryStatus::Delivered => "DELIVERED",
+            DeliveryStatus::Failed => "FAILED",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        [
+            DeliveryStatus::Pending,
+            DeliveryStatus::Delivered,
+            DeliveryStatus::Failed,
+        ]
+        .into_iter()
+        .find(|d| d.as_str() == s)
+    }
+}
+
+/// the body of the requests sent to the webhooks
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WebhookPayload {
+    #[serde(flatten)]
+    pub event: GroupEvent,
+    /// the expense the event is about as it was when the event happened, if it still exists
+    pub expense: Option<Expense>,
+    /// when the event happened
+    pub created_at: NaiveDateTime,
+}
+
+/// the hex encoded hmac-sha256 of `payload` with `secret`, which the deliveries are signed with
+pub fn signature(secret: &[u8], payload: &[u8]) -> String {
+    // hmac takes keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("hmac key");
+    mac.update(payload);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// whether `ip` can be reached on the internet, i.e. it is not a loopback, private, link-local,
+/// unspecified, shared or multicast address
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || v4.is_documentation()
+                // `0.0.0.0/8` and the shared address space `100.64.0.0/10` of carrier-grade nat
+                || a == 0
+                || (a == 100 && b & 0xc0 == 64))
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public(IpAddr::V4(v4)),
+            None => {
+                !(v6.is_loopback()
+                    || v6.is_unspecified()
+                    || v6.is_multicast()
+                    || v6.is_unique_local()
+                    || v6.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+/// resolves the host of `url` and returns it along with its addresses, or why the deliveries
+/// cannot be sent to it: every address has to be public unless `allow_private_hosts` is set
+fn resolve(config: &WebhooksConfig, url: &Url) -> Result<(String, Vec<SocketAddr>), String> {
+    // the brackets of an ipv6 host are not part of the address
+    let host = url
+        .host_str()
+        .map(|h| h.trim_start_matches('[').trim_end_matches(']'))
+        .filter(|h| !h.is_empty())
+        .ok_or("the url has no host")?;
+    let port = url.port_or_known_default().ok_or("the url has no port")?;
+
+    let addresses: Vec<SocketAddr> = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("could not resolve {}: {}", host, e))?
+        .collect();
+    if addresses.is_empty() {
+        return Err(format!("could not resolve {}", host));
+    }
+    if !config.allow_private_hosts
+        && let Some(a) = addresses.iter().find(|a| !is_public(a.ip()))
+    {
+        return Err(format!(
+            "{} resolves to {}, which is not public",
+            host,
+            a.ip()
+        ));
+    }
+    Ok((host.to_owned(), addresses))
+}
+
+/// parses `url` and checks that it is an `https` or `http` url the deliveries can be sent to
+fn parse_url(config: &WebhooksConfig, url: &str) -> Result<Url, String> {
+    if url.chars().count() > MAX_URL_LENGTH {
+        return Err("the url is too long".to_owned());
+    }
+    let url = Url::parse(url).map_err(|e| format!("invalid url: {}", e))?;
+    if url.scheme() != "https" && url.scheme() != "http" {
+        return Err(format!("unsupported scheme `{}`", url.scheme()));
+    }
+    resolve(config, &url)?;
+    Ok(url)
+}
+
+/// helper function that loads webhook `wid` of group `gid`
+fn group_webhook(gid: i32, wid: i32) -> Result<Webhook, Status> {
+    let mut conn = establish_connection();
+
+    match webhooks::table
+        .filter(webhooks::id.eq(wid))
+        .filter(webhooks::group_id.eq(gid))
+        .first::<Webhook>(&mut conn)
+    {
+        Ok(w) => Ok(w),
+        Err(NotFound) => Err(Status::NotFound),
+        Err(e) => {
+            error!("error loading webhook: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// returns the webhooks of a group, without their secrets, needs to be executed by an admin of
+/// the group
+#[openapi(tag = "Groups")]
+#[get("/<gid>/webhooks")]
+fn get_webhooks(gid: i32, _admin: GroupAdmin) -> Result<Json<Vec<Webhook>>, Status> {
+    let mut conn = establish_connection();
+
+    match webhooks::table
+        .filter(webhooks::group_id.eq(gid))
+        .order(webhooks::id)
+        .get_results::<Webhook>(&mut conn)
+    {
+        Ok(v) => Ok(Json(v)),
+        Err(e) => {
+            error!("error loading webhooks: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// registers a webhook for a group, the changes to the expenses and the members of the group are
+/// posted to it from now on. needs to be executed by an admin of the group
+#[openapi(tag = "Groups")]
+#[post("/<gid>/webhooks", data = "<webhook>")]
+async fn add_webhook(
+    gid: i32,
+    admin: GroupAdmin,
+    webhook: Json<NewWebhook>,
+    config: &State<Config>,
+) -> Result<Json<Webhook>, Status> {
+    if webhook.secret.chars().count() < MIN_SECRET_LENGTH {
+        error!("webhook secret is too short");
+        return Err(Status::BadRequest);
+    }
+    let url = webhook.url.trim().to_owned();
+    // resolving the host blocks, it is kept off the threads that serve the requests
+    let checked = {
+        let (config, url) = (config.webhooks.clone(), url.clone());
+        rocket::tokio::task::spawn_blocking(move || parse_url(&config, &url)).await
+    };
+    match checked {
+        Ok(Ok(_)) => (),
+        Ok(Err(e)) => {
+            error!("invalid webhook url `{}`: {}", url, e);
+            return Err(Status::BadRequest);
+        }
+        Err(e) => {
+            error!("error checking the webhook url: {}", e);
+            return Err(Status::InternalServerError);
+        }
+    }
+
+    let mut conn = establish_connection();
+
+    match (
+        webhooks::group_id.eq(gid),
+        webhooks::url.eq(url),
+        webhooks::secret.eq(&webhook.secret),
+        webhooks::created_by.eq(admin.user.id),
+        webhooks::created_at.eq(Utc::now().naive_utc()),
+    )
+        .insert_into(webhooks::table)
+        .get_result::<Webhook>(&mut conn)
+    {
+        Ok(w) => Ok(Json(w)),
+        Err(e) => {
+            error!("error inserting webhook: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// deletes a webhook of a group along with its deliveries, the pending ones are not sent. needs
+/// to be executed by an admin of the group
+#[openapi(tag = "Groups")]
+#[delete("/<gid>/webhooks/<wid>")]
+fn delete_webhook(gid: i32, wid: i32, _admin: GroupAdmin) -> Result<(), Status> {
+    let webhook = group_webhook(gid, wid)?;
+    let mut conn = establish_connection();
+
+    match diesel::delete(webhooks::table.find(webhook.id)).execute(&mut conn) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            error!("error deleting webhook: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// filters and pagination of the delivery log of a webhook, the newest deliveries come first
+#[derive(Debug, FromForm, JsonSchema)]
+pub struct DeliveryQuery {
+    /// only the deliveries in this state: `PENDING`, `DELIVERED` or `FAILED`, any other value is a
+    /// bad request
+    pub status: Param<String>,
+    /// `next_cursor` of the previous page
+    pub cursor: Param<String>,
+    /// deliveries in the page, 50 by default and 100 at most
+    pub limit: Param<i64>,
+}
+
+/// returns the deliveries of a webhook, newest first and paginated, with the outcome of their
+/// last attempt. fails with `400 Bad Request` on an unknown `status`. needs to be executed by an
+/// admin of the group
+#[openapi(tag = "Groups")]
+#[get("/<gid>/webhooks/<wid>/deliveries?<query..>")]
+fn get_deliveries(
+    gid: i32,
+    wid: i32,
+    _admin: GroupAdmin,
+    query: DeliveryQuery,
+) -> Result<Json<Page<WebhookDelivery>>, Status> {
+    group_webhook(gid, wid)?;
+    let limit = page_limit(query.limit.value())?;
+    let cursor = Cursor::<NaiveDateTime>::from_query(&query.cursor)?;
+
+    let mut q = webhook_deliveries::table
+        .filter(webhook_deliveries::webhook_id.eq(wid))
+        .into_boxed();
+    if let Some(s) = query.status.get() {
+        let Some(status) = DeliveryStatus::parse(s) else {
+            error!("unknown webhook delivery status `{}`", s);
+            return Err(Status::BadRequest);
+        };
+        q = q.filter(webhook_deliveries::status.eq(status.as_str()));
+    }
+    let q = keyset!(
+        q,
+        webhook_deliveries::created_at,
+        webhook_deliveries::id,
+        SortOrder::Desc,
+        cursor
+    );
+
+    let mut conn = establish_connection();
+
+    match q.limit(limit + 1).get_results::<WebhookDelivery>(&mut conn) {
+        Ok(v) => Ok(Json(Page::from_rows(v, limit, |d| {
+            Cursor::new(d.created_at, d.id).encode()
+        }))),
+        Err(e) => {
+            error!("error loading webhook deliveries: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+// ####################################################################
+// ##########################|DELIVERY|################################
+// ####################################################################
+
+/// queues `event` for the webhooks of its group on `conn`, called by [`crate::api::live::record`]
+/// in the transaction that makes the change. returns the number of deliveries queued
+pub fn enqueue(conn: &mut DbConnection, event: &GroupEvent) -> QueryResult<usize> {
+    if event.kind == GroupEventKind::Resync {
+        return Ok(0);
+    }
+
+    let hooks = webhooks::table
+        .filter(webhooks::group_id.eq(event.group_id))
+        .select(webhooks::id)
+        .get_results::<i32>(conn)?;
+    if hooks.is_empty() {
+        return Ok(0);
+    }
+
+    let expense = match event.expense_id {
+        Some(exid) => expenses::table
+            .find(exid)
+            .first::<Expense>(conn)
+            .optional()?,
+        None => None,
+    };
+    let now = Utc::now().naive_utc();
+    let payload = WebhookPayload {
+        event: *event,
+        expense,
+        created_at: now,
+    };
+    let payload = serde_json::to_string(&payload).unwrap_or_default();
+
+    let deliveries: Vec<_> = hooks
+        .into_iter()
+        .map(|wid| {
+            (
+                webhook_deliveries::webhook_id.eq(wid),
+                webhook_deliveries::event_type.eq(event.kind.as_str()),
+                webhook_deliveries::payload.eq(payload.clone()),
+                webhook_deliveries::next_attempt_at.eq(Some(now)),
+                webhook_deliveries::created_at.eq(now),
+            )
+        })
+        .collect();
+    diesel::insert_into(webhook_deliveries::table)
+        .values(&deliveries)
+        .execute(conn)
+}
+
+/// posts `body` to `url` and returns the status code of the response, or why there was none. the
+/// host is resolved and checked again, and the request is sent to the addresses that passed the
+/// check, so that a dns record changed after the registration cannot point it elsewhere
+fn post(
+    config: &WebhooksConfig,
+    url: &str,
+    headers: &[(&str, String)],
+    body: &str,
+) -> Result<u16, String> {
+    let url = Url::parse(url).map_err(|e| format!("invalid url: {}", e))?;
+    let (host, addresses) = resolve(config, &url)?;
+
+    // redirects are not followed, they could lead anywhere
+    let client = Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs))
+        .redirect(Policy::none())
+        .user_agent("SplitSmart-Webhooks")
+        .resolve_to_addrs(&host, &addresses)
+        .build()
+        .map_err(|e| format!("could not create the http client: {}", e))?;
+
+    let mut request = client
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.to_owned());
+    for (name, value) in headers {
+        request = request.header(*name, value);
+    }
+    let response = request
+        .send()
+        .map_err(|e| format!("could not send the request: {}", e))?;
+    Ok(response.status().as_u16())
+}
+
+/// when a delivery that failed for the `attempts` time is attempted again
+fn retry_at(config: &WebhooksConfig, now: NaiveDateTime, attempts: i32) -> NaiveDateTime {
+    let factor = 1i64 << (attempts - 1).clamp(0, 20);
+    now + chrono::Duration::seconds(config.retry_base_secs.saturating_mul(factor))
+}
+
+/// attempts `delivery` to `webhook` and records the outcome, returns whether it was delivered
+fn attempt(
+    config: &WebhooksConfig,
+    webhook: &Webhook,
+    delivery: &WebhookDelivery,
+    now: NaiveDateTime,
+) -> QueryResult<bool> {
+    let mut conn = establish_connection();
+
+    // claims the delivery for as long as the attempt can take, so that it is not sent twice when
+    // more than one instance of the server is running
+    let lease = now + chrono::Duration::seconds(3 * config.timeout_secs as i64);
+    let claimed = diesel::update(
+        webhook_deliveries::table
+            .filter(webhook_deliveries::id.eq(delivery.id))
+            .filter(webhook_deliveries::status.eq(DeliveryStatus::Pending.as_str()))
+            .filter(webhook_deliveries::next_attempt_at.eq(delivery.next_attempt_at)),
+    )
+    .set(webhook_deliveries::next_attempt_at.eq(Some(lease)))
+    .execute(&mut conn)?;
+    if claimed == 0 {
+        return Ok(false);
+    }
+
+    let signature = signature(webhook.secret.as_bytes(), delivery.payload.as_bytes());
+    let headers = [
+        ("X-SplitSmart-Event", delivery.event_type.clone()),
+        ("X-SplitSmart-Delivery", delivery.id.to_string()),
+        ("X-SplitSmart-Signature", format!("sha256={}", signature)),
+    ];
+    let outcome = post(config, &webhook.url, &headers, &delivery.payload);
+
+    let attempts = delivery.attempts + 1;
+    let (response_status, error) = match &outcome {
+        Ok(code) if (200..300).contains(code) => (Some(*code as i32), None),
+        Ok(code) => (
+            Some(*code as i32),
+            Some(format!("the webhook answered {}", code)),
+        ),
+        Err(e) => (None, Some(e.clone())),
+    };
+    let delivered = error.is_none();
+    let (status, next_attempt_at) = match (delivered, attempts >= config.max_attempts) {
+        (true, _) => (DeliveryStatus::Delivered, None),
+        (false, true) => (DeliveryStatus::Failed, None),
+        (false, false) => (
+            DeliveryStatus::Pending,
+            Some(retry_at(config, now, attempts)),
+        ),
+    };
+    if let Some(e) = &error {
+        warn!("webhook delivery {} failed: {}", delivery.id, e);
+    }
+
+    diesel::update(webhook_deliveries::table.find(delivery.id))
+        .set((
+            webhook_deliveries::status.eq(status.as_str()),
+            webhook_deliveries::attempts.eq(attempts),
+            webhook_deliveries::next_attempt_at.eq(next_attempt_at),
+            webhook_deliveries::last_attempt_at.eq(Some(now)),
+            webhook_deliveries::response_status.eq(response_status),
+            webhook_deliveries::error.eq(error),
+        ))
+        .execute(&mut conn)?;
+    Ok(delivered)
+}
+
+/// deliveries attempted in a single run, the rest wait for the next one
+const DELIVERY_BATCH: i64 = 100;
+
+/// attempts the pending deliveries that are due at `now`, oldest first, only the ones to webhook
+/// `wid` if given. returns the number of deliveries that succeeded
+pub fn deliver_due(
+    config: &WebhooksConfig,
+    now: NaiveDateTime,
+    wid: Option<i32>,
+) -> QueryResult<usize> {
+    let mut conn = establish_connection();
+
+    let mut q = webhook_deliveries::table
+        .inner_join(webhooks::table)
+        .filter(webhook_deliveries::status.eq(DeliveryStatus::Pending.as_str()))
+        .filter(webhook_deliveries::next_attempt_at.le(now))
+        .into_boxed();
+    if let Some(wid) = wid {
+        q = q.filter(webhooks::id.eq(wid));
+    }
+    let due = q
+        .order((
+            webhook_deliveries::next_attempt_at.asc(),
+            webhook_deliveries::id.asc(),
+        ))
+        .limit(DELIVERY_BATCH)
+        .get_results::<(WebhookDelivery, Webhook)>(&mut conn)?;
+
+    let mut delivered = 0;
+    for (delivery, webhook) in due {
+        if attempt(config, &webhook, &delivery, now)? {
+            delivered += 1;
+        }
+    }
+    Ok(delivered)
+}
+
+/// the background task that posts the queued deliveries, at launch, whenever an event is
+/// published and at the configured interval, until the rocket shuts down
+pub fn deliverer() -> AdHoc {
+    AdHoc::on_liftoff("Webhook deliveries", |rocket| {
+        Box::pin(async move {
+            let (Some(config), Some(feed)) =
+                (rocket.state::<Config>(), rocket.state::<Arc<GroupFeed>>())
+            else {
+                return;
+            };
+            if !config.webhooks.enabled {
+                return;
+            }
+
+            let config = config.webhooks.clone();
+            let mut events = feed.subscribe();
+            let period = Duration::from_secs(config.interval_secs);
+            let mut shutdown = rocket.shutdown();
+            rocket::tokio::spawn(async move {
+                let mut interval = rocket::tokio::time::interval(period);
+                loop {
+                    rocket::tokio::select! {
+                        _ = interval.tick() => (),
+                        e = events.recv() => match e {
+                            Ok(_) | Err(RecvError::Lagged(_)) => (),
+                            Err(RecvError::Closed) => break,
+                        },
+                        _ = &mut shutdown => break,
+                    }
+
+                    let config = config.clone();
+                    match rocket::tokio::task::spawn_blocking(move || {
+                        deliver_due(&config, Utc::now().naive_utc(), None)
+                    })
+                    .await
+                    {
+                        Ok(Ok(_)) => (),
+                        Ok(Err(e)) => error!("error delivering webhooks: {:?}", e),
+                        Err(e) => error!("webhook delivery task failed: {:?}", e),
+                    }
+                }
+            });
+        })
+    })
+}
@@ -0,0 +1,482 @@
+use rocket::data::ByteUnit;
+use rocket::fairing::AdHoc;
+use rocket::figment::{Figment, providers::Env};
+use rocket::http::{SameSite, uri::Absolute};
+use rocket::serde::Deserialize;
+use rocket::time::Duration;
+
+use dotenvy::dotenv;
+use rocket_cors::{AllowedHeaders, AllowedOrigins, Cors, CorsOptions};
+
+/// application settings, extracted from the same figment as rocket's own configuration, so they
+/// can be set in `Rocket.toml` (per profile) or through `ROCKET_` prefixed environment variables.
+/// the database url can also be given with the plain `DATABASE_URL` variable, read from `.env` too
+#[derive(Debug, Clone, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Config {
+    pub database_url: String,
+    #[serde(default)]
+    pub session: SessionConfig,
+    #[serde(default)]
+    pub cors: CorsConfig,
+    #[serde(default)]
+    pub mail: MailConfig,
+    #[serde(default)]
+    pub notification_emails: NotificationEmailsConfig,
+    #[serde(default)]
+    pub recurring: RecurringConfig,
+    #[serde(default)]
+    pub webhooks: WebhooksConfig,
+    #[serde(default)]
+    pub reminders: RemindersConfig,
+    #[serde(default)]
+    pub attachments: AttachmentsConfig,
+    #[serde(default)]
+    pub trash: TrashConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct SessionConfig {
+    /// how long a login lasts, both for the cookie and for the session on the server
+    pub ttl_days: i64,
+    /// marks the session cookie as `Secure`, should be enabled whenever the api is served on https
+    pub secure_cookie: bool,
+    pub same_site: CookieSameSite,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            ttl_days: 30,
+            secure_cookie: false,
+            same_site: CookieSameSite::Strict,
+        }
+    }
+}
+
+impl SessionConfig {
+    pub fn ttl(&self) -> Duration {
+        Duration::days(self.ttl_days)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum CookieSameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl From<CookieSameSite> for SameSite {
+    fn from(s: CookieSameSite) -> Self {
+        match s {
+            CookieSameSite::Strict => SameSite::Strict,
+            CookieSameSite::Lax => SameSite::Lax,
+            CookieSameSite::None => SameSite::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct CorsConfig {
+    /// origins allowed to make cross origin requests, e.g. `https://splitsmart.example.com`,
+    /// requests coming from any other origin are refused
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    /// lets the browser send the session cookie along with cross origin requests
+    pub allow_credentials: bool,
+    /// how long the browser can cache the answer to a preflight request
+    pub max_age_secs: Option<usize>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        CorsConfig {
+            allowed_origins: vec!["http://localhost:3000".to_owned()],
+            allowed_methods: ["GET", "POST", "PUT", "DELETE"].map(String::from).to_vec(),
+            allowed_headers: ["Accept", "Content-Type"].map(String::from).to_vec(),
+            allow_credentials: true,
+            max_age_secs: Some(3600),
+        }
+    }
+}
+
+impl CorsConfig {
+    /// the CORS fairing described by this configuration
+    pub fn to_cors(&self) -> Result<Cors, String> {
+        let allowed_methods = self
+            .allowed_methods
+            .iter()
+            .map(|m| {
+                m.parse::<rocket_cors::Method>()
+                    .map_err(|_| format!("`cors.allowed_methods`: invalid method `{}`", m))
+            })
+            .collect::<Result<_, _>>()?;
+        let allowed_headers: Vec<&str> = self.allowed_headers.iter().map(String::as_str).collect();
+
+        CorsOptions {
+            allowed_origins: AllowedOrigins::some_exact(&self.allowed_origins),
+            allowed_methods,
+            allowed_headers: AllowedHeaders::some(&allowed_headers),
+            allow_credentials: self.allow_credentials,
+            max_age: self.max_age_secs,
+            ..Default::default()
+        }
+        .to_cors()
+        .map_err(|e| format!("invalid cors configuration: {}", e))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct RecurringConfig {
+    /// runs the background task that adds the recurring expenses when they are due, the admins
+    /// of a group can still add them on demand when it is disabled
+    pub enabled: bool,
+    /// seconds between two runs of the background task, the first one is at launch
+    pub interval_secs: u64,
+}
+
+impl Default for RecurringConfig {
+    fn default() -> Self {
+        RecurringConfig {
+            enabled: true,
+            interval_secs: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct WebhooksConfig {
+    /// runs the background task that posts the events to the webhooks, they are still queued
+    /// when it is disabled
+    pub enabled: bool,
+    /// seconds between two checks for the deliveries that are due, they are also checked as soon
+    /// as an event is queued
+    pub interval_secs: u64,
+    /// seconds to wait for a webhook to answer
+    pub timeout_secs: u64,
+    /// attempts after which a delivery is given up on
+    pub max_attempts: i32,
+    /// seconds before the first retry of a delivery, doubled on every further retry
+    pub retry_base_secs: i64,
+    /// lets the webhooks point to loopback, private and link-local addresses, which are refused
+    /// otherwise so that the server cannot be used to reach its own network
+    pub allow_private_hosts: bool,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        WebhooksConfig {
+            enabled: true,
+            interval_secs: 30,
+            timeout_secs: 10,
+            max_attempts: 8,
+            retry_base_secs: 30,
+            allow_private_hosts: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct RemindersConfig {
+    /// runs the background task that sends the automatic reminders of the groups that scheduled
+    /// them, the members can still remind each other when it is disabled
+    pub enabled: bool,
+    /// seconds between two runs of the background task, the first one is at launch
+    pub interval_secs: u64,
+    /// hours before a member can remind the same member again in a group
+    pub min_interval_hours: i64,
+}
+
+impl Default for RemindersConfig {
+    fn default() -> Self {
+        RemindersConfig {
+            enabled: true,
+            interval_secs: 3600,
+            min_interval_hours: 24,
+        }
+    }
+}
+
+impl RemindersConfig {
+    pub fn min_interval(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.min_interval_hours)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct TrashConfig {
+    /// days a deleted expense or group can be restored for
+    pub retention_days: i64,
+    /// runs the background task that purges what has been in the trash for longer than
+    /// `retention_days`
+    pub purge_enabled: bool,
+    /// seconds between two runs of the purge, the first one is at launch
+    pub purge_interval_secs: u64,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        TrashConfig {
+            retention_days: 30,
+            purge_enabled: true,
+            purge_interval_secs: 3600,
+        }
+    }
+}
+
+impl TrashConfig {
+    pub fn retention(&self) -> chrono::Duration {
+        chrono::Duration::days(self.retention_days)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct AttachmentsConfig {
+    /// directory where the uploaded files are stored, created at launch if missing
+    pub dir: String,
+    /// size of the largest file that can be uploaded, e.g. `"10MiB"`
+    pub max_size: ByteUnit,
+    /// content types that can be uploaded, told apart by the content of the files
+    pub allowed_types: Vec<String>,
+}
+
+impl Default for AttachmentsConfig {
+    fn default() -> Self {
+        AttachmentsConfig {
+            dir: "attachments".to_owned(),
+            max_size: ByteUnit::Mebibyte(10),
+            allowed_types: crate::storage::SUPPORTED_TYPES.map(String::from).to_vec(),
+        }
+    }
+}
+
+/// how emails are delivered, `none` disables them
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(crate = "rocket::serde", tag = "transport", rename_all = "lowercase")]
+pub enum MailConfig {
+    #[default]
+    None,
+    Smtp {
+        host: String,
+        port: u16,
+        #[serde(default)]
+        tls: SmtpTls,
+        username: Option<String>,
+        password: Option<String>,
+        from: String,
+    },
+    /// writes every email as a file in `dir`, for development and tests
+    File { dir: String, from: String },
+}
+
+/// how the connection to the smtp relay is encrypted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// upgraded with `STARTTLS`, which the relay has to support, usually on port 587. the default
+    #[default]
+    Starttls,
+    /// encrypted from the start, usually on port 465
+    Tls,
+    /// not encrypted, only for a relay on the same host or network, that needs no credentials
+    None,
+}
+
+/// the notifications sent by email, when `mail` is configured
+#[derive(Debug, Clone, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct NotificationEmailsConfig {
+    /// address of the web application, which the links in the emails point to
+    pub app_url: String,
+    /// seconds between two checks for the notifications to send, they are also checked as soon
+    /// as a notification is created
+    pub interval_secs: u64,
+    /// hour of the day, in utc, the daily digests are sent at
+    pub digest_hour: u32,
+}
+
+impl Default for NotificationEmailsConfig {
+    fn default() -> Self {
+        NotificationEmailsConfig {
+            app_url: "http://localhost:3000".to_owned(),
+            interval_secs: 60,
+            digest_hour: 8,
+        }
+    }
+}
+
+impl Config {
+    /// checks the settings that cannot be expressed by their types, so that a misconfigured
+    /// server refuses to start instead of failing on the first request
+    pub fn validate(&self) -> Result<(), String> {
+        if self.database_url.trim().is_empty() {
+            return Err("`database_url` cannot be empty".to_owned());
+        }
+
+        if self.session.ttl_days <= 0 {
+            return Err("`session.ttl_days` must be positive".to_owned());
+        }
+        if self.session.same_site == CookieSameSite::None && !self.session.secure_cookie {
+            return Err(
+                "`session.same_site = \"none\"` requires `session.secure_cookie`".to_owned(),
+            );
+        }
+
+        for origin in &self.cors.allowed_origins {
+            match Absolute::parse(origin) {
+                Ok(uri) if matches!(uri.scheme(), "http" | "https") => (),
+                _ => {
+                    return Err(format!(
+                        "`cors.allowed_origins`: invalid origin `{}`",
+                        origin
+                    ));
+                }
+            }
+        }
+        self.cors.to_cors()?;
+
+        if self.recurring.interval_secs == 0 {
+            return Err("`recurring.interval_secs` must be positive".to_owned());
+        }
+
+        if self.webhooks.interval_secs == 0 || self.webhooks.timeout_secs == 0 {
+            return Err(
+                "`webhooks.interval_secs` and `webhooks.timeout_secs` must be positive".to_owned(),
+            );
+        }
+        if self.webhooks.max_attempts <= 0 || self.webhooks.retry_base_secs <= 0 {
+            return Err(
+                "`webhooks.max_attempts` and `webhooks.retry_base_secs` must be positive"
+                    .to_owned(),
+            );
+        }
+
+        if self.reminders.interval_secs == 0 || self.reminders.min_interval_hours <= 0 {
+            return Err(
+                "`reminders.interval_secs` and `reminders.min_interval_hours` must be positive"
+                    .to_owned(),
+            );
+        }
+
+        if self.trash.retention_days <= 0 {
+            return Err("`trash.retention_days` must be positive".to_owned());
+        }
+        if self.trash.purge_interval_secs == 0 {
+            return Err("`trash.purge_interval_secs` must be positive".to_owned());
+        }
+
+        if self.attachments.dir.trim().is_empty() {
+            return Err("`attachments.dir` cannot be empty".to_owned());
+        }
+        if self.attachments.max_size == 0 {
+            return Err("`attachments.max_size` must be positive".to_owned());
+        }
+        for t in &self.attachments.allowed_types {
+            if !crate::storage::SUPPORTED_TYPES.contains(&t.as_str()) {
+                return Err(format!(
+                    "`attachments.allowed_types`: unsupported type `{}`",
+                    t
+                ));
+            }
+        }
+
+        match Absolute::parse(&self.notification_emails.app_url) {
+            Ok(uri) if matches!(uri.scheme(), "http" | "https") => (),
+            _ => {
+                return Err(format!(
+                    "`notification_emails.app_url`: invalid url `{}`",
+                    self.notification_emails.app_url
+                ));
+            }
+        }
+        if self.notification_emails.interval_secs == 0 {
+            return Err("`notification_emails.interval_secs` must be positive".to_owned());
+        }
+        if self.notification_emails.digest_hour > 23 {
+            return Err("`notification_emails.digest_hour` must be between 0 and 23".to_owned());
+        }
+
+        match &self.mail {
+            MailConfig::None => (),
+            MailConfig::Smtp {
+                host,
+                port,
+                tls,
+                username,
+                password,
+                from,
+            } => {
+                if host.is_empty() || *port == 0 {
+                    return Err("`mail.host` and `mail.port` are required for smtp".to_owned());
+                }
+                if username.is_some() != password.is_some() {
+                    return Err("`mail.username` and `mail.password` go together".to_owned());
+                }
+                if *tls == SmtpTls::None && username.is_some() {
+                    return Err(
+                        "`mail.username` and `mail.password` cannot be sent with `mail.tls = \"none\"`"
+                            .to_owned(),
+                    );
+                }
+                validate_sender(from)?;
+            }
+            MailConfig::File { dir, from } => {
+                if dir.is_empty() {
+                    return Err("`mail.dir` is required for the file transport".to_owned());
+                }
+                validate_sender(from)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_sender(from: &str) -> Result<(), String> {
+    if from.contains('@') {
+        Ok(())
+    } else {
+        Err(format!("`mail.from`: invalid address `{}`", from))
+    }
+}
+
+/// rocket's default figment, plus the plain `DATABASE_URL` environment variable
+pub fn figment() -> Figment {
+    dotenv().ok();
+
+    rocket::Config::figment().merge(Env::raw().only(&["DATABASE_URL"]))
+}
+
+/// extracts and validates the [`Config`], sets up the database url and manages the configuration
+/// so that routes can ask for it with `&State<Config>`. aborts the launch if anything is wrong
+pub fn fairing() -> AdHoc {
+    AdHoc::try_on_ignite("Configuration", |rocket| async {
+        let config = match rocket.figment().extract::<Config>() {
+            Ok(c) => c,
+            Err(e) => {
+                rocket::config::pretty_print_error(e);
+                return Err(rocket);
+            }
+        };
+
+        if let Err(e) = config
+            .validate()
+            .and_then(|_| crate::db::set_database_url(&config.database_url))
+        {
+            error!("invalid configuration: {}", e);
+            return Err(rocket);
+        }
+
+        Ok(rocket.manage(config))
+    })
+}
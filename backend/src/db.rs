@@ -0,0 +1,83 @@
+use diesel::Connection;
+use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
+
+use std::sync::OnceLock;
+
+#[cfg(all(feature = "sqlite", feature = "postgres"))]
+compile_error!(
+    "features `sqlite` and `postgres` are mutually exclusive, build with `--no-default-features --features postgres` to use postgres"
+);
+
+#[cfg(not(any(feature = "sqlite", feature = "postgres")))]
+compile_error!("one of the features `sqlite` or `postgres` must be enabled");
+
+/// connection type of the database backend selected at compile time
+#[cfg(feature = "sqlite")]
+pub type DbConnection = diesel::SqliteConnection;
+/// diesel backend selected at compile time, used by the models in `check_for_backend`
+#[cfg(feature = "sqlite")]
+#[allow(dead_code)] // rustc does not see the uses inside the diesel derives
+pub type DbBackend = diesel::sqlite::Sqlite;
+#[cfg(feature = "sqlite")]
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+#[cfg(feature = "postgres")]
+pub type DbConnection = diesel::PgConnection;
+#[cfg(feature = "postgres")]
+#[allow(dead_code)]
+pub type DbBackend = diesel::pg::Pg;
+#[cfg(feature = "postgres")]
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations_postgres");
+
+/// url of the database, set from the configuration when the rocket ignites
+static DATABASE_URL: OnceLock<String> = OnceLock::new();
+
+/// sets the url used by [`establish_connection`], the process talks to a single database so it
+/// can only be set once, setting it again to a different url is an error
+pub fn set_database_url(url: &str) -> Result<(), String> {
+    let current = DATABASE_URL.get_or_init(|| url.to_owned());
+    if current == url {
+        Ok(())
+    } else {
+        Err(format!(
+            "database url already set to `{}`, cannot change it to `{}`",
+            current, url
+        ))
+    }
+}
+
+pub fn establish_connection() -> DbConnection {
+    let database_url = DATABASE_URL
+        .get()
+        .expect("database url is not configured, the rocket has not been ignited");
+    let mut conn = DbConnection::establish(database_url)
+        .unwrap_or_else(|_| panic!("Error connecting to {}", database_url));
+
+    configure_connection(&mut conn);
+    conn
+}
+
+/// sqlite does not enforce foreign keys unless asked to for every connection, without this the
+/// `ON DELETE` clauses of the schema would behave differently than on postgres. the busy timeout
+/// avoids failing right away when another connection is holding the write lock
+#[cfg(feature = "sqlite")]
+fn configure_connection(conn: &mut DbConnection) {
+    use diesel::connection::SimpleConnection;
+
+    conn.batch_execute("PRAGMA foreign_keys = ON; PRAGMA busy_timeout = 5000;")
+        .expect("could not configure sqlite connection");
+}
+
+#[cfg(feature = "postgres")]
+fn configure_connection(_conn: &mut DbConnection) {}
+
+/// applies the migrations embedded in the binary that have not been run yet on the database
+pub fn run_pending_migrations() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let mut conn = establish_connection();
+
+    let applied = conn.run_pending_migrations(MIGRATIONS)?;
+    for m in applied {
+        info!("applied migration {}", m);
+    }
+    Ok(())
+}
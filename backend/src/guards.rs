@@ -0,0 +1,116 @@
+use diesel::{dsl::exists, prelude::*, select};
+use rocket::{
+    Request,
+    http::Status,
+    request::{FromRequest, Outcome},
+};
+use rocket_okapi::request::OpenApiFromRequest;
+
+use crate::{
+    establish_connection,
+    models::User,
+    schema::{group_administrators, group_members, groups},
+};
+
+/// request guard for routes under `/groups/<gid>`, resolves the `<gid>` segment into a verified
+/// membership of the authenticated user.
+///
+/// fails with `401` if the user is not logged in, `404` if the group does not exist or is in the
+/// trash and `403` if the user is not a member of the group
+#[derive(Debug, OpenApiFromRequest)]
+pub struct GroupMembership {
+    pub group_id: i32,
+    pub user: User,
+    pub is_admin: bool,
+}
+
+/// request guard for routes under `/groups/<gid>` that can only be performed by an admin of the
+/// group, fails like [`GroupMembership`] and with `403` if the member is not an admin
+#[derive(Debug, OpenApiFromRequest)]
+pub struct GroupAdmin {
+    pub user: User,
+}
+
+/// the group id is the first segment after the mount point, as in `/<gid>/expenses`
+fn group_id_param(req: &Request<'_>) -> Option<i32> {
+    req.param::<i32>(0).and_then(|r| r.ok())
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for GroupMembership {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let user = match req.guard::<User>().await {
+            Outcome::Success(u) => u,
+            Outcome::Error(e) => return Outcome::Error(e),
+            Outcome::Forward(s) => return Outcome::Forward(s),
+        };
+
+        let gid = match group_id_param(req) {
+            Some(gid) => gid,
+            None => {
+                error!("group guard used on a route without a valid <gid> segment");
+                return Outcome::Error((Status::NotFound, ()));
+            }
+        };
+
+        let mut conn = establish_connection();
+
+        match select(exists(
+            groups::table
+                .filter(groups::id.eq(gid))
+                .filter(groups::deleted_at.is_null()),
+        ))
+        .get_result::<bool>(&mut conn)
+        {
+            Ok(true) => (),
+            Ok(false) => return Outcome::Error((Status::NotFound, ())),
+            Err(e) => {
+                error!("error checking group existence in group guard: {:?}", e);
+                return Outcome::Error((Status::InternalServerError, ()));
+            }
+        }
+
+        let member = select(exists(
+            group_members::table
+                .filter(group_members::group_id.eq(gid))
+                .filter(group_members::user_id.eq(user.id)),
+        ))
+        .get_result::<bool>(&mut conn);
+
+        let admin = select(exists(
+            group_administrators::table
+                .filter(group_administrators::group_id.eq(gid))
+                .filter(group_administrators::user_id.eq(user.id)),
+        ))
+        .get_result::<bool>(&mut conn);
+
+        match (member, admin) {
+            (Ok(true), Ok(is_admin)) => Outcome::Success(GroupMembership {
+                group_id: gid,
+                user,
+                is_admin,
+            }),
+            (Ok(false), Ok(_)) => Outcome::Error((Status::Forbidden, ())),
+            (Err(e), _) | (_, Err(e)) => {
+                error!("error checking group membership in group guard: {:?}", e);
+                Outcome::Error((Status::InternalServerError, ()))
+            }
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for GroupAdmin {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match req.guard::<GroupMembership>().await {
+            Outcome::Success(m) if m.is_admin => Outcome::Success(GroupAdmin { user: m.user }),
+            Outcome::Success(_) => Outcome::Error((Status::Forbidden, ())),
+            Outcome::Error(e) => Outcome::Error(e),
+            Outcome::Forward(s) => Outcome::Forward(s),
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! how the emails leave the server. the code that sends emails only sees the [`Mailer`] trait,
+//! the transport is chosen by the `mail` configuration: [`SmtpMailer`] hands them to an smtp
+//! relay, [`FileMailer`] writes them to a directory for development and tests
+
+use base64ct::{Base64, Encoding};
+use chrono::Utc;
+use lettre::Transport;
+use lettre::address::{Address, Envelope};
+use lettre::transport::smtp::{SmtpTransport, authentication::Credentials};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::config::{Config, MailConfig, SmtpTls};
+use rocket::fairing::AdHoc;
+
+/// a plain text email
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+    /// where the recipient can stop receiving emails like this one, sent as `List-Unsubscribe`
+    pub unsubscribe: Option<String>,
+}
+
+impl Email {
+    /// the email as sent on the wire, with `from` as the sender. the subject is encoded when it is
+    /// not plain ascii and the body is sent as utf-8
+    pub fn message(&self, from: &str) -> io::Result<String> {
+        for address in [from, self.to.as_str()] {
+            if address.is_empty() || address.contains(['\r', '\n', '<', '>', ',']) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid email address `{}`", address),
+                ));
+            }
+        }
+
+        let subject = self.subject.replace(['\r', '\n'], " ");
+        let subject = match subject.is_ascii() {
+            true => subject,
+            false => format!("=?UTF-8?B?{}?=", Base64::encode_string(subject.as_bytes())),
+        };
+        let domain = from.rsplit_once('@').map_or("localhost", |(_, d)| d);
+
+        let mut headers = vec![
+            format!("From: <{}>", from),
+            format!("To: <{}>", self.to),
+            format!("Subject: {}", subject),
+            format!("Date: {}", Utc::now().to_rfc2822()),
+            format!("Message-ID: <{}@{}>", Uuid::new_v4().simple(), domain),
+            "MIME-Version: 1.0".to_owned(),
+            "Content-Type: text/plain; charset=utf-8".to_owned(),
+            "Content-Transfer-Encoding: 8bit".to_owned(),
+        ];
+        if let Some(url) = &self.unsubscribe {
+            headers.push(format!(
+                "List-Unsubscribe: <{}>",
+                url.replace(['\r', '\n'], "")
+            ));
+        }
+
+        let body = self.body.replace("\r\n", "\n").replace('\n', "\r\n");
+        Ok(format!("{}\r\n\r\n{}\r\n", headers.join("\r\n"), body))
+    }
+}
+
+/// a way of delivering emails
+pub trait Mailer: Send + Sync {
+    fn send(&self, email: &Email) -> io::Result<()>;
+}
+
+/// the mailer described by the configuration, `None` if emails are disabled
+pub fn mailer(config: &MailConfig) -> io::Result<Option<Arc<dyn Mailer>>> {
+    Ok(match config {
+        MailConfig::None => None,
+        MailConfig::Smtp {
+            host,
+            port,
+            tls,
+            username,
+            password,
+            from,
+        } => Some(Arc::new(SmtpMailer::new(
+            host,
+            *port,
+            *tls,
+            username.clone().zip(password.clone()),
+            from,
+        )?)),
+        MailConfig::File { dir, from } => Some(Arc::new(FileMailer::new(dir, from)?)),
+    })
+}
+
+/// manages the [`Mailer`] described by the configuration as `Arc<dyn Mailer>`, if emails are
+/// enabled. aborts the launch if the directory of the file transport cannot be created
+pub fn fairing() -> AdHoc {
+    AdHoc::try_on_ignite("Mailer", |rocket| async {
+        let Some(config) = rocket.state::<Config>() else {
+            return Err(rocket);
+        };
+
+        match mailer(&config.mail) {
+            Ok(Some(mailer)) => Ok(rocket.manage(mailer)),
+            Ok(None) => Ok(rocket),
+            Err(e) => {
+                error!("could not set up the mail transport: {}", e);
+                Err(rocket)
+            }
+        }
+    })
+}
+
+/// writes every email to a `.eml` file of its own in a directory
+pub struct FileMailer {
+    dir: PathBuf,
+    from: String,
+}
+
+impl FileMailer {
+    /// mailer that writes to `dir`, which is created if it does not exist
+    pub fn new(dir: impl Into<PathBuf>, from: &str) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(FileMailer {
+            dir,
+            from: from.to_owned(),
+        })
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, email: &Email) -> io::Result<()> {
+        let name = format!(
+            "{}-{}.eml",
+            Utc::now().format("%Y%m%d%H%M%S"),
+            Uuid::new_v4().simple()
+        );
+        fs::write(self.dir.join(name), email.message(&self.from)?)
+    }
+}
+
+/// hands the emails to an smtp relay, one connection per email, encrypted as `mail.tls` says
+pub struct SmtpMailer {
+    transport: SmtpTransport,
+    from: String,
+}
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+impl SmtpMailer {
+    /// mailer that connects to the relay at `host` and `port`. the credentials are never sent over
+    /// a connection that is not encrypted
+    pub fn new(
+        host: &str,
+        port: u16,
+        tls: SmtpTls,
+        credentials: Option<(String, String)>,
+        from: &str,
+    ) -> io::Result<Self> {
+        let builder = match tls {
+            SmtpTls::Starttls => SmtpTransport::starttls_relay(host).map_err(io::Error::other)?,
+            SmtpTls::Tls => SmtpTransport::relay(host).map_err(io::Error::other)?,
+            SmtpTls::None => SmtpTransport::builder_dangerous(host),
+        };
+        let mut builder = builder.port(port).timeout(Some(SMTP_TIMEOUT));
+        if let Some((username, password)) = credentials {
+            if tls == SmtpTls::None {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the smtp credentials cannot be sent over a connection that is not encrypted",
+                ));
+            }
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(SmtpMailer {
+            transport: builder.build(),
+            from: from.to_owned(),
+        })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, email: &Email) -> io::Result<()> {
+        let message = email.message(&self.from)?;
+
+        let invalid =
+            |e: &dyn std::fmt::Display| io::Error::new(io::ErrorKind::InvalidInput, e.to_string());
+        let from = self.from.parse::<Address>().map_err(|e| invalid(&e))?;
+        let to = email.to.parse::<Address>().map_err(|e| invalid(&e))?;
+        let envelope = Envelope::new(Some(from), vec![to]).map_err(|e| invalid(&e))?;
+
+        self.transport
+            .send_raw(&envelope, message.as_bytes())
+            .map(|_| ())
+            .map_err(io::Error::other)
+    }
+}
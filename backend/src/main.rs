@@ -1,30 +1,37 @@
 #[macro_use]
 extern crate rocket;
 
-use diesel::prelude::*;
-use rocket_cors::CorsOptions;
+use chrono::NaiveDateTime;
+use rocket::fairing::AdHoc;
+use rocket::figment::Figment;
+use rocket::{Build, Rocket};
 use rocket_okapi::okapi::openapi3::OpenApi;
 use rocket_okapi::{mount_endpoints_and_merged_docs, swagger_ui::*};
 use std::collections::HashMap;
 use std::sync::Mutex;
 
 mod api;
+mod config;
+mod db;
+mod guards;
+mod mail;
 mod models;
+mod notify;
 mod schema;
+mod storage;
 
-use dotenvy::dotenv;
-use std::env;
+#[cfg(test)]
+mod tests;
 
-pub fn establish_connection() -> SqliteConnection {
-    dotenv().ok();
+pub use db::establish_connection;
 
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    SqliteConnection::establish(&database_url)
-        .unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
+pub struct Session {
+    pub user_id: i32,
+    pub expires: NaiveDateTime,
 }
 
 pub struct SessionStore {
-    pub sessions: Mutex<HashMap<String, i32>>,
+    pub sessions: Mutex<HashMap<String, Session>>,
 }
 
 impl SessionStore {
@@ -37,13 +44,54 @@ impl SessionStore {
 
 #[launch]
 fn rocket() -> _ {
-    let cors = CorsOptions::default()
-        .to_cors()
-        .expect("error creating CORS fairing");
+    app(config::figment())
+}
 
-    let mut building_rocket = rocket::build()
+/// builds the application on top of the given figment, which is where the configuration is read
+/// from, tests use it to point the application to their own database
+pub fn app(figment: Figment) -> Rocket<Build> {
+    let mut building_rocket = rocket::custom(figment)
         .manage(SessionStore::new())
-        .attach(cors)
+        .attach(config::fairing())
+        .attach(AdHoc::try_on_ignite(
+            "Database migrations",
+            |rocket| async {
+                // rocket runs the remaining fairings even if the configuration was rejected
+                if rocket.state::<config::Config>().is_none() {
+                    return Err(rocket);
+                }
+
+                match db::run_pending_migrations() {
+                    Ok(()) => Ok(rocket),
+                    Err(e) => {
+                        error!("could not run database migrations: {}", e);
+                        Err(rocket)
+                    }
+                }
+            },
+        ))
+        .attach(AdHoc::try_on_ignite("CORS", |rocket| async {
+            let Some(config) = rocket.state::<config::Config>() else {
+                return Err(rocket);
+            };
+
+            match config.cors.to_cors() {
+                Ok(cors) => Ok(rocket.attach(cors)),
+                Err(e) => {
+                    error!("error creating CORS fairing: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(storage::fairing())
+        .attach(mail::fairing())
+        .attach(api::recurring::scheduler())
+        .attach(api::reminders::scheduler())
+        .attach(api::trash::purger())
+        .attach(api::notifications::broadcaster())
+        .attach(api::live::feed())
+        .attach(api::emails::sender())
+        .attach(api::webhooks::deliverer())
         .mount(
             "/swagger-ui/",
             make_swagger_ui(&SwaggerUIConfig {
@@ -58,8 +106,20 @@ fn rocket() -> _ {
         building_rocket, "/".to_owned(), openapi_settings,
         "/external" => custom_route_spec,
         "/groups" => api::groups::get_routes_and_docs(&openapi_settings),
+        "/groups" => api::recurring::get_routes_and_docs(&openapi_settings),
+        "/groups" => api::comments::get_routes_and_docs(&openapi_settings),
+        "/groups" => api::attachments::get_routes_and_docs(&openapi_settings),
+        "/groups" => api::history::get_routes_and_docs(&openapi_settings),
+        "/groups" => api::trash::get_routes_and_docs(&openapi_settings),
+        "/groups" => api::receipts::get_routes_and_docs(&openapi_settings),
+        "/groups" => api::live::get_routes_and_docs(&openapi_settings),
+        "/groups" => api::webhooks::get_routes_and_docs(&openapi_settings),
+        "/groups" => api::reminders::get_routes_and_docs(&openapi_settings),
+        "/groups" => api::invite_links::get_routes_and_docs(&openapi_settings),
+        "/invites" => api::invite_links::get_invite_routes_and_docs(&openapi_settings),
         "/user" => api::users::get_routes_and_docs(&openapi_settings),
         "/notifications" => api::notifications::get_routes_and_docs(&openapi_settings),
+        "/notifications" => api::emails::get_routes_and_docs(&openapi_settings),
         "/friends" => api::friends::get_routes_and_docs(&openapi_settings),
         "/expenses" => api::expenses::get_routes_and_docs(&openapi_settings),
     };
@@ -68,9 +128,7 @@ fn rocket() -> _ {
 }
 
 fn custom_openapi_spec() -> OpenApi {
-    use rocket_okapi::okapi::map;
     use rocket_okapi::okapi::openapi3::*;
-    use rocket_okapi::okapi::schemars::schema::*;
     OpenApi {
         openapi: OpenApi::default_version(),
         info: Info {
@@ -1,10 +1,10 @@
-use crate::{establish_connection, schema::*, SessionStore};
-use chrono::NaiveDateTime;
-use diesel::{prelude::*, sqlite::Sqlite};
+use crate::{SessionStore, db::DbBackend, establish_connection, schema::*};
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use diesel::prelude::*;
 use rocket::{
+    State,
     http::Status,
     request::{FromRequest, Outcome},
-    State,
 };
 use rocket_okapi::request::OpenApiFromRequest;
 use schemars::JsonSchema;
@@ -12,7 +12,7 @@ use serde::{Deserialize, Serialize};
 
 //#[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 //#[diesel(table_name = balances)]
-//#[diesel(check_for_backend(Sqlite))]
+//#[diesel(check_for_backend(DbBackend))]
 //pub struct Balance {
 //    pub id: Option<i32>,
 //    pub debtor_user_id: i32,
@@ -21,10 +21,13 @@ use serde::{Deserialize, Serialize};
 //    pub group_id: Option<i32>,
 //}
 
-#[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[derive(
+    Queryable, Identifiable, Associations, Debug, Clone, Serialize, Deserialize, JsonSchema,
+)]
 #[diesel(table_name = expense_participations)]
 #[diesel(primary_key(expense_id, user_id))]
-#[diesel(check_for_backend(Sqlite))]
+#[diesel(belongs_to(Expense))]
+#[diesel(check_for_backend(DbBackend))]
 pub struct ExpenseParticipation {
     pub expense_id: i32,
     pub user_id: i32,
@@ -33,20 +36,244 @@ pub struct ExpenseParticipation {
 
 #[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[diesel(table_name = expenses)]
-#[diesel(check_for_backend(Sqlite))]
+#[diesel(check_for_backend(DbBackend))]
 pub struct Expense {
     pub id: i32,
     pub desc: String,
     pub total_amount: f64,
-    pub creation_date: NaiveDateTime,
+    /// when the expense was added
+    pub created_at: NaiveDateTime,
     pub paid_by: i32,
     pub group_id: Option<i32>,
+    /// when the expense was last modified
+    pub updated_at: NaiveDateTime,
+    /// the day the expense refers to, chosen by who adds it
+    pub expense_date: NaiveDate,
+    pub category_id: Option<i32>,
+    /// when the expense was moved to the trash, `None` if it was not
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+/// the state of a group expense after it was added or modified, `revision` counts from 1 for
+/// every expense
+#[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[diesel(table_name = expense_revisions)]
+#[diesel(check_for_backend(DbBackend))]
+pub struct ExpenseRevision {
+    pub id: i32,
+    pub expense_id: i32,
+    pub revision: i32,
+    /// `None` for the expenses added before their history was kept, or once the user is deleted
+    pub changed_by: Option<i32>,
+    pub changed_at: NaiveDateTime,
+    pub desc: String,
+    pub total_amount: f64,
+    pub paid_by: i32,
+    pub expense_date: NaiveDate,
+    pub category_id: Option<i32>,
+}
+
+#[derive(
+    Queryable, Identifiable, Associations, Debug, Clone, Serialize, Deserialize, JsonSchema,
+)]
+#[diesel(table_name = expense_revision_participations)]
+#[diesel(primary_key(revision_id, user_id))]
+#[diesel(belongs_to(ExpenseRevision, foreign_key = revision_id))]
+#[diesel(check_for_backend(DbBackend))]
+pub struct ExpenseRevisionParticipation {
+    pub revision_id: i32,
+    pub user_id: i32,
+    pub amount_due: Option<f64>,
+}
+
+/// a line item of the receipt of a group expense
+#[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[diesel(table_name = expense_items)]
+#[diesel(check_for_backend(DbBackend))]
+pub struct ExpenseItem {
+    pub id: i32,
+    pub expense_id: i32,
+    pub desc: String,
+    pub amount: f64,
+}
+
+#[derive(
+    Queryable, Identifiable, Associations, Debug, Clone, Serialize, Deserialize, JsonSchema,
+)]
+#[diesel(table_name = expense_item_consumers)]
+#[diesel(primary_key(item_id, user_id))]
+#[diesel(belongs_to(ExpenseItem, foreign_key = item_id))]
+#[diesel(check_for_backend(DbBackend))]
+pub struct ExpenseItemConsumer {
+    pub item_id: i32,
+    pub user_id: i32,
+}
+
+/// a message about an expense, left by a member of its group
+#[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[diesel(table_name = expense_comments)]
+#[diesel(check_for_backend(DbBackend))]
+pub struct ExpenseComment {
+    pub id: i32,
+    pub expense_id: i32,
+    pub author_id: i32,
+    pub body: String,
+    pub created_at: NaiveDateTime,
+    /// when the comment was last edited, the same as `created_at` if it never was
+    pub updated_at: NaiveDateTime,
+}
+
+/// a file attached to a group expense, such as the picture of its receipt
+#[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[diesel(table_name = attachments)]
+#[diesel(check_for_backend(DbBackend))]
+pub struct Attachment {
+    pub id: i32,
+    pub expense_id: i32,
+    /// `None` once the user that uploaded it has been deleted
+    pub uploaded_by: Option<i32>,
+    pub file_name: String,
+    pub content_type: String,
+    /// in bytes
+    pub size: i64,
+    /// key of the file in the attachment storage, files with the same content share it
+    #[serde(skip)]
+    pub content_hash: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// a category expenses can be filed under, the built-in ones have no group and can be used by
+/// every expense, the others only by the expenses of their group
+#[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[diesel(table_name = categories)]
+#[diesel(check_for_backend(DbBackend))]
+pub struct Category {
+    pub id: i32,
+    pub group_id: Option<i32>,
+    pub name: String,
+}
+
+// lets the reports group the participations by the category of their expense, kept here because
+// `schema.rs` is generated
+diesel::allow_columns_to_appear_in_same_group_by_clause!(
+    expenses::category_id,
+    expense_participations::user_id,
+);
+
+/// the template of the expenses added to a group on every occurrence of a recurrence rule
+#[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[diesel(table_name = recurring_expenses)]
+#[diesel(check_for_backend(DbBackend))]
+pub struct RecurringExpense {
+    pub id: i32,
+    pub group_id: i32,
+    pub created_by: i32,
+    pub desc: String,
+    pub total_amount: f64,
+    pub paid_by: i32,
+    pub category_id: Option<i32>,
+    /// `MONTHLY`, `WEEKLY` or `EVERY_N_DAYS`
+    pub frequency: String,
+    /// the day of the month of the `MONTHLY` expenses
+    pub day_of_month: Option<i32>,
+    /// the days between two `EVERY_N_DAYS` expenses
+    pub interval_days: Option<i32>,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    /// the next expense that will be added, it is past `end_date` once they have all been added
+    pub next_date: NaiveDate,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(
+    Queryable, Identifiable, Associations, Debug, Clone, Serialize, Deserialize, JsonSchema,
+)]
+#[diesel(table_name = recurring_expense_participations)]
+#[diesel(primary_key(recurring_expense_id, user_id))]
+#[diesel(belongs_to(RecurringExpense))]
+#[diesel(check_for_backend(DbBackend))]
+pub struct RecurringExpenseParticipation {
+    pub recurring_expense_id: i32,
+    pub user_id: i32,
+    pub amount_due: Option<f64>,
+}
+
+/// an url the events of a group are posted to
+#[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[diesel(table_name = webhooks)]
+#[diesel(check_for_backend(DbBackend))]
+pub struct Webhook {
+    pub id: i32,
+    pub group_id: i32,
+    pub url: String,
+    /// key of the signature of the deliveries, never sent back
+    #[serde(skip)]
+    pub secret: String,
+    /// `None` once the admin that registered it has been deleted
+    pub created_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+/// an event posted, or to be posted, to a webhook
+#[derive(
+    Queryable, Identifiable, Associations, Debug, Clone, Serialize, Deserialize, JsonSchema,
+)]
+#[diesel(table_name = webhook_deliveries)]
+#[diesel(belongs_to(Webhook))]
+#[diesel(check_for_backend(DbBackend))]
+pub struct WebhookDelivery {
+    pub id: i32,
+    pub webhook_id: i32,
+    pub event_type: String,
+    /// the json body of the request
+    pub payload: String,
+    /// `PENDING`, `DELIVERED` or `FAILED` once it ran out of attempts
+    pub status: String,
+    pub attempts: i32,
+    /// when the pending delivery is attempted next
+    pub next_attempt_at: Option<NaiveDateTime>,
+    pub last_attempt_at: Option<NaiveDateTime>,
+    /// the status code of the last response
+    pub response_status: Option<i32>,
+    /// why the last attempt failed
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// a reminder to a member of a group of what the member owes
+#[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[diesel(table_name = payment_reminders)]
+#[diesel(check_for_backend(DbBackend))]
+pub struct PaymentReminder {
+    pub id: i32,
+    pub group_id: i32,
+    /// `None` for the automatic reminders, or once the sender has been deleted
+    pub sender_id: Option<i32>,
+    pub recipient_id: i32,
+    /// what the recipient owed the sender when it was sent, or the group for the automatic ones
+    pub amount: f64,
+    pub message: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// when the members of a group that owe are reminded automatically
+#[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[diesel(table_name = reminder_schedules)]
+#[diesel(primary_key(group_id))]
+#[diesel(check_for_backend(DbBackend))]
+pub struct ReminderSchedule {
+    pub group_id: i32,
+    /// the members that owe since more than these days are reminded, again every these days
+    pub after_days: i32,
+    /// the admin that set it, `None` once deleted
+    pub updated_by: Option<i32>,
+    pub updated_at: NaiveDateTime,
 }
 
 #[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[diesel(primary_key(group_id, user_id))]
 #[diesel(table_name = group_administrators)]
-#[diesel(check_for_backend(Sqlite))]
+#[diesel(check_for_backend(DbBackend))]
 pub struct GroupAdministrator {
     pub group_id: i32,
     pub user_id: i32,
@@ -54,7 +281,7 @@ pub struct GroupAdministrator {
 
 #[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[diesel(table_name = group_invites)]
-#[diesel(check_for_backend(Sqlite))]
+#[diesel(check_for_backend(DbBackend))]
 pub struct GroupInvite {
     pub id: i32,
     pub group_id: i32,
@@ -65,9 +292,29 @@ pub struct GroupInvite {
     pub optional_message: Option<String>,
 }
 
+/// a link anyone can join a group with, while it is usable
+#[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[diesel(table_name = group_invite_links)]
+#[diesel(check_for_backend(DbBackend))]
+pub struct GroupInviteLink {
+    pub id: i32,
+    pub group_id: i32,
+    /// the secret part of the link, `/invites/<token>`
+    pub token: String,
+    /// `None` once the admin that created it has been deleted
+    pub created_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    /// how many users can join with it, `None` for as many as want to
+    pub max_uses: Option<i32>,
+    /// how many users joined with it
+    pub uses: i32,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
 #[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[diesel(table_name = friend_invites)]
-#[diesel(check_for_backend(Sqlite))]
+#[diesel(check_for_backend(DbBackend))]
 pub struct FriendInvite {
     pub id: i32,
     pub invited_user_id: i32,
@@ -79,7 +326,7 @@ pub struct FriendInvite {
 #[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[diesel(primary_key(user1, user2))]
 #[diesel(table_name = friendships)]
-#[diesel(check_for_backend(Sqlite))]
+#[diesel(check_for_backend(DbBackend))]
 pub struct Friendship {
     pub user1: i32,
     pub user2: i32,
@@ -88,7 +335,7 @@ pub struct Friendship {
 #[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[diesel(primary_key(group_id, user_id))]
 #[diesel(table_name = group_members)]
-#[diesel(check_for_backend(Sqlite))]
+#[diesel(check_for_backend(DbBackend))]
 pub struct GroupMember {
     pub group_id: i32,
     pub user_id: i32,
@@ -96,17 +343,19 @@ pub struct GroupMember {
 
 #[derive(Selectable, Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[diesel(table_name = groups)]
-#[diesel(check_for_backend(Sqlite))]
+#[diesel(check_for_backend(DbBackend))]
 pub struct Group {
     pub id: i32,
     pub group_name: String,
     pub desc: Option<String>,
     pub creation_date: NaiveDateTime,
+    /// when the group was moved to the trash, `None` if it was not
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 #[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[diesel(table_name = notifications)]
-#[diesel(check_for_backend(Sqlite))]
+#[diesel(check_for_backend(DbBackend))]
 pub struct Notification {
     pub id: i32,
     pub notified_user_id: i32,
@@ -116,11 +365,17 @@ pub struct Notification {
     pub expense_id: Option<i32>,
     pub creation_date: NaiveDateTime,
     pub read: bool,
+    /// the revision an `EXPENSE_MODIFIED` notification is about
+    pub revision_id: Option<i32>,
+    /// whether it was sent by email, or never will be
+    pub emailed: bool,
+    /// the reminder a `BALANCE_REQUESTED` notification is about
+    pub reminder_id: Option<i32>,
 }
 
 #[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[diesel(table_name = users)]
-#[diesel(check_for_backend(Sqlite))]
+#[diesel(check_for_backend(DbBackend))]
 #[derive(OpenApiFromRequest)]
 pub struct User {
     pub id: i32,
@@ -138,6 +393,11 @@ pub struct User {
     pub last_login: Option<NaiveDateTime>,
     pub preferred_language: String,
     pub notification_preferences: Option<String>,
+    /// `OFF`, `IMMEDIATE` or `DIGEST`
+    pub email_notifications: String,
+    pub email_unsubscribe_token: Option<String>,
+    /// when the last daily digest was sent
+    pub last_digest_at: Option<NaiveDateTime>,
 }
 
 // request guard to check that user is authenticated
@@ -153,9 +413,17 @@ impl<'r> FromRequest<'r> for User {
 
             match session_store {
                 rocket::outcome::Outcome::Success(s) => {
-                    let s = s.sessions.lock().unwrap();
-                    match s.get(cookie.value()) {
-                        Some(usrid) => {
+                    let mut s = s.sessions.lock().unwrap();
+                    let session = s
+                        .get(cookie.value())
+                        .map(|session| (session.user_id, session.expires));
+                    match session {
+                        Some((_, expires)) if expires <= Utc::now().naive_utc() => {
+                            s.remove(cookie.value());
+                            error!("User request guard failed, the session has expired");
+                            Outcome::Error((Status::Unauthorized, ()))
+                        }
+                        Some((usrid, _)) => {
                             use crate::schema::users::dsl::*;
                             let mut conn = establish_connection();
 
@@ -164,14 +432,19 @@ impl<'r> FromRequest<'r> for User {
                             match result {
                                 Ok(usr) => Outcome::Success(usr),
                                 Err(_) => {
-                                    error!("User request guard failed, no matching user found for uid {}",usrid);
+                                    error!(
+                                        "User request guard failed, no matching user found for uid {}",
+                                        usrid
+                                    );
                                     Outcome::Error((Status::NotFound, ()))
                                 }
                             }
                         }
                         None => {
-                            error!("User request guard failed, no matching user found for given cookie");
-                            Outcome::Error((Status::NotFound, ()))
+                            error!(
+                                "User request guard failed, no matching user found for given cookie"
+                            );
+                            Outcome::Error((Status::Unauthorized, ()))
                         }
                     }
                 }
@@ -0,0 +1,340 @@
+//! how the users are notified: every notification goes through [`notify`], which applies the
+//! preferences of the users and collapses the ones that repeat an unread notification. what it
+//! records is handed to the [`Channel`]s with [`dispatch`] once the transaction is committed,
+//! besides the list kept in the database
+
+use crate::{
+    db::DbConnection,
+    models::{Notification, User},
+    schema::{group_members, notifications, users},
+};
+
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, Insertable, OptionalExtension, QueryDsl, QueryResult,
+    RunQueryDsl,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock, Weak};
+
+/// what a notification is about, stored in `notification_type`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum NotificationKind {
+    NewExpense,
+    ExpenseDeleted,
+    ExpenseRestored,
+    ExpenseModified,
+    RemovedFromGroup,
+    GroupDeleted,
+    GroupRestored,
+    AdminPromotion,
+    AdminDemotion,
+    FriendshipRequestAccepted,
+    FriendshipRequestDenied,
+    ExpenseComment,
+    GroupInvite,
+    MemberAdded,
+    MemberJoined,
+    BalanceRequested,
+}
+
+impl NotificationKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NotificationKind::NewExpense => "NEW_EXPENSE",
+            NotificationKind::ExpenseDeleted => "EXPENSE_DELETED",
+            NotificationKind::ExpenseRestored => "EXPENSE_RESTORED",
+            NotificationKind::ExpenseModified => "EXPENSE_MODIFIED",
+            NotificationKind::RemovedFromGroup => "REMOVED_FROM_GROUP",
+            NotificationKind::GroupDeleted => "GROUP_DELETED",
+            NotificationKind::GroupRestored => "GROUP_RESTORED",
+            NotificationKind::AdminPromotion => "ADMIN_PROMOTION",
+            NotificationKind::AdminDemotion => "ADMIN_DEMOTION",
+            NotificationKind::FriendshipRequestAccepted => "FRIENDSHIP_REQUEST_ACCEPTED",
+            NotificationKind::FriendshipRequestDenied => "FRIENDSHIP_REQUEST_DENIED",
+            NotificationKind::ExpenseComment => "EXPENSE_COMMENT",
+            NotificationKind::GroupInvite => "GROUP_INVITE",
+            NotificationKind::MemberAdded => "MEMBER_ADDED",
+            NotificationKind::MemberJoined => "MEMBER_JOINED",
+            NotificationKind::BalanceRequested => "BALANCE_REQUESTED",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        [
+            NotificationKind::NewExpense,
+            NotificationKind::ExpenseDeleted,
+            NotificationKind::ExpenseRestored,
+            NotificationKind::ExpenseModified,
+            NotificationKind::RemovedFromGroup,
+            NotificationKind::GroupDeleted,
+            NotificationKind::GroupRestored,
+            NotificationKind::AdminPromotion,
+            NotificationKind::AdminDemotion,
+            NotificationKind::FriendshipRequestAccepted,
+            NotificationKind::FriendshipRequestDenied,
+            NotificationKind::ExpenseComment,
+            NotificationKind::GroupInvite,
+            NotificationKind::MemberAdded,
+            NotificationKind::MemberJoined,
+            NotificationKind::BalanceRequested,
+        ]
+        .into_iter()
+        .find(|k| k.as_str() == s)
+    }
+
+    /// whether it concerns only the users it is about, e.g. being removed from a group, so that
+    /// the other members of the group are not told even if they want to know everything
+    pub fn is_personal(self) -> bool {
+        matches!(
+            self,
+            NotificationKind::RemovedFromGroup
+                | NotificationKind::AdminPromotion
+                | NotificationKind::AdminDemotion
+                | NotificationKind::GroupInvite
+                | NotificationKind::MemberAdded
+                | NotificationKind::MemberJoined
+                | NotificationKind::BalanceRequested
+        )
+    }
+}
+
+/// what a user wants to be notified of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum NotificationPreference {
+    /// nothing
+    Silenced,
+    /// what involves the user: the expenses the user takes part in, the changes to the user's
+    /// groups and friendships. the default
+    Personal,
+    /// what involves the user and everything that happens in the user's groups
+    All,
+}
+
+impl NotificationPreference {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NotificationPreference::Silenced => "SILENCED",
+            NotificationPreference::Personal => "PERSONAL",
+            NotificationPreference::All => "ALL",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "SILENCED" => Some(NotificationPreference::Silenced),
+            "PERSONAL" => Some(NotificationPreference::Personal),
+            "ALL" => Some(NotificationPreference::All),
+            _ => None,
+        }
+    }
+
+    /// the preference stored for a user, `PERSONAL` if the user never chose one
+    pub fn of(user: &User) -> Self {
+        user.notification_preferences
+            .as_deref()
+            .and_then(NotificationPreference::parse)
+            .unwrap_or(NotificationPreference::Personal)
+    }
+}
+
+/// what happened, the same for everyone that is notified of it
+#[derive(Debug, Clone, Copy)]
+pub struct Notice {
+    pub kind: NotificationKind,
+    /// who did it, never notified of it
+    pub actor: Option<i32>,
+    pub group_id: Option<i32>,
+    pub expense_id: Option<i32>,
+    pub revision_id: Option<i32>,
+    pub reminder_id: Option<i32>,
+}
+
+impl Notice {
+    pub fn new(kind: NotificationKind, actor: i32) -> Self {
+        Notice {
+            actor: Some(actor),
+            ..Notice::automatic(kind)
+        }
+    }
+
+    /// something the server did on its own, e.g. an automatic reminder
+    pub fn automatic(kind: NotificationKind) -> Self {
+        Notice {
+            kind,
+            actor: None,
+            group_id: None,
+            expense_id: None,
+            revision_id: None,
+            reminder_id: None,
+        }
+    }
+
+    pub fn in_group(self, gid: i32) -> Self {
+        Notice {
+            group_id: Some(gid),
+            ..self
+        }
+    }
+
+    pub fn about_expense(self, exid: i32) -> Self {
+        Notice {
+            expense_id: Some(exid),
+            ..self
+        }
+    }
+
+    pub fn at_revision(self, revision_id: i32) -> Self {
+        Notice {
+            revision_id: Some(revision_id),
+            ..self
+        }
+    }
+
+    pub fn about_reminder(self, reminder_id: i32) -> Self {
+        Notice {
+            reminder_id: Some(reminder_id),
+            ..self
+        }
+    }
+}
+
+/// a way the notifications reach the users besides the list kept in the database
+pub trait Channel: Send + Sync {
+    /// called by [`dispatch`] with every notification recorded by [`notify`], including the ones
+    /// that were collapsed into an unread one, after the transaction that records it is committed
+    fn send(&self, notification: &Notification);
+}
+
+static CHANNELS: RwLock<Vec<Weak<dyn Channel>>> = RwLock::new(Vec::new());
+
+/// adds `channel` to the ones every notification is handed to, until it is dropped
+pub fn register_channel(channel: &Arc<dyn Channel>) {
+    let mut channels = CHANNELS.write().unwrap_or_else(|e| e.into_inner());
+    channels.retain(|c| c.strong_count() > 0);
+    channels.push(Arc::downgrade(channel));
+}
+
+/// who is notified of something that involves the users `involved`, according to their
+/// preferences: the involved users that did not silence their notifications and, for something
+/// that happens in group `gid`, the members of the group that want to know everything. ordered by
+/// user id
+fn recipients(
+    conn: &mut DbConnection,
+    involved: &[i32],
+    gid: Option<i32>,
+) -> QueryResult<Vec<i32>> {
+    let mut query =
+        users::table
+            .filter(users::id.eq_any(involved).and(
+                users::notification_preferences.is_null().or(
+                    users::notification_preferences.ne(NotificationPreference::Silenced.as_str()),
+                ),
+            ))
+            .select(users::id)
+            .into_boxed();
+    if let Some(gid) = gid {
+        query = query.or_filter(
+            users::notification_preferences
+                .eq(NotificationPreference::All.as_str())
+                .and(
+                    users::id.eq_any(
+                        group_members::table
+                            .filter(group_members::group_id.eq(gid))
+                            .select(group_members::user_id),
+                    ),
+                ),
+        );
+    }
+
+    query.order(users::id).get_results::<i32>(conn)
+}
+
+/// the unread notification of `user` that `notice` would repeat, if any
+fn unread_twin(conn: &mut DbConnection, user: i32, notice: &Notice) -> QueryResult<Option<i32>> {
+    let mut query = notifications::table
+        .filter(notifications::notified_user_id.eq(user))
+        .filter(notifications::notification_type.eq(notice.kind.as_str()))
+        .filter(notifications::read.eq(false))
+        .select(notifications::id)
+        .into_boxed();
+    query = match notice.actor {
+        Some(a) => query.filter(notifications::user_id.eq(a)),
+        None => query.filter(notifications::user_id.is_null()),
+    };
+    query = match notice.group_id {
+        Some(g) => query.filter(notifications::group_id.eq(g)),
+        None => query.filter(notifications::group_id.is_null()),
+    };
+    query = match notice.expense_id {
+        Some(e) => query.filter(notifications::expense_id.eq(e)),
+        None => query.filter(notifications::expense_id.is_null()),
+    };
+
+    query.first::<i32>(conn).optional()
+}
+
+/// notifies the users `involved` in `notice`, along with the other members of its group that want
+/// to know everything unless the notice is personal, but the actor and the users that silenced
+/// their notifications. a notice that repeats an unread notification, e.g. a second change to the
+/// same expense, brings that one up to date instead of adding another. returns the notifications
+/// recorded, for [`dispatch`]
+pub fn notify(
+    conn: &mut DbConnection,
+    notice: &Notice,
+    involved: &[i32],
+) -> QueryResult<Vec<Notification>> {
+    let mut recorded = Vec::new();
+
+    let gid = notice.group_id.filter(|_| !notice.kind.is_personal());
+    for user in recipients(conn, involved, gid)? {
+        if Some(user) == notice.actor {
+            continue;
+        }
+
+        let notification = match unread_twin(conn, user, notice)? {
+            Some(id) => diesel::update(notifications::table.find(id))
+                .set((
+                    notifications::creation_date.eq(diesel::dsl::now),
+                    notifications::revision_id.eq(notice.revision_id),
+                    notifications::reminder_id.eq(notice.reminder_id),
+                    // what changed since is worth another email
+                    notifications::emailed.eq(false),
+                ))
+                .get_result::<Notification>(conn)?,
+            None => (
+                notifications::notified_user_id.eq(user),
+                notifications::notification_type.eq(notice.kind.as_str()),
+                notifications::group_id.eq(notice.group_id),
+                notifications::user_id.eq(notice.actor),
+                notifications::expense_id.eq(notice.expense_id),
+                notifications::revision_id.eq(notice.revision_id),
+                notifications::reminder_id.eq(notice.reminder_id),
+                notifications::creation_date.eq(diesel::dsl::now),
+            )
+                .insert_into(notifications::table)
+                .get_result::<Notification>(conn)?,
+        };
+        recorded.push(notification);
+    }
+
+    Ok(recorded)
+}
+
+/// hands `notifications` to the channels, to be called with what [`notify`] returned once the
+/// transaction that recorded them is committed, since a rolled back one never existed
+pub fn dispatch(notifications: &[Notification]) {
+    let channels: Vec<Arc<dyn Channel>> = CHANNELS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect();
+    for notification in notifications {
+        for channel in channels.iter() {
+            channel.send(notification);
+        }
+    }
+}
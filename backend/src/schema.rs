@@ -1,5 +1,53 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    attachments (id) {
+        id -> Integer,
+        expense_id -> Integer,
+        uploaded_by -> Nullable<Integer>,
+        file_name -> Text,
+        content_type -> Text,
+        size -> BigInt,
+        content_hash -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    categories (id) {
+        id -> Integer,
+        group_id -> Nullable<Integer>,
+        name -> Text,
+    }
+}
+
+diesel::table! {
+    expense_comments (id) {
+        id -> Integer,
+        expense_id -> Integer,
+        author_id -> Integer,
+        body -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    expense_item_consumers (item_id, user_id) {
+        item_id -> Integer,
+        user_id -> Integer,
+    }
+}
+
+diesel::table! {
+    expense_items (id) {
+        id -> Integer,
+        expense_id -> Integer,
+        desc -> Text,
+        amount -> Double,
+    }
+}
+
 diesel::table! {
     expense_participations (expense_id, user_id) {
         expense_id -> Integer,
@@ -8,14 +56,41 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    expense_revision_participations (revision_id, user_id) {
+        revision_id -> Integer,
+        user_id -> Integer,
+        amount_due -> Nullable<Double>,
+    }
+}
+
+diesel::table! {
+    expense_revisions (id) {
+        id -> Integer,
+        expense_id -> Integer,
+        revision -> Integer,
+        changed_by -> Nullable<Integer>,
+        changed_at -> Timestamp,
+        desc -> Text,
+        total_amount -> Double,
+        paid_by -> Integer,
+        expense_date -> Date,
+        category_id -> Nullable<Integer>,
+    }
+}
+
 diesel::table! {
     expenses (id) {
         id -> Integer,
         desc -> Text,
         total_amount -> Double,
-        creation_date -> Timestamp,
+        created_at -> Timestamp,
         paid_by -> Integer,
         group_id -> Nullable<Integer>,
+        updated_at -> Timestamp,
+        expense_date -> Date,
+        category_id -> Nullable<Integer>,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -43,6 +118,20 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    group_invite_links (id) {
+        id -> Integer,
+        group_id -> Integer,
+        token -> Text,
+        created_by -> Nullable<Integer>,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        max_uses -> Nullable<Integer>,
+        uses -> Integer,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     group_invites (id) {
         id -> Integer,
@@ -68,6 +157,7 @@ diesel::table! {
         group_name -> Text,
         desc -> Nullable<Text>,
         creation_date -> Timestamp,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -81,6 +171,57 @@ diesel::table! {
         expense_id -> Nullable<Integer>,
         creation_date -> Timestamp,
         read -> Bool,
+        revision_id -> Nullable<Integer>,
+        emailed -> Bool,
+        reminder_id -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    payment_reminders (id) {
+        id -> Integer,
+        group_id -> Integer,
+        sender_id -> Nullable<Integer>,
+        recipient_id -> Integer,
+        amount -> Double,
+        message -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    recurring_expense_participations (recurring_expense_id, user_id) {
+        recurring_expense_id -> Integer,
+        user_id -> Integer,
+        amount_due -> Nullable<Double>,
+    }
+}
+
+diesel::table! {
+    recurring_expenses (id) {
+        id -> Integer,
+        group_id -> Integer,
+        created_by -> Integer,
+        desc -> Text,
+        total_amount -> Double,
+        paid_by -> Integer,
+        category_id -> Nullable<Integer>,
+        frequency -> Text,
+        day_of_month -> Nullable<Integer>,
+        interval_days -> Nullable<Integer>,
+        start_date -> Date,
+        end_date -> Nullable<Date>,
+        next_date -> Date,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    reminder_schedules (group_id) {
+        group_id -> Integer,
+        after_days -> Integer,
+        updated_by -> Nullable<Integer>,
+        updated_at -> Timestamp,
     }
 }
 
@@ -101,30 +242,100 @@ diesel::table! {
         last_login -> Nullable<Timestamp>,
         preferred_language -> Text,
         notification_preferences -> Nullable<Text>,
+        email_notifications -> Text,
+        email_unsubscribe_token -> Nullable<Text>,
+        last_digest_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    webhook_deliveries (id) {
+        id -> Integer,
+        webhook_id -> Integer,
+        event_type -> Text,
+        payload -> Text,
+        status -> Text,
+        attempts -> Integer,
+        next_attempt_at -> Nullable<Timestamp>,
+        last_attempt_at -> Nullable<Timestamp>,
+        response_status -> Nullable<Integer>,
+        error -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    webhooks (id) {
+        id -> Integer,
+        group_id -> Integer,
+        url -> Text,
+        secret -> Text,
+        created_by -> Nullable<Integer>,
+        created_at -> Timestamp,
     }
 }
 
+diesel::joinable!(attachments -> expenses (expense_id));
+diesel::joinable!(attachments -> users (uploaded_by));
+diesel::joinable!(categories -> groups (group_id));
+diesel::joinable!(expense_comments -> expenses (expense_id));
+diesel::joinable!(expense_comments -> users (author_id));
+diesel::joinable!(expense_item_consumers -> expense_items (item_id));
+diesel::joinable!(expense_item_consumers -> users (user_id));
+diesel::joinable!(expense_items -> expenses (expense_id));
 diesel::joinable!(expense_participations -> expenses (expense_id));
 diesel::joinable!(expense_participations -> users (user_id));
+diesel::joinable!(expense_revision_participations -> expense_revisions (revision_id));
+diesel::joinable!(expense_revisions -> expenses (expense_id));
+diesel::joinable!(expense_revisions -> users (changed_by));
+diesel::joinable!(expenses -> categories (category_id));
 diesel::joinable!(expenses -> groups (group_id));
 diesel::joinable!(expenses -> users (paid_by));
 diesel::joinable!(group_administrators -> groups (group_id));
 diesel::joinable!(group_administrators -> users (user_id));
+diesel::joinable!(group_invite_links -> groups (group_id));
+diesel::joinable!(group_invite_links -> users (created_by));
 diesel::joinable!(group_invites -> groups (group_id));
 diesel::joinable!(group_members -> groups (group_id));
 diesel::joinable!(group_members -> users (user_id));
 diesel::joinable!(notifications -> expenses (expense_id));
+diesel::joinable!(notifications -> expense_revisions (revision_id));
 diesel::joinable!(notifications -> groups (group_id));
+diesel::joinable!(notifications -> payment_reminders (reminder_id));
+diesel::joinable!(payment_reminders -> groups (group_id));
+diesel::joinable!(recurring_expense_participations -> recurring_expenses (recurring_expense_id));
+diesel::joinable!(recurring_expense_participations -> users (user_id));
+diesel::joinable!(recurring_expenses -> categories (category_id));
+diesel::joinable!(recurring_expenses -> groups (group_id));
+diesel::joinable!(reminder_schedules -> groups (group_id));
+diesel::joinable!(reminder_schedules -> users (updated_by));
+diesel::joinable!(webhook_deliveries -> webhooks (webhook_id));
+diesel::joinable!(webhooks -> groups (group_id));
+diesel::joinable!(webhooks -> users (created_by));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    attachments,
+    categories,
+    expense_comments,
+    expense_item_consumers,
+    expense_items,
     expense_participations,
+    expense_revision_participations,
+    expense_revisions,
     expenses,
     friend_invites,
     friendships,
     group_administrators,
+    group_invite_links,
     group_invites,
     group_members,
     groups,
     notifications,
+    payment_reminders,
+    recurring_expense_participations,
+    recurring_expenses,
+    reminder_schedules,
     users,
+    webhook_deliveries,
+    webhooks,
 );
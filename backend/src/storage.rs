@@ -0,0 +1,145 @@
+//! where the files attached to the expenses are kept. the routes only see the [`Storage`] trait,
+//! managed as `Arc<dyn Storage>`, the files are stored on the local disk by [`LocalStorage`]
+
+use blake2::{Blake2s256, Digest};
+use rocket::data::{ByteUnit, Limits};
+use rocket::fairing::AdHoc;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::config::Config;
+
+/// the content types that can be recognized by [`sniff`]
+pub const SUPPORTED_TYPES: [&str; 5] = [
+    "image/jpeg",
+    "image/png",
+    "image/gif",
+    "image/webp",
+    "application/pdf",
+];
+
+/// a place where files can be kept under a key, keys are made of ascii letters and digits
+pub trait Storage: Send + Sync {
+    /// stores `data` under `key`, replacing what was there
+    fn put(&self, key: &str, data: &[u8]) -> io::Result<()>;
+    fn get(&self, key: &str) -> io::Result<Vec<u8>>;
+    /// removes the file under `key`, removing a missing file is not an error
+    fn delete(&self, key: &str) -> io::Result<()>;
+}
+
+/// keeps the files in a directory, spread over subdirectories named after the first two
+/// characters of their key
+pub struct LocalStorage {
+    dir: PathBuf,
+}
+
+impl LocalStorage {
+    /// storage in `dir`, which is created if it does not exist
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(LocalStorage { dir })
+    }
+
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        if key.len() < 3 || !key.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid storage key `{}`", key),
+            ));
+        }
+        Ok(self.dir.join(&key[..2]).join(key))
+    }
+}
+
+impl Storage for LocalStorage {
+    fn put(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        let path = self.path(key)?;
+        let parent = path.parent().expect("keys are stored in a subdirectory");
+        fs::create_dir_all(parent)?;
+
+        // written aside and then renamed, so that a file is never read while half written
+        let tmp = parent.join(format!(".{}.tmp", Uuid::new_v4()));
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &path).inspect_err(|_| {
+            let _ = fs::remove_file(&tmp);
+        })
+    }
+
+    fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.path(key)?)
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(key)?) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// the hex encoded hash of `data`, the same content always has the same key
+pub fn content_hash(data: &[u8]) -> String {
+    Blake2s256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// the content type of `data` among [`SUPPORTED_TYPES`], told by its first bytes
+pub fn sniff(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if data.starts_with(b"%PDF-") {
+        Some("application/pdf")
+    } else {
+        None
+    }
+}
+
+/// room left in a multipart form for everything but the file
+const FORM_OVERHEAD: ByteUnit = ByteUnit::Kibibyte(64);
+
+/// manages the [`Storage`] described by the configuration and raises rocket's limits on the
+/// uploaded files to `attachments.max_size`, so that larger files are refused as a whole. aborts
+/// the launch if the storage directory cannot be created
+pub fn fairing() -> AdHoc {
+    AdHoc::try_on_ignite("Attachment storage", |rocket| async {
+        let Some(config) = rocket.state::<Config>() else {
+            return Err(rocket);
+        };
+        let max_size = config.attachments.max_size;
+
+        let storage = match LocalStorage::new(&config.attachments.dir) {
+            Ok(s) => s,
+            Err(e) => {
+                error!(
+                    "could not create attachment directory `{}`: {}",
+                    config.attachments.dir, e
+                );
+                return Err(rocket);
+            }
+        };
+
+        let limits = rocket
+            .figment()
+            .extract_inner::<Limits>("limits")
+            .unwrap_or_default()
+            .limit("file", max_size)
+            .limit("data-form", max_size + FORM_OVERHEAD);
+        let figment = rocket.figment().clone().merge(("limits", limits));
+
+        Ok(rocket
+            .configure(figment)
+            .manage::<Arc<dyn Storage>>(Arc::new(storage)))
+    })
+}
@@ -0,0 +1,293 @@
+//! tests for the attachments of the group expenses in `api/attachments.rs`
+
+use rocket::http::{ContentType, Method, Status};
+use rocket::local::blocking::Client;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use super::{
+    TestUser, add_member, attachments_dir, client, expense, figment, group, logged_in, purge_trash,
+    status, trashed_expense, trashed_group, user,
+};
+use crate::models::{Attachment, Expense, Group};
+use crate::storage::content_hash;
+
+const BOUNDARY: &str = "splitsmart-test-boundary";
+
+/// a png image no other test uploads, only its signature is checked
+fn png() -> Vec<u8> {
+    let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+    data.extend_from_slice(Uuid::new_v4().as_bytes());
+    data
+}
+
+fn pdf() -> Vec<u8> {
+    let mut data = b"%PDF-1.7\n".to_vec();
+    data.extend_from_slice(Uuid::new_v4().as_bytes());
+    data
+}
+
+/// where the storage keeps a file with content `data`
+fn stored_file(data: &[u8]) -> PathBuf {
+    let key = content_hash(data);
+    attachments_dir().join(&key[..2]).join(key)
+}
+
+fn uri(group: &Group, expense: &Expense) -> String {
+    format!("/groups/{}/expenses/{}/attachments", group.id, expense.id)
+}
+
+/// a multipart form with `data` as its `file` field
+fn form(file_name: &str, data: &[u8]) -> Vec<u8> {
+    let mut body = format!(
+        "--{BOUNDARY}\r\n\
+         Content-Disposition: form-data; name=\"file\"; filename=\"{file_name}\"\r\n\
+         Content-Type: application/octet-stream\r\n\r\n"
+    )
+    .into_bytes();
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+    body
+}
+
+fn upload_status(client: &Client, uri: String, file_name: &str, data: &[u8]) -> Status {
+    client
+        .post(uri)
+        .header(ContentType::new("multipart", "form-data").with_params(("boundary", BOUNDARY)))
+        .body(form(file_name, data))
+        .dispatch()
+        .status()
+}
+
+fn upload(user: &TestUser, uri: String, file_name: &str, data: &[u8]) -> Attachment {
+    let res = user
+        .client
+        .post(uri)
+        .header(ContentType::new("multipart", "form-data").with_params(("boundary", BOUNDARY)))
+        .body(form(file_name, data))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json().expect("attachment in response")
+}
+
+#[test]
+fn attachments_are_for_members_only() {
+    let admin = user();
+    let member = user();
+    let outsider = user();
+    let g = group(&admin);
+    add_member(&admin, &g, &member);
+    let e = expense(&admin, &g, 30.0, &[&admin, &member]);
+    let uri = uri(&g, &e);
+    let data = png();
+
+    let anonymous = client();
+    for (c, expected) in [
+        (&anonymous, Status::Unauthorized),
+        (&outsider.client, Status::Forbidden),
+    ] {
+        assert_eq!(
+            upload_status(c, uri.clone(), "receipt.png", &data),
+            expected
+        );
+    }
+
+    let a = upload(&member, uri.clone(), "receipt.png", &data);
+    assert_eq!(a.expense_id, e.id);
+    assert_eq!(a.uploaded_by, Some(member.id));
+    assert_eq!(a.file_name, "receipt.png");
+    assert_eq!(a.content_type, "image/png");
+    assert_eq!(a.size, data.len() as i64);
+
+    let file_uri = format!("{}/{}", uri, a.id);
+    for (c, expected) in [
+        (&anonymous, Status::Unauthorized),
+        (&outsider.client, Status::Forbidden),
+    ] {
+        assert_eq!(status(c, Method::Get, uri.clone(), None), expected);
+        assert_eq!(status(c, Method::Get, file_uri.clone(), None), expected);
+    }
+
+    let res = admin.client.get(uri.clone()).dispatch();
+    let listed: Vec<Attachment> = res.into_json().unwrap();
+    assert_eq!(listed.iter().map(|a| a.id).collect::<Vec<_>>(), [a.id]);
+
+    let res = admin.client.get(file_uri.clone()).dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    assert_eq!(res.content_type(), Some(ContentType::PNG));
+    assert_eq!(
+        res.headers().get_one("Content-Disposition"),
+        Some("inline; filename=\"receipt.png\"")
+    );
+    assert_eq!(res.into_bytes().unwrap(), data);
+
+    // the attachment belongs to its own expense only
+    let other = expense(&admin, &g, 10.0, &[&admin]);
+    assert_eq!(
+        status(
+            &admin.client,
+            Method::Get,
+            format!(
+                "/groups/{}/expenses/{}/attachments/{}",
+                g.id, other.id, a.id
+            ),
+            None
+        ),
+        Status::NotFound
+    );
+}
+
+#[test]
+fn only_allowed_files_are_accepted() {
+    let admin = user();
+    let g = group(&admin);
+    let e = expense(&admin, &g, 30.0, &[&admin]);
+
+    assert_eq!(
+        upload_status(&admin.client, uri(&g, &e), "notes.txt", b"not a receipt"),
+        Status::UnsupportedMediaType
+    );
+    // the type is told by the content, not by the name
+    assert_eq!(
+        upload_status(&admin.client, uri(&g, &e), "receipt.png", b"GIF89a"),
+        Status::Ok
+    );
+
+    let small = logged_in(figment().merge(("attachments.max_size", 64)), &admin);
+    let mut big = png();
+    big.resize(65, 0);
+    assert_eq!(
+        upload_status(&small, uri(&g, &e), "big.png", &big),
+        Status::PayloadTooLarge
+    );
+    big.truncate(64);
+    assert_eq!(
+        upload_status(&small, uri(&g, &e), "big.png", &big),
+        Status::Ok
+    );
+
+    let pdf_only = logged_in(
+        figment().merge(("attachments.allowed_types", ["application/pdf"])),
+        &admin,
+    );
+    assert_eq!(
+        upload_status(&pdf_only, uri(&g, &e), "receipt.png", &png()),
+        Status::UnsupportedMediaType
+    );
+    assert_eq!(
+        upload_status(&pdf_only, uri(&g, &e), "receipt.pdf", &pdf()),
+        Status::Ok
+    );
+}
+
+#[test]
+fn same_content_is_stored_once() {
+    let admin = user();
+    let g = group(&admin);
+    let first = expense(&admin, &g, 30.0, &[&admin]);
+    let second = expense(&admin, &g, 20.0, &[&admin]);
+    let data = pdf();
+
+    let a = upload(&admin, uri(&g, &first), "receipt", &data);
+    assert_eq!(a.file_name, "receipt.pdf");
+    assert!(stored_file(&data).is_file());
+
+    // uploading it again to the same expense returns the same attachment
+    let again = upload(&admin, uri(&g, &first), "copy.pdf", &data);
+    assert_eq!(again.id, a.id);
+
+    let b = upload(&admin, uri(&g, &second), "receipt.pdf", &data);
+    assert_ne!(b.id, a.id);
+
+    // the file is removed with the last attachment referring to it, once the expenses are purged
+    let res = admin
+        .client
+        .delete(format!("/groups/{}/expenses/{}", g.id, first.id))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    assert!(stored_file(&data).is_file());
+
+    assert_eq!(
+        status(
+            &admin.client,
+            Method::Get,
+            format!("{}/{}", uri(&g, &second), b.id),
+            None
+        ),
+        Status::Ok
+    );
+
+    let res = admin
+        .client
+        .delete(format!("/groups/{}/expenses/{}", g.id, second.id))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    // it can still be restored
+    assert!(stored_file(&data).is_file());
+
+    trashed_expense(first.id, 31);
+    purge_trash();
+    assert!(stored_file(&data).is_file());
+    trashed_expense(second.id, 31);
+    purge_trash();
+    assert!(!stored_file(&data).exists());
+}
+
+#[test]
+fn deleting_attachments() {
+    let admin = user();
+    let payer = user();
+    let uploader = user();
+    let other = user();
+    let g = group(&admin);
+    for u in [&payer, &uploader, &other] {
+        add_member(&admin, &g, u);
+    }
+    let e = expense(&payer, &g, 30.0, &[&payer, &uploader, &other]);
+
+    // the uploader, the payer and the admins can delete it, anybody else cannot
+    for deleter in [&uploader, &payer, &admin] {
+        let data = png();
+        let a = upload(&uploader, uri(&g, &e), "receipt.png", &data);
+        let a_uri = format!("{}/{}", uri(&g, &e), a.id);
+
+        assert_eq!(
+            status(&other.client, Method::Delete, a_uri.clone(), None),
+            Status::Forbidden
+        );
+        assert_eq!(
+            status(&deleter.client, Method::Delete, a_uri.clone(), None),
+            Status::Ok
+        );
+        assert_eq!(
+            status(&deleter.client, Method::Get, a_uri, None),
+            Status::NotFound
+        );
+        assert!(!stored_file(&data).exists());
+    }
+}
+
+#[test]
+fn purging_a_group_removes_its_files() {
+    let admin = user();
+    let g = group(&admin);
+    let e = expense(&admin, &g, 30.0, &[&admin]);
+    let data = png();
+    upload(&admin, uri(&g, &e), "receipt.png", &data);
+    assert!(stored_file(&data).is_file());
+
+    assert_eq!(
+        status(
+            &admin.client,
+            Method::Delete,
+            format!("/groups/{}", g.id),
+            None
+        ),
+        Status::Ok
+    );
+    assert!(stored_file(&data).is_file());
+
+    trashed_group(g.id, 31);
+    purge_trash();
+    assert!(!stored_file(&data).exists());
+}
@@ -0,0 +1,140 @@
+//! guards against performance regressions of the list endpoints: the number of queries run to
+//! list a page must not depend on how many rows there are. the timing benchmark is ignored by
+//! default, run it with `cargo test --release benchmarks -- --ignored --nocapture`
+
+use diesel::connection::{Instrumentation, InstrumentationEvent, set_default_instrumentation};
+use diesel::prelude::*;
+use rocket::http::Status;
+use std::cell::Cell;
+use std::sync::Once;
+use std::time::Instant;
+
+use super::{TestUser, group, user};
+use crate::api::{expenses::ExpenseWithParticipations, pagination::Page};
+use crate::establish_connection;
+use crate::models::Group;
+use crate::schema::{expense_participations, expenses};
+
+thread_local! {
+    static QUERIES: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count_queries() -> Option<Box<dyn Instrumentation>> {
+    Some(Box::new(|event: InstrumentationEvent<'_>| {
+        if let InstrumentationEvent::StartQuery { .. } = event {
+            QUERIES.with(|q| q.set(q.get() + 1));
+        }
+    }))
+}
+
+/// number of queries run on the current thread by `f`, the local client handles the requests on
+/// the thread that dispatches them
+fn queries_made_by(f: impl FnOnce()) -> usize {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| set_default_instrumentation(count_queries).expect("instrumentation set"));
+
+    let before = QUERIES.with(Cell::get);
+    f();
+    QUERIES.with(Cell::get) - before
+}
+
+/// inserts `n` expenses paid by `payer` in `g`, each split with the `participants`, straight into
+/// the database since going through the api would take far too long
+fn seed_expenses(g: &Group, payer: &TestUser, participants: &[&TestUser], n: usize) {
+    let mut conn = establish_connection();
+
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let share = 10.0 / participants.len() as f64;
+        let mut participations = Vec::new();
+        for i in 0..n {
+            // sqlite cannot return the ids of a batch insert
+            let id = (
+                expenses::desc.eq(format!("seeded expense {}", i)),
+                expenses::total_amount.eq(10.0),
+                expenses::paid_by.eq(payer.id),
+                expenses::group_id.eq(Some(g.id)),
+                expenses::expense_date.eq(chrono::Utc::now().date_naive()),
+                expenses::created_at.eq(diesel::dsl::now),
+                expenses::updated_at.eq(diesel::dsl::now),
+            )
+                .insert_into(expenses::table)
+                .returning(expenses::id)
+                .get_result::<i32>(conn)?;
+            participations.extend(participants.iter().map(|u| {
+                (
+                    expense_participations::expense_id.eq(id),
+                    expense_participations::user_id.eq(u.id),
+                    expense_participations::amount_due.eq(share),
+                )
+            }));
+        }
+        for chunk in participations.chunks(500) {
+            diesel::insert_into(expense_participations::table)
+                .values(chunk)
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+    .expect("expenses seeded");
+}
+
+fn list_expenses(u: &TestUser, g: &Group) -> Page<ExpenseWithParticipations> {
+    let res = u
+        .client
+        .get(format!("/groups/{}/expenses?limit=100", g.id))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json().unwrap()
+}
+
+#[test]
+fn expense_listing_runs_a_constant_number_of_queries() {
+    let alice = user();
+    let bob = user();
+
+    let small = group(&alice);
+    seed_expenses(&small, &alice, &[&alice, &bob], 1);
+    let large = group(&alice);
+    seed_expenses(&large, &alice, &[&alice, &bob], 150);
+
+    let mut page = None;
+    let few = queries_made_by(|| page = Some(list_expenses(&alice, &small)));
+    assert_eq!(page.take().unwrap().items.len(), 1);
+    let many = queries_made_by(|| page = Some(list_expenses(&alice, &large)));
+    let page = page.unwrap();
+    assert_eq!(page.items.len(), 100);
+    assert!(page.items.iter().all(|(_, p)| p.len() == 2));
+
+    assert!(few > 0, "queries are not being counted");
+    assert_eq!(few, many);
+}
+
+#[test]
+#[ignore]
+fn bench_expense_listing() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    seed_expenses(&g, &alice, &[&alice, &bob], 5000);
+
+    let start = Instant::now();
+    let mut pages = 0;
+    let mut next = Some(format!("/groups/{}/expenses?limit=100", g.id));
+    while let Some(uri) = next {
+        let res = alice.client.get(uri).dispatch();
+        let page = res.into_json::<Page<ExpenseWithParticipations>>().unwrap();
+        pages += 1;
+        next = page
+            .next_cursor
+            .map(|c| format!("/groups/{}/expenses?limit=100&cursor={}", g.id, c));
+    }
+    let elapsed = start.elapsed();
+
+    assert_eq!(pages, 50);
+    println!(
+        "listed 5000 expenses in {} pages: {:?} ({:?} per page)",
+        pages,
+        elapsed,
+        elapsed / pages
+    );
+}
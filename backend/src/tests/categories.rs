@@ -0,0 +1,275 @@
+//! tests for the expense categories and the category report in `api/groups.rs`
+
+use rocket::http::{Method, Status};
+use serde_json::{Value, json};
+
+use super::{TestUser, add_member, client, group, status, user};
+use crate::api::groups::CategoryReport;
+use crate::models::{Category, Expense, Group};
+
+/// the built-in category called `name`
+fn builtin(user: &TestUser, group: &Group, name: &str) -> Category {
+    categories(user, group)
+        .into_iter()
+        .find(|c| c.group_id.is_none() && c.name == name)
+        .expect("built-in category")
+}
+
+fn categories(user: &TestUser, group: &Group) -> Vec<Category> {
+    let res = user
+        .client
+        .get(format!("/groups/{}/categories", group.id))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json().expect("categories in response")
+}
+
+fn create_category(admin: &TestUser, group: &Group, name: &str) -> Status {
+    status(
+        &admin.client,
+        Method::Post,
+        format!("/groups/{}/categories", group.id),
+        Some(json!({ "name": name })),
+    )
+}
+
+/// the body of an expense paid by `payer` and split evenly between `participants`
+fn expense_body(
+    payer: &TestUser,
+    total: f64,
+    category: Option<i32>,
+    date: &str,
+    participants: &[&TestUser],
+) -> Value {
+    let share = total / participants.len() as f64;
+    let division: Vec<(i32, f64)> = participants.iter().map(|u| (u.id, share)).collect();
+    json!({
+        "desc": "shopping",
+        "total_amount": total,
+        "paid_by": payer.id,
+        "division": division,
+        "expense_date": date,
+        "category_id": category,
+    })
+}
+
+fn add_expense(payer: &TestUser, group: &Group, body: Value) -> Expense {
+    let res = payer
+        .client
+        .post(format!("/groups/{}/expenses", group.id))
+        .json(&body)
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json().expect("expense in response")
+}
+
+fn report(user: &TestUser, group: &Group, period: &str) -> CategoryReport {
+    let res = user
+        .client
+        .get(format!("/groups/{}/reports/categories{}", group.id, period))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json().expect("report in response")
+}
+
+#[test]
+fn categories_authorization() {
+    let admin = user();
+    let member = user();
+    let outsider = user();
+    let g = group(&admin);
+    add_member(&admin, &g, &member);
+    let uri = format!("/groups/{}/categories", g.id);
+
+    for uri in [uri.clone(), format!("/groups/{}/reports/categories", g.id)] {
+        assert_eq!(
+            status(&client(), Method::Get, uri.clone(), None),
+            Status::Unauthorized
+        );
+        assert_eq!(
+            status(&outsider.client, Method::Get, uri.clone(), None),
+            Status::Forbidden
+        );
+        assert_eq!(status(&member.client, Method::Get, uri, None), Status::Ok);
+    }
+
+    assert_eq!(create_category(&member, &g, "cleaning"), Status::Forbidden);
+    assert_eq!(create_category(&admin, &g, "cleaning"), Status::Ok);
+    let custom = categories(&member, &g).pop().unwrap();
+    assert_eq!(
+        status(
+            &member.client,
+            Method::Delete,
+            format!("{}/{}", uri, custom.id),
+            None
+        ),
+        Status::Forbidden
+    );
+}
+
+#[test]
+fn groups_define_their_own_categories() {
+    let admin = user();
+    let g = group(&admin);
+    let other = group(&admin);
+
+    let food = builtin(&admin, &g, "food");
+    assert_eq!(create_category(&admin, &g, "  Cleaning "), Status::Ok);
+    assert_eq!(create_category(&admin, &g, "cleaning"), Status::Conflict);
+    assert_eq!(create_category(&admin, &g, "FOOD"), Status::Conflict);
+    assert_eq!(create_category(&admin, &g, " "), Status::BadRequest);
+
+    let cleaning = categories(&admin, &g).pop().unwrap();
+    assert_eq!(cleaning.name, "Cleaning");
+    assert_eq!(cleaning.group_id, Some(g.id));
+    assert!(
+        categories(&admin, &other)
+            .iter()
+            .all(|c| c.id != cleaning.id)
+    );
+
+    // the category of a group cannot be used by the expenses of another group, or private ones
+    let body = expense_body(&admin, 10.0, Some(cleaning.id), "2025-05-01", &[&admin]);
+    let uri = format!("/groups/{}/expenses", other.id);
+    assert_eq!(
+        status(&admin.client, Method::Post, uri, Some(body.clone())),
+        Status::BadRequest
+    );
+    assert_eq!(
+        status(&admin.client, Method::Post, "/expenses".into(), Some(body)),
+        Status::BadRequest
+    );
+    let body = expense_body(&admin, 10.0, Some(food.id), "2025-05-01", &[&admin]);
+    assert_eq!(
+        status(&admin.client, Method::Post, "/expenses".into(), Some(body)),
+        Status::Ok
+    );
+
+    let e = add_expense(
+        &admin,
+        &g,
+        expense_body(&admin, 10.0, Some(cleaning.id), "2025-05-01", &[&admin]),
+    );
+    assert_eq!(e.category_id, Some(cleaning.id));
+
+    let res = admin
+        .client
+        .get(format!(
+            "/groups/{}/expenses?category={}",
+            g.id, cleaning.id
+        ))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let page = res.into_json::<Value>().unwrap();
+    assert_eq!(page["items"].as_array().unwrap().len(), 1);
+
+    // built-in categories cannot be deleted, deleting a custom one uncategorizes its expenses
+    let uri = format!("/groups/{}/categories", g.id);
+    assert_eq!(
+        status(
+            &admin.client,
+            Method::Delete,
+            format!("{}/{}", uri, food.id),
+            None
+        ),
+        Status::NotFound
+    );
+    assert_eq!(
+        status(
+            &admin.client,
+            Method::Delete,
+            format!("{}/{}", uri, cleaning.id),
+            None
+        ),
+        Status::Ok
+    );
+    let report = report(&admin, &g, "");
+    assert_eq!(report.categories.len(), 1);
+    assert_eq!(report.categories[0].category_id, None);
+}
+
+#[test]
+fn report_splits_spending_by_category_and_member() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+
+    let food = builtin(&alice, &g, "food").id;
+    let rent = builtin(&alice, &g, "rent").id;
+    add_expense(
+        &alice,
+        &g,
+        expense_body(&alice, 30.0, Some(food), "2025-01-10", &[&alice, &bob]),
+    );
+    add_expense(
+        &alice,
+        &g,
+        expense_body(&bob, 10.0, Some(food), "2025-02-10", &[&alice, &bob]),
+    );
+    add_expense(
+        &alice,
+        &g,
+        expense_body(&bob, 800.0, Some(rent), "2025-02-01", &[&alice, &bob]),
+    );
+    add_expense(
+        &alice,
+        &g,
+        expense_body(&alice, 5.0, None, "2025-03-01", &[&bob]),
+    );
+
+    let r = report(&bob, &g, "");
+    assert_eq!(r.total, 845.0);
+    let ids: Vec<Option<i32>> = r.categories.iter().map(|c| c.category_id).collect();
+    assert_eq!(ids, vec![Some(rent), Some(food), None]);
+
+    let food_spending = &r.categories[1];
+    assert_eq!(food_spending.name.as_deref(), Some("food"));
+    assert_eq!(food_spending.total, 40.0);
+    assert_eq!(food_spending.expenses, 2);
+    let a = food_spending
+        .members
+        .iter()
+        .find(|m| m.user_id == alice.id)
+        .unwrap();
+    let b = food_spending
+        .members
+        .iter()
+        .find(|m| m.user_id == bob.id)
+        .unwrap();
+    assert_eq!((a.paid, a.share), (30.0, 20.0));
+    assert_eq!((b.paid, b.share), (10.0, 20.0));
+
+    let uncategorized = &r.categories[2];
+    assert_eq!(uncategorized.name, None);
+    let a = uncategorized
+        .members
+        .iter()
+        .find(|m| m.user_id == alice.id)
+        .unwrap();
+    assert_eq!((a.paid, a.share), (5.0, 0.0));
+
+    let r = report(&bob, &g, "?from=2025-02-01&to=2025-02-28");
+    assert_eq!(r.total, 810.0);
+    assert_eq!(r.from.unwrap().to_string(), "2025-02-01");
+    let food_spending = r
+        .categories
+        .iter()
+        .find(|c| c.category_id == Some(food))
+        .unwrap();
+    assert_eq!(food_spending.expenses, 1);
+
+    assert_eq!(report(&bob, &g, "?from=2026-01-01").categories.len(), 0);
+    assert_eq!(
+        status(
+            &bob.client,
+            Method::Get,
+            format!(
+                "/groups/{}/reports/categories?from=2025-03-01&to=2025-02-01",
+                g.id
+            ),
+            None
+        ),
+        Status::BadRequest
+    );
+}
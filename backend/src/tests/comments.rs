@@ -0,0 +1,151 @@
+//! tests for the routes in `api/comments.rs`
+
+use rocket::http::{Method, Status};
+use serde_json::json;
+
+use super::{TestUser, add_member, client, expense, group, notifications, status, user};
+use crate::api::{comments::MAX_COMMENT_LENGTH, pagination::Page};
+use crate::models::{Expense, ExpenseComment, Group};
+
+fn comments_uri(group: &Group, expense: &Expense) -> String {
+    format!("/groups/{}/expenses/{}/comments", group.id, expense.id)
+}
+
+fn comment(user: &TestUser, group: &Group, expense: &Expense, body: &str) -> ExpenseComment {
+    let res = user
+        .client
+        .post(comments_uri(group, expense))
+        .json(&json!({ "body": body }))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json().expect("comment in response")
+}
+
+/// the notifications about comments received by `user`
+fn comment_notifications(user: &TestUser, expense: &Expense) -> usize {
+    notifications(user)
+        .iter()
+        .filter(|n| {
+            n.notification_type.as_deref() == Some("EXPENSE_COMMENT")
+                && n.expense_id == Some(expense.id)
+        })
+        .count()
+}
+
+#[test]
+fn comments_authorization() {
+    let admin = user();
+    let outsider = user();
+    let g = group(&admin);
+    let other = group(&admin);
+    let e = expense(&admin, &g, 10.0, &[&admin]);
+    let uri = comments_uri(&g, &e);
+    let body = Some(json!({ "body": "ok" }));
+
+    for (c, expected) in [
+        (&client(), Status::Unauthorized),
+        (&outsider.client, Status::Forbidden),
+    ] {
+        assert_eq!(status(c, Method::Get, uri.clone(), None), expected);
+        assert_eq!(status(c, Method::Post, uri.clone(), body.clone()), expected);
+    }
+
+    // the expense must belong to the group in the path
+    let uri = comments_uri(&other, &e);
+    assert_eq!(
+        status(&admin.client, Method::Get, uri.clone(), None),
+        Status::NotFound
+    );
+    assert_eq!(
+        status(&admin.client, Method::Post, uri, body),
+        Status::NotFound
+    );
+}
+
+#[test]
+fn participants_are_notified_of_comments() {
+    let alice = user();
+    let bob = user();
+    let carol = user();
+    let dave = user();
+    let g = group(&alice);
+    for u in [&bob, &carol, &dave] {
+        add_member(&alice, &g, u);
+    }
+    // paid by alice, who does not take part in it
+    let e = expense(&alice, &g, 20.0, &[&bob, &carol]);
+
+    let first = comment(&bob, &g, &e, "  who had the extra beer?  ");
+    assert_eq!(first.body, "who had the extra beer?");
+    assert_eq!(first.author_id, bob.id);
+    assert_eq!(comment_notifications(&alice, &e), 1);
+    assert_eq!(comment_notifications(&carol, &e), 1);
+    assert_eq!(comment_notifications(&bob, &e), 0);
+    assert_eq!(comment_notifications(&dave, &e), 0);
+
+    // members that do not take part in the expense can comment too
+    let second = comment(&dave, &g, &e, "not me");
+    assert_eq!(comment_notifications(&bob, &e), 1);
+
+    let uri = comments_uri(&g, &e);
+    let res = carol.client.get(format!("{}?limit=1", uri)).dispatch();
+    let page = res.into_json::<Page<ExpenseComment>>().unwrap();
+    assert_eq!(page.items[0].id, first.id);
+    let cursor = page.next_cursor.expect("a second page");
+    let res = carol
+        .client
+        .get(format!("{}?limit=1&cursor={}", uri, cursor))
+        .dispatch();
+    let page = res.into_json::<Page<ExpenseComment>>().unwrap();
+    assert_eq!(page.items[0].id, second.id);
+    assert!(page.next_cursor.is_none());
+
+    for body in [" ".to_owned(), "x".repeat(MAX_COMMENT_LENGTH + 1)] {
+        assert_eq!(
+            status(
+                &bob.client,
+                Method::Post,
+                uri.clone(),
+                Some(json!({ "body": body }))
+            ),
+            Status::BadRequest
+        );
+    }
+}
+
+#[test]
+fn only_the_author_edits_and_deletes_a_comment() {
+    let admin = user();
+    let author = user();
+    let g = group(&admin);
+    add_member(&admin, &g, &author);
+    let e = expense(&admin, &g, 20.0, &[&admin, &author]);
+    let c = comment(&author, &g, &e, "I paid the tip");
+    let uri = format!("{}/{}", comments_uri(&g, &e), c.id);
+    let edit = Some(json!({ "body": "I paid the tip, 2 euros" }));
+
+    assert_eq!(
+        status(&admin.client, Method::Put, uri.clone(), edit.clone()),
+        Status::Forbidden
+    );
+    assert_eq!(
+        status(&admin.client, Method::Delete, uri.clone(), None),
+        Status::Forbidden
+    );
+
+    let res = author.client.put(uri.clone()).json(&edit).dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let edited = res.into_json::<ExpenseComment>().unwrap();
+    assert_eq!(edited.body, "I paid the tip, 2 euros");
+    assert_eq!(edited.created_at, c.created_at);
+    assert!(edited.updated_at >= c.updated_at);
+
+    assert_eq!(
+        status(&author.client, Method::Delete, uri.clone(), None),
+        Status::Ok
+    );
+    assert_eq!(
+        status(&author.client, Method::Delete, uri, None),
+        Status::NotFound
+    );
+}
@@ -0,0 +1,99 @@
+//! tests for the configuration in `config.rs`
+
+use chrono::{Duration, Utc};
+use rocket::http::{Method, SameSite, Status};
+use serde_json::json;
+
+use super::{PASSWORD, client_with, figment, status, user};
+use crate::SessionStore;
+
+#[test]
+fn invalid_configuration_aborts_launch() {
+    for (key, value) in [
+        ("cors.allowed_origins", json!(["not an origin"])),
+        ("cors.allowed_origins", json!(["ftp://example.com"])),
+        ("cors.allowed_methods", json!(["FETCH"])),
+        ("session.ttl_days", json!(0)),
+        ("session.same_site", json!("none")),
+        ("session.same_site", json!("sometimes")),
+        (
+            "mail",
+            json!({ "transport": "file", "dir": "", "from": "a@example.com" }),
+        ),
+        ("mail", json!({ "transport": "pigeon" })),
+        (
+            "mail",
+            json!({
+                "transport": "smtp",
+                "host": "localhost",
+                "port": 25,
+                "tls": "none",
+                "username": "user",
+                "password": "secret",
+                "from": "a@example.com",
+            }),
+        ),
+        ("notification_emails.app_url", json!("localhost")),
+        ("notification_emails.interval_secs", json!(0)),
+        ("notification_emails.digest_hour", json!(24)),
+        ("webhooks.timeout_secs", json!(0)),
+        ("webhooks.max_attempts", json!(0)),
+        ("reminders.interval_secs", json!(0)),
+        ("reminders.min_interval_hours", json!(-1)),
+        ("trash.retention_days", json!(0)),
+        ("trash.purge_interval_secs", json!(0)),
+        ("attachments.max_size", json!(0)),
+        ("attachments.allowed_types", json!(["text/plain"])),
+    ] {
+        let result = rocket::local::blocking::Client::tracked(crate::app(
+            figment().merge((key, value.clone())),
+        ));
+        // an unhandled launch error panics when dropped, formatting it marks it as handled
+        match result {
+            Ok(_) => panic!("{} = {} was accepted", key, value),
+            Err(e) => assert!(e.to_string().contains("fairing")),
+        }
+    }
+}
+
+#[test]
+fn session_cookie_follows_configuration() {
+    let u = user();
+    let c = client_with(
+        figment()
+            .merge(("session.ttl_days", 2))
+            .merge(("session.secure_cookie", true))
+            .merge(("session.same_site", "lax")),
+    );
+
+    let res = c
+        .post("/user/login")
+        .json(&json!({ "email": u.email, "password": PASSWORD }))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let cookie = res.cookies().get("session_id").unwrap();
+    assert_eq!(cookie.secure(), Some(true));
+    assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+    assert_eq!(cookie.max_age(), Some(rocket::time::Duration::days(2)));
+}
+
+#[test]
+fn expired_session_is_rejected() {
+    let u = user();
+    let uri = format!("/user/{}", u.id);
+    assert_eq!(
+        status(&u.client, Method::Get, uri.clone(), None),
+        Status::Ok
+    );
+
+    let store = u.client.rocket().state::<SessionStore>().unwrap();
+    for session in store.sessions.lock().unwrap().values_mut() {
+        session.expires = Utc::now().naive_utc() - Duration::minutes(1);
+    }
+
+    assert_eq!(
+        status(&u.client, Method::Get, uri, None),
+        Status::Unauthorized
+    );
+    assert!(store.sessions.lock().unwrap().is_empty());
+}
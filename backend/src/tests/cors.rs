@@ -0,0 +1,98 @@
+//! tests for the CORS policy configured in `config.rs`
+
+use rocket::http::{Header, Method, Status};
+use rocket::local::blocking::{Client, LocalResponse};
+
+use super::{client, client_with, figment, user};
+
+const ALLOWED: &str = "http://localhost:3000";
+
+fn preflight<'c>(c: &'c Client, origin: &str, method: &str) -> LocalResponse<'c> {
+    c.req(Method::Options, "/user/invites")
+        .header(Header::new("Origin", origin.to_owned()))
+        .header(Header::new(
+            "Access-Control-Request-Method",
+            method.to_owned(),
+        ))
+        .header(Header::new(
+            "Access-Control-Request-Headers",
+            "Content-Type",
+        ))
+        .dispatch()
+}
+
+#[test]
+fn allowed_origin_can_send_credentials() {
+    let u = user();
+
+    let res = u
+        .client
+        .get("/user/invites")
+        .header(Header::new("Origin", ALLOWED))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let headers = res.headers();
+    assert_eq!(
+        headers.get_one("Access-Control-Allow-Origin"),
+        Some(ALLOWED)
+    );
+    assert_eq!(
+        headers.get_one("Access-Control-Allow-Credentials"),
+        Some("true")
+    );
+}
+
+#[test]
+fn disallowed_origin_is_rejected() {
+    let u = user();
+
+    let res = u
+        .client
+        .get("/user/invites")
+        .header(Header::new("Origin", "https://evil.example.com"))
+        .dispatch();
+    assert_eq!(res.status(), Status::Forbidden);
+    assert!(
+        res.headers()
+            .get_one("Access-Control-Allow-Origin")
+            .is_none()
+    );
+
+    let c = client();
+    let res = preflight(&c, "https://evil.example.com", "GET");
+    assert_eq!(res.status(), Status::Forbidden);
+    assert!(
+        res.headers()
+            .get_one("Access-Control-Allow-Origin")
+            .is_none()
+    );
+}
+
+#[test]
+fn preflight_is_cached_and_limited_to_allowed_methods() {
+    let c = client();
+
+    let res = preflight(&c, ALLOWED, "PUT");
+    assert!(res.status().class().is_success());
+    let headers = res.headers();
+    assert_eq!(
+        headers.get_one("Access-Control-Allow-Origin"),
+        Some(ALLOWED)
+    );
+    assert_eq!(headers.get_one("Access-Control-Max-Age"), Some("3600"));
+    assert_eq!(
+        headers.get_one("Access-Control-Allow-Credentials"),
+        Some("true")
+    );
+
+    assert_eq!(preflight(&c, ALLOWED, "PATCH").status(), Status::Forbidden);
+}
+
+#[test]
+fn allowed_origins_come_from_configuration() {
+    let origin = "https://splitsmart.example.com";
+    let c = client_with(figment().merge(("cors.allowed_origins", [origin])));
+
+    assert!(preflight(&c, origin, "GET").status().class().is_success());
+    assert_eq!(preflight(&c, ALLOWED, "GET").status(), Status::Forbidden);
+}
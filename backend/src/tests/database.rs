@@ -0,0 +1,220 @@
+//! checks that do not depend on a specific route, but on the database backend behaving the same
+//! way on sqlite and postgres
+
+use diesel::prelude::*;
+use diesel_migrations::MigrationHarness;
+use rocket::http::Status;
+
+use super::{add_member, client, expense, group, user};
+use crate::{
+    api::{expenses::ExpenseWithParticipations, pagination::Page},
+    db::MIGRATIONS,
+    establish_connection,
+    schema::{expense_participations, expenses, groups},
+};
+
+#[test]
+fn migrations_are_all_applied() {
+    let _client = client();
+    let mut conn = establish_connection();
+
+    assert!(
+        !conn
+            .has_pending_migration(MIGRATIONS)
+            .expect("can read applied migrations")
+    );
+}
+
+#[test]
+fn expense_roundtrip() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+
+    let e = expense(&alice, &g, 30.0, &[&alice, &bob]);
+    assert_eq!(e.total_amount, 30.0);
+    assert_eq!(e.group_id, Some(g.id));
+
+    let res = bob
+        .client
+        .get(format!("/groups/{}/expenses", g.id))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let list = res
+        .into_json::<Page<ExpenseWithParticipations>>()
+        .unwrap()
+        .items;
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].0.id, e.id);
+    assert_eq!(list[0].1.len(), 2);
+    assert!(list[0].1.iter().all(|p| p.amount_due == Some(15.0)));
+}
+
+#[test]
+fn deleting_group_cascades_to_expenses() {
+    let alice = user();
+    let g = group(&alice);
+    let e = expense(&alice, &g, 10.0, &[&alice]);
+
+    // the route only moves the group to the trash, the purge deletes it for good
+    let mut conn = establish_connection();
+    diesel::delete(groups::table.filter(groups::id.eq(g.id)))
+        .execute(&mut conn)
+        .unwrap();
+
+    let remaining = expenses::table
+        .filter(expenses::id.eq(e.id))
+        .count()
+        .get_result::<i64>(&mut conn)
+        .unwrap();
+    assert_eq!(remaining, 0);
+    let participations = expense_participations::table
+        .filter(expense_participations::expense_id.eq(e.id))
+        .count()
+        .get_result::<i64>(&mut conn)
+        .unwrap();
+    assert_eq!(participations, 0);
+}
+
+/// migrates a fresh sqlite database up to the migration `name` excluded, runs `seed` to fill the
+/// old schema and then applies the remaining migrations, to check that they carry data over
+#[cfg(feature = "sqlite")]
+fn migrate_over(name: &str, seed: &str) -> crate::db::DbConnection {
+    use diesel::connection::SimpleConnection;
+    use diesel::migration::MigrationSource;
+
+    let path = std::env::temp_dir().join(format!(
+        "splitsmart-migration-{}.sqlite",
+        super::unique("db")
+    ));
+    let mut conn = crate::db::DbConnection::establish(&path.display().to_string())
+        .expect("can create a sqlite database");
+
+    let migrations = MigrationSource::<crate::db::DbBackend>::migrations(&MIGRATIONS)
+        .expect("migrations are embedded");
+    let before: Vec<_> = migrations
+        .iter()
+        .take_while(|m| m.name().to_string() != name)
+        .collect();
+    assert!(
+        before.len() < migrations.len(),
+        "no migration named {}",
+        name
+    );
+    // creates the table that keeps track of the applied migrations
+    conn.applied_migrations().expect("migration table created");
+    for m in before {
+        conn.run_migration(m).expect("old migration applies");
+    }
+
+    conn.batch_execute(seed)
+        .expect("seed data fits the old schema");
+    conn.run_pending_migrations(MIGRATIONS)
+        .expect("migrations apply over existing data");
+    conn
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn expense_dates_are_migrated() {
+    use crate::models::Expense;
+
+    let mut conn = migrate_over(
+        "2026-10-19-090000_expense_dates",
+        "INSERT INTO users (id, username, email, password_hash, registration_date, preferred_language)
+            VALUES (1, 'alice', 'alice@example.com', 'x', '2025-01-01 10:00:00', 'it');
+        INSERT INTO expenses (id, desc, total_amount, creation_date, paid_by)
+            VALUES (1, 'dinner', 30.0, '2025-03-04 21:30:00', 1);",
+    );
+
+    let e = expenses::table.find(1).first::<Expense>(&mut conn).unwrap();
+    let created = chrono::NaiveDate::from_ymd_opt(2025, 3, 4).unwrap();
+    assert_eq!(e.expense_date, created);
+    assert_eq!(e.created_at, created.and_hms_opt(21, 30, 0).unwrap());
+    assert_eq!(e.updated_at, e.created_at);
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn notifications_survive_the_type_change() {
+    use crate::models::Notification;
+    use crate::schema::notifications;
+
+    let mut conn = migrate_over(
+        "2026-10-19-120000_expense_comments",
+        "INSERT INTO users (id, username, email, password_hash, registration_date, preferred_language)
+            VALUES (1, 'alice', 'alice@example.com', 'x', '2025-01-01 10:00:00', 'it');
+        INSERT INTO expenses (id, desc, total_amount, created_at, paid_by, updated_at, expense_date)
+            VALUES (1, 'dinner', 30.0, '2025-03-04 21:30:00', 1, '2025-03-04 21:30:00', '2025-03-04');
+        INSERT INTO notifications (id, notified_user_id, notification_type, user_id, expense_id, creation_date, read)
+            VALUES (7, 1, 'NEW_EXPENSE', 1, 1, '2025-03-04 21:30:00', TRUE);",
+    );
+
+    let n = notifications::table
+        .find(7)
+        .first::<Notification>(&mut conn)
+        .unwrap();
+    assert_eq!(n.notification_type.as_deref(), Some("NEW_EXPENSE"));
+    assert_eq!(n.expense_id, Some(1));
+    assert!(n.read);
+
+    // the new type is accepted and the foreign keys still clear the deleted expenses
+    diesel::insert_into(notifications::table)
+        .values((
+            notifications::notified_user_id.eq(1),
+            notifications::notification_type.eq("EXPENSE_COMMENT"),
+            notifications::creation_date.eq(diesel::dsl::now),
+        ))
+        .execute(&mut conn)
+        .unwrap();
+    diesel::delete(expenses::table.find(1))
+        .execute(&mut conn)
+        .unwrap();
+    let n = notifications::table
+        .find(7)
+        .first::<Notification>(&mut conn)
+        .unwrap();
+    assert_eq!(n.expense_id, None);
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn group_expenses_start_their_history() {
+    use crate::models::{ExpenseRevision, ExpenseRevisionParticipation};
+    use crate::schema::{expense_revision_participations, expense_revisions};
+
+    let mut conn = migrate_over(
+        "2026-10-19-140000_expense_revisions",
+        "INSERT INTO users (id, username, email, password_hash, registration_date, preferred_language)
+            VALUES (1, 'alice', 'alice@example.com', 'x', '2025-01-01 10:00:00', 'it');
+        INSERT INTO groups (id, group_name, creation_date) VALUES (1, 'flat', '2025-01-01 10:00:00');
+        INSERT INTO expenses (id, desc, total_amount, created_at, paid_by, group_id, updated_at, expense_date)
+            VALUES (1, 'dinner', 30.0, '2025-03-04 21:30:00', 1, 1, '2025-03-05 09:00:00', '2025-03-04'),
+                   (2, 'taxi', 12.0, '2025-03-04 23:00:00', 1, NULL, '2025-03-04 23:00:00', '2025-03-04');
+        INSERT INTO expense_participations (expense_id, user_id, amount_due) VALUES (1, 1, 30.0), (2, 1, 12.0);",
+    );
+
+    // only the group expenses have a history, which starts from their current state
+    let revisions = expense_revisions::table
+        .get_results::<ExpenseRevision>(&mut conn)
+        .unwrap();
+    assert_eq!(revisions.len(), 1);
+    let r = &revisions[0];
+    assert_eq!((r.expense_id, r.revision, r.changed_by), (1, 1, None));
+    assert_eq!(r.desc, "dinner");
+    assert_eq!(
+        r.changed_at,
+        chrono::NaiveDate::from_ymd_opt(2025, 3, 5)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap()
+    );
+
+    let participations = expense_revision_participations::table
+        .get_results::<ExpenseRevisionParticipation>(&mut conn)
+        .unwrap();
+    assert_eq!(participations.len(), 1);
+    assert_eq!(participations[0].revision_id, r.id);
+    assert_eq!(participations[0].amount_due, Some(30.0));
+}
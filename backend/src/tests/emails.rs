@@ -0,0 +1,361 @@
+//! tests for the notification emails in `api/emails.rs` and the transports in `mail.rs`
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use rocket::http::{Method, Status};
+use serde_json::json;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use super::{TestUser, add_member, client, expense, group, notifications, status, unique, user};
+use crate::api::emails::send_emails;
+use crate::config::{MailConfig, NotificationEmailsConfig, SmtpTls};
+use crate::establish_connection;
+use crate::mail::{Email, FileMailer, Mailer, mailer};
+use crate::schema::users;
+
+/// keeps the emails instead of sending them
+#[derive(Default)]
+struct Outbox(Mutex<Vec<Email>>);
+
+impl Mailer for Outbox {
+    fn send(&self, email: &Email) -> std::io::Result<()> {
+        self.0.lock().unwrap().push(email.clone());
+        Ok(())
+    }
+}
+
+/// sends the emails of `user` that are due at `now`, returns the ones that were sent
+fn send_at(user: &TestUser, now: NaiveDateTime) -> Vec<Email> {
+    let outbox = Outbox::default();
+    send_emails(
+        &outbox,
+        &NotificationEmailsConfig::default(),
+        now,
+        Some(user.id),
+    )
+    .expect("emails sent");
+    outbox.0.into_inner().unwrap()
+}
+
+fn send(user: &TestUser) -> Vec<Email> {
+    send_at(user, Utc::now().naive_utc())
+}
+
+fn set_delivery(user: &TestUser, delivery: &str) -> Status {
+    status(
+        &user.client,
+        Method::Put,
+        format!("/notifications/email/{}", delivery),
+        None,
+    )
+}
+
+fn delivery(user: &TestUser) -> String {
+    let res = user.client.get("/notifications/email").dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json::<String>().expect("email delivery")
+}
+
+#[test]
+fn email_delivery_needs_a_known_value() {
+    let alice = user();
+
+    assert_eq!(
+        status(
+            &client(),
+            Method::Get,
+            "/notifications/email".to_owned(),
+            None
+        ),
+        Status::Unauthorized
+    );
+    assert_eq!(
+        status(
+            &client(),
+            Method::Put,
+            "/notifications/email/IMMEDIATE".to_owned(),
+            None
+        ),
+        Status::Unauthorized
+    );
+
+    assert_eq!(delivery(&alice), "OFF");
+    assert_eq!(set_delivery(&alice, "WEEKLY"), Status::BadRequest);
+    assert_eq!(set_delivery(&alice, "DIGEST"), Status::Ok);
+    assert_eq!(delivery(&alice), "DIGEST");
+}
+
+#[test]
+fn notifications_are_emailed_immediately() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    // created while the emails were off, never sent
+    expense(&alice, &g, 10.0, &[&alice, &bob]);
+    assert_eq!(set_delivery(&bob, "IMMEDIATE"), Status::Ok);
+    assert!(send(&bob).is_empty());
+
+    let e = expense(&alice, &g, 20.0, &[&alice, &bob]);
+    let emails = send(&bob);
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].to, bob.email);
+    assert!(emails[0].subject.starts_with("SplitSmart: "));
+    assert!(
+        emails[0]
+            .subject
+            .contains(&format!("ha aggiunto la spesa \"{}\"", e.desc))
+    );
+    assert!(emails[0].subject.contains(&g.group_name));
+    assert!(emails[0].body.starts_with("Ciao "));
+    let unsubscribe = emails[0].unsubscribe.clone().expect("unsubscribe link");
+    assert!(unsubscribe.starts_with("http://localhost:3000/unsubscribe?token="));
+    assert!(emails[0].body.contains(&unsubscribe));
+    // every notification is sent once
+    assert!(send(&bob).is_empty());
+
+    diesel::update(users::table.filter(users::id.eq(bob.id)))
+        .set(users::preferred_language.eq("en"))
+        .execute(&mut establish_connection())
+        .expect("language updated");
+    let e = expense(&alice, &g, 30.0, &[&alice, &bob]);
+    let emails = send(&bob);
+    assert_eq!(emails.len(), 1);
+    assert!(
+        emails[0]
+            .subject
+            .contains(&format!("added the expense \"{}\"", e.desc))
+    );
+    assert!(emails[0].body.starts_with("Hi "));
+}
+
+#[test]
+fn reminders_are_emailed_with_their_message() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    expense(&alice, &g, 25.0, &[&alice, &bob]);
+    assert_eq!(set_delivery(&bob, "IMMEDIATE"), Status::Ok);
+
+    assert_eq!(
+        status(
+            &alice.client,
+            Method::Post,
+            format!("/groups/{}/reminders/{}", g.id, bob.id),
+            Some(json!({ "message": "la cena di sabato" }))
+        ),
+        Status::Ok
+    );
+    let emails = send(&bob);
+    assert_eq!(emails.len(), 1);
+    assert!(emails[0].subject.contains(&format!(
+        "ti ricorda che devi 12.50 € nel gruppo \"{}\": \"la cena di sabato\"",
+        g.group_name
+    )));
+}
+
+#[test]
+fn read_notifications_are_not_emailed() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    assert_eq!(set_delivery(&bob, "IMMEDIATE"), Status::Ok);
+
+    expense(&alice, &g, 10.0, &[&alice, &bob]);
+    let n = notifications(&bob).remove(0);
+    assert_eq!(
+        status(
+            &bob.client,
+            Method::Put,
+            format!("/notifications/{}/read", n.id),
+            None
+        ),
+        Status::Ok
+    );
+    assert!(send(&bob).is_empty());
+}
+
+#[test]
+fn digests_are_sent_once_a_day() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    assert_eq!(set_delivery(&bob, "DIGEST"), Status::Ok);
+
+    let first = expense(&alice, &g, 10.0, &[&alice, &bob]);
+    let second = expense(&alice, &g, 20.0, &[&alice, &bob]);
+    // the digests are sent at 8 by default
+    let tomorrow = Utc::now().date_naive() + Duration::days(1);
+    assert!(send_at(&bob, tomorrow.and_hms_opt(7, 0, 0).unwrap()).is_empty());
+
+    let emails = send_at(&bob, tomorrow.and_hms_opt(9, 0, 0).unwrap());
+    assert_eq!(emails.len(), 1);
+    assert_eq!(
+        emails[0].subject,
+        "SplitSmart: il riepilogo delle tue notifiche"
+    );
+    assert!(emails[0].body.contains(&first.desc));
+    assert!(emails[0].body.contains(&second.desc));
+
+    expense(&alice, &g, 30.0, &[&alice, &bob]);
+    assert!(send_at(&bob, tomorrow.and_hms_opt(10, 0, 0).unwrap()).is_empty());
+    let day_after = tomorrow + Duration::days(1);
+    assert_eq!(
+        send_at(&bob, day_after.and_hms_opt(8, 0, 0).unwrap()).len(),
+        1
+    );
+}
+
+#[test]
+fn emails_can_be_turned_off_from_the_link() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    assert_eq!(set_delivery(&bob, "IMMEDIATE"), Status::Ok);
+    expense(&alice, &g, 10.0, &[&alice, &bob]);
+
+    let unsubscribe = send(&bob)[0].unsubscribe.clone().expect("unsubscribe link");
+    let token = unsubscribe.split("token=").nth(1).expect("token in link");
+
+    assert_eq!(
+        status(
+            &client(),
+            Method::Post,
+            format!("/notifications/email/unsubscribe/{}", unique("token")),
+            None
+        ),
+        Status::NotFound
+    );
+    assert_eq!(
+        status(
+            &client(),
+            Method::Post,
+            format!("/notifications/email/unsubscribe/{}", token),
+            None
+        ),
+        Status::Ok
+    );
+    assert_eq!(delivery(&bob), "OFF");
+
+    expense(&alice, &g, 20.0, &[&alice, &bob]);
+    assert!(send(&bob).is_empty());
+}
+
+fn email(subject: &str, body: &str) -> Email {
+    Email {
+        to: "bob@example.com".to_owned(),
+        subject: subject.to_owned(),
+        body: body.to_owned(),
+        unsubscribe: Some("http://localhost:3000/unsubscribe?token=t".to_owned()),
+    }
+}
+
+#[test]
+fn file_mailer_writes_the_emails() {
+    let dir = std::env::temp_dir().join(format!("splitsmart-test-mail-{}", Uuid::new_v4()));
+    let mailer = FileMailer::new(&dir, "splitsmart@example.com").expect("mail directory");
+
+    mailer
+        .send(&email("Già pagato", "prima riga\nseconda riga"))
+        .expect("email written");
+
+    let files: Vec<_> = std::fs::read_dir(&dir)
+        .expect("mail directory")
+        .map(|f| f.expect("file").path())
+        .collect();
+    assert_eq!(files.len(), 1);
+    let message = std::fs::read_to_string(&files[0]).expect("email file");
+    assert!(message.contains("To: <bob@example.com>\r\n"));
+    assert!(message.contains("Subject: =?UTF-8?B?"));
+    assert!(message.contains("List-Unsubscribe: <http://localhost:3000/unsubscribe?token=t>"));
+    assert!(message.ends_with("\r\n\r\nprima riga\r\nseconda riga\r\n"));
+
+    assert!(
+        mailer
+            .send(&Email {
+                to: "bob@example.com\r\nBcc: eve@example.com".to_owned(),
+                ..email("subject", "body")
+            })
+            .is_err()
+    );
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn smtp_mailer_talks_to_the_relay() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("listener");
+    let port = listener.local_addr().expect("address").port();
+
+    // a relay that accepts everything and returns what it was told
+    let relay = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().expect("connection");
+        let mut reader = BufReader::new(stream.try_clone().expect("stream"));
+        let mut stream = stream;
+        let mut transcript = Vec::new();
+        stream.write_all(b"220 relay\r\n").unwrap();
+        let mut data = false;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap() == 0 {
+                break;
+            }
+            let line = line.trim_end().to_owned();
+            let reply: &[u8] = match (data, line.as_str()) {
+                (true, ".") => {
+                    data = false;
+                    b"250 queued\r\n"
+                }
+                (true, _) => b"",
+                (false, "DATA") => {
+                    data = true;
+                    b"354 go on\r\n"
+                }
+                (false, l) if l.starts_with("EHLO") => b"250-relay\r\n250 AUTH PLAIN\r\n",
+                (false, l) if l.starts_with("AUTH") => b"235 ok\r\n",
+                (false, "QUIT") => b"221 bye\r\n",
+                _ => b"250 ok\r\n",
+            };
+            transcript.push(line);
+            stream.write_all(reply).unwrap();
+            if transcript.last().is_some_and(|l| l == "QUIT") {
+                break;
+            }
+        }
+        transcript
+    });
+
+    // credentials are only sent over an encrypted connection
+    let relay_config = |tls: SmtpTls, username: Option<&str>| MailConfig::Smtp {
+        host: "127.0.0.1".to_owned(),
+        port,
+        tls,
+        username: username.map(str::to_owned),
+        password: username.map(|_| "secret".to_owned()),
+        from: "splitsmart@example.com".to_owned(),
+    };
+    assert!(mailer(&relay_config(SmtpTls::None, Some("user"))).is_err());
+    assert!(mailer(&relay_config(SmtpTls::Starttls, Some("user"))).is_ok());
+
+    let mailer = mailer(&relay_config(SmtpTls::None, None))
+        .expect("mailer")
+        .expect("mail enabled");
+    mailer
+        .send(&email("subject", "first\n.hidden"))
+        .expect("email sent");
+
+    let transcript = relay.join().expect("relay");
+    assert!(transcript[0].starts_with("EHLO "));
+    assert!(!transcript.iter().any(|l| l.starts_with("AUTH")));
+    assert!(transcript.contains(&"MAIL FROM:<splitsmart@example.com>".to_owned()));
+    assert!(transcript.contains(&"RCPT TO:<bob@example.com>".to_owned()));
+    // the line starting with a dot does not end the data
+    assert!(transcript.contains(&"..hidden".to_owned()));
+    assert_eq!(transcript.last().map(String::as_str), Some("QUIT"));
+}
@@ -0,0 +1,215 @@
+//! tests for the routes in `api/expenses.rs`
+
+use chrono::Utc;
+use rocket::http::{Method, Status};
+use serde_json::{Value, json};
+
+use super::{TestUser, client, expense, group, notifications, private_expense, status, user};
+use crate::api::{expenses::ExpenseWithParticipations, pagination::Page};
+use crate::models::Expense;
+
+fn private_expenses(u: &TestUser) -> Vec<ExpenseWithParticipations> {
+    let res = u.client.get("/expenses").dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json::<Page<ExpenseWithParticipations>>()
+        .unwrap()
+        .items
+}
+
+fn expense_body(payer: &TestUser, participants: &[&TestUser]) -> Value {
+    let division: Vec<(i32, f64)> = participants.iter().map(|u| (u.id, 4.0)).collect();
+    json!({
+        "desc": "groceries",
+        "total_amount": 4.0 * participants.len() as f64,
+        "paid_by": payer.id,
+        "division": division,
+    })
+}
+
+#[test]
+fn routes_require_authentication() {
+    let alice = user();
+    let anonymous = client();
+    let body = expense_body(&alice, &[&alice]);
+
+    assert_eq!(
+        status(&anonymous, Method::Get, "/expenses".to_owned(), None),
+        Status::Unauthorized
+    );
+    assert_eq!(
+        status(
+            &anonymous,
+            Method::Post,
+            "/expenses".to_owned(),
+            Some(body.clone())
+        ),
+        Status::Unauthorized
+    );
+    assert_eq!(
+        status(
+            &anonymous,
+            Method::Put,
+            "/expenses/1".to_owned(),
+            Some(body)
+        ),
+        Status::Unauthorized
+    );
+    assert_eq!(
+        status(&anonymous, Method::Delete, "/expenses/1".to_owned(), None),
+        Status::Unauthorized
+    );
+}
+
+#[test]
+fn add_private_expense_notifies_participants() {
+    let alice = user();
+    let bob = user();
+
+    let e = private_expense(&alice, 20.0, &[&alice, &bob]);
+    assert_eq!(e.group_id, None);
+    assert_eq!(e.paid_by, alice.id);
+
+    let n = notifications(&bob);
+    assert_eq!(n.len(), 1);
+    assert_eq!(n[0].notification_type.as_deref(), Some("NEW_EXPENSE"));
+    assert_eq!(n[0].expense_id, Some(e.id));
+    assert_eq!(n[0].user_id, Some(alice.id));
+}
+
+#[test]
+fn get_private_expenses_lists_participated_expenses() {
+    let alice = user();
+    let bob = user();
+    let carol = user();
+
+    let e = private_expense(&alice, 20.0, &[&alice, &bob]);
+    // group expenses are not private expenses
+    let g = group(&alice);
+    expense(&alice, &g, 10.0, &[&alice]);
+
+    for u in [&alice, &bob] {
+        let list = private_expenses(u);
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].0.id, e.id);
+        assert_eq!(list[0].1.len(), 2);
+    }
+    assert!(private_expenses(&carol).is_empty());
+}
+
+#[test]
+fn update_private_expense_authorization() {
+    let alice = user();
+    let bob = user();
+    let e = private_expense(&alice, 20.0, &[&alice, &bob]);
+    let uri = format!("/expenses/{}", e.id);
+
+    // a participant cannot take over the expense
+    let body = expense_body(&bob, &[&alice, &bob]);
+    assert_eq!(
+        status(&bob.client, Method::Put, uri.clone(), Some(body)),
+        Status::Forbidden
+    );
+
+    let res = alice
+        .client
+        .put(uri)
+        .json(&expense_body(&alice, &[&alice, &bob]))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let updated = res.into_json::<Expense>().unwrap();
+    assert_eq!(updated.desc, "groceries");
+    assert_eq!(updated.total_amount, 8.0);
+
+    let list = private_expenses(&bob);
+    assert!(list[0].1.iter().all(|p| p.amount_due == Some(4.0)));
+    let n = notifications(&bob);
+    assert!(
+        n.iter()
+            .any(|n| n.notification_type.as_deref() == Some("EXPENSE_MODIFIED"))
+    );
+}
+
+#[test]
+fn group_expenses_are_not_reachable_as_private_expenses() {
+    let alice = user();
+    let g = group(&alice);
+    let e = expense(&alice, &g, 10.0, &[&alice]);
+    let uri = format!("/expenses/{}", e.id);
+
+    let body = expense_body(&alice, &[&alice]);
+    assert_eq!(
+        status(&alice.client, Method::Put, uri.clone(), Some(body)),
+        Status::NotFound
+    );
+    assert_eq!(
+        status(&alice.client, Method::Delete, uri, None),
+        Status::NotFound
+    );
+}
+
+#[test]
+fn delete_private_expense_authorization() {
+    let alice = user();
+    let bob = user();
+    let e = private_expense(&alice, 20.0, &[&alice, &bob]);
+    let uri = format!("/expenses/{}", e.id);
+
+    assert_eq!(
+        status(&bob.client, Method::Delete, uri.clone(), None),
+        Status::Forbidden
+    );
+    assert_eq!(private_expenses(&bob).len(), 1);
+
+    assert_eq!(
+        status(&alice.client, Method::Delete, uri.clone(), None),
+        Status::Ok
+    );
+    assert!(private_expenses(&bob).is_empty());
+    assert!(
+        notifications(&bob)
+            .iter()
+            .any(|n| n.notification_type.as_deref() == Some("EXPENSE_DELETED"))
+    );
+
+    assert_eq!(
+        status(&alice.client, Method::Delete, uri, None),
+        Status::NotFound
+    );
+}
+
+#[test]
+fn expense_date_is_kept_apart_from_timestamps() {
+    let alice = user();
+    let bob = user();
+
+    let mut body = expense_body(&alice, &[&alice, &bob]);
+    body["expense_date"] = json!("2025-03-04");
+    let res = alice.client.post("/expenses").json(&body).dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let e = res.into_json::<Expense>().unwrap();
+    assert_eq!(e.expense_date.to_string(), "2025-03-04");
+    assert_eq!(e.created_at.date(), Utc::now().date_naive());
+    assert_eq!(e.updated_at, e.created_at);
+
+    // editing without a date keeps both the date and the creation time
+    let uri = format!("/expenses/{}", e.id);
+    let res = alice
+        .client
+        .put(uri.clone())
+        .json(&expense_body(&alice, &[&alice, &bob]))
+        .dispatch();
+    let updated = res.into_json::<Expense>().unwrap();
+    assert_eq!(updated.expense_date, e.expense_date);
+    assert_eq!(updated.created_at, e.created_at);
+    assert!(updated.updated_at >= e.updated_at);
+
+    body["expense_date"] = json!("2025-03-05");
+    let res = alice.client.put(uri).json(&body).dispatch();
+    let updated = res.into_json::<Expense>().unwrap();
+    assert_eq!(updated.expense_date.to_string(), "2025-03-05");
+    assert_eq!(updated.created_at, e.created_at);
+
+    // without a date the expense is dated today
+    let e = private_expense(&alice, 10.0, &[&alice]);
+    assert_eq!(e.expense_date, Utc::now().date_naive());
+}
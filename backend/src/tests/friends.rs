@@ -0,0 +1,202 @@
+//! tests for the routes in `api/friends.rs`
+
+use rocket::http::{Method, Status};
+use serde_json::json;
+
+use super::{TestUser, client, notifications, status, unique, user};
+use crate::models::{FriendInvite, Friendship};
+
+fn invite(from: &TestUser, to: &TestUser) -> FriendInvite {
+    let res = from
+        .client
+        .post("/friends/invites")
+        .json(&json!({ "email": to.email }))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json::<FriendInvite>().unwrap()
+}
+
+fn friends(u: &TestUser) -> Vec<Friendship> {
+    let res = u.client.get("/friends").dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json::<Vec<Friendship>>().unwrap()
+}
+
+#[test]
+fn routes_require_authentication() {
+    let anonymous = client();
+
+    for (method, uri) in [
+        (Method::Get, "/friends"),
+        (Method::Delete, "/friends/1"),
+        (Method::Get, "/friends/invites"),
+        (Method::Put, "/friends/invites/1/accept"),
+        (Method::Put, "/friends/invites/1/reject"),
+    ] {
+        assert_eq!(
+            status(&anonymous, method, uri.to_owned(), None),
+            Status::Unauthorized
+        );
+    }
+    let body = json!({ "email": "someone@example.com" });
+    assert_eq!(
+        status(
+            &anonymous,
+            Method::Post,
+            "/friends/invites".to_owned(),
+            Some(body)
+        ),
+        Status::Unauthorized
+    );
+}
+
+#[test]
+fn invite_friend() {
+    let alice = user();
+    let bob = user();
+
+    let inv = invite(&alice, &bob);
+    assert_eq!(inv.inviting_user_id, alice.id);
+    assert_eq!(inv.invited_user_id, bob.id);
+    assert_eq!(inv.invite_status.as_deref(), Some("PENDING"));
+
+    let res = bob.client.get("/friends/invites").dispatch();
+    let received = res.into_json::<Vec<FriendInvite>>().unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].id, inv.id);
+
+    // sent invites are not listed
+    let res = alice.client.get("/friends/invites").dispatch();
+    assert!(res.into_json::<Vec<FriendInvite>>().unwrap().is_empty());
+}
+
+#[test]
+fn invite_unknown_email_is_not_found() {
+    let alice = user();
+
+    let body = json!({ "email": format!("{}@example.com", unique("nobody")) });
+    assert_eq!(
+        status(
+            &alice.client,
+            Method::Post,
+            "/friends/invites".to_owned(),
+            Some(body)
+        ),
+        Status::NotFound
+    );
+}
+
+#[test]
+fn accept_invite_creates_friendship() {
+    let alice = user();
+    let bob = user();
+    let inv = invite(&alice, &bob);
+
+    // only the invited user can accept
+    let uri = format!("/friends/invites/{}/accept", inv.id);
+    assert_eq!(
+        status(&alice.client, Method::Put, uri.clone(), None),
+        Status::NotFound
+    );
+
+    let res = bob.client.put(uri).dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let accepted = res.into_json::<FriendInvite>().unwrap();
+    assert_eq!(accepted.invite_status.as_deref(), Some("ACCEPTED"));
+
+    for u in [&alice, &bob] {
+        let f = friends(u);
+        assert_eq!(f.len(), 1);
+        assert_eq!(f[0].user1, alice.id.min(bob.id));
+        assert_eq!(f[0].user2, alice.id.max(bob.id));
+    }
+
+    let n = notifications(&alice);
+    assert_eq!(n.len(), 1);
+    assert_eq!(
+        n[0].notification_type.as_deref(),
+        Some("FRIENDSHIP_REQUEST_ACCEPTED")
+    );
+    assert_eq!(n[0].user_id, Some(bob.id));
+}
+
+#[test]
+fn reject_invite_does_not_create_friendship() {
+    let alice = user();
+    let bob = user();
+    let inv = invite(&alice, &bob);
+
+    let uri = format!("/friends/invites/{}/reject", inv.id);
+    assert_eq!(
+        status(&alice.client, Method::Put, uri.clone(), None),
+        Status::NotFound
+    );
+
+    let res = bob.client.put(uri).dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let rejected = res.into_json::<FriendInvite>().unwrap();
+    assert_eq!(rejected.invite_status.as_deref(), Some("REJECTED"));
+
+    assert!(friends(&alice).is_empty());
+    assert!(friends(&bob).is_empty());
+
+    let n = notifications(&alice);
+    assert_eq!(n.len(), 1);
+    assert_eq!(
+        n[0].notification_type.as_deref(),
+        Some("FRIENDSHIP_REQUEST_DENIED")
+    );
+}
+
+#[test]
+fn remove_friend() {
+    let alice = user();
+    let bob = user();
+    let carol = user();
+    let inv = invite(&alice, &bob);
+    bob.client
+        .put(format!("/friends/invites/{}/accept", inv.id))
+        .dispatch();
+
+    // removing someone that is not a friend does nothing
+    let res = carol
+        .client
+        .delete(format!("/friends/{}", alice.id))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    assert!(res.into_json::<Vec<Friendship>>().unwrap().is_empty());
+    assert_eq!(friends(&alice).len(), 1);
+
+    let res = bob
+        .client
+        .delete(format!("/friends/{}", alice.id))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    assert_eq!(res.into_json::<Vec<Friendship>>().unwrap().len(), 1);
+
+    assert!(friends(&alice).is_empty());
+    assert!(friends(&bob).is_empty());
+}
+
+#[test]
+fn silenced_users_are_not_told_about_answers() {
+    let alice = user();
+    let bob = user();
+    assert_eq!(
+        status(
+            &alice.client,
+            Method::Put,
+            "/notifications/preferences/SILENCED".to_owned(),
+            None
+        ),
+        Status::Ok
+    );
+
+    let i = invite(&alice, &bob);
+    let res = bob
+        .client
+        .put(format!("/friends/invites/{}/accept", i.id))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    assert!(notifications(&alice).is_empty());
+}
@@ -0,0 +1,713 @@
+//! tests for the routes in `api/groups.rs`
+
+use rocket::http::{Method, Status};
+use rocket::local::blocking::Client;
+use serde_json::{Value, json};
+use std::sync::{Arc, Barrier};
+
+use super::{
+    TestUser, add_member, clear_notifications, client, expense, group, notifications, status, user,
+};
+use crate::api::{expenses::ExpenseWithParticipations, pagination::Page};
+use crate::models::{Expense, Group, GroupMember};
+
+/// a group with an admin and a member, plus a logged in user that is not part of the group and a
+/// client that is not logged in at all
+struct Roles {
+    admin: TestUser,
+    member: TestUser,
+    outsider: TestUser,
+    anonymous: Client,
+    group: Group,
+}
+
+fn roles() -> Roles {
+    let admin = user();
+    let member = user();
+    let outsider = user();
+    let group = group(&admin);
+    add_member(&admin, &group, &member);
+
+    Roles {
+        admin,
+        member,
+        outsider,
+        anonymous: client(),
+        group,
+    }
+}
+
+/// checks the status returned to the anonymous client, the outsider and the member, the request
+/// is expected to be refused for all of them
+fn assert_refused_up_to_member(r: &Roles, method: Method, uri: &str, body: Option<Value>) {
+    assert_eq!(
+        status(&r.anonymous, method, uri.to_owned(), body.clone()),
+        Status::Unauthorized
+    );
+    assert_eq!(
+        status(&r.outsider.client, method, uri.to_owned(), body.clone()),
+        Status::Forbidden
+    );
+    assert_eq!(
+        status(&r.member.client, method, uri.to_owned(), body),
+        Status::Forbidden
+    );
+}
+
+/// checks the status returned to the anonymous client and the outsider, the request is expected to
+/// be refused for both
+fn assert_refused_to_outsiders(r: &Roles, method: Method, uri: &str, body: Option<Value>) {
+    assert_eq!(
+        status(&r.anonymous, method, uri.to_owned(), body.clone()),
+        Status::Unauthorized
+    );
+    assert_eq!(
+        status(&r.outsider.client, method, uri.to_owned(), body),
+        Status::Forbidden
+    );
+}
+
+fn expense_body(payer: &TestUser, participants: &[&TestUser]) -> Value {
+    let division: Vec<(i32, f64)> = participants.iter().map(|u| (u.id, 5.0)).collect();
+    json!({
+        "desc": "pizza",
+        "total_amount": 5.0 * participants.len() as f64,
+        "paid_by": payer.id,
+        "division": division,
+    })
+}
+
+#[test]
+fn get_group_authorization() {
+    let r = roles();
+    let uri = format!("/groups/{}", r.group.id);
+
+    assert_refused_to_outsiders(&r, Method::Get, &uri, None);
+    assert_eq!(
+        status(&r.member.client, Method::Get, uri.clone(), None),
+        Status::Ok
+    );
+    assert_eq!(status(&r.admin.client, Method::Get, uri, None), Status::Ok);
+}
+
+#[test]
+fn missing_group_is_not_found() {
+    let r = roles();
+    r.admin
+        .client
+        .delete(format!("/groups/{}", r.group.id))
+        .dispatch();
+
+    let uri = format!("/groups/{}", r.group.id);
+    assert_eq!(
+        status(&r.admin.client, Method::Get, uri, None),
+        Status::NotFound
+    );
+}
+
+#[test]
+fn update_group_authorization() {
+    let r = roles();
+    let uri = format!("/groups/{}", r.group.id);
+    let body = json!({ "name": "renamed", "description": "new description" });
+
+    assert_refused_up_to_member(&r, Method::Put, &uri, Some(body.clone()));
+    assert_eq!(
+        status(&r.admin.client, Method::Put, uri, Some(body)),
+        Status::Ok
+    );
+}
+
+#[test]
+fn delete_group_authorization() {
+    let r = roles();
+    let uri = format!("/groups/{}", r.group.id);
+
+    assert_refused_up_to_member(&r, Method::Delete, &uri, None);
+    assert_eq!(
+        status(&r.admin.client, Method::Delete, uri, None),
+        Status::Ok
+    );
+}
+
+#[test]
+fn add_expense_authorization() {
+    let r = roles();
+    let uri = format!("/groups/{}/expenses", r.group.id);
+
+    let body = expense_body(&r.outsider, &[&r.outsider]);
+    assert_refused_to_outsiders(&r, Method::Post, &uri, Some(body));
+
+    let body = expense_body(&r.member, &[&r.member, &r.admin]);
+    assert_eq!(
+        status(&r.member.client, Method::Post, uri.clone(), Some(body)),
+        Status::Ok
+    );
+    let body = expense_body(&r.admin, &[&r.member, &r.admin]);
+    assert_eq!(
+        status(&r.admin.client, Method::Post, uri, Some(body)),
+        Status::Ok
+    );
+}
+
+#[test]
+fn get_expenses_authorization() {
+    let r = roles();
+    let uri = format!("/groups/{}/expenses", r.group.id);
+
+    assert_refused_to_outsiders(&r, Method::Get, &uri, None);
+    assert_eq!(
+        status(&r.member.client, Method::Get, uri.clone(), None),
+        Status::Ok
+    );
+    assert_eq!(status(&r.admin.client, Method::Get, uri, None), Status::Ok);
+}
+
+#[test]
+fn update_expense_authorization() {
+    let r = roles();
+    let other_member = user();
+    add_member(&r.admin, &r.group, &other_member);
+    let e = expense(&r.member, &r.group, 10.0, &[&r.member, &other_member]);
+    let uri = format!("/groups/{}/expenses/{}", r.group.id, e.id);
+
+    // someone that did not pay for the expense cannot take it over by claiming to have paid it
+    let body = expense_body(&other_member, &[&r.member, &other_member]);
+    assert_refused_to_outsiders(&r, Method::Put, &uri, Some(body.clone()));
+    assert_eq!(
+        status(&other_member.client, Method::Put, uri.clone(), Some(body)),
+        Status::Forbidden
+    );
+
+    let body = expense_body(&r.member, &[&r.member, &other_member]);
+    assert_eq!(
+        status(
+            &r.member.client,
+            Method::Put,
+            uri.clone(),
+            Some(body.clone())
+        ),
+        Status::Ok
+    );
+    assert_eq!(
+        status(&r.admin.client, Method::Put, uri, Some(body)),
+        Status::Ok
+    );
+}
+
+#[test]
+fn delete_expense_authorization() {
+    let r = roles();
+    let other_member = user();
+    add_member(&r.admin, &r.group, &other_member);
+    let by_member = expense(&r.member, &r.group, 10.0, &[&r.member, &other_member]);
+    let by_other = expense(&other_member, &r.group, 10.0, &[&r.member, &other_member]);
+
+    let uri = format!("/groups/{}/expenses/{}", r.group.id, by_member.id);
+    assert_refused_to_outsiders(&r, Method::Delete, &uri, None);
+    assert_eq!(
+        status(&other_member.client, Method::Delete, uri.clone(), None),
+        Status::Forbidden
+    );
+    assert_eq!(
+        status(&r.member.client, Method::Delete, uri, None),
+        Status::Ok
+    );
+
+    let uri = format!("/groups/{}/expenses/{}", r.group.id, by_other.id);
+    assert_eq!(
+        status(&r.admin.client, Method::Delete, uri, None),
+        Status::Ok
+    );
+}
+
+#[test]
+fn expense_of_another_group_is_not_found() {
+    let r = roles();
+    let other_group = group(&r.outsider);
+    let e = expense(&r.outsider, &other_group, 10.0, &[&r.outsider]);
+
+    // the admin of a group cannot reach expenses of other groups through it
+    let uri = format!("/groups/{}/expenses/{}", r.group.id, e.id);
+    assert_eq!(
+        status(&r.admin.client, Method::Delete, uri.clone(), None),
+        Status::NotFound
+    );
+    let body = expense_body(&r.admin, &[&r.admin]);
+    assert_eq!(
+        status(&r.admin.client, Method::Put, uri, Some(body)),
+        Status::NotFound
+    );
+}
+
+#[test]
+fn add_member_authorization() {
+    let r = roles();
+    let uri = format!("/groups/{}/members", r.group.id);
+    let body = json!({ "user_id": r.outsider.id });
+
+    assert_refused_up_to_member(&r, Method::Post, &uri, Some(body.clone()));
+    assert_eq!(
+        status(&r.admin.client, Method::Post, uri, Some(body)),
+        Status::Ok
+    );
+}
+
+#[test]
+fn view_members_and_admins_authorization() {
+    let r = roles();
+
+    for uri in [
+        format!("/groups/{}/members", r.group.id),
+        format!("/groups/{}/admins", r.group.id),
+    ] {
+        assert_refused_to_outsiders(&r, Method::Get, &uri, None);
+        assert_eq!(
+            status(&r.member.client, Method::Get, uri.clone(), None),
+            Status::Ok
+        );
+        assert_eq!(status(&r.admin.client, Method::Get, uri, None), Status::Ok);
+    }
+}
+
+#[test]
+fn invite_user_authorization() {
+    let r = roles();
+    let invitee = user();
+    let uri = format!("/groups/{}/members/invite", r.group.id);
+    let body = json!({ "email": invitee.email, "message": null });
+
+    assert_refused_up_to_member(&r, Method::Post, &uri, Some(body.clone()));
+    assert_eq!(
+        status(&r.admin.client, Method::Post, uri, Some(body)),
+        Status::Ok
+    );
+}
+
+#[test]
+fn remove_member_authorization() {
+    let r = roles();
+    let uri = format!("/groups/{}/members/{}", r.group.id, r.member.id);
+
+    assert_refused_up_to_member(&r, Method::Delete, &uri, None);
+    assert_eq!(
+        status(&r.admin.client, Method::Delete, uri.clone(), None),
+        Status::Ok
+    );
+    // the member is gone now
+    assert_eq!(
+        status(&r.admin.client, Method::Delete, uri, None),
+        Status::NotFound
+    );
+}
+
+#[test]
+fn last_admin_cannot_be_removed() {
+    let r = roles();
+    let uri = format!("/groups/{}/members/{}", r.group.id, r.admin.id);
+
+    assert_eq!(
+        status(&r.admin.client, Method::Delete, uri.clone(), None),
+        Status::Conflict
+    );
+
+    let promote = format!("/groups/{}/admins/{}", r.group.id, r.member.id);
+    assert_eq!(
+        status(&r.admin.client, Method::Post, promote, None),
+        Status::Ok
+    );
+    assert_eq!(
+        status(&r.admin.client, Method::Delete, uri, None),
+        Status::Ok
+    );
+}
+
+#[test]
+fn admins_demoting_each_other_leave_one() {
+    let r = roles();
+    let promote = format!("/groups/{}/admins/{}", r.group.id, r.member.id);
+    assert_eq!(
+        status(&r.admin.client, Method::Post, promote, None),
+        Status::Ok
+    );
+
+    // both ask at the same time to demote the other
+    let barrier = Arc::new(Barrier::new(2));
+    let demote = |user: TestUser, other: i32| {
+        let barrier = barrier.clone();
+        let uri = format!("/groups/{}/admins/{}", r.group.id, other);
+        std::thread::spawn(move || {
+            barrier.wait();
+            let s = status(&user.client, Method::Delete, uri, None);
+            (user, s)
+        })
+    };
+    let (admin_id, member_id) = (r.admin.id, r.member.id);
+    let first = demote(r.admin, member_id);
+    let second = demote(r.member, admin_id);
+    let (admin, a) = first.join().unwrap();
+    let (_, b) = second.join().unwrap();
+    assert!([a, b].contains(&Status::Ok), "{} {}", a, b);
+
+    let res = admin
+        .client
+        .get(format!("/groups/{}/admins", r.group.id))
+        .dispatch();
+    assert_eq!(res.into_json::<Vec<GroupMember>>().unwrap().len(), 1);
+}
+
+#[test]
+fn promote_to_admin_authorization() {
+    let r = roles();
+    let uri = format!("/groups/{}/admins/{}", r.group.id, r.member.id);
+
+    assert_refused_up_to_member(&r, Method::Post, &uri, None);
+    // only members can be promoted
+    let outsider = format!("/groups/{}/admins/{}", r.group.id, r.outsider.id);
+    assert_eq!(
+        status(&r.admin.client, Method::Post, outsider, None),
+        Status::NotFound
+    );
+    assert_eq!(status(&r.admin.client, Method::Post, uri, None), Status::Ok);
+}
+
+#[test]
+fn demote_admin_authorization() {
+    let r = roles();
+    let uri = format!("/groups/{}/admins/{}", r.group.id, r.admin.id);
+
+    assert_refused_up_to_member(&r, Method::Delete, &uri, None);
+    // the last admin of the group cannot step down
+    assert_eq!(
+        status(&r.admin.client, Method::Delete, uri.clone(), None),
+        Status::Conflict
+    );
+
+    let promote = format!("/groups/{}/admins/{}", r.group.id, r.member.id);
+    assert_eq!(
+        status(&r.admin.client, Method::Post, promote, None),
+        Status::Ok
+    );
+    assert_eq!(
+        status(&r.admin.client, Method::Delete, uri, None),
+        Status::Ok
+    );
+}
+
+fn members(u: &TestUser, g: &Group, role: &str) -> Vec<i32> {
+    let res = u
+        .client
+        .get(format!("/groups/{}/{}", g.id, role))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let mut ids: Vec<i32> = res
+        .into_json::<Vec<GroupMember>>()
+        .unwrap()
+        .iter()
+        .map(|m| m.user_id)
+        .collect();
+    ids.sort();
+    ids
+}
+
+fn groups(u: &TestUser) -> Vec<i32> {
+    let res = u.client.get("/groups").dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json::<Page<Group>>()
+        .unwrap()
+        .items
+        .iter()
+        .map(|g| g.id)
+        .collect()
+}
+
+#[test]
+fn create_group_makes_creator_member_and_admin() {
+    let alice = user();
+    let g = group(&alice);
+
+    assert_eq!(members(&alice, &g, "members"), vec![alice.id]);
+    assert_eq!(members(&alice, &g, "admins"), vec![alice.id]);
+
+    let anonymous = client();
+    let body = json!({ "name": "group", "description": null });
+    assert_eq!(
+        status(&anonymous, Method::Post, "/groups".to_owned(), Some(body)),
+        Status::Unauthorized
+    );
+}
+
+#[test]
+fn get_groups_lists_only_own_groups() {
+    let alice = user();
+    let bob = user();
+    let shared = group(&alice);
+    let private = group(&alice);
+    add_member(&alice, &shared, &bob);
+
+    let mut own = groups(&alice);
+    own.sort();
+    assert_eq!(own, vec![shared.id, private.id]);
+    assert_eq!(groups(&bob), vec![shared.id]);
+
+    assert_eq!(
+        status(&client(), Method::Get, "/groups".to_owned(), None),
+        Status::Unauthorized
+    );
+}
+
+#[test]
+fn update_group_changes_name_and_description() {
+    let alice = user();
+    let g = group(&alice);
+
+    let res = alice
+        .client
+        .put(format!("/groups/{}", g.id))
+        .json(&json!({ "name": "flat", "description": "bills of the flat" }))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+
+    let res = alice.client.get(format!("/groups/{}", g.id)).dispatch();
+    let updated = res.into_json::<Group>().unwrap();
+    assert_eq!(updated.group_name, "flat");
+    assert_eq!(updated.desc.as_deref(), Some("bills of the flat"));
+}
+
+#[test]
+fn delete_group_removes_it_for_members() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+
+    let res = alice.client.delete(format!("/groups/{}", g.id)).dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    assert!(groups(&bob).is_empty());
+}
+
+#[test]
+fn member_and_admin_lists_follow_changes() {
+    let r = roles();
+    let mut both = vec![r.admin.id, r.member.id];
+    both.sort();
+
+    assert_eq!(members(&r.member, &r.group, "members"), both);
+    assert_eq!(members(&r.member, &r.group, "admins"), vec![r.admin.id]);
+
+    r.admin
+        .client
+        .post(format!("/groups/{}/admins/{}", r.group.id, r.member.id))
+        .dispatch();
+    assert_eq!(members(&r.member, &r.group, "admins"), both);
+
+    r.admin
+        .client
+        .delete(format!("/groups/{}/admins/{}", r.group.id, r.admin.id))
+        .dispatch();
+    assert_eq!(members(&r.member, &r.group, "admins"), vec![r.member.id]);
+
+    // removing a member also removes its admin rights
+    r.member
+        .client
+        .delete(format!("/groups/{}/members/{}", r.group.id, r.admin.id))
+        .dispatch();
+    assert_eq!(members(&r.member, &r.group, "members"), vec![r.member.id]);
+    assert_eq!(members(&r.member, &r.group, "admins"), vec![r.member.id]);
+}
+
+#[test]
+fn add_member_of_unknown_user_is_not_found() {
+    let r = roles();
+    let uri = format!("/groups/{}/members", r.group.id);
+
+    let body = json!({ "user_id": -1 });
+    assert_eq!(
+        status(&r.admin.client, Method::Post, uri, Some(body)),
+        Status::NotFound
+    );
+}
+
+#[test]
+fn expense_lifecycle_notifies_participants() {
+    let r = roles();
+    let e = expense(&r.admin, &r.group, 10.0, &[&r.admin, &r.member]);
+
+    let res = r
+        .admin
+        .client
+        .put(format!("/groups/{}/expenses/{}", r.group.id, e.id))
+        .json(&json!({
+            "desc": "dinner",
+            "total_amount": 12.0,
+            "paid_by": r.admin.id,
+            "division": [(r.admin.id, 4.0), (r.member.id, 8.0)],
+        }))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let updated = res.into_json::<Expense>().unwrap();
+    assert_eq!(updated.total_amount, 12.0);
+
+    let res = r
+        .member
+        .client
+        .get(format!("/groups/{}/expenses", r.group.id))
+        .dispatch();
+    let list = res
+        .into_json::<Page<ExpenseWithParticipations>>()
+        .unwrap()
+        .items;
+    assert_eq!(list.len(), 1);
+    let due = list[0]
+        .1
+        .iter()
+        .find(|p| p.user_id == r.member.id)
+        .and_then(|p| p.amount_due);
+    assert_eq!(due, Some(8.0));
+
+    let res = r
+        .admin
+        .client
+        .delete(format!("/groups/{}/expenses/{}", r.group.id, e.id))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+
+    let kinds: Vec<String> = notifications(&r.member)
+        .into_iter()
+        .filter_map(|n| n.notification_type)
+        .collect();
+    assert!(kinds.contains(&"NEW_EXPENSE".to_owned()));
+    assert!(kinds.contains(&"EXPENSE_MODIFIED".to_owned()));
+    assert!(kinds.contains(&"EXPENSE_DELETED".to_owned()));
+}
+
+/// the types of the notifications received by `user`, the newest first
+fn kinds(user: &TestUser) -> Vec<String> {
+    notifications(user)
+        .into_iter()
+        .filter_map(|n| n.notification_type)
+        .collect()
+}
+
+#[test]
+fn modified_expenses_notify_the_old_and_new_participants() {
+    let alice = user();
+    let bob = user();
+    let carol = user();
+    let dave = user();
+    let g = group(&alice);
+    for u in [&bob, &carol, &dave] {
+        add_member(&alice, &g, u);
+    }
+    // paid by bob, divided between bob and carol
+    let e = expense(&bob, &g, 10.0, &[&bob, &carol]);
+    for u in [&alice, &bob, &carol, &dave] {
+        clear_notifications(u);
+    }
+
+    // now paid by alice and divided between alice and dave
+    let res = alice
+        .client
+        .put(format!("/groups/{}/expenses/{}", g.id, e.id))
+        .json(&json!({
+            "desc": "dinner",
+            "total_amount": 10.0,
+            "paid_by": alice.id,
+            "division": [(alice.id, 5.0), (dave.id, 5.0)],
+        }))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+
+    for u in [&bob, &carol, &dave] {
+        assert_eq!(kinds(u), ["EXPENSE_MODIFIED"]);
+    }
+    // who made the change is not told
+    assert!(kinds(&alice).is_empty());
+}
+
+#[test]
+fn membership_changes_notify_the_member() {
+    let r = roles();
+    let invitee = user();
+    // wants to know everything, but not what concerns only the others
+    assert_eq!(
+        status(
+            &r.member.client,
+            Method::Put,
+            "/notifications/preferences/ALL".to_owned(),
+            None
+        ),
+        Status::Ok
+    );
+    clear_notifications(&r.member);
+
+    let uri = format!("/groups/{}/members/invite", r.group.id);
+    let body = json!({ "email": invitee.email, "message": null });
+    assert_eq!(
+        status(&r.admin.client, Method::Post, uri, Some(body)),
+        Status::Ok
+    );
+    let n = notifications(&invitee);
+    assert_eq!(n.len(), 1);
+    assert_eq!(n[0].notification_type.as_deref(), Some("GROUP_INVITE"));
+    assert_eq!(n[0].group_id, Some(r.group.id));
+    assert_eq!(n[0].user_id, Some(r.admin.id));
+
+    add_member(&r.admin, &r.group, &invitee);
+    let admins = format!("/groups/{}/admins/{}", r.group.id, invitee.id);
+    for method in [Method::Post, Method::Delete] {
+        assert_eq!(
+            status(&r.admin.client, method, admins.clone(), None),
+            Status::Ok
+        );
+    }
+    assert_eq!(
+        status(
+            &r.admin.client,
+            Method::Delete,
+            format!("/groups/{}/members/{}", r.group.id, invitee.id),
+            None
+        ),
+        Status::Ok
+    );
+
+    assert_eq!(
+        kinds(&invitee),
+        [
+            "REMOVED_FROM_GROUP",
+            "ADMIN_DEMOTION",
+            "ADMIN_PROMOTION",
+            "MEMBER_ADDED",
+            "GROUP_INVITE"
+        ]
+    );
+    assert!(
+        notifications(&invitee)
+            .iter()
+            .all(|n| n.group_id == Some(r.group.id))
+    );
+    assert!(kinds(&r.member).is_empty());
+    assert!(kinds(&r.admin).is_empty());
+}
+
+#[test]
+fn group_deletion_notifies_the_members() {
+    let r = roles();
+
+    assert_eq!(
+        status(
+            &r.admin.client,
+            Method::Delete,
+            format!("/groups/{}", r.group.id),
+            None
+        ),
+        Status::Ok
+    );
+
+    let n = notifications(&r.member);
+    assert_eq!(n[0].notification_type.as_deref(), Some("GROUP_DELETED"));
+    assert_eq!(n[0].group_id, Some(r.group.id));
+    assert_eq!(n[0].user_id, Some(r.admin.id));
+    assert!(kinds(&r.admin).is_empty());
+    assert!(kinds(&r.outsider).is_empty());
+}
@@ -0,0 +1,196 @@
+//! tests for the history of the group expenses in `api/history.rs`
+
+use rocket::http::{Method, Status};
+use serde_json::json;
+
+use super::{TestUser, add_member, client, expense, group, notifications, status, user};
+use crate::api::history::ExpenseHistoryEntry;
+use crate::models::{Expense, Group, Notification};
+
+fn history(user: &TestUser, group: &Group, expense: &Expense) -> Vec<ExpenseHistoryEntry> {
+    let res = user
+        .client
+        .get(format!(
+            "/groups/{}/expenses/{}/history",
+            group.id, expense.id
+        ))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json().expect("history in response")
+}
+
+fn update(user: &TestUser, group: &Group, expense: &Expense, body: serde_json::Value) {
+    let res = user
+        .client
+        .put(format!("/groups/{}/expenses/{}", group.id, expense.id))
+        .json(&body)
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+}
+
+#[test]
+fn history_is_for_members_only() {
+    let admin = user();
+    let outsider = user();
+    let g = group(&admin);
+    let e = expense(&admin, &g, 30.0, &[&admin]);
+    let uri = format!("/groups/{}/expenses/{}/history", g.id, e.id);
+
+    let anonymous = client();
+    assert_eq!(
+        status(&anonymous, Method::Get, uri.clone(), None),
+        Status::Unauthorized
+    );
+    assert_eq!(
+        status(&outsider.client, Method::Get, uri, None),
+        Status::Forbidden
+    );
+
+    // the expense has to belong to the group
+    let other = group(&admin);
+    assert_eq!(
+        status(
+            &admin.client,
+            Method::Get,
+            format!("/groups/{}/expenses/{}/history", other.id, e.id),
+            None
+        ),
+        Status::NotFound
+    );
+}
+
+#[test]
+fn every_change_is_a_revision() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    let e = expense(&bob, &g, 30.0, &[&alice, &bob]);
+
+    let h = history(&alice, &g, &e);
+    assert_eq!(h.len(), 1);
+    assert_eq!(h[0].revision, 1);
+    assert_eq!(h[0].changed_by, Some(bob.id));
+    assert!(h[0].changed.is_empty());
+    assert!(h[0].old.is_none());
+    assert_eq!(h[0].new.total_amount, 30.0);
+    let mut division = vec![(alice.id, Some(15.0)), (bob.id, Some(15.0))];
+    division.sort_by_key(|(u, _)| *u);
+    assert_eq!(h[0].new.division, division);
+
+    // the admin raises the amount and bob pays for all of it
+    update(
+        &alice,
+        &g,
+        &e,
+        json!({
+            "desc": e.desc,
+            "total_amount": 40.0,
+            "paid_by": bob.id,
+            "division": [(alice.id, 40.0)],
+        }),
+    );
+    // bob only fixes the description
+    update(
+        &bob,
+        &g,
+        &e,
+        json!({
+            "desc": "groceries",
+            "total_amount": 40.0,
+            "paid_by": bob.id,
+            "division": [(alice.id, 40.0)],
+        }),
+    );
+
+    let h = history(&bob, &g, &e);
+    assert_eq!(h.iter().map(|r| r.revision).collect::<Vec<_>>(), [1, 2, 3]);
+
+    assert_eq!(h[1].changed_by, Some(alice.id));
+    assert_eq!(h[1].changed, ["total_amount", "division"]);
+    let old = h[1].old.as_ref().unwrap();
+    assert_eq!(old, &h[0].new);
+    assert_eq!(h[1].new.total_amount, 40.0);
+    assert_eq!(h[1].new.division, [(alice.id, Some(40.0))]);
+
+    assert_eq!(h[2].changed_by, Some(bob.id));
+    assert_eq!(h[2].changed, ["desc"]);
+    assert_eq!(h[2].old.as_ref().unwrap().desc, e.desc);
+    assert_eq!(h[2].new.desc, "groceries");
+
+    // the history goes away with the expense
+    let res = bob
+        .client
+        .delete(format!("/groups/{}/expenses/{}", g.id, e.id))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    assert_eq!(
+        status(
+            &bob.client,
+            Method::Get,
+            format!("/groups/{}/expenses/{}/history", g.id, e.id),
+            None
+        ),
+        Status::NotFound
+    );
+}
+
+#[test]
+fn modified_notifications_refer_to_the_revision() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    let e = expense(&alice, &g, 20.0, &[&alice, &bob]);
+
+    let modified = |bob: &TestUser| -> Vec<Notification> {
+        notifications(bob)
+            .into_iter()
+            .filter(|n| n.notification_type.as_deref() == Some("EXPENSE_MODIFIED"))
+            .filter(|n| n.expense_id == Some(e.id))
+            .collect()
+    };
+    let change = |total: f64| {
+        update(
+            &alice,
+            &g,
+            &e,
+            json!({
+                "desc": e.desc,
+                "total_amount": total,
+                "paid_by": alice.id,
+                "division": [(alice.id, total / 2.0), (bob.id, total / 2.0)],
+            }),
+        )
+    };
+
+    // the second change is collapsed into the unread notification of the first one
+    change(30.0);
+    change(50.0);
+    let h = history(&bob, &g, &e);
+    let n = modified(&bob);
+    assert_eq!(n.len(), 1);
+    assert_eq!(n[0].revision_id, Some(h[2].id));
+
+    let res = bob
+        .client
+        .put(format!("/notifications/{}/read", n[0].id))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    change(60.0);
+    let h = history(&bob, &g, &e);
+    let mut referenced: Vec<i32> = modified(&bob)
+        .into_iter()
+        .map(|n| n.revision_id.expect("modification refers to its revision"))
+        .collect();
+    referenced.sort();
+    assert_eq!(referenced, [h[2].id, h[3].id]);
+
+    // the other notifications do not
+    assert!(
+        notifications(&bob)
+            .into_iter()
+            .filter(|n| n.notification_type.as_deref() == Some("NEW_EXPENSE"))
+            .all(|n| n.revision_id.is_none())
+    );
+}
@@ -0,0 +1,186 @@
+//! tests for the invite links in `api/invite_links.rs`
+
+use chrono::{Duration, Utc};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use rocket::http::{Method, Status};
+use serde_json::{Value, json};
+
+use super::{TestUser, add_member, client, group, notifications, status, user};
+use crate::api::invite_links::InvitePreview;
+use crate::establish_connection;
+use crate::models::{Group, GroupInviteLink, GroupMember};
+use crate::schema::group_invite_links;
+
+fn create_link(admin: &TestUser, g: &Group, body: Value) -> GroupInviteLink {
+    let res = admin
+        .client
+        .post(format!("/groups/{}/invite-links", g.id))
+        .json(&body)
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json::<GroupInviteLink>().expect("invite link")
+}
+
+fn join(user: &TestUser, link: &GroupInviteLink) -> Status {
+    status(
+        &user.client,
+        Method::Post,
+        format!("/invites/{}/join", link.token),
+        None,
+    )
+}
+
+fn preview(link: &GroupInviteLink) -> Status {
+    status(
+        &client(),
+        Method::Get,
+        format!("/invites/{}", link.token),
+        None,
+    )
+}
+
+#[test]
+fn invite_links_are_managed_by_admins() {
+    let admin = user();
+    let member = user();
+    let g = group(&admin);
+    add_member(&admin, &g, &member);
+    let uri = format!("/groups/{}/invite-links", g.id);
+
+    assert_eq!(
+        status(&member.client, Method::Get, uri.clone(), None),
+        Status::Forbidden
+    );
+    assert_eq!(
+        status(&member.client, Method::Post, uri.clone(), Some(json!({}))),
+        Status::Forbidden
+    );
+    for body in [
+        json!({ "valid_for_hours": 0 }),
+        json!({ "valid_for_hours": 30 * 24 + 1 }),
+        json!({ "max_uses": 0 }),
+    ] {
+        assert_eq!(
+            status(&admin.client, Method::Post, uri.clone(), Some(body.clone())),
+            Status::BadRequest,
+            "{} was accepted",
+            body
+        );
+    }
+
+    let link = create_link(&admin, &g, json!({}));
+    assert_eq!(link.created_by, Some(admin.id));
+    assert_eq!(link.uses, 0);
+    assert!(link.max_uses.is_none());
+    assert!(link.expires_at - link.created_at == Duration::days(7));
+    assert_ne!(create_link(&admin, &g, json!({})).token, link.token);
+
+    let res = admin.client.get(uri.clone()).dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    assert_eq!(res.into_json::<Vec<GroupInviteLink>>().unwrap().len(), 2);
+
+    let revoke = format!("{}/{}", uri, link.id);
+    assert_eq!(
+        status(&member.client, Method::Delete, revoke.clone(), None),
+        Status::Forbidden
+    );
+    assert_eq!(preview(&link), Status::Ok);
+    assert_eq!(
+        status(&admin.client, Method::Delete, revoke.clone(), None),
+        Status::Ok
+    );
+    assert_eq!(
+        status(&admin.client, Method::Delete, revoke, None),
+        Status::NotFound
+    );
+    assert_eq!(preview(&link), Status::Gone);
+    assert_eq!(join(&user(), &link), Status::Gone);
+}
+
+#[test]
+fn users_join_with_a_link() {
+    let admin = user();
+    let g = group(&admin);
+    let link = create_link(&admin, &g, json!({ "max_uses": 2 }));
+
+    // the group is shown to who has the link, even before logging in
+    assert_eq!(
+        status(&client(), Method::Get, "/invites/unknown".to_owned(), None),
+        Status::NotFound
+    );
+    let anonymous = client();
+    let res = anonymous.get(format!("/invites/{}", link.token)).dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let p = res.into_json::<InvitePreview>().expect("preview");
+    assert_eq!(p.group_id, g.id);
+    assert_eq!(p.group_name, g.group_name);
+    assert_eq!(p.members, 1);
+    assert_eq!(p.expires_at, link.expires_at);
+
+    assert_eq!(
+        status(
+            &client(),
+            Method::Post,
+            format!("/invites/{}/join", link.token),
+            None
+        ),
+        Status::Unauthorized
+    );
+    assert_eq!(join(&admin, &link), Status::Conflict);
+
+    let first = user();
+    let second = user();
+    let res = first
+        .client
+        .post(format!("/invites/{}/join", link.token))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    assert_eq!(res.into_json::<Group>().expect("group").id, g.id);
+    assert_eq!(join(&first, &link), Status::Conflict);
+    assert_eq!(join(&second, &link), Status::Ok);
+
+    // the admin is told who joined, not the users that joined
+    assert!(notifications(&first).is_empty());
+    let joined = notifications(&admin);
+    assert_eq!(joined.len(), 2);
+    assert!(joined.iter().all(
+        |n| n.notification_type.as_deref() == Some("MEMBER_JOINED") && n.group_id == Some(g.id)
+    ));
+    let mut actors: Vec<_> = joined.iter().map(|n| n.user_id).collect();
+    actors.sort();
+    assert_eq!(actors, [Some(first.id), Some(second.id)]);
+    // used up
+    assert_eq!(join(&user(), &link), Status::Gone);
+
+    let res = admin
+        .client
+        .get(format!("/groups/{}/members", g.id))
+        .dispatch();
+    let members: Vec<i32> = res
+        .into_json::<Vec<GroupMember>>()
+        .unwrap()
+        .iter()
+        .map(|m| m.user_id)
+        .collect();
+    assert!(members.contains(&first.id) && members.contains(&second.id));
+    let res = admin
+        .client
+        .get(format!("/groups/{}/invite-links", g.id))
+        .dispatch();
+    assert_eq!(res.into_json::<Vec<GroupInviteLink>>().unwrap()[0].uses, 2);
+}
+
+#[test]
+fn expired_links_cannot_be_used() {
+    let admin = user();
+    let g = group(&admin);
+    let link = create_link(&admin, &g, json!({ "valid_for_hours": 1 }));
+
+    diesel::update(group_invite_links::table.filter(group_invite_links::id.eq(link.id)))
+        .set(group_invite_links::expires_at.eq(Utc::now().naive_utc() - Duration::minutes(1)))
+        .execute(&mut establish_connection())
+        .expect("link expired");
+
+    assert_eq!(preview(&link), Status::Gone);
+    assert_eq!(join(&user(), &link), Status::Gone);
+}
@@ -0,0 +1,136 @@
+//! tests for the live updates of the groups in `api/live.rs`
+
+use rocket::http::{Method, Status};
+use rocket::local::blocking::LocalResponse;
+use serde_json::json;
+
+use super::{add_member, client, expense, figment, group, logged_in, status, user};
+use crate::api::live::{GroupEvent, GroupEventKind};
+
+/// the events sent by a stream until it ends
+fn events(res: LocalResponse<'_>) -> Vec<GroupEvent> {
+    assert_eq!(res.status(), Status::Ok);
+    res.into_string()
+        .expect("events in response")
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(|data| serde_json::from_str(data.trim()).expect("group event"))
+        .collect()
+}
+
+#[test]
+fn live_updates_are_for_members_only() {
+    let admin = user();
+    let outsider = user();
+    let g = group(&admin);
+    let uri = format!("/groups/{}/live", g.id);
+
+    assert_eq!(
+        status(&client(), Method::Get, uri.clone(), None),
+        Status::Unauthorized
+    );
+    assert_eq!(
+        status(&outsider.client, Method::Get, uri, None),
+        Status::Forbidden
+    );
+}
+
+#[test]
+fn members_see_the_changes_to_the_group() {
+    let alice = user();
+    let bob = user();
+    let carol = user();
+    let g = group(&alice);
+    let other = group(&alice);
+    add_member(&alice, &g, &bob);
+
+    let stream = logged_in(figment(), &bob);
+    let res = stream.get(format!("/groups/{}/live", g.id)).dispatch();
+
+    let e = expense(&alice, &g, 10.0, &[&alice, &bob]);
+    // nothing about the other groups
+    expense(&alice, &other, 10.0, &[&alice]);
+    let updated = alice
+        .client
+        .put(format!("/groups/{}/expenses/{}", g.id, e.id))
+        .json(&json!({
+            "desc": e.desc,
+            "total_amount": 20.0,
+            "paid_by": alice.id,
+            "division": [(alice.id, 10.0), (bob.id, 10.0)],
+        }))
+        .dispatch()
+        .status();
+    assert_eq!(updated, Status::Ok);
+    for (method, uri) in [
+        (
+            Method::Delete,
+            format!("/groups/{}/expenses/{}", g.id, e.id),
+        ),
+        (
+            Method::Post,
+            format!("/groups/{}/expenses/{}/restore", g.id, e.id),
+        ),
+        (Method::Post, format!("/groups/{}/admins/{}", g.id, bob.id)),
+        (
+            Method::Delete,
+            format!("/groups/{}/admins/{}", g.id, bob.id),
+        ),
+    ] {
+        assert_eq!(status(&alice.client, method, uri, None), Status::Ok);
+    }
+    add_member(&alice, &g, &carol);
+    // removing bob ends his stream
+    assert_eq!(
+        status(
+            &alice.client,
+            Method::Delete,
+            format!("/groups/{}/members/{}", g.id, bob.id),
+            None
+        ),
+        Status::Ok
+    );
+
+    let sent = events(res);
+    assert_eq!(
+        sent.iter().map(|e| e.kind).collect::<Vec<_>>(),
+        [
+            GroupEventKind::ExpenseCreated,
+            GroupEventKind::ExpenseUpdated,
+            GroupEventKind::ExpenseDeleted,
+            GroupEventKind::ExpenseRestored,
+            GroupEventKind::AdminPromoted,
+            GroupEventKind::AdminDemoted,
+            GroupEventKind::MemberAdded,
+            GroupEventKind::MemberRemoved,
+        ]
+    );
+    assert!(sent.iter().all(|e| e.group_id == g.id));
+    assert!(sent.iter().all(|e| e.actor == Some(alice.id)));
+    assert!(sent[..4].iter().all(|ev| ev.expense_id == Some(e.id)));
+    assert_eq!(sent[4].member_id, Some(bob.id));
+    assert_eq!(sent[6].member_id, Some(carol.id));
+    assert_eq!(sent[7].member_id, Some(bob.id));
+}
+
+#[test]
+fn deleting_the_group_ends_the_stream() {
+    let alice = user();
+    let g = group(&alice);
+
+    let stream = logged_in(figment(), &alice);
+    let res = stream.get(format!("/groups/{}/live", g.id)).dispatch();
+    assert_eq!(
+        status(
+            &alice.client,
+            Method::Delete,
+            format!("/groups/{}", g.id),
+            None
+        ),
+        Status::Ok
+    );
+
+    let sent = events(res);
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].kind, GroupEventKind::GroupDeleted);
+}
@@ -0,0 +1,268 @@
+//! test harness: every test builds its own rocket instance through the local client, all of them
+//! share a single test database that is migrated once per test run.
+//!
+//! with the default `sqlite` feature the database is a temporary file, with the `postgres` feature
+//! the tests run against the database pointed to by `TEST_DATABASE_URL`, e.g.
+//! `TEST_DATABASE_URL=postgres://localhost/splitsmart_test cargo test --no-default-features --features postgres`
+
+use chrono::{Duration, Utc};
+use rocket::figment::Figment;
+use rocket::http::{Method, Status};
+use rocket::local::blocking::Client;
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+use crate::api::pagination::Page;
+use crate::models::{Expense, Group, Notification};
+use crate::storage::LocalStorage;
+
+mod attachments;
+mod benchmarks;
+mod categories;
+mod comments;
+mod config;
+mod cors;
+mod database;
+mod emails;
+mod expenses;
+mod friends;
+mod groups;
+mod history;
+mod invite_links;
+mod live;
+mod notifications;
+mod pagination;
+mod receipts;
+mod recurring;
+mod reminders;
+mod trash;
+mod users;
+mod webhooks;
+
+static DATABASE_URL: OnceLock<String> = OnceLock::new();
+
+#[cfg(feature = "sqlite")]
+fn test_database_url() -> String {
+    let path = std::env::temp_dir().join(format!("splitsmart-test-{}.sqlite", Uuid::new_v4()));
+    format!("file:{}", path.display())
+}
+
+#[cfg(feature = "postgres")]
+fn test_database_url() -> String {
+    std::env::var("TEST_DATABASE_URL")
+        .expect("TEST_DATABASE_URL must be set to run the tests against postgres")
+}
+
+/// url of the test database, migrated the first time it is asked for
+fn database_url() -> &'static str {
+    DATABASE_URL.get_or_init(|| {
+        let url = test_database_url();
+        crate::db::set_database_url(&url).expect("database url not set yet");
+        crate::db::run_pending_migrations().expect("could not migrate test database");
+        url
+    })
+}
+
+static ATTACHMENTS_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// where the attachments of this test run are stored, a fresh temporary directory
+pub fn attachments_dir() -> &'static Path {
+    ATTACHMENTS_DIR.get_or_init(|| {
+        std::env::temp_dir().join(format!("splitsmart-test-attachments-{}", Uuid::new_v4()))
+    })
+}
+
+/// the configuration of the application pointed to the test database and attachment directory,
+/// the recurring expenses are only added, the trash only purged, the webhooks only called and the
+/// automatic reminders only sent when the tests ask for it
+pub fn figment() -> Figment {
+    crate::config::figment()
+        .merge(("database_url", database_url()))
+        .merge(("recurring.enabled", false))
+        .merge(("trash.purge_enabled", false))
+        .merge(("webhooks.enabled", false))
+        .merge(("webhooks.allow_private_hosts", true))
+        .merge(("reminders.enabled", false))
+        .merge(("attachments.dir", attachments_dir()))
+}
+
+/// a fresh rocket instance that keeps track of cookies, ready to be logged in
+pub fn client() -> Client {
+    client_with(figment())
+}
+
+/// like [`client`], with a custom configuration
+pub fn client_with(figment: Figment) -> Client {
+    Client::tracked(crate::app(figment)).expect("valid rocket instance")
+}
+
+/// dispatches a request on `client` and returns the response status, used to check authorization
+pub fn status(client: &Client, method: Method, uri: String, body: Option<Value>) -> Status {
+    let req = client.req(method, uri);
+    match body {
+        Some(b) => req.json(&b).dispatch().status(),
+        None => req.dispatch().status(),
+    }
+}
+
+/// returns a string that has not been used by any other test, to avoid clashes on unique columns
+pub fn unique(prefix: &str) -> String {
+    format!("{}-{}", prefix, Uuid::new_v4().simple())
+}
+
+/// a registered user, with a client that carries its session cookie
+pub struct TestUser {
+    pub id: i32,
+    pub email: String,
+    pub client: Client,
+}
+
+pub const PASSWORD: &str = "correct horse battery staple";
+
+/// registers a new user and logs it in on its own client
+pub fn user() -> TestUser {
+    let client = client();
+    let email = format!("{}@example.com", unique("user"));
+
+    let status = client
+        .post("/user/register")
+        .json(&json!({ "username": unique("user"), "email": email, "password": PASSWORD }))
+        .dispatch()
+        .status();
+    assert_eq!(status, Status::Ok);
+
+    let id = {
+        let res = client
+            .post("/user/login")
+            .json(&json!({ "email": email, "password": PASSWORD }))
+            .dispatch();
+        assert_eq!(res.status(), Status::Ok);
+        res.into_json::<i32>().expect("login returns the user id")
+    };
+
+    TestUser { id, email, client }
+}
+
+/// a client of an application configured by `figment`, with `user` logged in
+pub fn logged_in(figment: Figment, user: &TestUser) -> Client {
+    let client = client_with(figment);
+    let status = client
+        .post("/user/login")
+        .json(&json!({ "email": user.email, "password": PASSWORD }))
+        .dispatch()
+        .status();
+    assert_eq!(status, Status::Ok);
+    client
+}
+
+/// creates a group with `owner` as its only member and admin
+pub fn group(owner: &TestUser) -> Group {
+    let res = owner
+        .client
+        .post("/groups")
+        .json(&json!({ "name": unique("group"), "description": null }))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json::<Group>().expect("group in response")
+}
+
+/// adds `member` to `group`, the request is made by `admin`
+pub fn add_member(admin: &TestUser, group: &Group, member: &TestUser) {
+    let res = admin
+        .client
+        .post(format!("/groups/{}/members", group.id))
+        .json(&json!({ "user_id": member.id }))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+}
+
+/// adds a group expense paid by `payer`, split evenly between `participants`
+pub fn expense(payer: &TestUser, group: &Group, total: f64, participants: &[&TestUser]) -> Expense {
+    let share = total / participants.len() as f64;
+    let division: Vec<(i32, f64)> = participants.iter().map(|u| (u.id, share)).collect();
+
+    let res = payer
+        .client
+        .post(format!("/groups/{}/expenses", group.id))
+        .json(&json!({
+            "desc": unique("expense"),
+            "total_amount": total,
+            "paid_by": payer.id,
+            "division": division,
+        }))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json::<Expense>().expect("expense in response")
+}
+
+/// adds a private expense paid by `payer`, split evenly between `participants`
+pub fn private_expense(payer: &TestUser, total: f64, participants: &[&TestUser]) -> Expense {
+    let share = total / participants.len() as f64;
+    let division: Vec<(i32, f64)> = participants.iter().map(|u| (u.id, share)).collect();
+
+    let res = payer
+        .client
+        .post("/expenses")
+        .json(&json!({
+            "desc": unique("expense"),
+            "total_amount": total,
+            "paid_by": payer.id,
+            "division": division,
+        }))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json::<Expense>().expect("expense in response")
+}
+
+/// all the notifications received by `user`
+pub fn notifications(user: &TestUser) -> Vec<Notification> {
+    let res = user.client.get("/notifications").dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json::<Page<Notification>>()
+        .expect("notifications in response")
+        .items
+}
+
+/// deletes all the notifications of `user`, e.g. the ones about being added to the groups of a
+/// test, so that only the ones that follow are counted
+pub fn clear_notifications(user: &TestUser) {
+    for read in [true, false] {
+        let res = user
+            .client
+            .delete(format!("/notifications?read={}", read))
+            .dispatch();
+        assert_eq!(res.status(), Status::Ok);
+    }
+}
+
+/// pretends that the expense `id` was moved to the trash `days` ago
+pub fn trashed_expense(id: i32, days: i64) {
+    use crate::schema::expenses;
+    use diesel::prelude::*;
+
+    diesel::update(expenses::table.filter(expenses::id.eq(id)))
+        .set(expenses::deleted_at.eq(Utc::now().naive_utc() - Duration::days(days)))
+        .execute(&mut crate::establish_connection())
+        .expect("expense moved to the trash");
+}
+
+/// pretends that the group `id` was moved to the trash `days` ago
+pub fn trashed_group(id: i32, days: i64) {
+    use crate::schema::groups;
+    use diesel::prelude::*;
+
+    diesel::update(groups::table.filter(groups::id.eq(id)))
+        .set(groups::deleted_at.eq(Utc::now().naive_utc() - Duration::days(days)))
+        .execute(&mut crate::establish_connection())
+        .expect("group moved to the trash");
+}
+
+/// purges what has been in the trash for longer than the default retention window, like the
+/// background task does
+pub fn purge_trash() -> (usize, usize) {
+    let retention = crate::config::TrashConfig::default().retention();
+    let storage = LocalStorage::new(attachments_dir()).expect("attachment directory");
+    crate::api::trash::purge(Utc::now().naive_utc() - retention, &storage).expect("trash purged")
+}
@@ -0,0 +1,437 @@
+//! tests for the routes in `api/notifications.rs`
+
+use rocket::http::{Header, Method, Status};
+use rocket::local::blocking::{Client, LocalResponse};
+use std::sync::{Arc, Mutex};
+
+use super::{
+    TestUser, add_member, clear_notifications, client, expense, figment, group, logged_in,
+    notifications, private_expense, status, user,
+};
+use crate::establish_connection;
+use crate::models::Notification;
+use crate::notify::{
+    Channel, Notice, NotificationKind, NotificationPreference, notify, register_channel,
+};
+use diesel::connection::Connection;
+
+#[test]
+fn routes_require_authentication() {
+    let anonymous = client();
+
+    for (method, uri) in [
+        (Method::Get, "/notifications"),
+        (Method::Put, "/notifications/1/read"),
+        (Method::Put, "/notifications/read-all"),
+        (Method::Get, "/notifications/unread-count"),
+        (Method::Delete, "/notifications/1"),
+        (Method::Delete, "/notifications?read=true"),
+        (Method::Get, "/notifications/stream"),
+        (Method::Get, "/notifications/preferences"),
+        (Method::Put, "/notifications/preferences/ALL"),
+    ] {
+        assert_eq!(
+            status(&anonymous, method, uri.to_owned(), None),
+            Status::Unauthorized
+        );
+    }
+}
+
+#[test]
+fn group_expense_notifies_participants() {
+    let alice = user();
+    let bob = user();
+    let carol = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    add_member(&alice, &g, &carol);
+    clear_notifications(&bob);
+    clear_notifications(&carol);
+
+    let e = expense(&alice, &g, 10.0, &[&alice, &bob]);
+
+    let n = notifications(&bob);
+    assert_eq!(n.len(), 1);
+    assert_eq!(n[0].notification_type.as_deref(), Some("NEW_EXPENSE"));
+    assert_eq!(n[0].group_id, Some(g.id));
+    assert_eq!(n[0].expense_id, Some(e.id));
+    assert!(!n[0].read);
+
+    // only the participants are notified
+    assert!(notifications(&carol).is_empty());
+}
+
+#[test]
+fn read_notification() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    expense(&alice, &g, 10.0, &[&alice, &bob]);
+    let nid = notifications(&bob)[0].id;
+    let uri = format!("/notifications/{}/read", nid);
+
+    // notifications of other users cannot be touched
+    assert_eq!(
+        status(&alice.client, Method::Put, uri.clone(), None),
+        Status::NotFound
+    );
+
+    let res = bob.client.put(uri).dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    assert!(res.into_json::<Notification>().unwrap().read);
+    assert!(notifications(&bob)[0].read);
+}
+
+fn unread_count(user: &TestUser) -> i64 {
+    let res = user.client.get("/notifications/unread-count").dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json().expect("count in response")
+}
+
+#[test]
+fn notifications_can_be_read_and_deleted_in_bulk() {
+    let alice = user();
+    let bob = user();
+    let first = group(&alice);
+    let second = group(&alice);
+    add_member(&alice, &first, &bob);
+    add_member(&alice, &second, &bob);
+    clear_notifications(&bob);
+    expense(&alice, &first, 10.0, &[&alice, &bob]);
+    expense(&alice, &first, 20.0, &[&alice, &bob]);
+    expense(&alice, &second, 30.0, &[&alice, &bob]);
+    assert_eq!(unread_count(&bob), 3);
+    assert_eq!(unread_count(&alice), 0);
+
+    // only the notifications about the group
+    let res = bob
+        .client
+        .put(format!("/notifications/read-all?group={}", first.id))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let read = res.into_json::<Vec<Notification>>().unwrap();
+    assert_eq!(read.len(), 2);
+    assert!(read.iter().all(|n| n.read && n.group_id == Some(first.id)));
+    assert_eq!(unread_count(&bob), 1);
+
+    // not everything at once
+    assert_eq!(
+        status(
+            &bob.client,
+            Method::Delete,
+            "/notifications".to_owned(),
+            None
+        ),
+        Status::BadRequest
+    );
+    assert_eq!(unread_count(&bob), 1);
+
+    // the read ones are cleared, the unread one is kept
+    let res = bob.client.delete("/notifications?read=true").dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    assert_eq!(res.into_json::<Vec<Notification>>().unwrap().len(), 2);
+    let left = notifications(&bob);
+    assert_eq!(left.len(), 1);
+    assert_eq!(left[0].group_id, Some(second.id));
+
+    // a single one, only by who received it
+    let uri = format!("/notifications/{}", left[0].id);
+    assert_eq!(
+        status(&alice.client, Method::Delete, uri.clone(), None),
+        Status::NotFound
+    );
+    assert_eq!(
+        status(&bob.client, Method::Delete, uri.clone(), None),
+        Status::Ok
+    );
+    assert_eq!(
+        status(&bob.client, Method::Delete, uri, None),
+        Status::NotFound
+    );
+    assert!(notifications(&bob).is_empty());
+
+    // without a filter every notification is marked
+    expense(&alice, &second, 40.0, &[&alice, &bob]);
+    let res = bob.client.put("/notifications/read-all").dispatch();
+    assert_eq!(res.into_json::<Vec<Notification>>().unwrap().len(), 1);
+    assert_eq!(unread_count(&bob), 0);
+}
+
+fn preference(user: &TestUser) -> NotificationPreference {
+    let res = user.client.get("/notifications/preferences").dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json().expect("preference in response")
+}
+
+fn set_preference(user: &TestUser, preference: &str) -> Status {
+    status(
+        &user.client,
+        Method::Put,
+        format!("/notifications/preferences/{}", preference),
+        None,
+    )
+}
+
+#[test]
+fn preferences_can_be_changed() {
+    let alice = user();
+    assert_eq!(preference(&alice), NotificationPreference::Personal);
+
+    for (value, expected) in [
+        ("ALL", NotificationPreference::All),
+        ("SILENCED", NotificationPreference::Silenced),
+        ("PERSONAL", NotificationPreference::Personal),
+    ] {
+        assert_eq!(set_preference(&alice, value), Status::Ok);
+        assert_eq!(preference(&alice), expected);
+    }
+
+    for value in ["NONE", "all"] {
+        assert_eq!(set_preference(&alice, value), Status::BadRequest);
+    }
+    assert_eq!(preference(&alice), NotificationPreference::Personal);
+}
+
+#[test]
+fn silenced_users_are_not_notified() {
+    let alice = user();
+    let bob = user();
+    let carol = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    add_member(&alice, &g, &carol);
+    clear_notifications(&bob);
+    clear_notifications(&carol);
+    assert_eq!(set_preference(&bob, "SILENCED"), Status::Ok);
+
+    let e = expense(&alice, &g, 10.0, &[&alice, &bob, &carol]);
+    private_expense(&alice, 10.0, &[&alice, &bob, &carol]);
+    assert_eq!(
+        status(
+            &alice.client,
+            Method::Delete,
+            format!("/groups/{}/expenses/{}", g.id, e.id),
+            None
+        ),
+        Status::Ok
+    );
+    assert!(notifications(&bob).is_empty());
+    assert_eq!(notifications(&carol).len(), 3);
+
+    // nothing is kept back, what happens afterwards is notified again
+    assert_eq!(set_preference(&bob, "PERSONAL"), Status::Ok);
+    expense(&alice, &g, 10.0, &[&alice, &bob]);
+    assert_eq!(notifications(&bob).len(), 1);
+}
+
+#[test]
+fn all_notifies_of_everything_in_the_groups() {
+    let alice = user();
+    let bob = user();
+    let carol = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    add_member(&alice, &g, &carol);
+    clear_notifications(&carol);
+    assert_eq!(set_preference(&carol, "ALL"), Status::Ok);
+
+    let e = expense(&alice, &g, 10.0, &[&alice, &bob]);
+    let n = notifications(&carol);
+    assert_eq!(n.len(), 1);
+    assert_eq!(n[0].notification_type.as_deref(), Some("NEW_EXPENSE"));
+    assert_eq!(n[0].expense_id, Some(e.id));
+
+    // only in the groups carol is a member of
+    let other = group(&alice);
+    add_member(&alice, &other, &bob);
+    expense(&alice, &other, 10.0, &[&alice, &bob]);
+    private_expense(&alice, 10.0, &[&alice, &bob]);
+    assert_eq!(notifications(&carol).len(), 1);
+}
+
+#[test]
+fn nobody_is_notified_of_what_they_did() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+
+    let e = expense(&alice, &g, 10.0, &[&alice, &bob]);
+    private_expense(&alice, 10.0, &[&alice, &bob]);
+    assert!(notifications(&alice).is_empty());
+
+    // unless somebody else did it
+    expense(&bob, &g, 10.0, &[&alice, &bob]);
+    assert_eq!(notifications(&alice).len(), 1);
+
+    // the deletion refers to the deleted expense
+    assert_eq!(
+        status(
+            &alice.client,
+            Method::Delete,
+            format!("/groups/{}/expenses/{}", g.id, e.id),
+            None
+        ),
+        Status::Ok
+    );
+    let deleted: Vec<Notification> = notifications(&bob)
+        .into_iter()
+        .filter(|n| n.notification_type.as_deref() == Some("EXPENSE_DELETED"))
+        .collect();
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0].expense_id, Some(e.id));
+    assert_eq!(deleted[0].group_id, Some(g.id));
+    assert_eq!(deleted[0].user_id, Some(alice.id));
+}
+
+/// keeps what it is handed, for the checks on the channels
+struct Recorder(Mutex<Vec<Notification>>);
+
+impl Channel for Recorder {
+    fn send(&self, notification: &Notification) {
+        self.0.lock().unwrap().push(notification.clone());
+    }
+}
+
+#[test]
+fn notifications_reach_the_channels() {
+    let recorder = Arc::new(Recorder(Mutex::new(Vec::new())));
+    let channel: Arc<dyn Channel> = recorder.clone();
+    register_channel(&channel);
+
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    expense(&alice, &g, 10.0, &[&alice, &bob]);
+
+    let sent: Vec<Notification> = recorder
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|n| n.notified_user_id == alice.id || n.notified_user_id == bob.id)
+        .filter(|n| n.notification_type.as_deref() == Some("NEW_EXPENSE"))
+        .cloned()
+        .collect();
+    let stored = notifications(&bob);
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].id, stored[0].id);
+}
+
+/// the events sent by a stream up to when its rocket is shut down, as the id of the event and the
+/// notification it carries
+fn events(client: &Client, res: LocalResponse<'_>) -> Vec<(String, Notification)> {
+    assert_eq!(res.status(), Status::Ok);
+    client.rocket().shutdown().notify();
+    let body = res.into_string().expect("events in response");
+
+    body.split("\n\n")
+        .filter_map(|event| {
+            let mut id = None;
+            let mut data = None;
+            for line in event.lines() {
+                if let Some(v) = line.strip_prefix("id:") {
+                    id = Some(v.trim().to_owned());
+                } else if let Some(v) = line.strip_prefix("data:") {
+                    data = Some(serde_json::from_str(v.trim()).expect("notification in event"));
+                }
+            }
+            Some((id?, data?))
+        })
+        .collect()
+}
+
+#[test]
+fn new_notifications_are_streamed() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    expense(&alice, &g, 10.0, &[&alice, &bob]);
+
+    // what was there before connecting is not sent again
+    let stream = logged_in(figment(), &bob);
+    let res = stream.get("/notifications/stream").dispatch();
+    expense(&alice, &g, 20.0, &[&alice, &bob]);
+    // alice is not notified, and nothing of others is sent to bob
+    expense(&bob, &g, 30.0, &[&alice, &bob]);
+
+    let sent = events(&stream, res);
+    let stored = notifications(&bob);
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].1.id, stored[0].id);
+    assert_eq!(sent[0].1.notified_user_id, bob.id);
+}
+
+#[test]
+fn rolled_back_notifications_are_not_streamed() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    clear_notifications(&bob);
+
+    let stream = logged_in(figment(), &bob);
+    let res = stream.get("/notifications/stream").dispatch();
+    let rolled_back = establish_connection().transaction::<(), _, _>(|conn| {
+        let recorded = notify(
+            conn,
+            &Notice::new(NotificationKind::NewExpense, alice.id).in_group(g.id),
+            &[bob.id],
+        )?;
+        assert_eq!(recorded.len(), 1);
+        Err(diesel::result::Error::RollbackTransaction)
+    });
+    assert!(rolled_back.is_err());
+    expense(&alice, &g, 10.0, &[&alice, &bob]);
+
+    let sent = events(&stream, res);
+    let stored = notifications(&bob);
+    assert_eq!(stored.len(), 1);
+    assert_eq!(
+        sent.iter().map(|(_, n)| n.id).collect::<Vec<_>>(),
+        [stored[0].id]
+    );
+}
+
+#[test]
+fn reconnecting_sends_what_was_missed() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    expense(&alice, &g, 10.0, &[&alice, &bob]);
+
+    let stream = logged_in(figment(), &bob);
+    let res = stream.get("/notifications/stream").dispatch();
+    expense(&alice, &g, 20.0, &[&alice, &bob]);
+    let first = events(&stream, res);
+    assert_eq!(first.len(), 1);
+
+    // created while bob was disconnected
+    expense(&alice, &g, 30.0, &[&alice, &bob]);
+    expense(&alice, &g, 40.0, &[&alice, &bob]);
+
+    let stream = logged_in(figment(), &bob);
+    let res = stream
+        .get("/notifications/stream")
+        .header(Header::new("Last-Event-ID", first[0].0.clone()))
+        .dispatch();
+    let missed = events(&stream, res);
+    let mut expected: Vec<i32> = notifications(&bob).iter().map(|n| n.id).take(2).collect();
+    expected.reverse();
+    assert_eq!(
+        missed.iter().map(|(_, n)| n.id).collect::<Vec<_>>(),
+        expected
+    );
+
+    // an id that was not sent by the stream
+    let res = bob
+        .client
+        .get("/notifications/stream")
+        .header(Header::new("Last-Event-ID", "nonsense"))
+        .dispatch();
+    assert_eq!(res.status(), Status::BadRequest);
+}
@@ -0,0 +1,324 @@
+//! tests for the pagination, filters and sorting of the list endpoints, in `api/pagination.rs`
+
+use chrono::{NaiveDate, Utc};
+use rocket::http::{Method, Status};
+use rocket::local::blocking::Client;
+use serde::de::DeserializeOwned;
+
+use super::{add_member, clear_notifications, expense, group, notifications, status, user};
+use crate::api::expenses::ExpenseWithParticipations;
+use crate::api::pagination::{Cursor, Page};
+use crate::models::{Group, Notification};
+
+fn page<T: DeserializeOwned + Send + 'static>(client: &Client, uri: &str) -> Page<T> {
+    let res = client.get(uri.to_owned()).dispatch();
+    assert_eq!(res.status(), Status::Ok, "GET {}", uri);
+    res.into_json::<Page<T>>().unwrap()
+}
+
+/// follows the cursors from the first page to the last one, collecting the ids of the expenses
+fn walk_expenses(client: &Client, uri: &str) -> Vec<i32> {
+    let mut ids = Vec::new();
+    let mut next = uri.to_owned();
+    loop {
+        let p = page::<ExpenseWithParticipations>(client, &next);
+        assert!(p.items.len() <= 2);
+        ids.extend(p.items.iter().map(|(e, _)| e.id));
+        match p.next_cursor {
+            Some(c) => next = format!("{}&cursor={}", uri, c),
+            None => return ids,
+        }
+    }
+}
+
+#[test]
+fn cursors_round_trip() {
+    let now = Utc::now().naive_utc();
+    let c = Cursor::new(now, 42);
+    assert_eq!(Cursor::decode(&c.encode()), Ok(c));
+    let c = Cursor::new(12.34, 7);
+    assert_eq!(Cursor::decode(&c.encode()), Ok(c));
+    let c = Cursor::new("a.b & c".to_owned(), 3);
+    assert_eq!(Cursor::decode(&c.encode()), Ok(c));
+
+    assert!(Cursor::<f64>::decode("zz").is_err());
+    assert!(Cursor::<f64>::decode(&Cursor::new("x".to_owned(), 1).encode()).is_err());
+}
+
+#[test]
+fn expense_pages_follow_the_sort_order() {
+    let alice = user();
+    let g = group(&alice);
+    let mut created: Vec<(f64, i32)> = [30.0, 10.0, 50.0, 20.0, 40.0]
+        .into_iter()
+        .map(|total| (total, expense(&alice, &g, total, &[&alice]).id))
+        .collect();
+    let base = format!("/groups/{}/expenses", g.id);
+
+    // newest first by default
+    let ids = walk_expenses(&alice.client, &format!("{}?limit=2", base));
+    let mut newest_first: Vec<i32> = created.iter().map(|(_, id)| *id).collect();
+    newest_first.reverse();
+    assert_eq!(ids, newest_first);
+
+    created.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let by_amount: Vec<i32> = created.iter().map(|(_, id)| *id).collect();
+    let uri = format!("{}?limit=2&sort=amount&order=asc", base);
+    assert_eq!(walk_expenses(&alice.client, &uri), by_amount);
+}
+
+#[test]
+fn invalid_page_requests_are_refused() {
+    let alice = user();
+    let g = group(&alice);
+    let base = format!("/groups/{}/expenses", g.id);
+
+    for query in ["limit=0", "limit=101", "cursor=nonsense", "cursor=31"] {
+        assert_eq!(
+            status(
+                &alice.client,
+                Method::Get,
+                format!("{}?{}", base, query),
+                None
+            ),
+            Status::BadRequest,
+            "{}",
+            query
+        );
+    }
+    for query in ["sort=color", "order=up", "from=yesterday"] {
+        assert_eq!(
+            status(
+                &alice.client,
+                Method::Get,
+                format!("{}?{}", base, query),
+                None
+            ),
+            Status::UnprocessableEntity,
+            "{}",
+            query
+        );
+    }
+}
+
+#[test]
+fn expense_filters() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+
+    let shared = expense(&alice, &g, 10.0, &[&alice, &bob]);
+    let by_bob = expense(&bob, &g, 25.0, &[&alice, &bob]);
+    let alone = expense(&alice, &g, 40.0, &[&alice]);
+    let base = format!("/groups/{}/expenses", g.id);
+
+    let ids = |query: &str| {
+        let mut ids: Vec<i32> =
+            page::<ExpenseWithParticipations>(&alice.client, &format!("{}?{}", base, query))
+                .items
+                .iter()
+                .map(|(e, _)| e.id)
+                .collect();
+        ids.sort();
+        ids
+    };
+
+    assert_eq!(ids(&format!("paid_by={}", bob.id)), vec![by_bob.id]);
+    assert_eq!(
+        ids(&format!("participant={}", bob.id)),
+        vec![shared.id, by_bob.id]
+    );
+    assert_eq!(ids("min_amount=20"), vec![by_bob.id, alone.id]);
+    assert_eq!(ids("min_amount=20&max_amount=30"), vec![by_bob.id]);
+
+    let today = Utc::now().date_naive();
+    let day = |d: NaiveDate| d.format("%Y-%m-%d").to_string();
+    let yesterday = day(today.pred_opt().unwrap());
+    let tomorrow = day(today.succ_opt().unwrap());
+    assert_eq!(ids(&format!("from={}&to={}", yesterday, tomorrow)).len(), 3);
+    assert!(ids(&format!("from={}", tomorrow)).is_empty());
+    assert!(ids(&format!("to={}", yesterday)).is_empty());
+}
+
+#[test]
+fn expense_text_search() {
+    let alice = user();
+    let g = group(&alice);
+    let base = format!("/groups/{}/expenses", g.id);
+
+    let mut ids = Vec::new();
+    for desc in ["Pizza night", "pizza_delivery", "Groceries 100%"] {
+        let res = alice
+            .client
+            .post(base.clone())
+            .json(&serde_json::json!({
+                "desc": desc,
+                "total_amount": 10.0,
+                "paid_by": alice.id,
+                "division": [(alice.id, 10.0)],
+            }))
+            .dispatch();
+        assert_eq!(res.status(), Status::Ok);
+        ids.push(res.into_json::<crate::models::Expense>().unwrap().id);
+    }
+
+    let found = |search: &str| {
+        let mut found: Vec<i32> = page::<ExpenseWithParticipations>(
+            &alice.client,
+            &format!("{}?search={}", base, search),
+        )
+        .items
+        .iter()
+        .map(|(e, _)| e.id)
+        .collect();
+        found.sort();
+        found
+    };
+
+    // case insensitive, and the wildcards are matched literally
+    assert_eq!(found("PIZZA"), vec![ids[0], ids[1]]);
+    assert_eq!(found("a_d"), vec![ids[1]]);
+    assert_eq!(found("100%25"), vec![ids[2]]);
+    assert!(found("%25%25").is_empty());
+}
+
+#[test]
+fn notification_filters() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    clear_notifications(&bob);
+    for _ in 0..3 {
+        expense(&alice, &g, 10.0, &[&alice, &bob]);
+    }
+    let first = notifications(&bob)[0].id;
+    bob.client
+        .put(format!("/notifications/{}/read", first))
+        .dispatch();
+
+    let unread = page::<Notification>(&bob.client, "/notifications?read=false");
+    assert_eq!(unread.items.len(), 2);
+    assert!(unread.items.iter().all(|n| !n.read));
+    let read = page::<Notification>(&bob.client, "/notifications?read=true");
+    assert_eq!(read.items.len(), 1);
+    assert_eq!(read.items[0].id, first);
+
+    let p = page::<Notification>(&bob.client, "/notifications?type=NEW_EXPENSE&limit=2");
+    assert_eq!(p.items.len(), 2);
+    assert!(p.next_cursor.is_some());
+    assert!(
+        page::<Notification>(&bob.client, "/notifications?type=EXPENSE_DELETED")
+            .items
+            .is_empty()
+    );
+}
+
+#[test]
+fn group_pages() {
+    let alice = user();
+    let names: Vec<i32> = (0..3).map(|_| group(&alice).id).collect();
+
+    let p = page::<Group>(&alice.client, "/groups?limit=2&order=asc");
+    assert_eq!(p.items.iter().map(|g| g.id).collect::<Vec<_>>(), names[..2]);
+    let next = format!(
+        "/groups?limit=2&order=asc&cursor={}",
+        p.next_cursor.unwrap()
+    );
+    let p = page::<Group>(&alice.client, &next);
+    assert_eq!(p.items.iter().map(|g| g.id).collect::<Vec<_>>(), names[2..]);
+    assert!(p.next_cursor.is_none());
+
+    let p = page::<Group>(&alice.client, "/groups?sort=name");
+    let mut sorted: Vec<String> = p.items.iter().map(|g| g.group_name.clone()).collect();
+    sorted.sort();
+    sorted.reverse();
+    assert_eq!(
+        p.items
+            .iter()
+            .map(|g| g.group_name.clone())
+            .collect::<Vec<_>>(),
+        sorted
+    );
+}
+
+#[test]
+fn list_parameters_are_documented() {
+    let c = super::client();
+    let res = c.get("/openapi.json").dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let spec = res.into_json::<serde_json::Value>().unwrap();
+
+    for (path, names) in [
+        (
+            "/groups/{gid}/expenses",
+            &[
+                "from",
+                "to",
+                "paid_by",
+                "participant",
+                "min_amount",
+                "max_amount",
+                "search",
+                "sort",
+                "order",
+                "cursor",
+                "limit",
+            ][..],
+        ),
+        ("/expenses/", &["search", "sort", "cursor", "limit"][..]),
+        ("/notifications/", &["type", "read", "cursor", "limit"][..]),
+        ("/user/invites", &["order", "cursor", "limit"][..]),
+        ("/groups/", &["sort", "order", "cursor", "limit"][..]),
+    ] {
+        let params = spec["paths"][path]["get"]["parameters"]
+            .as_array()
+            .unwrap_or_else(|| panic!("no parameters documented for {}", path));
+        for name in names {
+            let param = params
+                .iter()
+                .find(|p| p["name"] == *name && p["in"] == "query")
+                .unwrap_or_else(|| panic!("{} is not documented for {}", name, path));
+            // `required` is left out when false
+            assert_ne!(param["required"], true, "{} on {}", name, path);
+        }
+    }
+}
+
+#[test]
+fn expenses_are_sorted_and_filtered_by_their_date() {
+    let alice = user();
+    let g = group(&alice);
+    let base = format!("/groups/{}/expenses", g.id);
+
+    let mut ids = Vec::new();
+    for date in ["2025-03-04", "2025-01-10", "2025-02-20"] {
+        let res = alice
+            .client
+            .post(base.clone())
+            .json(&serde_json::json!({
+                "desc": "backdated",
+                "total_amount": 10.0,
+                "paid_by": alice.id,
+                "division": [(alice.id, 10.0)],
+                "expense_date": date,
+            }))
+            .dispatch();
+        assert_eq!(res.status(), Status::Ok);
+        ids.push(res.into_json::<crate::models::Expense>().unwrap().id);
+    }
+
+    // the newest expense comes first, even if it was entered before the others
+    assert_eq!(
+        walk_expenses(&alice.client, &format!("{}?limit=2", base)),
+        vec![ids[0], ids[2], ids[1]]
+    );
+    assert_eq!(
+        walk_expenses(
+            &alice.client,
+            &format!("{}?limit=2&from=2025-02-01&to=2025-03-04", base)
+        ),
+        vec![ids[0], ids[2]]
+    );
+}
@@ -0,0 +1,269 @@
+//! tests for the itemized receipts in `api/receipts.rs`
+
+use rocket::http::{Method, Status};
+use serde_json::{Value, json};
+
+use super::{TestUser, add_member, client, group, status, unique, user};
+use crate::api::expenses::ExpenseWithParticipations;
+use crate::api::pagination::Page;
+use crate::api::receipts::ReceiptItem;
+use crate::models::{Expense, Group};
+
+/// the amount due by every participant of expense `exid`, ordered by user id
+fn division(user: &TestUser, uri: String, exid: i32) -> Vec<(i32, Option<f64>)> {
+    let res = user.client.get(uri).dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let (_, participations) = res
+        .into_json::<Page<ExpenseWithParticipations>>()
+        .expect("expenses in response")
+        .items
+        .into_iter()
+        .find(|(e, _)| e.id == exid)
+        .expect("expense in the list");
+    let mut division: Vec<(i32, Option<f64>)> = participations
+        .into_iter()
+        .map(|p| (p.user_id, p.amount_due))
+        .collect();
+    division.sort_by_key(|(u, _)| *u);
+    division
+}
+
+fn items(user: &TestUser, group: &Group, exid: i32) -> Vec<ReceiptItem> {
+    let res = user
+        .client
+        .get(format!("/groups/{}/expenses/{}/items", group.id, exid))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json().expect("items in response")
+}
+
+fn receipt(payer: &TestUser, total: f64, items: Value) -> Value {
+    json!({
+        "desc": unique("dinner"),
+        "total_amount": total,
+        "paid_by": payer.id,
+        "items": items,
+    })
+}
+
+fn add(user: &TestUser, uri: String, body: Value) -> Expense {
+    let res = user.client.post(uri).json(&body).dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json().expect("expense in response")
+}
+
+#[test]
+fn tax_and_tip_are_split_in_proportion() {
+    let alice = user();
+    let bob = user();
+    let carol = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    add_member(&alice, &g, &carol);
+
+    // alice consumed 15, bob 13 and carol 3, the 6.20 of tax and tip are 20% more for everyone
+    let body = receipt(
+        &alice,
+        37.2,
+        json!([
+            { "desc": "pizza", "amount": 12.0, "consumers": [alice.id] },
+            { "desc": "pasta", "amount": 10.0, "consumers": [bob.id] },
+            { "desc": "wine", "amount": 9.0, "consumers": [carol.id, bob.id, alice.id] },
+        ]),
+    );
+    let e = add(&alice, format!("/groups/{}/expenses", g.id), body);
+
+    let mut expected = vec![
+        (alice.id, Some(18.0)),
+        (bob.id, Some(15.6)),
+        (carol.id, Some(3.6)),
+    ];
+    expected.sort_by_key(|(u, _)| *u);
+    assert_eq!(
+        division(&bob, format!("/groups/{}/expenses", g.id), e.id),
+        expected
+    );
+
+    let listed = items(&carol, &g, e.id);
+    assert_eq!(
+        listed.iter().map(|i| i.desc.as_str()).collect::<Vec<_>>(),
+        ["pizza", "pasta", "wine"]
+    );
+    assert_eq!(listed[1].amount, 10.0);
+    let mut wine = vec![alice.id, bob.id, carol.id];
+    wine.sort();
+    assert_eq!(listed[2].consumers, wine);
+
+    // the items are for the members of the group only
+    let outsider = user();
+    assert_eq!(
+        status(
+            &outsider.client,
+            Method::Get,
+            format!("/groups/{}/expenses/{}/items", g.id, e.id),
+            None
+        ),
+        Status::Forbidden
+    );
+    assert_eq!(
+        status(
+            &client(),
+            Method::Get,
+            format!("/groups/{}/expenses/{}/items", g.id, e.id),
+            None
+        ),
+        Status::Unauthorized
+    );
+}
+
+#[test]
+fn every_cent_is_assigned() {
+    let alice = user();
+    let bob = user();
+    let carol = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    add_member(&alice, &g, &carol);
+
+    // the first consumer pays the cent that cannot be split, the division sent is ignored
+    let mut body = receipt(
+        &alice,
+        10.0,
+        json!([{ "desc": "fries", "amount": 10.0, "consumers": [carol.id, alice.id, bob.id] }]),
+    );
+    body["division"] = json!([(alice.id, 10.0)]);
+    let e = add(&alice, format!("/groups/{}/expenses", g.id), body);
+
+    let split = division(&alice, format!("/groups/{}/expenses", g.id), e.id);
+    assert_eq!(split.len(), 3);
+    for (u, amount) in split.iter() {
+        let expected = if *u == carol.id { 3.34 } else { 3.33 };
+        assert_eq!(*amount, Some(expected));
+    }
+
+    // a tip that does not split evenly still adds up to the total
+    let body = receipt(
+        &alice,
+        10.0,
+        json!([
+            { "desc": "coffee", "amount": 3.0, "consumers": [alice.id] },
+            { "desc": "coffee", "amount": 3.0, "consumers": [bob.id] },
+            { "desc": "coffee", "amount": 3.0, "consumers": [carol.id] },
+        ]),
+    );
+    let e = add(&alice, format!("/groups/{}/expenses", g.id), body);
+    let split = division(&alice, format!("/groups/{}/expenses", g.id), e.id);
+    let sum: f64 = split.iter().filter_map(|(_, a)| *a).sum();
+    assert!((sum - 10.0).abs() < 1e-9);
+    assert!(
+        split
+            .iter()
+            .all(|(_, a)| *a == Some(3.33) || *a == Some(3.34))
+    );
+}
+
+#[test]
+fn items_have_to_describe_the_expense() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    let uri = format!("/groups/{}/expenses", g.id);
+
+    for items in [
+        json!([]),
+        // worth more than the total
+        json!([{ "desc": "steak", "amount": 30.0, "consumers": [alice.id] }]),
+        json!([{ "desc": "steak", "amount": 10.0, "consumers": [] }]),
+        json!([{ "desc": "steak", "amount": 10.0, "consumers": [bob.id, bob.id] }]),
+        json!([{ "desc": "discount", "amount": -5.0, "consumers": [alice.id] }]),
+        // nothing to split the tip in proportion to
+        json!([{ "desc": "water", "amount": 0.0, "consumers": [alice.id] }]),
+    ] {
+        assert_eq!(
+            status(
+                &alice.client,
+                Method::Post,
+                uri.clone(),
+                Some(receipt(&alice, 20.0, items))
+            ),
+            Status::BadRequest
+        );
+    }
+}
+
+#[test]
+fn updating_an_expense_replaces_its_items() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    let uri = format!("/groups/{}/expenses", g.id);
+
+    let e = add(
+        &alice,
+        uri.clone(),
+        receipt(
+            &alice,
+            20.0,
+            json!([{ "desc": "salad", "amount": 20.0, "consumers": [alice.id] }]),
+        ),
+    );
+
+    let e_uri = format!("/groups/{}/expenses/{}", g.id, e.id);
+    let res = alice
+        .client
+        .put(e_uri.clone())
+        .json(&receipt(
+            &alice,
+            24.0,
+            json!([
+                { "desc": "salad", "amount": 10.0, "consumers": [alice.id] },
+                { "desc": "soup", "amount": 10.0, "consumers": [bob.id] },
+            ]),
+        ))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    assert_eq!(items(&bob, &g, e.id).len(), 2);
+    let mut expected = vec![(alice.id, Some(12.0)), (bob.id, Some(12.0))];
+    expected.sort_by_key(|(u, _)| *u);
+    assert_eq!(division(&bob, uri.clone(), e.id), expected);
+
+    // without items the expense goes back to its division
+    let res = alice
+        .client
+        .put(e_uri)
+        .json(&json!({
+            "desc": e.desc,
+            "total_amount": 24.0,
+            "paid_by": alice.id,
+            "division": [(bob.id, 24.0)],
+        }))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    assert!(items(&bob, &g, e.id).is_empty());
+    assert_eq!(division(&bob, uri, e.id), [(bob.id, Some(24.0))]);
+}
+
+#[test]
+fn private_expenses_can_be_itemized() {
+    let alice = user();
+    let bob = user();
+
+    let e = add(
+        &alice,
+        "/expenses".to_owned(),
+        receipt(
+            &alice,
+            11.0,
+            json!([
+                { "desc": "beer", "amount": 5.0, "consumers": [alice.id] },
+                { "desc": "beer", "amount": 5.0, "consumers": [bob.id] },
+            ]),
+        ),
+    );
+
+    let mut expected = vec![(alice.id, Some(5.5)), (bob.id, Some(5.5))];
+    expected.sort_by_key(|(u, _)| *u);
+    assert_eq!(division(&alice, "/expenses".to_owned(), e.id), expected);
+}
@@ -0,0 +1,332 @@
+//! tests for the recurring expenses in `api/recurring.rs`
+
+use chrono::{Days, NaiveDate, Utc};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use rocket::http::{Method, Status};
+use serde_json::{Value, json};
+use std::time::{Duration, Instant};
+
+use super::{
+    TestUser, add_member, client, client_with, figment, group, notifications, status, user,
+};
+use crate::api::{
+    expenses::ExpenseWithParticipations,
+    pagination::Page,
+    recurring::{MAX_CATCH_UP, RecurringExpenseWithParticipations},
+};
+use crate::establish_connection;
+use crate::models::Group;
+use crate::schema::recurring_expenses;
+
+fn recurring_body(payer: &TestUser, participants: &[&TestUser], rule: Value) -> Value {
+    let division: Vec<(i32, f64)> = participants.iter().map(|u| (u.id, 400.0)).collect();
+    let mut body = json!({
+        "expense": {
+            "desc": "rent",
+            "total_amount": 400.0 * participants.len() as f64,
+            "paid_by": payer.id,
+            "division": division,
+        },
+    });
+    for (k, v) in rule.as_object().unwrap() {
+        body[k] = v.clone();
+    }
+    body
+}
+
+fn create(user: &TestUser, group: &Group, body: Value) -> RecurringExpenseWithParticipations {
+    let res = user
+        .client
+        .post(format!("/groups/{}/recurring", group.id))
+        .json(&body)
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json().expect("recurring expense in response")
+}
+
+fn run(admin: &TestUser, group: &Group) -> Status {
+    status(
+        &admin.client,
+        Method::Post,
+        format!("/groups/{}/recurring/run", group.id),
+        None,
+    )
+}
+
+/// the dates of the expenses of the group, oldest first
+fn expense_dates(user: &TestUser, group: &Group) -> Vec<NaiveDate> {
+    let res = user
+        .client
+        .get(format!("/groups/{}/expenses?order=asc", group.id))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json::<Page<ExpenseWithParticipations>>()
+        .unwrap()
+        .items
+        .into_iter()
+        .map(|(e, _)| e.expense_date)
+        .collect()
+}
+
+fn date(s: &str) -> NaiveDate {
+    s.parse().unwrap()
+}
+
+#[test]
+fn recurring_expenses_authorization() {
+    let admin = user();
+    let member = user();
+    let other = user();
+    let outsider = user();
+    let g = group(&admin);
+    add_member(&admin, &g, &member);
+    add_member(&admin, &g, &other);
+    let uri = format!("/groups/{}/recurring", g.id);
+    let body = recurring_body(
+        &member,
+        &[&member],
+        json!({ "frequency": "WEEKLY", "start_date": "2030-01-01" }),
+    );
+
+    let anonymous = client();
+    for (c, expected) in [
+        (&anonymous, Status::Unauthorized),
+        (&outsider.client, Status::Forbidden),
+    ] {
+        assert_eq!(status(c, Method::Get, uri.clone(), None), expected);
+        assert_eq!(
+            status(c, Method::Post, uri.clone(), Some(body.clone())),
+            expected
+        );
+    }
+
+    assert_eq!(run(&member, &g), Status::Forbidden);
+    assert_eq!(run(&admin, &g), Status::Ok);
+
+    let (r, participations) = create(&member, &g, body.clone());
+    assert_eq!(r.created_by, member.id);
+    assert_eq!(participations.len(), 1);
+    let res = other.client.get(uri.clone()).dispatch();
+    assert_eq!(
+        res.into_json::<Vec<RecurringExpenseWithParticipations>>()
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // only the creator and the admins can delete it
+    let r_uri = format!("{}/{}", uri, r.id);
+    assert_eq!(
+        status(&other.client, Method::Delete, r_uri.clone(), None),
+        Status::Forbidden
+    );
+    assert_eq!(
+        status(&member.client, Method::Delete, r_uri.clone(), None),
+        Status::Ok
+    );
+    assert_eq!(
+        status(&member.client, Method::Delete, r_uri, None),
+        Status::NotFound
+    );
+
+    let (r, _) = create(&member, &g, body);
+    assert_eq!(
+        status(
+            &admin.client,
+            Method::Delete,
+            format!("{}/{}", uri, r.id),
+            None
+        ),
+        Status::Ok
+    );
+}
+
+#[test]
+fn invalid_rules_are_rejected() {
+    let admin = user();
+    let g = group(&admin);
+    let uri = format!("/groups/{}/recurring", g.id);
+
+    for rule in [
+        json!({ "frequency": "MONTHLY", "start_date": "2025-01-01" }),
+        json!({ "frequency": "MONTHLY", "day_of_month": 32, "start_date": "2025-01-01" }),
+        json!({ "frequency": "MONTHLY", "day_of_month": 0, "start_date": "2025-01-01" }),
+        json!({ "frequency": "WEEKLY", "day_of_month": 3, "start_date": "2025-01-01" }),
+        json!({ "frequency": "EVERY_N_DAYS", "interval_days": 0, "start_date": "2025-01-01" }),
+        json!({ "frequency": "EVERY_N_DAYS", "interval_days": 3651, "start_date": "2025-01-01" }),
+        // chrono cannot add this many days
+        json!({ "frequency": "EVERY_N_DAYS", "interval_days": i32::MAX, "start_date": "2025-01-01" }),
+        json!({ "frequency": "WEEKLY", "start_date": "2025-01-01", "end_date": "2024-12-31" }),
+    ] {
+        let body = recurring_body(&admin, &[&admin], rule.clone());
+        assert_eq!(
+            status(&admin.client, Method::Post, uri.clone(), Some(body)),
+            Status::BadRequest,
+            "{}",
+            rule
+        );
+    }
+}
+
+#[test]
+fn monthly_expenses_are_added_once() {
+    let alice = user();
+    let bob = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+
+    // the 31st falls on the last day of the shorter months
+    let (r, _) = create(
+        &bob,
+        &g,
+        recurring_body(
+            &alice,
+            &[&alice, &bob],
+            json!({
+                "frequency": "MONTHLY",
+                "day_of_month": 31,
+                "start_date": "2025-01-15",
+                "end_date": "2025-04-30",
+            }),
+        ),
+    );
+    assert_eq!(r.next_date, date("2025-01-31"));
+
+    assert_eq!(run(&alice, &g), Status::Ok);
+    let expected = ["2025-01-31", "2025-02-28", "2025-03-31", "2025-04-30"].map(date);
+    assert_eq!(expense_dates(&alice, &g), expected);
+
+    // running again adds nothing
+    let res = alice
+        .client
+        .post(format!("/groups/{}/recurring/run", g.id))
+        .dispatch();
+    assert_eq!(res.into_json::<Vec<Value>>().unwrap().len(), 0);
+    assert_eq!(expense_dates(&alice, &g).len(), 4);
+
+    // the participants are notified of every expense, by who created the recurring expense
+    let notified: Vec<_> = notifications(&alice)
+        .into_iter()
+        .filter(|n| n.group_id == Some(g.id))
+        .collect();
+    assert_eq!(notified.len(), 4);
+    assert!(notified.iter().all(|n| {
+        n.notification_type.as_deref() == Some("NEW_EXPENSE")
+            && n.user_id == Some(bob.id)
+            && n.expense_id.is_some()
+    }));
+}
+
+#[test]
+fn interval_expenses_catch_up_to_today() {
+    let admin = user();
+    let g = group(&admin);
+    let today = Utc::now().date_naive();
+
+    let start = today - Days::new(10);
+    let (r, _) = create(
+        &admin,
+        &g,
+        recurring_body(
+            &admin,
+            &[&admin],
+            json!({ "frequency": "EVERY_N_DAYS", "interval_days": 3, "start_date": start }),
+        ),
+    );
+    create(
+        &admin,
+        &g,
+        recurring_body(
+            &admin,
+            &[&admin],
+            json!({ "frequency": "WEEKLY", "start_date": today + Days::new(1) }),
+        ),
+    );
+
+    assert_eq!(run(&admin, &g), Status::Ok);
+    let expected: Vec<NaiveDate> = [10, 7, 4, 1].map(|d| today - Days::new(d)).to_vec();
+    assert_eq!(expense_dates(&admin, &g), expected);
+
+    let res = admin
+        .client
+        .get(format!("/groups/{}/recurring", g.id))
+        .dispatch();
+    let all = res
+        .into_json::<Vec<RecurringExpenseWithParticipations>>()
+        .unwrap();
+    let updated = all.iter().find(|(e, _)| e.id == r.id).unwrap();
+    assert_eq!(updated.0.next_date, today + Days::new(2));
+}
+
+#[test]
+fn catching_up_is_bounded() {
+    let admin = user();
+    let g = group(&admin);
+    let today = Utc::now().date_naive();
+    let daily = |days_ago: usize| {
+        recurring_body(
+            &admin,
+            &[&admin],
+            json!({
+                "frequency": "EVERY_N_DAYS",
+                "interval_days": 1,
+                "start_date": today - Days::new(days_ago as u64),
+            }),
+        )
+    };
+    let added = || {
+        let res = admin
+            .client
+            .post(format!("/groups/{}/recurring/run", g.id))
+            .dispatch();
+        assert_eq!(res.status(), Status::Ok);
+        res.into_json::<Vec<Value>>().unwrap().len()
+    };
+
+    // today is due too
+    assert_eq!(
+        status(
+            &admin.client,
+            Method::Post,
+            format!("/groups/{}/recurring", g.id),
+            Some(daily(MAX_CATCH_UP))
+        ),
+        Status::BadRequest
+    );
+    let (r, _) = create(&admin, &g, daily(MAX_CATCH_UP - 1));
+
+    // e.g. the group was in the trash for a while
+    diesel::update(recurring_expenses::table.find(r.id))
+        .set(recurring_expenses::next_date.eq(today - Days::new(149)))
+        .execute(&mut establish_connection())
+        .expect("recurring expense moved back");
+
+    assert_eq!(added(), MAX_CATCH_UP);
+    assert_eq!(added(), 150 - MAX_CATCH_UP);
+    assert_eq!(added(), 0);
+}
+
+#[test]
+fn scheduler_adds_due_expenses() {
+    let admin = user();
+    let g = group(&admin);
+    let today = Utc::now().date_naive();
+    create(
+        &admin,
+        &g,
+        recurring_body(
+            &admin,
+            &[&admin],
+            json!({ "frequency": "WEEKLY", "start_date": today }),
+        ),
+    );
+
+    // the background task runs right after launch
+    let _server = client_with(figment().merge(("recurring.enabled", true)));
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while expense_dates(&admin, &g).is_empty() {
+        assert!(Instant::now() < deadline, "recurring expense not added");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    assert_eq!(expense_dates(&admin, &g), vec![today]);
+}
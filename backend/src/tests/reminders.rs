@@ -0,0 +1,194 @@
+//! tests for the payment reminders in `api/reminders.rs`
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use rocket::http::{Method, Status};
+use serde_json::{Value, json};
+
+use super::{
+    TestUser, add_member, clear_notifications, expense, group, notifications, status, user,
+};
+use crate::api::reminders::send_due_reminders;
+use crate::models::{Group, PaymentReminder, ReminderSchedule};
+
+fn remind(sender: &TestUser, g: &Group, recipient: &TestUser, message: Value) -> Status {
+    status(
+        &sender.client,
+        Method::Post,
+        format!("/groups/{}/reminders/{}", g.id, recipient.id),
+        Some(json!({ "message": message })),
+    )
+}
+
+fn reminders(user: &TestUser, g: &Group) -> Vec<PaymentReminder> {
+    let res = user
+        .client
+        .get(format!("/groups/{}/reminders", g.id))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json::<Vec<PaymentReminder>>().expect("reminders")
+}
+
+fn schedule(user: &TestUser, g: &Group) -> Option<ReminderSchedule> {
+    let res = user
+        .client
+        .get(format!("/groups/{}/reminders/schedule", g.id))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json::<Option<ReminderSchedule>>()
+        .expect("reminder schedule")
+}
+
+fn set_schedule(user: &TestUser, g: &Group, after_days: Value) -> Status {
+    status(
+        &user.client,
+        Method::Put,
+        format!("/groups/{}/reminders/schedule", g.id),
+        Some(json!({ "after_days": after_days })),
+    )
+}
+
+/// adds an expense of `total` paid by `payer` on behalf of `debtor` alone, `days` days ago
+fn old_expense(payer: &TestUser, g: &Group, total: f64, debtor: &TestUser, days: i64) {
+    let date = Utc::now().date_naive() - Duration::days(days);
+    let res = payer
+        .client
+        .post(format!("/groups/{}/expenses", g.id))
+        .json(&json!({
+            "desc": "old dinner",
+            "total_amount": total,
+            "paid_by": payer.id,
+            "division": [(debtor.id, total)],
+            "expense_date": date,
+        }))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+}
+
+fn due(g: &Group, now: NaiveDateTime) -> Vec<PaymentReminder> {
+    send_due_reminders(now, Some(g.id)).expect("reminders sent")
+}
+
+#[test]
+fn members_remind_who_owes_them() {
+    let alice = user();
+    let bob = user();
+    let carol = user();
+    let outsider = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    add_member(&alice, &g, &carol);
+    expense(&alice, &g, 30.0, &[&alice, &bob, &carol]);
+    clear_notifications(&bob);
+    clear_notifications(&carol);
+
+    assert_eq!(remind(&alice, &g, &alice, Value::Null), Status::BadRequest);
+    assert_eq!(remind(&alice, &g, &outsider, Value::Null), Status::NotFound);
+    assert_eq!(remind(&outsider, &g, &bob, Value::Null), Status::Forbidden);
+    // bob owes, nobody owes him
+    assert_eq!(remind(&bob, &g, &carol, Value::Null), Status::Conflict);
+    assert_eq!(remind(&bob, &g, &alice, Value::Null), Status::Conflict);
+    assert_eq!(
+        remind(&alice, &g, &bob, json!("x".repeat(501))),
+        Status::BadRequest
+    );
+
+    assert_eq!(remind(&alice, &g, &bob, json!("  the pizza  ")), Status::Ok);
+    let sent = reminders(&bob, &g);
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].sender_id, Some(alice.id));
+    assert_eq!(sent[0].recipient_id, bob.id);
+    assert!((sent[0].amount - 10.0).abs() < 1e-9);
+    assert_eq!(sent[0].message.as_deref(), Some("the pizza"));
+    assert_eq!(reminders(&alice, &g).len(), 1);
+    assert!(reminders(&carol, &g).is_empty());
+
+    let n = notifications(&bob);
+    assert_eq!(n.len(), 1);
+    assert_eq!(n[0].notification_type.as_deref(), Some("BALANCE_REQUESTED"));
+    assert_eq!(n[0].user_id, Some(alice.id));
+    assert_eq!(n[0].group_id, Some(g.id));
+    assert_eq!(n[0].reminder_id, Some(sent[0].id));
+    assert!(notifications(&carol).is_empty());
+
+    // once a day for the same pair
+    assert_eq!(
+        remind(&alice, &g, &bob, Value::Null),
+        Status::TooManyRequests
+    );
+    assert_eq!(remind(&alice, &g, &carol, Value::Null), Status::Ok);
+
+    // what bob owes carol alone, not what carol is owed by the group
+    expense(&carol, &g, 12.0, &[&bob]);
+    assert_eq!(remind(&carol, &g, &bob, Value::Null), Status::Ok);
+    let sent = reminders(&carol, &g);
+    let to_bob = sent.iter().find(|r| r.recipient_id == bob.id).unwrap();
+    assert!((to_bob.amount - 12.0).abs() < 1e-9);
+    // carol still owes alice, alice does not owe her
+    assert_eq!(remind(&carol, &g, &alice, Value::Null), Status::Conflict);
+}
+
+#[test]
+fn reminder_schedule_is_set_by_admins() {
+    let admin = user();
+    let member = user();
+    let g = group(&admin);
+    add_member(&admin, &g, &member);
+
+    assert!(schedule(&member, &g).is_none());
+    assert_eq!(set_schedule(&member, &g, json!(7)), Status::Forbidden);
+    assert_eq!(set_schedule(&admin, &g, json!(0)), Status::BadRequest);
+    assert_eq!(set_schedule(&admin, &g, json!(366)), Status::BadRequest);
+
+    assert_eq!(set_schedule(&admin, &g, json!(7)), Status::Ok);
+    assert_eq!(set_schedule(&admin, &g, json!(14)), Status::Ok);
+    let s = schedule(&member, &g).expect("reminders scheduled");
+    assert_eq!(s.after_days, 14);
+    assert_eq!(s.updated_by, Some(admin.id));
+
+    assert_eq!(set_schedule(&admin, &g, Value::Null), Status::Ok);
+    assert!(schedule(&member, &g).is_none());
+}
+
+#[test]
+fn old_debts_are_reminded_automatically() {
+    let alice = user();
+    let bob = user();
+    let carol = user();
+    let g = group(&alice);
+    add_member(&alice, &g, &bob);
+    add_member(&alice, &g, &carol);
+    old_expense(&alice, &g, 20.0, &bob, 10);
+    // too recent
+    old_expense(&alice, &g, 5.0, &carol, 1);
+    let now = Utc::now().naive_utc();
+
+    // nothing is sent without a schedule
+    assert!(due(&g, now).is_empty());
+    assert_eq!(set_schedule(&alice, &g, json!(7)), Status::Ok);
+
+    let sent = due(&g, now);
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].recipient_id, bob.id);
+    assert_eq!(sent[0].sender_id, None);
+    assert!((sent[0].amount - 20.0).abs() < 1e-9);
+    let n = notifications(&bob);
+    assert_eq!(n[0].notification_type.as_deref(), Some("BALANCE_REQUESTED"));
+    assert_eq!(n[0].user_id, None);
+    assert_eq!(n[0].reminder_id, Some(sent[0].id));
+
+    // once every `after_days`
+    assert!(due(&g, now + Duration::days(1)).is_empty());
+    let sent = due(&g, now + Duration::days(8));
+    assert_eq!(
+        sent.iter().map(|r| r.recipient_id).collect::<Vec<_>>(),
+        [bob.id, carol.id]
+    );
+
+    // a member that paid back is left alone
+    old_expense(&bob, &g, 20.0, &alice, 0);
+    assert!(
+        due(&g, now + Duration::days(20))
+            .iter()
+            .all(|r| r.recipient_id != bob.id)
+    );
+}
@@ -0,0 +1,307 @@
+//! tests for the trash of the expenses and of the groups in `api/trash.rs`
+
+use rocket::http::{Method, Status};
+use serde_json::json;
+
+use super::{
+    TestUser, add_member, expense, figment, group, logged_in, notifications, private_expense,
+    purge_trash, status, trashed_expense, trashed_group, user,
+};
+use crate::api::expenses::ExpenseWithParticipations;
+use crate::api::pagination::Page;
+use crate::models::{Expense, Group, Webhook, WebhookDelivery};
+
+fn expense_ids(user: &TestUser, uri: String) -> Vec<i32> {
+    let res = user.client.get(uri).dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json::<Page<ExpenseWithParticipations>>()
+        .expect("expenses in response")
+        .items
+        .into_iter()
+        .map(|(e, _)| e.id)
+        .collect()
+}
+
+fn group_ids(user: &TestUser, uri: &str) -> Vec<i32> {
+    let res = user.client.get(uri).dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    match uri {
+        "/groups/trash" => res.into_json::<Vec<Group>>().expect("groups in response"),
+        _ => {
+            res.into_json::<Page<Group>>()
+                .expect("groups in response")
+                .items
+        }
+    }
+    .into_iter()
+    .map(|g| g.id)
+    .collect()
+}
+
+fn delete(user: &TestUser, uri: String) {
+    assert_eq!(status(&user.client, Method::Delete, uri, None), Status::Ok);
+}
+
+#[test]
+fn deleted_expenses_go_to_the_trash() {
+    let admin = user();
+    let payer = user();
+    let other = user();
+    let outsider = user();
+    let g = group(&admin);
+    add_member(&admin, &g, &payer);
+    add_member(&admin, &g, &other);
+    let kept = expense(&payer, &g, 10.0, &[&payer, &other]);
+    let e = expense(&payer, &g, 30.0, &[&payer, &other]);
+
+    delete(&payer, format!("/groups/{}/expenses/{}", g.id, e.id));
+
+    let expenses = format!("/groups/{}/expenses", g.id);
+    let trash = format!("/groups/{}/trash", g.id);
+    assert_eq!(expense_ids(&other, expenses.clone()), [kept.id]);
+    assert_eq!(expense_ids(&other, trash.clone()), [e.id]);
+    assert_eq!(
+        status(&outsider.client, Method::Get, trash.clone(), None),
+        Status::Forbidden
+    );
+    // a deleted expense cannot be deleted again
+    assert_eq!(
+        status(
+            &payer.client,
+            Method::Delete,
+            format!("/groups/{}/expenses/{}", g.id, e.id),
+            None
+        ),
+        Status::NotFound
+    );
+
+    // the payer and the admins can restore it, the other members cannot
+    let restore = format!("/groups/{}/expenses/{}/restore", g.id, e.id);
+    assert_eq!(
+        status(&other.client, Method::Post, restore.clone(), None),
+        Status::Forbidden
+    );
+    for restorer in [&payer, &admin] {
+        let res = restorer.client.post(restore.clone()).dispatch();
+        assert_eq!(res.status(), Status::Ok);
+        let restored = res.into_json::<Expense>().unwrap();
+        assert_eq!(restored.id, e.id);
+        assert!(restored.deleted_at.is_none());
+        assert!(expense_ids(&other, trash.clone()).is_empty());
+
+        // only what is in the trash can be restored
+        assert_eq!(
+            status(&restorer.client, Method::Post, restore.clone(), None),
+            Status::NotFound
+        );
+        delete(&payer, format!("/groups/{}/expenses/{}", g.id, e.id));
+    }
+
+    // the participants are told of the restoring like they were of the deletion
+    let restored: Vec<_> = notifications(&other)
+        .into_iter()
+        .filter(|n| n.notification_type.as_deref() == Some("EXPENSE_RESTORED"))
+        .map(|n| (n.expense_id, n.group_id, n.user_id))
+        .collect();
+    assert_eq!(
+        restored,
+        [
+            (Some(e.id), Some(g.id), Some(admin.id)),
+            (Some(e.id), Some(g.id), Some(payer.id))
+        ]
+    );
+}
+
+#[test]
+fn expenses_can_be_restored_within_the_retention_window() {
+    let admin = user();
+    let g = group(&admin);
+    let e = expense(&admin, &g, 30.0, &[&admin]);
+    delete(&admin, format!("/groups/{}/expenses/{}", g.id, e.id));
+    trashed_expense(e.id, 20);
+
+    let restore = format!("/groups/{}/expenses/{}/restore", g.id, e.id);
+    let trash = format!("/groups/{}/trash", g.id);
+    let short = logged_in(figment().merge(("trash.retention_days", 10)), &admin);
+    assert_eq!(
+        status(&short, Method::Post, restore.clone(), None),
+        Status::Gone
+    );
+    assert_eq!(status(&short, Method::Get, trash.clone(), None), Status::Ok);
+    let res = short.get(trash.clone()).dispatch();
+    let listed = res.into_json::<Page<ExpenseWithParticipations>>().unwrap();
+    assert!(listed.items.is_empty());
+
+    // with the default window of 30 days it is still there
+    assert_eq!(expense_ids(&admin, trash), [e.id]);
+    assert_eq!(
+        status(&admin.client, Method::Post, restore, None),
+        Status::Ok
+    );
+    assert_eq!(
+        expense_ids(&admin, format!("/groups/{}/expenses", g.id)),
+        [e.id]
+    );
+}
+
+#[test]
+fn deleted_groups_go_to_the_trash() {
+    let admin = user();
+    let member = user();
+    let g = group(&admin);
+    add_member(&admin, &g, &member);
+    let e = expense(&member, &g, 30.0, &[&admin, &member]);
+    let webhook = admin
+        .client
+        .post(format!("/groups/{}/webhooks", g.id))
+        .json(
+            &json!({ "url": "http://127.0.0.1:9/hook", "secret": "a secret that is long enough" }),
+        )
+        .dispatch()
+        .into_json::<Webhook>()
+        .expect("webhook");
+
+    delete(&admin, format!("/groups/{}", g.id));
+
+    assert!(!group_ids(&member, "/groups").contains(&g.id));
+    assert_eq!(
+        status(
+            &member.client,
+            Method::Get,
+            format!("/groups/{}", g.id),
+            None
+        ),
+        Status::NotFound
+    );
+    // only the admins see it in the trash and can restore it
+    assert!(group_ids(&admin, "/groups/trash").contains(&g.id));
+    assert!(!group_ids(&member, "/groups/trash").contains(&g.id));
+
+    let restore = format!("/groups/{}/restore", g.id);
+    assert_eq!(
+        status(&member.client, Method::Post, restore.clone(), None),
+        Status::Forbidden
+    );
+    let short = logged_in(figment().merge(("trash.retention_days", 10)), &admin);
+    trashed_group(g.id, 20);
+    assert_eq!(
+        status(&short, Method::Post, restore.clone(), None),
+        Status::Gone
+    );
+
+    let res = admin.client.post(restore.clone()).dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    assert!(res.into_json::<Group>().unwrap().deleted_at.is_none());
+    assert_eq!(
+        status(&admin.client, Method::Post, restore, None),
+        Status::NotFound
+    );
+
+    // the expenses come back with the group
+    assert!(group_ids(&member, "/groups").contains(&g.id));
+    assert_eq!(
+        expense_ids(&member, format!("/groups/{}/expenses", g.id)),
+        [e.id]
+    );
+
+    // the members are told, and so are the webhooks
+    let told = notifications(&member);
+    assert_eq!(told[0].notification_type.as_deref(), Some("GROUP_RESTORED"));
+    assert_eq!(told[0].user_id, Some(admin.id));
+    let res = admin
+        .client
+        .get(format!(
+            "/groups/{}/webhooks/{}/deliveries",
+            g.id, webhook.id
+        ))
+        .dispatch();
+    let events: Vec<String> = res
+        .into_json::<Page<WebhookDelivery>>()
+        .expect("deliveries")
+        .items
+        .into_iter()
+        .map(|d| d.event_type)
+        .collect();
+    assert_eq!(events, ["GROUP_RESTORED", "GROUP_DELETED"]);
+}
+
+#[test]
+fn purge_deletes_what_is_past_the_window() {
+    let admin = user();
+    let g = group(&admin);
+    let old = expense(&admin, &g, 10.0, &[&admin]);
+    let recent = expense(&admin, &g, 20.0, &[&admin]);
+    delete(&admin, format!("/groups/{}/expenses/{}", g.id, old.id));
+    delete(&admin, format!("/groups/{}/expenses/{}", g.id, recent.id));
+    trashed_expense(old.id, 31);
+
+    let deleted = group(&admin);
+    let inside = expense(&admin, &deleted, 10.0, &[&admin]);
+    delete(&admin, format!("/groups/{}", deleted.id));
+    trashed_group(deleted.id, 31);
+
+    let (expenses, groups) = purge_trash();
+    assert!(expenses >= 2);
+    assert!(groups >= 1);
+
+    assert_eq!(
+        expense_ids(&admin, format!("/groups/{}/trash", g.id)),
+        [recent.id]
+    );
+    for (uri, expected) in [
+        (
+            format!("/groups/{}/expenses/{}/restore", g.id, old.id),
+            Status::NotFound,
+        ),
+        (
+            format!("/groups/{}/expenses/{}/restore", g.id, recent.id),
+            Status::Ok,
+        ),
+        (format!("/groups/{}/restore", deleted.id), Status::NotFound),
+        (format!("/expenses/{}/restore", inside.id), Status::NotFound),
+    ] {
+        assert_eq!(status(&admin.client, Method::Post, uri, None), expected);
+    }
+    assert!(!group_ids(&admin, "/groups/trash").contains(&deleted.id));
+}
+
+#[test]
+fn private_expenses_go_to_the_trash() {
+    let payer = user();
+    let friend = user();
+    let e = private_expense(&payer, 30.0, &[&payer, &friend]);
+
+    delete(&payer, format!("/expenses/{}", e.id));
+    assert!(!expense_ids(&friend, "/expenses".to_owned()).contains(&e.id));
+    assert_eq!(expense_ids(&payer, "/expenses/trash".to_owned()), [e.id]);
+    assert!(expense_ids(&friend, "/expenses/trash".to_owned()).is_empty());
+
+    // only the payer can restore it
+    let restore = format!("/expenses/{}/restore", e.id);
+    assert_eq!(
+        status(&friend.client, Method::Post, restore.clone(), None),
+        Status::Forbidden
+    );
+    let short = logged_in(figment().merge(("trash.retention_days", 10)), &payer);
+    trashed_expense(e.id, 20);
+    assert_eq!(
+        status(&short, Method::Post, restore.clone(), None),
+        Status::Gone
+    );
+
+    assert_eq!(
+        status(&payer.client, Method::Post, restore.clone(), None),
+        Status::Ok
+    );
+    assert!(expense_ids(&friend, "/expenses".to_owned()).contains(&e.id));
+    let told = notifications(&friend);
+    assert_eq!(
+        told[0].notification_type.as_deref(),
+        Some("EXPENSE_RESTORED")
+    );
+    assert_eq!(told[0].expense_id, Some(e.id));
+    assert_eq!(
+        status(&payer.client, Method::Post, restore, None),
+        Status::NotFound
+    );
+}
@@ -0,0 +1,283 @@
+//! tests for the routes in `api/users.rs`
+
+use rocket::http::{Cookie, Method, Status};
+use serde_json::{Value, json};
+
+use super::{PASSWORD, TestUser, client, group, status, unique, user};
+use crate::api::pagination::Page;
+use crate::models::{Group, GroupInvite};
+
+fn register(email: &str, username: &str) -> Status {
+    client()
+        .post("/user/register")
+        .json(&json!({ "username": username, "email": email, "password": PASSWORD }))
+        .dispatch()
+        .status()
+}
+
+fn invite(admin: &TestUser, group: &Group, invitee: &TestUser) -> GroupInvite {
+    let res = admin
+        .client
+        .post(format!("/groups/{}/members/invite", group.id))
+        .json(&json!({ "email": invitee.email, "message": "join us" }))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json::<GroupInvite>().unwrap()
+}
+
+#[test]
+fn register_conflicts_on_email_and_username() {
+    let email = format!("{}@example.com", unique("user"));
+    let username = unique("user");
+
+    assert_eq!(register(&email, &username), Status::Ok);
+    assert_eq!(register(&email, &unique("user")), Status::Conflict);
+    let other_email = format!("{}@example.com", unique("user"));
+    assert_eq!(register(&other_email, &username), Status::Conflict);
+}
+
+#[test]
+fn login_sets_session_cookie() {
+    let u = user();
+
+    assert!(u.client.cookies().get("session_id").is_some());
+    let uri = format!("/user/{}", u.id);
+    assert_eq!(status(&u.client, Method::Get, uri, None), Status::Ok);
+}
+
+#[test]
+fn login_with_wrong_credentials_fails() {
+    let u = user();
+    let c = client();
+
+    let body = json!({ "email": u.email, "password": "wrong password" });
+    assert_eq!(
+        status(&c, Method::Post, "/user/login".to_owned(), Some(body)),
+        Status::Unauthorized
+    );
+    let body =
+        json!({ "email": format!("{}@example.com", unique("nobody")), "password": PASSWORD });
+    assert_eq!(
+        status(&c, Method::Post, "/user/login".to_owned(), Some(body)),
+        Status::Unauthorized
+    );
+}
+
+#[test]
+fn logout_invalidates_session() {
+    let u = user();
+    let session = u
+        .client
+        .cookies()
+        .get("session_id")
+        .map(|c| c.value().to_owned())
+        .unwrap();
+
+    assert_eq!(
+        status(&u.client, Method::Post, "/user/logout".to_owned(), None),
+        Status::Ok
+    );
+    let uri = format!("/user/{}", u.id);
+    assert_eq!(
+        status(&u.client, Method::Get, uri.clone(), None),
+        Status::Unauthorized
+    );
+
+    // the old session id cannot be reused either
+    let status = u
+        .client
+        .get(uri)
+        .cookie(Cookie::new("session_id", session))
+        .dispatch()
+        .status();
+    assert_eq!(status, Status::Unauthorized);
+}
+
+#[test]
+fn logout_without_session_is_not_acceptable() {
+    let c = client();
+    assert_eq!(
+        status(&c, Method::Post, "/user/logout".to_owned(), None),
+        Status::NotAcceptable
+    );
+}
+
+#[test]
+fn user_info() {
+    let u = user();
+    let other = user();
+
+    let res = u.client.get(format!("/user/{}", other.id)).dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let info = res.into_json::<Value>().unwrap();
+    assert_eq!(info["email"], json!(other.email));
+    assert!(info.get("password_hash").is_none());
+
+    assert_eq!(
+        status(&u.client, Method::Get, "/user/-1".to_owned(), None),
+        Status::NotFound
+    );
+    let uri = format!("/user/{}", other.id);
+    assert_eq!(
+        status(&client(), Method::Get, uri, None),
+        Status::Unauthorized
+    );
+}
+
+#[test]
+fn unimplemented_routes_require_authentication() {
+    let u = user();
+    let anonymous = client();
+
+    for (method, uri) in [
+        (Method::Put, "/user/language/it"),
+        (Method::Put, "/user/verify/1"),
+    ] {
+        assert_eq!(
+            status(&anonymous, method, uri.to_owned(), None),
+            Status::Unauthorized
+        );
+        assert_eq!(
+            status(&u.client, method, uri.to_owned(), None),
+            Status::NotImplemented
+        );
+    }
+}
+
+#[test]
+fn view_invites_lists_received_invites() {
+    let admin = user();
+    let invitee = user();
+    let g = group(&admin);
+    let inv = invite(&admin, &g, &invitee);
+    assert_eq!(inv.invite_status.as_deref(), Some("PENDING"));
+    assert_eq!(inv.optional_message.as_deref(), Some("join us"));
+
+    let res = invitee.client.get("/user/invites").dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let invites = res.into_json::<Page<GroupInvite>>().unwrap().items;
+    assert_eq!(invites.len(), 1);
+    assert_eq!(invites[0].id, inv.id);
+
+    // invites sent are not listed
+    let res = admin.client.get("/user/invites").dispatch();
+    assert!(
+        res.into_json::<Page<GroupInvite>>()
+            .unwrap()
+            .items
+            .is_empty()
+    );
+
+    assert_eq!(
+        status(&client(), Method::Get, "/user/invites".to_owned(), None),
+        Status::Unauthorized
+    );
+}
+
+#[test]
+fn invite_unknown_email_is_not_found() {
+    let admin = user();
+    let g = group(&admin);
+
+    let uri = format!("/groups/{}/members/invite", g.id);
+    let body = json!({ "email": format!("{}@example.com", unique("nobody")), "message": null });
+    assert_eq!(
+        status(&admin.client, Method::Post, uri, Some(body)),
+        Status::NotFound
+    );
+}
+
+#[test]
+fn accept_invite_joins_group() {
+    let admin = user();
+    let invitee = user();
+    let g = group(&admin);
+    let inv = invite(&admin, &g, &invitee);
+
+    let group_uri = format!("/groups/{}", g.id);
+    assert_eq!(
+        status(&invitee.client, Method::Get, group_uri.clone(), None),
+        Status::Forbidden
+    );
+
+    // only the invited user can accept the invite
+    let uri = format!("/user/invites/{}/accept", inv.id);
+    assert_eq!(
+        status(&admin.client, Method::Put, uri.clone(), None),
+        Status::NotFound
+    );
+    assert_eq!(
+        status(&client(), Method::Put, uri.clone(), None),
+        Status::Unauthorized
+    );
+
+    let res = invitee.client.put(uri).dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let accepted = res.into_json::<GroupInvite>().unwrap();
+    assert_eq!(accepted.invite_status.as_deref(), Some("ACCEPTED"));
+
+    assert_eq!(
+        status(&invitee.client, Method::Get, group_uri, None),
+        Status::Ok
+    );
+}
+
+#[test]
+fn invites_to_deleted_groups_cannot_be_accepted() {
+    let admin = user();
+    let invitee = user();
+    let g = group(&admin);
+    let inv = invite(&admin, &g, &invitee);
+    assert_eq!(
+        status(
+            &admin.client,
+            Method::Delete,
+            format!("/groups/{}", g.id),
+            None
+        ),
+        Status::Ok
+    );
+
+    let uri = format!("/user/invites/{}/accept", inv.id);
+    assert_eq!(
+        status(&invitee.client, Method::Put, uri.clone(), None),
+        Status::NotFound
+    );
+
+    // still pending, it can be accepted once the group is restored
+    assert_eq!(
+        status(
+            &admin.client,
+            Method::Post,
+            format!("/groups/{}/restore", g.id),
+            None
+        ),
+        Status::Ok
+    );
+    assert_eq!(status(&invitee.client, Method::Put, uri, None), Status::Ok);
+}
+
+#[test]
+fn reject_invite_does_not_join_group() {
+    let admin = user();
+    let invitee = user();
+    let g = group(&admin);
+    let inv = invite(&admin, &g, &invitee);
+
+    let uri = format!("/user/invites/{}/reject", inv.id);
+    assert_eq!(
+        status(&admin.client, Method::Put, uri.clone(), None),
+        Status::NotFound
+    );
+
+    let res = invitee.client.put(uri).dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let rejected = res.into_json::<GroupInvite>().unwrap();
+    assert_eq!(rejected.invite_status.as_deref(), Some("REJECTED"));
+
+    let group_uri = format!("/groups/{}", g.id);
+    assert_eq!(
+        status(&invitee.client, Method::Get, group_uri, None),
+        Status::Forbidden
+    );
+}
@@ -0,0 +1,433 @@
+//! tests for the outgoing webhooks in `api/webhooks.rs`
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::connection::Connection;
+use rocket::http::{Method, Status};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread::JoinHandle;
+
+use super::{TestUser, add_member, expense, figment, group, logged_in, status, user};
+use crate::api::live::{GroupEvent, GroupEventKind, record};
+use crate::api::pagination::Page;
+use crate::api::webhooks::{deliver_due, is_public, signature};
+use crate::config::WebhooksConfig;
+use crate::establish_connection;
+use crate::models::{Group, Webhook, WebhookDelivery};
+
+const SECRET: &str = "a secret that is long enough";
+
+/// a request received by [`receiver`]
+struct Received {
+    path: String,
+    headers: HashMap<String, String>,
+    body: String,
+}
+
+/// a local http server that answers one request with each of `codes`, then stops and returns the
+/// requests. returns its url too
+fn receiver(codes: Vec<u16>) -> (String, JoinHandle<Vec<Received>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("listener");
+    let url = format!(
+        "http://{}/hooks/splitsmart?bot=1",
+        listener.local_addr().expect("address")
+    );
+
+    let handle = std::thread::spawn(move || {
+        let mut received = Vec::new();
+        for code in codes {
+            let (mut stream, _) = listener.accept().expect("connection");
+            let mut reader = BufReader::new(stream.try_clone().expect("stream"));
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let path = request_line
+                .split_whitespace()
+                .nth(1)
+                .unwrap_or_default()
+                .to_owned();
+            let mut headers = HashMap::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                match line.trim_end().split_once(": ") {
+                    Some((name, value)) => {
+                        headers.insert(name.to_lowercase(), value.to_owned());
+                    }
+                    None => break,
+                }
+            }
+            let length = headers["content-length"].parse().unwrap();
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+
+            stream
+                .write_all(
+                    format!("HTTP/1.1 {} Whatever\r\nContent-Length: 0\r\n\r\n", code).as_bytes(),
+                )
+                .unwrap();
+            received.push(Received {
+                path,
+                headers,
+                body: String::from_utf8(body).unwrap(),
+            });
+        }
+        received
+    });
+
+    (url, handle)
+}
+
+fn add_webhook(admin: &TestUser, g: &Group, url: &str) -> Webhook {
+    let res = admin
+        .client
+        .post(format!("/groups/{}/webhooks", g.id))
+        .json(&json!({ "url": url, "secret": SECRET }))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json::<Webhook>().expect("webhook")
+}
+
+fn deliveries(admin: &TestUser, g: &Group, w: &Webhook) -> Vec<WebhookDelivery> {
+    let res = admin
+        .client
+        .get(format!("/groups/{}/webhooks/{}/deliveries", g.id, w.id))
+        .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    res.into_json::<Page<WebhookDelivery>>()
+        .expect("deliveries")
+        .items
+}
+
+/// the configuration of the deliveries to the local receivers
+fn local() -> WebhooksConfig {
+    WebhooksConfig {
+        allow_private_hosts: true,
+        ..WebhooksConfig::default()
+    }
+}
+
+fn deliver(config: &WebhooksConfig, w: &Webhook, now: NaiveDateTime) -> usize {
+    deliver_due(config, now, Some(w.id)).expect("deliveries attempted")
+}
+
+#[test]
+fn hmac_matches_the_reference_vectors() {
+    // rfc 4231, test cases 1, 2 and 6
+    assert_eq!(
+        signature(&[0x0b; 20], b"Hi There"),
+        "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"
+    );
+    assert_eq!(
+        signature(b"Jefe", b"what do ya want for nothing?"),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+    assert_eq!(
+        signature(
+            &[0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First"
+        ),
+        "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+    );
+}
+
+#[test]
+fn webhooks_are_managed_by_admins() {
+    let admin = user();
+    let member = user();
+    let g = group(&admin);
+    add_member(&admin, &g, &member);
+    let uri = format!("/groups/{}/webhooks", g.id);
+    let body = json!({ "url": "http://127.0.0.1:9/hook", "secret": SECRET });
+
+    assert_eq!(
+        status(&member.client, Method::Get, uri.clone(), None),
+        Status::Forbidden
+    );
+    assert_eq!(
+        status(&member.client, Method::Post, uri.clone(), Some(body)),
+        Status::Forbidden
+    );
+    for (url, secret) in [
+        ("not an url", SECRET),
+        ("https://", SECRET),
+        ("ftp://example.com/hook", SECRET),
+        ("http://127.0.0.1:9/hook", "short"),
+    ] {
+        assert_eq!(
+            status(
+                &admin.client,
+                Method::Post,
+                uri.clone(),
+                Some(json!({ "url": url, "secret": secret }))
+            ),
+            Status::BadRequest,
+            "{} was accepted",
+            url
+        );
+    }
+
+    let w = add_webhook(&admin, &g, "http://127.0.0.1:9/hook");
+    let res = admin.client.get(uri.clone()).dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let listed = res.into_json::<Vec<Value>>().expect("webhooks");
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0]["id"], w.id);
+    assert!(listed[0].get("secret").is_none());
+
+    let webhook = format!("{}/{}", uri, w.id);
+    assert_eq!(
+        status(&member.client, Method::Delete, webhook.clone(), None),
+        Status::Forbidden
+    );
+    assert_eq!(
+        status(&admin.client, Method::Delete, webhook.clone(), None),
+        Status::Ok
+    );
+    assert_eq!(
+        status(
+            &admin.client,
+            Method::Get,
+            format!("{}/deliveries", webhook),
+            None
+        ),
+        Status::NotFound
+    );
+}
+
+#[test]
+fn events_are_posted_signed() {
+    let admin = user();
+    let member = user();
+    let g = group(&admin);
+    let (url, receiver) = receiver(vec![200, 204]);
+    let w = add_webhook(&admin, &g, &url);
+
+    add_member(&admin, &g, &member);
+    let e = expense(&admin, &g, 30.0, &[&admin, &member]);
+    let config = local();
+    assert_eq!(deliver(&config, &w, Utc::now().naive_utc()), 2);
+    // nothing left to deliver
+    assert_eq!(deliver(&config, &w, Utc::now().naive_utc()), 0);
+
+    let received = receiver.join().expect("receiver");
+    assert_eq!(received[0].path, "/hooks/splitsmart?bot=1");
+    for r in &received {
+        assert_eq!(r.headers["content-type"], "application/json");
+        assert_eq!(
+            r.headers["x-splitsmart-signature"],
+            format!("sha256={}", signature(SECRET.as_bytes(), r.body.as_bytes()))
+        );
+    }
+
+    let added: Value = serde_json::from_str(&received[0].body).expect("json body");
+    assert_eq!(received[0].headers["x-splitsmart-event"], "MEMBER_ADDED");
+    assert_eq!(added["type"], "MEMBER_ADDED");
+    assert_eq!(added["group_id"], g.id);
+    assert_eq!(added["member_id"], member.id);
+
+    let created: Value = serde_json::from_str(&received[1].body).expect("json body");
+    assert_eq!(created["type"], "EXPENSE_CREATED");
+    assert_eq!(created["actor"], admin.id);
+    assert_eq!(created["expense"]["desc"], e.desc.as_str());
+    assert_eq!(created["expense"]["total_amount"], 30.0);
+
+    let log = deliveries(&admin, &g, &w);
+    assert_eq!(log.len(), 2);
+    assert_eq!(
+        received[1].headers["x-splitsmart-delivery"],
+        log[0].id.to_string()
+    );
+    for d in &log {
+        assert_eq!(d.status, "DELIVERED");
+        assert_eq!(d.attempts, 1);
+        assert!(d.error.is_none());
+    }
+    assert_eq!(log[0].response_status, Some(204));
+}
+
+#[test]
+fn failed_deliveries_are_retried_with_backoff() {
+    let admin = user();
+    let g = group(&admin);
+    let (url, receiver) = receiver(vec![500, 200]);
+    let w = add_webhook(&admin, &g, &url);
+    let config = WebhooksConfig {
+        max_attempts: 2,
+        retry_base_secs: 60,
+        ..local()
+    };
+
+    expense(&admin, &g, 10.0, &[&admin]);
+    let now = Utc::now().naive_utc();
+    assert_eq!(deliver(&config, &w, now), 0);
+    let d = deliveries(&admin, &g, &w).remove(0);
+    assert_eq!(d.status, "PENDING");
+    assert_eq!(d.attempts, 1);
+    assert_eq!(d.response_status, Some(500));
+    assert!(d.error.is_some());
+    // postgres keeps the timestamps to the microsecond
+    let retry = d.next_attempt_at.expect("retry scheduled") - now;
+    assert!((retry - Duration::seconds(60)).abs() < Duration::milliseconds(1));
+
+    // not due yet
+    assert_eq!(deliver(&config, &w, now + Duration::seconds(30)), 0);
+    assert_eq!(deliver(&config, &w, now + Duration::seconds(61)), 1);
+    let d = deliveries(&admin, &g, &w).remove(0);
+    assert_eq!(d.status, "DELIVERED");
+    assert_eq!(d.attempts, 2);
+    assert_eq!(receiver.join().expect("receiver").len(), 2);
+}
+
+#[test]
+fn deliveries_are_given_up_after_the_last_attempt() {
+    let admin = user();
+    let g = group(&admin);
+    // nobody listens on the port once the listener is dropped
+    let url = {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("listener");
+        format!("http://{}/hook", listener.local_addr().expect("address"))
+    };
+    let w = add_webhook(&admin, &g, &url);
+    let config = WebhooksConfig {
+        max_attempts: 2,
+        retry_base_secs: 1,
+        timeout_secs: 1,
+        ..local()
+    };
+
+    expense(&admin, &g, 10.0, &[&admin]);
+    let now = Utc::now().naive_utc();
+    assert_eq!(deliver(&config, &w, now), 0);
+    assert_eq!(deliver(&config, &w, now + Duration::seconds(5)), 0);
+
+    let d = deliveries(&admin, &g, &w).remove(0);
+    assert_eq!(d.status, "FAILED");
+    assert_eq!(d.attempts, 2);
+    assert!(d.next_attempt_at.is_none());
+    assert!(d.response_status.is_none());
+    assert!(d.error.is_some());
+
+    let res = admin
+        .client
+        .get(format!(
+            "/groups/{}/webhooks/{}/deliveries?status=PENDING",
+            g.id, w.id
+        ))
+        .dispatch();
+    assert!(
+        res.into_json::<Page<WebhookDelivery>>()
+            .expect("deliveries")
+            .items
+            .is_empty()
+    );
+    // a typo in the state is not ignored
+    assert_eq!(
+        status(
+            &admin.client,
+            Method::Get,
+            format!(
+                "/groups/{}/webhooks/{}/deliveries?status=failed",
+                g.id, w.id
+            ),
+            None
+        ),
+        Status::BadRequest
+    );
+}
+
+#[test]
+fn events_are_queued_with_their_change() {
+    let admin = user();
+    let g = group(&admin);
+    let w = add_webhook(&admin, &g, "http://127.0.0.1:9/hook");
+
+    let rolled_back = establish_connection().transaction::<(), _, _>(|conn| {
+        record(
+            conn,
+            GroupEvent::new(GroupEventKind::MemberRemoved, g.id, Some(admin.id))
+                .about_member(admin.id),
+        )?;
+        Err(diesel::result::Error::RollbackTransaction)
+    });
+    assert!(rolled_back.is_err());
+    assert!(deliveries(&admin, &g, &w).is_empty());
+
+    expense(&admin, &g, 10.0, &[&admin]);
+    let log = deliveries(&admin, &g, &w);
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0].event_type, "EXPENSE_CREATED");
+}
+
+#[test]
+fn private_addresses_are_refused() {
+    for ip in [
+        "127.0.0.1",
+        "10.1.2.3",
+        "172.16.0.1",
+        "192.168.1.1",
+        "169.254.169.254",
+        "100.64.0.1",
+        "0.0.0.0",
+        "::1",
+        "::",
+        "fd00::1",
+        "fe80::1",
+        "::ffff:127.0.0.1",
+    ] {
+        assert!(!is_public(ip.parse().unwrap()), "{} is public", ip);
+    }
+    for ip in ["93.184.216.34", "2606:2800:220:1:248:1893:25c8:1946"] {
+        assert!(is_public(ip.parse().unwrap()), "{} is not public", ip);
+    }
+
+    let admin = user();
+    let g = group(&admin);
+    let strict = logged_in(
+        figment().merge(("webhooks.allow_private_hosts", false)),
+        &admin,
+    );
+    let uri = format!("/groups/{}/webhooks", g.id);
+    for url in [
+        "http://127.0.0.1:9/hook",
+        "http://localhost/hook",
+        "https://169.254.169.254/latest/meta-data",
+        "http://[::1]/hook",
+        "http://[::ffff:10.0.0.1]/hook",
+    ] {
+        assert_eq!(
+            status(
+                &strict,
+                Method::Post,
+                uri.clone(),
+                Some(json!({ "url": url, "secret": SECRET }))
+            ),
+            Status::BadRequest,
+            "{} was accepted",
+            url
+        );
+    }
+    assert_eq!(
+        status(
+            &strict,
+            Method::Post,
+            uri,
+            Some(json!({ "url": "https://93.184.216.34/hook", "secret": SECRET }))
+        ),
+        Status::Ok
+    );
+
+    // a webhook that passed the check when it was registered is checked again when delivered
+    let w = add_webhook(&admin, &g, "http://127.0.0.1:9/hook");
+    expense(&admin, &g, 10.0, &[&admin]);
+    let config = WebhooksConfig {
+        max_attempts: 1,
+        ..WebhooksConfig::default()
+    };
+    assert_eq!(deliver(&config, &w, Utc::now().naive_utc()), 0);
+    let d = deliveries(&admin, &g, &w).remove(0);
+    assert_eq!(d.status, "FAILED");
+    assert!(d.error.expect("error").contains("not public"));
+}
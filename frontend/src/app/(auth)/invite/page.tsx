@@ -0,0 +1,12 @@
+import { Suspense } from 'react';
+import { InviteLinkCard } from '@/component/groups/inviteLinkCard';
+
+export default function InvitePage() {
+  return (
+    <div className="flex items-center justify-center min-h-screen bg-gray-50">
+      <Suspense>
+        <InviteLinkCard />
+      </Suspense>
+    </div>
+  );
+}
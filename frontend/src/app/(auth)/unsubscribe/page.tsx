@@ -0,0 +1,12 @@
+import { Suspense } from 'react';
+import { UnsubscribeCard } from '@/component/notifications/unsubscribeCard';
+
+export default function UnsubscribePage() {
+  return (
+    <div className="flex items-center justify-center min-h-screen bg-gray-50">
+      <Suspense>
+        <UnsubscribeCard />
+      </Suspense>
+    </div>
+  );
+}
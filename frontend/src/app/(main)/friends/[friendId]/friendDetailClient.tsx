@@ -75,7 +75,7 @@ export default function FriendDetailClient({ friendId }: FriendDetailClientProps
           if (expense.paid_by === currentUser.id) totalBalance += myShare;
           else totalBalance -= myShare;
 
-          timelineItems.push({ type: 'private_expense', date: expense.creation_date, data: expenseItem });
+          timelineItems.push({ type: 'private_expense', date: expense.expense_date, data: expenseItem });
         });
 
       await Promise.all(myGroups.map(async (group) => {
@@ -188,7 +188,7 @@ export default function FriendDetailClient({ friendId }: FriendDetailClientProps
                     <div>
                       <p className="font-semibold text-blue-500">{expense.desc}</p>
                       <p className="text-sm text-gray-500 mt-1">
-                        {new Date(expense.creation_date).toLocaleDateString('it-IT', { day: '2-digit', month: 'long' })}
+                        {new Date(expense.expense_date).toLocaleDateString('it-IT', { day: '2-digit', month: 'long' })}
                       </p>
                     </div>
                     <div className="text-right ">
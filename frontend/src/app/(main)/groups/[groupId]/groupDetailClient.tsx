@@ -1,7 +1,7 @@
 'use client';
 
 import { useEffect, useState } from 'react';
-import { useQuery } from '@tanstack/react-query';
+import { useQuery, useQueryClient } from '@tanstack/react-query';
 import { api } from '@/lib/api';
 import { ExpenseWithParticipants, ProcessedMember} from '@/types';
 import { useAuth } from '@/lib/authContext';
@@ -33,6 +33,7 @@ export default function GroupDetailClient({ groupId }: GroupDetailClientProps) {
 
   const searchParams = useSearchParams();
   const router = useRouter();
+  const queryClient = useQueryClient();
 
   const { data: processedData, isLoading, isError, error } = useQuery({
     queryKey: ['group-details-simplified', groupId, currentUser?.id],
@@ -113,6 +114,18 @@ export default function GroupDetailClient({ groupId }: GroupDetailClientProps) {
     enabled: !isNaN(groupId) && !!currentUser,
   });
 
+  useEffect(() => {
+    if (isNaN(groupId) || !currentUser) return;
+
+    return api.subscribeToGroup(groupId, (event) => {
+      if (event.type === 'GROUP_DELETED' || (event.type === 'MEMBER_REMOVED' && event.member_id === currentUser.id)) {
+        router.push('/groups');
+        return;
+      }
+      queryClient.invalidateQueries({ queryKey: ['group-details-simplified', groupId] });
+    });
+  }, [groupId, currentUser, queryClient, router]);
+
   useEffect(() => {
     const expenseIdToOpen = searchParams.get('openExpense');
     
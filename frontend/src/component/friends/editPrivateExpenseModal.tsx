@@ -90,6 +90,7 @@ export default function EditPrivateExpenseModal({ isOpen, onClose, friend, expen
       total_amount: numericTotalAmount,
       paid_by: paidById,
       division,
+      category_id: expense.category_id,
     };
     updateMutation.mutate(expenseData);
   };
@@ -113,6 +113,7 @@ export default function EditExpenseModal({ isOpen, onClose, groupId, expenseData
       total_amount: numericTotalAmount,
       paid_by: paidById,
       division,
+      category_id: expense.category_id,
     };
     updateMutation.mutate(expenseData);
   };
@@ -68,7 +68,7 @@ export default function ExpenseDetailModal({ isOpen, onClose, expenseData, group
           </div>
           <div className="flex justify-between items-center text-sm sm:text-base">
             <span className="text-gray-600">Data</span>
-            <span className="font-semibold text-gray-800">{new Date(expense.creation_date).toLocaleDateString('it-IT')}</span>
+            <span className="font-semibold text-gray-800">{new Date(expense.expense_date).toLocaleDateString('it-IT')}</span>
           </div>
         </div>
         
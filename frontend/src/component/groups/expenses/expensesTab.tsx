@@ -63,7 +63,7 @@ export default function ExpensesTab({ groupId, initialExpenses, onSelectExpense
                     <div>
                       <p className="font-semibold text-blue-500">{expense.desc}</p>
                       <p className="text-sm text-gray-500 mt-1">
-                        {new Date(expense.creation_date).toLocaleDateString('it-IT', { day: '2-digit', month: 'long' })}
+                        {new Date(expense.expense_date).toLocaleDateString('it-IT', { day: '2-digit', month: 'long' })}
                       </p>
                     </div>
 
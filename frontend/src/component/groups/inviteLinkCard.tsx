@@ -0,0 +1,70 @@
+'use client';
+
+import { useEffect, useState } from 'react';
+import { useRouter, useSearchParams } from 'next/navigation';
+import { api } from '@/lib/api';
+import { InvitePreview } from '@/types';
+import { Button } from '../ui/button';
+import Link from 'next/link';
+
+export const InviteLinkCard = () => {
+  const token = useSearchParams().get('token');
+  const router = useRouter();
+  const [preview, setPreview] = useState<InvitePreview | null>(null);
+  const [isLoading, setIsLoading] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    if (!token) return;
+    api.previewInviteLink(token)
+      .then(setPreview)
+      .catch(() => setError('Il link di invito non è valido, è scaduto o è già stato usato.'));
+  }, [token]);
+
+  const handleJoin = async () => {
+    if (!token) return;
+    setError(null);
+    setIsLoading(true);
+    try {
+      const group = await api.joinWithInviteLink(token);
+      router.push(`/groups/${group.id}`);
+    } catch (err) {
+      setError(err instanceof Error ? err.message : 'Impossibile entrare nel gruppo.');
+    } finally {
+      setIsLoading(false);
+    }
+  };
+
+  return (
+    <div className="w-full max-w-sm p-6 sm:p-8 space-y-6 bg-white rounded-lg shadow-md m-4 sm:m-0">
+      <div className="text-center">
+        <h1 className="text-2xl sm:text-3xl font-bold text-gray-800">
+          {preview ? preview.group_name : 'Invito a un gruppo'}
+        </h1>
+        {preview && (
+          <p className="text-gray-500 mt-2">
+            {preview.desc && <>{preview.desc}<br /></>}
+            {preview.members} {preview.members === 1 ? 'membro' : 'membri'} · valido fino al{' '}
+            {new Date(preview.expires_at).toLocaleDateString('it-IT')}
+          </p>
+        )}
+      </div>
+
+      {!token && <p className="text-sm text-center text-red-600 font-medium">Il link non è completo.</p>}
+      {error && <p className="text-sm text-center text-red-600 font-medium">{error}</p>}
+
+      {preview && (
+        <Button onClick={handleJoin} disabled={isLoading} className="w-full">
+          {isLoading ? 'Ingresso in corso...' : 'Entra nel gruppo'}
+        </Button>
+      )}
+
+      <p className="text-sm text-center text-gray-600">
+        Per entrare devi aver{' '}
+        <Link href="/login" className="font-medium text-blue-600 hover:underline">
+          effettuato l'accesso
+        </Link>
+      </p>
+    </div>
+  );
+};
@@ -49,7 +49,7 @@ export default function NotificationItem({ notification, onMarkAsRead }: Notific
     }
     if (notification.group_id) {
       let url = `/groups/${notification.group_id}`;
-      if (notification.expense_id && ['NEW_EXPENSE', 'EXPENSE_UPDATED'].includes(notification.notification_type || '')) {
+      if (notification.expense_id && ['NEW_EXPENSE', 'EXPENSE_UPDATED', 'EXPENSE_COMMENT'].includes(notification.notification_type || '')) {
         url += `?openExpense=${notification.expense_id}`;
       }
       router.push(url);
@@ -90,6 +90,9 @@ export default function NotificationItem({ notification, onMarkAsRead }: Notific
           message = <p><strong>{actor.username}</strong> ha cancellato una spesa privata.</p>;
         }
         break;
+      case 'EXPENSE_COMMENT':
+        message = <p><strong>{actor.username}</strong> ha commentato una spesa nel gruppo <strong>{group?.group_name}</strong>.</p>;
+        break;
       case 'FRIENDSHIP_REQUEST_ACCEPTED':
         message = <p><strong>{actor.username}</strong> ha accettato la richiesta di amicizia.</p>;
         break;
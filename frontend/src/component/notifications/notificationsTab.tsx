@@ -33,17 +33,22 @@ export default function NotificationsTab() {
     onError: (error) => console.error("Errore nel segnare la notifica come letta:", error),
   });
 
+  const markAllAsReadMutation = useMutation({
+    mutationFn: () => api.markAllNotificationsAsRead(),
+    onSuccess: () => {
+      queryClient.invalidateQueries({ queryKey: ['notifications'] });
+      refetchNotifications();
+    },
+    onError: (error) => console.error("Errore nel segnare le notifiche come lette:", error),
+  });
+
   useEffect(() => {
     if (notifications && !markedAsRead.current && notifications.some(n => !n.read)) {
-      const unreadNotifications = notifications.filter(n => !n.read);
-      
-      unreadNotifications.forEach(notif => {
-        markAsReadMutation.mutate(notif.id);
-      });
+      markAllAsReadMutation.mutate();
 
       markedAsRead.current = true;
     }
-  }, [notifications, markAsReadMutation]); 
+  }, [notifications, markAllAsReadMutation]); 
 
   const handleMarkAsRead = (id: number) => {
     markAsReadMutation.mutate(id);
@@ -0,0 +1,56 @@
+'use client';
+
+import { useState } from 'react';
+import { useSearchParams } from 'next/navigation';
+import { api } from '@/lib/api';
+import { Button } from '../ui/button';
+import Link from 'next/link';
+
+export const UnsubscribeCard = () => {
+  const token = useSearchParams().get('token');
+  const [done, setDone] = useState(false);
+  const [isLoading, setIsLoading] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  const handleUnsubscribe = async () => {
+    if (!token) return;
+    setError(null);
+    setIsLoading(true);
+    try {
+      await api.unsubscribeFromEmails(token);
+      setDone(true);
+    } catch (err) {
+      setError(err instanceof Error ? err.message : 'Link non valido.');
+    } finally {
+      setIsLoading(false);
+    }
+  };
+
+  return (
+    <div className="w-full max-w-sm p-6 sm:p-8 space-y-6 bg-white rounded-lg shadow-md m-4 sm:m-0">
+      <div className="text-center">
+        <h1 className="text-2xl sm:text-3xl font-bold text-gray-800">Email di notifica</h1>
+        <p className="text-gray-500 mt-2">
+          {done
+            ? 'Non riceverai più le notifiche via email.'
+            : 'Vuoi smettere di ricevere le notifiche via email?'}
+        </p>
+      </div>
+
+      {!token && <p className="text-sm text-center text-red-600 font-medium">Il link non è completo.</p>}
+      {error && <p className="text-sm text-center text-red-600 font-medium">{error}</p>}
+
+      {!done && (
+        <Button onClick={handleUnsubscribe} disabled={isLoading || !token} className="w-full">
+          {isLoading ? 'Disattivazione in corso...' : 'Disattiva le email'}
+        </Button>
+      )}
+
+      <p className="text-sm text-center text-gray-600">
+        <Link href="/login" className="font-medium text-blue-600 hover:underline">
+          Vai a SplitSmart
+        </Link>
+      </p>
+    </div>
+  );
+};
@@ -1,4 +1,4 @@
-import type { UserInfo, LoginCredentials, UserRegisterData, Group, CreateGroupData, InviteUserData, GroupInvite, GroupMember, ExpenseWithParticipants, Expense, AddExpenseData, Notific, Friendship, FriendInvite, InviteFriendData } from '@/types';
+import type { UserInfo, LoginCredentials, UserRegisterData, Group, CreateGroupData, InviteUserData, GroupInvite, GroupMember, ExpenseWithParticipants, Expense, AddExpenseData, Notific, Friendship, FriendInvite, InviteFriendData, Page, Category, CategoryReport, RecurringExpenseWithParticipations, AddRecurringExpenseData, ExpenseComment, Attachment, ExpenseHistoryEntry, ReceiptItem, NotificationPreference, EmailDelivery, GroupEvent, Webhook, AddWebhookData, WebhookDelivery, WebhookDeliveryStatus, PaymentReminder, ReminderSchedule, GroupInviteLink, CreateInviteLinkData, InvitePreview } from '@/types';
 
 const API_PROXY_URL = '/api-proxy';
 /**
@@ -15,6 +15,27 @@ async function handleResponse<T>(response: Response): Promise<T | null> {
   return text ? (JSON.parse(text) as T) : null;
 }
 
+/**
+ * Scarica tutte le pagine di una lista paginata seguendo i cursori.
+ */
+async function fetchAllPages<T>(path: string): Promise<T[]> {
+  const items: T[] = [];
+  let cursor: string | null = null;
+  do {
+    const separator = path.includes('?') ? '&' : '?';
+    const url = cursor ? `${API_PROXY_URL}${path}${separator}cursor=${cursor}` : `${API_PROXY_URL}${path}`;
+    const response = await fetch(url, {
+      method: 'GET',
+      credentials: 'include',
+    });
+    const page: Page<T> | null = await handleResponse<Page<T>>(response);
+    if (!page) break;
+    items.push(...page.items);
+    cursor = page.next_cursor;
+  } while (cursor);
+  return items;
+}
+
 
 export const api = {
   /**
@@ -68,12 +89,7 @@ export const api = {
    * Recupera la lista dei gruppi dell'utente.
    */
   getGroups: async (): Promise<Group[]> => {
-    const response = await fetch(`${API_PROXY_URL}/groups`, {
-      method: 'GET',
-      credentials: 'include',
-    });
-    const groups = await handleResponse<Group[]>(response);
-    return groups || [];
+    return fetchAllPages<Group>('/groups?limit=100');
   },
 
   /**
@@ -123,6 +139,32 @@ export const api = {
     }
   },
 
+  /**
+   * Recupera i gruppi eliminati che l'utente amministra e può ancora ripristinare.
+   */
+  getDeletedGroups: async (): Promise<Group[]> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/trash`, {
+      method: 'GET',
+      credentials: 'include',
+    });
+    return (await handleResponse<Group[]>(response)) || [];
+  },
+
+  /**
+   * Ripristina un gruppo eliminato, insieme alle sue spese.
+   */
+  restoreGroup: async (groupId: number): Promise<Group> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/restore`, {
+      method: 'POST',
+      credentials: 'include',
+    });
+    const restored = await handleResponse<Group>(response);
+    if (!restored) {
+      throw new Error("Il backend non ha restituito il gruppo ripristinato.");
+    }
+    return restored;
+  },
+
    /**
     * Modifica un gruppo.
     */
@@ -182,12 +224,261 @@ export const api = {
    * Recupera le spese di un gruppo
    */
   getGroupExpenses: async (groupId: number): Promise<ExpenseWithParticipants[]> => {
-    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/expenses`, {
+    return fetchAllPages<ExpenseWithParticipants>(`/groups/${groupId}/expenses?limit=100`);
+  },
+
+  /**
+   * Recupera le spese eliminate del gruppo che possono ancora essere ripristinate.
+   */
+  getGroupTrash: async (groupId: number): Promise<ExpenseWithParticipants[]> => {
+    return fetchAllPages<ExpenseWithParticipants>(`/groups/${groupId}/trash?limit=100`);
+  },
+
+  /**
+   * Ripristina una spesa eliminata del gruppo.
+   */
+  restoreGroupExpense: async (groupId: number, expenseId: number): Promise<Expense> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/expenses/${expenseId}/restore`, {
+      method: 'POST',
+      credentials: 'include',
+    });
+    const restored = await handleResponse<Expense>(response);
+    if (!restored) {
+      throw new Error("Il backend non ha restituito la spesa ripristinata.");
+    }
+    return restored;
+  },
+
+  /**
+   * Recupera i commenti di una spesa del gruppo, dal più vecchio.
+   */
+  getExpenseComments: async (groupId: number, expenseId: number): Promise<ExpenseComment[]> => {
+    return fetchAllPages<ExpenseComment>(`/groups/${groupId}/expenses/${expenseId}/comments?limit=100`);
+  },
+
+  /**
+   * Commenta una spesa del gruppo, i partecipanti alla spesa ricevono una notifica.
+   */
+  addExpenseComment: async (groupId: number, expenseId: number, body: string): Promise<ExpenseComment> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/expenses/${expenseId}/comments`, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ body }),
+      credentials: 'include',
+    });
+    const comment = await handleResponse<ExpenseComment>(response);
+    if (!comment) {
+      throw new Error("Il backend non ha restituito il commento creato.");
+    }
+    return comment;
+  },
+
+  /**
+   * Modifica un commento, solo l'autore può farlo.
+   */
+  updateExpenseComment: async (groupId: number, expenseId: number, commentId: number, body: string): Promise<ExpenseComment> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/expenses/${expenseId}/comments/${commentId}`, {
+      method: 'PUT',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ body }),
+      credentials: 'include',
+    });
+    const comment = await handleResponse<ExpenseComment>(response);
+    if (!comment) {
+      throw new Error("Il backend non ha restituito il commento aggiornato.");
+    }
+    return comment;
+  },
+
+  /**
+   * Elimina un commento, solo l'autore può farlo.
+   */
+  deleteExpenseComment: async (groupId: number, expenseId: number, commentId: number): Promise<void> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/expenses/${expenseId}/comments/${commentId}`, {
+      method: 'DELETE',
+      credentials: 'include',
+    });
+    await handleResponse<void>(response);
+  },
+
+  /**
+   * Recupera le voci dello scontrino di una spesa del gruppo, vuote se la spesa non è dettagliata.
+   */
+  getExpenseItems: async (groupId: number, expenseId: number): Promise<ReceiptItem[]> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/expenses/${expenseId}/items`, {
+      method: 'GET',
+      credentials: 'include',
+    });
+    return (await handleResponse<ReceiptItem[]>(response)) || [];
+  },
+
+  /**
+   * Recupera la cronologia delle modifiche di una spesa del gruppo, dalla prima versione.
+   */
+  getExpenseHistory: async (groupId: number, expenseId: number): Promise<ExpenseHistoryEntry[]> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/expenses/${expenseId}/history`, {
       method: 'GET',
       credentials: 'include',
     });
-    const expenses = await handleResponse<ExpenseWithParticipants[]>(response);
-    return expenses || [];
+    return (await handleResponse<ExpenseHistoryEntry[]>(response)) || [];
+  },
+
+  /**
+   * Recupera gli allegati di una spesa del gruppo, come le foto degli scontrini.
+   */
+  getExpenseAttachments: async (groupId: number, expenseId: number): Promise<Attachment[]> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/expenses/${expenseId}/attachments`, {
+      method: 'GET',
+      credentials: 'include',
+    });
+    return (await handleResponse<Attachment[]>(response)) || [];
+  },
+
+  /**
+   * Allega un'immagine o un pdf a una spesa del gruppo.
+   */
+  addExpenseAttachment: async (groupId: number, expenseId: number, file: File): Promise<Attachment> => {
+    const form = new FormData();
+    form.append('file', file);
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/expenses/${expenseId}/attachments`, {
+      method: 'POST',
+      body: form,
+      credentials: 'include',
+    });
+    const attachment = await handleResponse<Attachment>(response);
+    if (!attachment) {
+      throw new Error("Il backend non ha restituito l'allegato creato.");
+    }
+    return attachment;
+  },
+
+  /**
+   * Indirizzo da cui scaricare un allegato.
+   */
+  expenseAttachmentUrl: (groupId: number, expenseId: number, attachmentId: number): string => {
+    return `${API_PROXY_URL}/groups/${groupId}/expenses/${expenseId}/attachments/${attachmentId}`;
+  },
+
+  /**
+   * Elimina un allegato, può farlo chi l'ha caricato, chi ha pagato la spesa o un admin.
+   */
+  deleteExpenseAttachment: async (groupId: number, expenseId: number, attachmentId: number): Promise<void> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/expenses/${expenseId}/attachments/${attachmentId}`, {
+      method: 'DELETE',
+      credentials: 'include',
+    });
+    await handleResponse<void>(response);
+  },
+
+  /**
+   * Recupera le spese ricorrenti del gruppo.
+   */
+  getRecurringExpenses: async (groupId: number): Promise<RecurringExpenseWithParticipations[]> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/recurring`, {
+      method: 'GET',
+      credentials: 'include',
+    });
+    const recurring = await handleResponse<RecurringExpenseWithParticipations[]>(response);
+    return recurring || [];
+  },
+
+  /**
+   * Aggiunge una spesa ricorrente al gruppo.
+   */
+  addRecurringExpense: async (groupId: number, data: AddRecurringExpenseData): Promise<RecurringExpenseWithParticipations> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/recurring`, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify(data),
+      credentials: 'include',
+    });
+    const recurring = await handleResponse<RecurringExpenseWithParticipations>(response);
+    if (!recurring) {
+      throw new Error("Il backend non ha restituito la spesa ricorrente creata.");
+    }
+    return recurring;
+  },
+
+  /**
+   * Elimina una spesa ricorrente, le spese già aggiunte restano. Richiede di esserne il creatore o admin.
+   */
+  deleteRecurringExpense: async (groupId: number, recurringId: number): Promise<void> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/recurring/${recurringId}`, {
+      method: 'DELETE',
+      credentials: 'include',
+    });
+    await handleResponse<void>(response);
+  },
+
+  /**
+   * Aggiunge subito le spese ricorrenti dovute del gruppo. Richiede privilegi di admin.
+   */
+  runRecurringExpenses: async (groupId: number): Promise<Expense[]> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/recurring/run`, {
+      method: 'POST',
+      credentials: 'include',
+    });
+    const added = await handleResponse<Expense[]>(response);
+    return added || [];
+  },
+
+  /**
+   * Recupera le categorie utilizzabili dalle spese del gruppo, predefinite e personalizzate.
+   */
+  getGroupCategories: async (groupId: number): Promise<Category[]> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/categories`, {
+      method: 'GET',
+      credentials: 'include',
+    });
+    const categories = await handleResponse<Category[]>(response);
+    return categories || [];
+  },
+
+  /**
+   * Crea una categoria personalizzata del gruppo. Richiede privilegi di admin.
+   */
+  createCategory: async (groupId: number, name: string): Promise<Category> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/categories`, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ name }),
+      credentials: 'include',
+    });
+    const category = await handleResponse<Category>(response);
+    if (!category) {
+      throw new Error("Il backend non ha restituito la categoria creata.");
+    }
+    return category;
+  },
+
+  /**
+   * Elimina una categoria personalizzata del gruppo. Richiede privilegi di admin.
+   */
+  deleteCategory: async (groupId: number, categoryId: number): Promise<void> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/categories/${categoryId}`, {
+      method: 'DELETE',
+      credentials: 'include',
+    });
+    await handleResponse<void>(response);
+  },
+
+  /**
+   * Recupera la spesa del gruppo per categoria e per membro nel periodo indicato (date YYYY-MM-DD).
+   */
+  getCategoryReport: async (groupId: number, from?: string, to?: string): Promise<CategoryReport> => {
+    const params = new URLSearchParams();
+    if (from) params.set('from', from);
+    if (to) params.set('to', to);
+    const query = params.toString() ? `?${params.toString()}` : '';
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/reports/categories${query}`, {
+      method: 'GET',
+      credentials: 'include',
+    });
+    const report = await handleResponse<CategoryReport>(response);
+    if (!report) {
+      throw new Error("Il backend non ha restituito il report.");
+    }
+    return report;
   },
 
    /**
@@ -289,12 +580,7 @@ export const api = {
    * Recupera tutti gli inviti per l'utente corrente.
    */
   getInvites: async (): Promise<GroupInvite[]> => {
-    const response = await fetch(`${API_PROXY_URL}/user/invites`, {
-      method: 'GET',
-      credentials: 'include',
-    });
-    const invites = await handleResponse<GroupInvite[]>(response);
-    return invites || [];
+    return fetchAllPages<GroupInvite>('/user/invites?limit=100');
   },
 
   /**
@@ -365,11 +651,7 @@ export const api = {
    * Recupera le notifiche dell'utente.
    */
    getNotifications: async (): Promise<Notific[]> => {
-    const response = await fetch(`${API_PROXY_URL}/notifications`, {
-      method: 'GET',
-      credentials: 'include',
-    });
-    return (await handleResponse<Notific[]>(response)) || [];
+    return fetchAllPages<Notific>('/notifications?limit=100');
   },
 
   /**
@@ -377,7 +659,7 @@ export const api = {
    */
    markNotificationAsRead: async (notificationId: number): Promise<Notific> => {
     const response = await fetch(`${API_PROXY_URL}/notifications/${notificationId}/read`, {
-      method: 'GET',
+      method: 'PUT',
       credentials: 'include',
     });
     const updatedNotification = await handleResponse<Notific>(response);
@@ -387,6 +669,309 @@ export const api = {
     return updatedNotification;
   },
 
+  /**
+   * Si iscrive alle nuove notifiche inviate dal server appena vengono create.
+   * In caso di riconnessione il browser invia l'ultimo Last-Event-ID ricevuto
+   * e il server rimanda le notifiche perse. Restituisce la funzione per chiudere lo stream.
+   */
+  subscribeToNotifications: (onNotification: (notification: Notific) => void): (() => void) => {
+    const source = new EventSource(`${API_PROXY_URL}/notifications/stream`, { withCredentials: true });
+    source.onmessage = (event) => onNotification(JSON.parse(event.data) as Notific);
+    return () => source.close();
+  },
+
+  /**
+   * Si iscrive alle modifiche di un gruppo: spese aggiunte, modificate o eliminate
+   * e cambi di membri e amministratori. Restituisce la funzione per chiudere lo stream.
+   */
+  subscribeToGroup: (groupId: number, onEvent: (event: GroupEvent) => void): (() => void) => {
+    const source = new EventSource(`${API_PROXY_URL}/groups/${groupId}/live`, { withCredentials: true });
+    source.onmessage = (event) => onEvent(JSON.parse(event.data) as GroupEvent);
+    return () => source.close();
+  },
+
+  /**
+   * Recupera i webhook di un gruppo (solo per amministratori).
+   */
+  getWebhooks: async (groupId: number): Promise<Webhook[]> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/webhooks`, {
+      method: 'GET',
+      credentials: 'include',
+    });
+    return (await handleResponse<Webhook[]>(response)) || [];
+  },
+
+  /**
+   * Registra un webhook a cui inviare gli eventi del gruppo, firmati con il segreto.
+   */
+  addWebhook: async (groupId: number, data: AddWebhookData): Promise<Webhook> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/webhooks`, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify(data),
+      credentials: 'include',
+    });
+    const created = await handleResponse<Webhook>(response);
+    if (!created) {
+      throw new Error("Il backend non ha restituito il webhook creato.");
+    }
+    return created;
+  },
+
+  /**
+   * Elimina un webhook del gruppo e le sue consegne.
+   */
+  deleteWebhook: async (groupId: number, webhookId: number): Promise<void> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/webhooks/${webhookId}`, {
+      method: 'DELETE',
+      credentials: 'include',
+    });
+    await handleResponse<null>(response);
+  },
+
+  /**
+   * Recupera il registro delle consegne di un webhook, dalla più recente.
+   */
+  getWebhookDeliveries: async (groupId: number, webhookId: number, status?: WebhookDeliveryStatus): Promise<WebhookDelivery[]> => {
+    const query = status ? `?status=${status}` : '';
+    return fetchAllPages<WebhookDelivery>(`/groups/${groupId}/webhooks/${webhookId}/deliveries${query}`);
+  },
+
+  /**
+   * Recupera i promemoria di pagamento inviati o ricevuti dall'utente nel gruppo, dal più recente.
+   */
+  getReminders: async (groupId: number): Promise<PaymentReminder[]> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/reminders`, {
+      method: 'GET',
+      credentials: 'include',
+    });
+    return (await handleResponse<PaymentReminder[]>(response)) || [];
+  },
+
+  /**
+   * Ricorda a un membro del gruppo quanto deve, con un messaggio facoltativo.
+   * Allo stesso membro si può scrivere una volta al giorno.
+   */
+  sendReminder: async (groupId: number, userId: number, message?: string): Promise<PaymentReminder> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/reminders/${userId}`, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ message: message || null }),
+      credentials: 'include',
+    });
+    const sent = await handleResponse<PaymentReminder>(response);
+    if (!sent) {
+      throw new Error("Il backend non ha restituito il promemoria inviato.");
+    }
+    return sent;
+  },
+
+  /**
+   * Recupera la programmazione dei promemoria automatici del gruppo, null se disattivati.
+   */
+  getReminderSchedule: async (groupId: number): Promise<ReminderSchedule | null> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/reminders/schedule`, {
+      method: 'GET',
+      credentials: 'include',
+    });
+    return handleResponse<ReminderSchedule>(response);
+  },
+
+  /**
+   * Imposta dopo quanti giorni i debiti vengono ricordati automaticamente (solo per amministratori),
+   * null li disattiva.
+   */
+  setReminderSchedule: async (groupId: number, afterDays: number | null): Promise<ReminderSchedule | null> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/reminders/schedule`, {
+      method: 'PUT',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ after_days: afterDays }),
+      credentials: 'include',
+    });
+    return handleResponse<ReminderSchedule>(response);
+  },
+
+  /**
+   * Recupera tutti i link di invito del gruppo, anche quelli scaduti o revocati (solo per amministratori).
+   */
+  getInviteLinks: async (groupId: number): Promise<GroupInviteLink[]> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/invite-links`, {
+      method: 'GET',
+      credentials: 'include',
+    });
+    return (await handleResponse<GroupInviteLink[]>(response)) || [];
+  },
+
+  /**
+   * Crea un link di invito al gruppo (solo per amministratori).
+   * Di default dura una settimana e può essere usato da chiunque lo riceva.
+   */
+  createInviteLink: async (groupId: number, data: CreateInviteLinkData = {}): Promise<GroupInviteLink> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/invite-links`, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify(data),
+      credentials: 'include',
+    });
+    const link = await handleResponse<GroupInviteLink>(response);
+    if (!link) {
+      throw new Error("Il backend non ha restituito il link creato.");
+    }
+    return link;
+  },
+
+  /**
+   * Revoca un link di invito, che non potrà più essere usato (solo per amministratori).
+   */
+  revokeInviteLink: async (groupId: number, linkId: number): Promise<GroupInviteLink | null> => {
+    const response = await fetch(`${API_PROXY_URL}/groups/${groupId}/invite-links/${linkId}`, {
+      method: 'DELETE',
+      credentials: 'include',
+    });
+    return handleResponse<GroupInviteLink>(response);
+  },
+
+  /**
+   * Mostra il gruppo a cui porta un link di invito, anche senza aver effettuato l'accesso.
+   */
+  previewInviteLink: async (token: string): Promise<InvitePreview> => {
+    const response = await fetch(`${API_PROXY_URL}/invites/${encodeURIComponent(token)}`, {
+      method: 'GET',
+      credentials: 'include',
+    });
+    const preview = await handleResponse<InvitePreview>(response);
+    if (!preview) {
+      throw new Error("Il link di invito non è valido.");
+    }
+    return preview;
+  },
+
+  /**
+   * Entra nel gruppo di un link di invito.
+   */
+  joinWithInviteLink: async (token: string): Promise<Group> => {
+    const response = await fetch(`${API_PROXY_URL}/invites/${encodeURIComponent(token)}/join`, {
+      method: 'POST',
+      credentials: 'include',
+    });
+    const group = await handleResponse<Group>(response);
+    if (!group) {
+      throw new Error("Il backend non ha restituito il gruppo.");
+    }
+    return group;
+  },
+
+  /**
+   * Segna come lette tutte le notifiche, eventualmente solo quelle di un gruppo.
+   */
+  markAllNotificationsAsRead: async (groupId?: number): Promise<Notific[]> => {
+    const query = groupId !== undefined ? `?group=${groupId}` : '';
+    const response = await fetch(`${API_PROXY_URL}/notifications/read-all${query}`, {
+      method: 'PUT',
+      credentials: 'include',
+    });
+    return (await handleResponse<Notific[]>(response)) || [];
+  },
+
+  /**
+   * Recupera il numero di notifiche non ancora lette.
+   */
+  getUnreadNotificationsCount: async (): Promise<number> => {
+    const response = await fetch(`${API_PROXY_URL}/notifications/unread-count`, {
+      method: 'GET',
+      credentials: 'include',
+    });
+    return (await handleResponse<number>(response)) || 0;
+  },
+
+  /**
+   * Elimina una notifica.
+   */
+  deleteNotification: async (notificationId: number): Promise<Notific> => {
+    const response = await fetch(`${API_PROXY_URL}/notifications/${notificationId}`, {
+      method: 'DELETE',
+      credentials: 'include',
+    });
+    const deleted = await handleResponse<Notific>(response);
+    if (!deleted) {
+      throw new Error("Il backend non ha restituito la notifica eliminata.");
+    }
+    return deleted;
+  },
+
+  /**
+   * Elimina tutte le notifiche già lette.
+   */
+  deleteReadNotifications: async (): Promise<Notific[]> => {
+    const response = await fetch(`${API_PROXY_URL}/notifications?read=true`, {
+      method: 'DELETE',
+      credentials: 'include',
+    });
+    return (await handleResponse<Notific[]>(response)) || [];
+  },
+
+  /**
+   * Recupera la preferenza di notifica dell'utente: SILENCED, PERSONAL o ALL.
+   */
+  getNotificationPreference: async (): Promise<NotificationPreference> => {
+    const response = await fetch(`${API_PROXY_URL}/notifications/preferences`, {
+      method: 'GET',
+      credentials: 'include',
+    });
+    return (await handleResponse<NotificationPreference>(response)) || 'PERSONAL';
+  },
+
+  /**
+   * Imposta la preferenza di notifica dell'utente.
+   */
+  setNotificationPreference: async (preference: NotificationPreference): Promise<NotificationPreference> => {
+    const response = await fetch(`${API_PROXY_URL}/notifications/preferences/${preference}`, {
+      method: 'PUT',
+      credentials: 'include',
+    });
+    const updated = await handleResponse<NotificationPreference>(response);
+    if (!updated) {
+      throw new Error("Il backend non ha restituito la preferenza aggiornata.");
+    }
+    return updated;
+  },
+
+  /**
+   * Recupera come l'utente riceve le notifiche via email: OFF, IMMEDIATE o DIGEST.
+   */
+  getEmailDelivery: async (): Promise<EmailDelivery> => {
+    const response = await fetch(`${API_PROXY_URL}/notifications/email`, {
+      method: 'GET',
+      credentials: 'include',
+    });
+    return (await handleResponse<EmailDelivery>(response)) || 'OFF';
+  },
+
+  /**
+   * Imposta come l'utente riceve le notifiche via email.
+   */
+  setEmailDelivery: async (delivery: EmailDelivery): Promise<EmailDelivery> => {
+    const response = await fetch(`${API_PROXY_URL}/notifications/email/${delivery}`, {
+      method: 'PUT',
+      credentials: 'include',
+    });
+    const updated = await handleResponse<EmailDelivery>(response);
+    if (!updated) {
+      throw new Error("Il backend non ha restituito l'impostazione aggiornata.");
+    }
+    return updated;
+  },
+
+  /**
+   * Disattiva le email di notifica con il token del link contenuto nelle email, senza login.
+   */
+  unsubscribeFromEmails: async (token: string): Promise<void> => {
+    const response = await fetch(`${API_PROXY_URL}/notifications/email/unsubscribe/${encodeURIComponent(token)}`, {
+      method: 'POST',
+    });
+    await handleResponse<null>(response);
+  },
+
   /**
    * Recupera la lista degli amici dell'utente.
    */
@@ -464,8 +1049,7 @@ export const api = {
    * Recupera la lista delle spese private.
    */
   getPrivateExpenses: async (): Promise<ExpenseWithParticipants[]> => {
-    const response = await fetch(`${API_PROXY_URL}/expenses`, { credentials: 'include' });
-    return (await handleResponse<ExpenseWithParticipants[]>(response)) || [];
+    return fetchAllPages<ExpenseWithParticipants>('/expenses?limit=100');
   },
 
   /**
@@ -508,4 +1092,26 @@ export const api = {
     });
     if (!response.ok) throw new Error("Impossibile eliminare la spesa privata.");
   },
-};
\ No newline at end of file
+
+  /**
+   * Recupera le spese private eliminate che possono ancora essere ripristinate.
+   */
+  getPrivateTrash: async (): Promise<ExpenseWithParticipants[]> => {
+    return fetchAllPages<ExpenseWithParticipants>('/expenses/trash?limit=100');
+  },
+
+  /**
+   * Ripristina una spesa privata eliminata.
+   */
+  restorePrivateExpense: async (expenseId: number): Promise<Expense> => {
+    const response = await fetch(`${API_PROXY_URL}/expenses/${expenseId}/restore`, {
+      method: 'POST',
+      credentials: 'include',
+    });
+    const restored = await handleResponse<Expense>(response);
+    if (!restored) {
+      throw new Error("Il backend non ha restituito la spesa ripristinata.");
+    }
+    return restored;
+  },
+};
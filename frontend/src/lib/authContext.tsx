@@ -27,12 +27,11 @@ export const AuthProvider = ({ children }: { children: ReactNode }) => {
     queryFn: async () => {
       if (!user) return 0;
       
-      const [notifications, groupInvites] = await Promise.all([
-        api.getNotifications(),
+      const [unreadNotifs, groupInvites] = await Promise.all([
+        api.getUnreadNotificationsCount(),
         api.getInvites(),
       ]);
 
-      const unreadNotifs = notifications.filter(n => !n.read).length;
       const pendingInvites = groupInvites.filter(i => i.invite_status === 'PENDING').length;
       
       return unreadNotifs + pendingInvites;
@@ -41,6 +40,15 @@ export const AuthProvider = ({ children }: { children: ReactNode }) => {
     staleTime: 1000 * 60 * 5,
   });
 
+  useEffect(() => {
+    if (!user) return;
+
+    return api.subscribeToNotifications(() => {
+      queryClient.invalidateQueries({ queryKey: ['notifications'] });
+      refetchNotifications();
+    });
+  }, [user, queryClient, refetchNotifications]);
+
   const checkAuthStatus = useCallback(async () => {
     const storedUserId = sessionStorage.getItem('userId');
     if (storedUserId) {
@@ -44,11 +44,39 @@ export function formatNotificationMessage(notification: Notific): string {
     case 'NEW_EXPENSE':
     case 'EXPENSE_UPDATED':
     case 'EXPENSE_DELETED':
+    case 'EXPENSE_RESTORED':
       return `Aggiornamento su una spesa nel gruppo ID ${notification.group_id}.`;
+
+    case 'EXPENSE_COMMENT':
+      return `Nuovo commento su una spesa nel gruppo ID ${notification.group_id}.`;
     
     case 'GROUP_INVITE':
       return `Hai ricevuto un invito per il gruppo ID ${notification.group_id}.`;
-    
+
+    case 'MEMBER_ADDED':
+      return `Sei stato aggiunto al gruppo ID ${notification.group_id}.`;
+
+    case 'MEMBER_JOINED':
+      return `Un nuovo membro è entrato nel gruppo ID ${notification.group_id} con un link di invito.`;
+
+    case 'REMOVED_FROM_GROUP':
+      return `Sei stato rimosso dal gruppo ID ${notification.group_id}.`;
+
+    case 'ADMIN_PROMOTION':
+      return `Sei stato nominato amministratore del gruppo ID ${notification.group_id}.`;
+
+    case 'ADMIN_DEMOTION':
+      return `Non sei più amministratore del gruppo ID ${notification.group_id}.`;
+
+    case 'GROUP_DELETED':
+      return `Il gruppo ID ${notification.group_id} è stato eliminato.`;
+
+    case 'GROUP_RESTORED':
+      return `Il gruppo ID ${notification.group_id} è stato ripristinato.`;
+
+    case 'BALANCE_REQUESTED':
+      return `Hai ricevuto un promemoria di pagamento per il gruppo ID ${notification.group_id}.`;
+
     case 'FRIENDSHIP_REQUEST_ACCEPTED':
       return `Notifica di amicizia accettata.`;
       
@@ -34,6 +34,7 @@ export interface Group {
   group_name: string;
   desc: string | null;
   creation_date: string;
+  deleted_at: string | null;
 }
 
 export interface CreateGroupData {
@@ -45,9 +46,114 @@ export interface Expense {
   id: number;
   desc: string;
   total_amount: number;
-  creation_date: string;
+  created_at: string;
+  updated_at: string;
+  expense_date: string;
+  paid_by: number;
+  group_id: number | null;
+  category_id: number | null;
+  deleted_at: string | null;
+}
+
+export interface ExpenseComment {
+  id: number;
+  expense_id: number;
+  author_id: number;
+  body: string;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface Attachment {
+  id: number;
+  expense_id: number;
+  uploaded_by: number | null;
+  file_name: string;
+  content_type: string;
+  size: number;
+  created_at: string;
+}
+
+export interface ExpenseState {
+  desc: string;
+  total_amount: number;
+  paid_by: number;
+  expense_date: string;
+  category_id: number | null;
+  division: [number, number | null][];
+}
+
+export interface ExpenseHistoryEntry {
+  id: number;
+  revision: number;
+  changed_by: number | null;
+  changed_at: string;
+  changed: string[];
+  old: ExpenseState | null;
+  new: ExpenseState;
+}
+
+export type RecurrenceFrequency = 'MONTHLY' | 'WEEKLY' | 'EVERY_N_DAYS';
+
+export interface RecurringExpense {
+  id: number;
+  group_id: number;
+  created_by: number;
+  desc: string;
+  total_amount: number;
   paid_by: number;
+  category_id: number | null;
+  frequency: RecurrenceFrequency;
+  day_of_month: number | null;
+  interval_days: number | null;
+  start_date: string;
+  end_date: string | null;
+  next_date: string;
+  created_at: string;
+}
+
+export interface RecurringExpenseParticipation {
+  recurring_expense_id: number;
+  user_id: number;
+  amount_due: number | null;
+}
+
+export type RecurringExpenseWithParticipations = [RecurringExpense, RecurringExpenseParticipation[]];
+
+export interface AddRecurringExpenseData {
+  expense: AddExpenseData;
+  frequency: RecurrenceFrequency;
+  day_of_month?: number;
+  interval_days?: number;
+  start_date: string;
+  end_date?: string;
+}
+
+export interface Category {
+  id: number;
   group_id: number | null;
+  name: string;
+}
+
+export interface MemberSpending {
+  user_id: number;
+  paid: number;
+  share: number;
+}
+
+export interface CategorySpending {
+  category_id: number | null;
+  name: string | null;
+  total: number;
+  expenses: number;
+  members: MemberSpending[];
+}
+
+export interface CategoryReport {
+  from: string | null;
+  to: string | null;
+  total: number;
+  categories: CategorySpending[];
 }
 
 export interface ExpenseParticipation {
@@ -64,7 +170,20 @@ export interface AddExpenseData {
   desc: string;
   total_amount: number;
   paid_by: number;
-  division: [number, number][];
+  division?: [number, number][];
+  expense_date?: string;
+  category_id?: number | null;
+  items?: ExpenseItemData[];
+}
+
+export interface ExpenseItemData {
+  desc: string;
+  amount: number;
+  consumers: number[];
+}
+
+export interface ReceiptItem extends ExpenseItemData {
+  id: number;
 }
 
 
@@ -132,9 +251,96 @@ export interface Notific {
   expense_id: number | null;
   creation_date: string;
   read: boolean;
+  revision_id: number | null;
+  reminder_id: number | null;
   message: string; 
 }
 
+export type NotificationPreference = 'SILENCED' | 'PERSONAL' | 'ALL';
+
+export type EmailDelivery = 'OFF' | 'IMMEDIATE' | 'DIGEST';
+
+export type GroupEventType = 'EXPENSE_CREATED' | 'EXPENSE_UPDATED' | 'EXPENSE_DELETED' | 'EXPENSE_RESTORED' | 'MEMBER_ADDED' | 'MEMBER_REMOVED' | 'ADMIN_PROMOTED' | 'ADMIN_DEMOTED' | 'GROUP_DELETED' | 'GROUP_RESTORED' | 'RESYNC';
+
+export interface GroupEvent {
+  type: GroupEventType;
+  group_id: number;
+  actor: number | null;
+  expense_id: number | null;
+  member_id: number | null;
+}
+
+export interface Webhook {
+  id: number;
+  group_id: number;
+  url: string;
+  created_by: number | null;
+  created_at: string;
+}
+
+export interface AddWebhookData {
+  url: string;
+  secret: string;
+}
+
+export type WebhookDeliveryStatus = 'PENDING' | 'DELIVERED' | 'FAILED';
+
+export interface WebhookDelivery {
+  id: number;
+  webhook_id: number;
+  event_type: GroupEventType;
+  payload: string;
+  status: WebhookDeliveryStatus;
+  attempts: number;
+  next_attempt_at: string | null;
+  last_attempt_at: string | null;
+  response_status: number | null;
+  error: string | null;
+  created_at: string;
+}
+
+export interface PaymentReminder {
+  id: number;
+  group_id: number;
+  sender_id: number | null;
+  recipient_id: number;
+  amount: number;
+  message: string | null;
+  created_at: string;
+}
+
+export interface ReminderSchedule {
+  group_id: number;
+  after_days: number;
+  updated_by: number | null;
+  updated_at: string;
+}
+
+export interface GroupInviteLink {
+  id: number;
+  group_id: number;
+  token: string;
+  created_by: number | null;
+  created_at: string;
+  expires_at: string;
+  max_uses: number | null;
+  uses: number;
+  revoked_at: string | null;
+}
+
+export interface CreateInviteLinkData {
+  valid_for_hours?: number;
+  max_uses?: number;
+}
+
+export interface InvitePreview {
+  group_id: number;
+  group_name: string;
+  desc: string | null;
+  members: number;
+  expires_at: string;
+}
+
 export interface Friendship {
   user1: number;
   user2: number;
@@ -168,4 +374,13 @@ export type TimelineItem = {
     group: Group;
     balance: number; 
   };
-};
\ No newline at end of file
+};
+
+/**
+ * Una pagina di una lista restituita dal backend: `next_cursor` va passato come
+ * parametro `cursor` per ottenere la pagina successiva, è `null` sull'ultima pagina.
+ */
+export interface Page<T> {
+  items: T[];
+  next_cursor: string | null;
+}